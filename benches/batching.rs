@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use penum_ingress::{
+    recover_sender, shuffle_with_seed, transaction_nonce, BatchingEngine, PenumIngress, TransactionEnvelope,
+};
+
+mod support;
+
+// Submissions per measured iteration; each iteration needs a fresh ingress since repeats are refused
+const SUBMISSIONS: usize = 256;
+const BATCH_SIZES: [usize; 3] = [64, 512, 4_096];
+
+// Transactions that are never batched on their own, so only the measured step cuts a batch
+const NEVER: Duration = Duration::from_secs(3600);
+
+// Envelopes as the ingress pools them, with sender and nonce already recovered
+fn envelopes(size: usize) -> Vec<TransactionEnvelope> {
+    support::signed_txs(size)
+        .into_iter()
+        .map(|tx_bytes| {
+            let mut envelope = TransactionEnvelope::new(tx_bytes, String::new());
+            envelope.sender = Some(recover_sender(&envelope.tx_bytes).unwrap());
+            envelope.nonce = Some(transaction_nonce(&envelope.tx_bytes).unwrap());
+            envelope
+        })
+        .collect()
+}
+
+// Validation, signature recovery, dedup and pooling per submission
+fn bench_submit(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let txs = support::signed_txs(SUBMISSIONS);
+    let mut group = c.benchmark_group("submit_transaction");
+    group.throughput(Throughput::Elements(SUBMISSIONS as u64));
+
+    group.bench_function("sequential", |b| {
+        b.iter_batched(
+            || (PenumIngress::new(usize::MAX, NEVER, Vec::new()), txs.clone()),
+            |(ingress, txs)| {
+                runtime.block_on(async {
+                    for tx in txs {
+                        ingress.submit_transaction(tx).await.unwrap();
+                    }
+                })
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("bulk", |b| {
+        b.iter_batched(
+            || (PenumIngress::new(usize::MAX, NEVER, Vec::new()), txs.clone()),
+            |(ingress, txs)| runtime.block_on(ingress.submit_transactions(txs)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+// Cutting a batch from the pending pool: dedup bookkeeping, shuffle, and the salted Merkle commitment
+fn bench_create_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("create_batch");
+
+    for size in BATCH_SIZES {
+        let transactions = envelopes(size);
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &transactions, |b, transactions| {
+            b.iter_batched(
+                || {
+                    let engine = BatchingEngine::new(usize::MAX, NEVER);
+                    for tx in transactions.clone() {
+                        engine.add_transaction(tx).unwrap();
+                    }
+                    engine
+                },
+                |engine| engine.flush().unwrap().unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_shuffle(c: &mut Criterion) {
+    let mut group = c.benchmark_group("shuffle");
+
+    for size in BATCH_SIZES {
+        let items: Vec<usize> = (0..size).collect();
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &items, |b, items| {
+            b.iter(|| shuffle_with_seed(items, [7; 32]))
+        });
+    }
+    group.finish();
+}
+
+// Few, short samples so the whole suite finishes in well under a minute on CI
+fn config() -> Criterion {
+    Criterion::default()
+        .sample_size(10)
+        .warm_up_time(Duration::from_millis(500))
+        .measurement_time(Duration::from_secs(2))
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = bench_submit, bench_create_batch, bench_shuffle
+}
+criterion_main!(benches);
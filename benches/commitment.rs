@@ -0,0 +1,36 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use penum_ingress::{TransactionBatch, TransactionEnvelope};
+
+// Batch sizes around and well past the parallel hashing threshold
+const BATCH_SIZES: [usize; 3] = [512, 4_096, 32_768];
+
+fn large_batch(size: usize) -> Vec<TransactionEnvelope> {
+    (0..size as u32)
+        .map(|i| {
+            let mut tx_bytes = vec![0x02; 256];
+            tx_bytes[1..5].copy_from_slice(&i.to_be_bytes());
+            TransactionEnvelope::new(tx_bytes, String::new())
+        })
+        .collect()
+}
+
+// Compares the commitment on the default rayon pool against a one-thread pool, which runs the
+// same code serially
+fn bench_commitment(c: &mut Criterion) {
+    let serial_pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let mut group = c.benchmark_group("batch_commitment");
+
+    for size in BATCH_SIZES {
+        let transactions = large_batch(size);
+        group.bench_with_input(BenchmarkId::new("serial", size), &transactions, |b, transactions| {
+            b.iter(|| serial_pool.install(|| TransactionBatch::new(transactions.clone()).unwrap()))
+        });
+        group.bench_with_input(BenchmarkId::new("parallel", size), &transactions, |b, transactions| {
+            b.iter(|| TransactionBatch::new(transactions.clone()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_commitment);
+criterion_main!(benches);
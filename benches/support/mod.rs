@@ -0,0 +1,72 @@
+// Signed transactions for the benches and the load generator, which go through the ingress's
+// signature check and so cannot reuse the crate's test fixtures
+
+use k256::ecdsa::SigningKey;
+use sha3::{Digest, Keccak256};
+
+fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    match bytes {
+        [byte] if *byte < 0x80 => vec![*byte],
+        _ => [encode_length(bytes.len(), 0x80), bytes.to_vec()].concat(),
+    }
+}
+
+fn encode_u64(value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    encode_bytes(trim(&bytes))
+}
+
+fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    [encode_length(payload.len(), 0xc0), payload].concat()
+}
+
+fn encode_length(len: usize, offset: u8) -> Vec<u8> {
+    if len < 56 {
+        return vec![offset + len as u8];
+    }
+    let len_bytes = len.to_be_bytes();
+    let len_bytes = trim(&len_bytes);
+    [vec![offset + 55 + len_bytes.len() as u8], len_bytes.to_vec()].concat()
+}
+
+// Helper function to strip leading zeros from a big-endian integer
+fn trim(bytes: &[u8]) -> &[u8] {
+    let first = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    &bytes[first..]
+}
+
+// EIP-1559 transfer on chain 1 from the key [sender; 32], signed with the given nonce
+pub fn signed_tx(sender: u8, nonce: u64) -> Vec<u8> {
+    let key = SigningKey::from_slice(&[sender; 32]).unwrap();
+    let mut fields = vec![
+        encode_u64(1),              // chain ID
+        encode_u64(nonce),
+        encode_u64(1_000_000_000),  // max priority fee
+        encode_u64(30_000_000_000), // max fee
+        encode_u64(21_000),         // gas limit
+        encode_bytes(&[0x35; 20]),  // to
+        encode_u64(1),              // value
+        encode_bytes(&[]),          // data
+        encode_list(&[]),           // access list
+    ];
+
+    let mut signing_payload = vec![0x02];
+    signing_payload.extend(encode_list(&fields));
+    let (signature, recovery_id) = key.sign_prehash_recoverable(&Keccak256::digest(&signing_payload)).unwrap();
+
+    fields.extend([
+        encode_u64(recovery_id.to_byte() as u64),
+        encode_bytes(trim(&signature.r().to_bytes())),
+        encode_bytes(trim(&signature.s().to_bytes())),
+    ]);
+    [vec![0x02], encode_list(&fields)].concat()
+}
+
+// Distinct sender keys; [0xff; 32] and its neighbours exceed the curve order, so not every byte is usable
+const SENDERS: usize = 200;
+
+// count distinct transactions spread over SENDERS keys, each sender's nonces counting up from 0
+pub fn signed_txs(count: usize) -> Vec<Vec<u8>> {
+    (0..count).map(|i| signed_tx((i % SENDERS) as u8 + 1, (i / SENDERS) as u64)).collect()
+}
@@ -0,0 +1,97 @@
+// Load generator: submits signed transactions at a fixed rate and reports the throughput the ingress
+// sustained and how long transactions waited to be committed in a batch
+//
+// cargo run --release --example load_test -- [tx_per_sec] [seconds] [max_batch_size] [batch_window_ms]
+//
+// No relays are configured, so the numbers cover validation, pooling, batching and commitment only.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use penum_ingress::{transaction_hash, PenumIngress};
+
+#[path = "../benches/support/mod.rs"]
+mod support;
+
+// Transactions are sent in evenly spaced bursts, since timers are too coarse to pace single sends at high rates
+const TICK: Duration = Duration::from_millis(10);
+
+fn arg(position: usize, default: u64) -> u64 {
+    std::env::args().nth(position).map_or(default, |value| value.parse().expect("arguments must be integers"))
+}
+
+// Nearest-rank percentile of sorted samples
+fn percentile(sorted: &[Duration], fraction: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((sorted.len() as f64 * fraction).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+#[tokio::main]
+async fn main() {
+    let rate = arg(1, 1_000).max(1);
+    let seconds = arg(2, 10);
+    let max_batch_size = arg(3, 100) as usize;
+    let batch_window = Duration::from_millis(arg(4, 200));
+
+    let total = (rate * seconds) as usize;
+    println!("signing {} transactions...", total);
+    let txs = support::signed_txs(total);
+
+    let ingress = PenumIngress::new(max_batch_size, batch_window, Vec::new());
+    let mut batches = ingress.subscribe_batches();
+    let processor = ingress.clone().spawn();
+
+    // Submission time by transaction hash, matched against each committed batch as it arrives
+    let submitted_at: Arc<Mutex<HashMap<[u8; 32], Instant>>> = Arc::new(Mutex::new(HashMap::new()));
+    let collector = {
+        let submitted_at = submitted_at.clone();
+        tokio::spawn(async move {
+            let (mut latencies, mut batch_count) = (Vec::new(), 0);
+            while let Ok(batch) = batches.recv().await {
+                let committed_at = Instant::now();
+                batch_count += 1;
+                let submitted_at = submitted_at.lock().unwrap();
+                for tx in batch.transactions.iter().filter(|tx| !tx.is_decoy()) {
+                    if let Some(at) = submitted_at.get(&transaction_hash(&tx.tx_bytes)) {
+                        latencies.push(committed_at.duration_since(*at));
+                    }
+                }
+            }
+            (latencies, batch_count)
+        })
+    };
+
+    let per_tick = ((rate as f64 * TICK.as_secs_f64()).ceil() as usize).max(1);
+    let mut ticker = tokio::time::interval(TICK);
+    let (mut accepted, mut rejected) = (0usize, 0usize);
+    let started = Instant::now();
+    for burst in txs.chunks(per_tick) {
+        ticker.tick().await;
+        for tx in burst {
+            let hash = transaction_hash(tx);
+            submitted_at.lock().unwrap().insert(hash, Instant::now());
+            match ingress.submit_transaction(tx.clone()).await {
+                Ok(_) => accepted += 1,
+                Err(_) => rejected += 1,
+            }
+        }
+    }
+    let elapsed = started.elapsed();
+
+    // Shutting down flushes the partial last batch; dropping the ingress closes the batch channel
+    ingress.shutdown();
+    processor.await.unwrap();
+    drop(ingress);
+    let (mut latencies, batch_count) = collector.await.unwrap();
+    latencies.sort();
+
+    println!("target rate:       {} tx/s for {} s", rate, seconds);
+    println!("achieved rate:     {:.0} tx/s", accepted as f64 / elapsed.as_secs_f64());
+    println!("accepted/rejected: {}/{}", accepted, rejected);
+    println!("batches committed: {}", batch_count);
+    println!("batching latency:  p50 {:?}, p99 {:?}", percentile(&latencies, 0.50), percentile(&latencies, 0.99));
+}
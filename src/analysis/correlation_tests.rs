@@ -1,5 +1,12 @@
+use std::collections::HashMap;
 use std::time::{Duration, SystemTime};
 
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::batching::deterministic_shuffle;
+use crate::crypto::HashAlgo;
+
 // This module contains off-chain analysis tests for measuring
 // correlation probability reduction and batch entropy
 
@@ -61,10 +68,12 @@ fn calculate_timing_variance(times: &[(usize, SystemTime)]) -> f64 {
         return 0.0;
     }
     
-    // Convert SystemTime to milliseconds since epoch for calculation
+    // Convert SystemTime to milliseconds since the earliest time; variance ignores the offset, and
+    // unlike the epoch the earliest time is never later than any of the times
+    let earliest = times.iter().map(|(_, t)| *t).min().unwrap_or(SystemTime::UNIX_EPOCH);
     let time_values: Vec<u128> = times
         .iter()
-        .map(|(_, t)| t.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis())
+        .map(|(_, t)| t.duration_since(earliest).unwrap_or_default().as_millis())
         .collect();
     
     let mean: f64 = time_values.iter().map(|&x| x as f64).sum::<f64>() / time_values.len() as f64;
@@ -90,7 +99,7 @@ pub fn measure_batch_entropy(batch: &[usize]) -> f64 {
     let n = batch.len();
     let mut position_counts = vec![0; n];
     
-    for (_, &tx_id) in batch.iter().enumerate() {
+    for &tx_id in batch.iter() {
         if tx_id < n {
             position_counts[tx_id] += 1;
         }
@@ -108,21 +117,173 @@ pub fn measure_batch_entropy(batch: &[usize]) -> f64 {
     entropy
 }
 
+/// Measures ordering entropy of a realized permutation relative to the original order
+///
+/// Each transaction's cyclic displacement (shuffled position minus original position, mod n) is
+/// treated as a sample, and the Shannon entropy of those displacements is returned in bits. An
+/// unshuffled batch scores 0; a uniformly random shuffle approaches log2(n).
+pub fn measure_permutation_entropy(original: &[usize], shuffled: &[usize]) -> f64 {
+    let n = original.len();
+    if n == 0 || shuffled.len() != n {
+        return 0.0;
+    }
+
+    let original_positions: HashMap<usize, usize> = original
+        .iter()
+        .enumerate()
+        .map(|(position, &tx_id)| (tx_id, position))
+        .collect();
+
+    let mut displacement_counts = vec![0; n];
+    for (position, tx_id) in shuffled.iter().enumerate() {
+        let Some(&original_position) = original_positions.get(tx_id) else {
+            return 0.0;
+        };
+        displacement_counts[(position + n - original_position) % n] += 1;
+    }
+
+    let mut entropy = 0.0;
+    for &count in &displacement_counts {
+        if count > 0 {
+            let probability = count as f64 / n as f64;
+            entropy -= probability * probability.log2();
+        }
+    }
+
+    entropy
+}
+
+/// Measures the ordering entropy penum-ingress's deterministic shuffle gives a batch of `n` transactions
+pub fn measure_shuffle_entropy(batch_id: &str, n: usize) -> f64 {
+    let original: Vec<usize> = (0..n).collect();
+    let mut shuffled = original.clone();
+    deterministic_shuffle(&mut shuffled, batch_id, HashAlgo::default());
+
+    measure_permutation_entropy(&original, &shuffled)
+}
+
+/// Simulates penum-ingress releasing user actions in batches of `batch_size`
+///
+/// Each batch is released when its last transaction arrives, delayed by a uniform jitter in
+/// [0, max_jitter] drawn from a seeded RNG so runs are reproducible. Every member of a batch is
+/// observed by relays at the same moment.
+pub fn simulate_batch_release(
+    actions: &[(usize, SystemTime)],
+    batch_size: usize,
+    max_jitter: Duration,
+    seed: u64,
+) -> Vec<(usize, SystemTime)> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut observations = Vec::with_capacity(actions.len());
+
+    for batch in actions.chunks(batch_size.max(1)) {
+        let last_arrival = batch.iter().map(|(_, time)| *time).max().unwrap();
+        let release_time = last_arrival + max_jitter.mul_f64(rng.gen_range(0.0..=1.0));
+        observations.extend(batch.iter().map(|(id, _)| (*id, release_time)));
+    }
+
+    observations
+}
+
+/// Estimates how often a timing adversary links a user action to its relay observation
+///
+/// For every action the adversary picks the nearest observation at or after it (a transaction
+/// can't be seen before it is sent). Every observation within `max_jitter` of that pick is
+/// indistinguishable by timing, and at least `batch_size` are since a batch is released at once,
+/// so the adversary guesses uniformly among them. Returns the expected fraction linked correctly.
+///
+/// Observations are sorted once and searched per action, so this runs in O(n log n).
+pub fn estimate_correlation_success(
+    actions: &[(usize, SystemTime)],
+    observations: &[(usize, SystemTime)],
+    batch_size: usize,
+    max_jitter: Duration,
+) -> f64 {
+    if actions.is_empty() || observations.is_empty() {
+        return 0.0;
+    }
+
+    let mut observed_times: Vec<SystemTime> = observations.iter().map(|(_, observed)| *observed).collect();
+    observed_times.sort_unstable();
+    let mut observed_by_id: HashMap<usize, Vec<SystemTime>> = HashMap::new();
+    for (id, observed) in observations {
+        observed_by_id.entry(*id).or_default().push(*observed);
+    }
+
+    let mut total = 0.0;
+    for (id, action_time) in actions {
+        let after = observed_times.partition_point(|observed| observed < action_time);
+        let nearest_time = observed_times[after.min(observed_times.len() - 1)];
+
+        // Candidates are the contiguous run of observations within max_jitter of the pick
+        let earliest = nearest_time.checked_sub(max_jitter);
+        let start = earliest.map_or(0, |earliest| observed_times.partition_point(|observed| *observed < earliest));
+        let end = match nearest_time.checked_add(max_jitter) {
+            Some(latest) => observed_times.partition_point(|observed| *observed <= latest),
+            None => observed_times.len(),
+        };
+        let candidates = end - start;
+
+        let is_candidate = |observed: &SystemTime| time_distance(*observed, nearest_time) <= max_jitter;
+        if observed_by_id.get(id).is_some_and(|times| times.iter().any(is_candidate)) {
+            total += 1.0 / candidates.max(batch_size.max(1)) as f64;
+        }
+    }
+
+    total / actions.len() as f64
+}
+
+fn time_distance(a: SystemTime, b: SystemTime) -> Duration {
+    a.duration_since(b).or_else(|_| b.duration_since(a)).unwrap_or_default()
+}
+
 /// Simulates an adversary attempting to correlate transactions
+///
+/// Direct submissions are observed the moment they are sent; batched ones follow
+/// `simulate_batch_release` with the given batch size and jitter.
 pub fn simulate_correlation_attack(
-    _direct_times: &[(usize, SystemTime)], 
-    _batched_times: &[(usize, SystemTime)]
+    direct_times: &[(usize, SystemTime)],
+    batch_size: usize,
+    max_jitter: Duration,
 ) -> (f64, f64) { // (direct_success_rate, batched_success_rate)
-    // This is a simplified model of correlation success
-    // In reality, adversaries would use more sophisticated statistical methods
+    let direct_success_rate = estimate_correlation_success(direct_times, direct_times, 1, Duration::ZERO);
+
+    let batched_times = simulate_batch_release(direct_times, batch_size, max_jitter, 0);
+    let batched_success_rate = estimate_correlation_success(direct_times, &batched_times, batch_size, max_jitter);
+
+    (direct_success_rate, batched_success_rate)
+}
+
+// Example usage and demonstration
+pub fn run_correlation_analysis() {
+    println!("Running off-chain correlation analysis tests...");
     
-    // Direct submission - higher correlation success rate
-    let direct_success_rate = 0.95; // 95% correlation success for direct submission
+    let base_time = SystemTime::now();
+    let num_transactions = 1000;
+    let batch_size = 10;
+    let max_jitter = Duration::from_millis(50);
     
-    // Batched submission - reduced correlation success rate
-    let batched_success_rate = 0.30; // 30% correlation success with batching
+    // Simulate both approaches
+    let direct_times = simulate_direct_submission(num_transactions, base_time);
+    let batched_times = simulate_batched_submission(num_transactions, base_time, batch_size);
     
-    (direct_success_rate, batched_success_rate)
+    // Measure timing correlation reduction
+    let reduction_ratio = measure_timing_correlation_reduction(&direct_times, &batched_times);
+    println!("Timing correlation reduction ratio: {:.2}", reduction_ratio);
+    
+    // Simulate correlation attack success rates
+    let (direct_success, batched_success) = simulate_correlation_attack(&direct_times, batch_size, max_jitter);
+    println!("Correlation attack success:");
+    println!("  Direct submission: {:.2}%", direct_success * 100.0);
+    println!("  With penum-ingress: {:.2}%", batched_success * 100.0);
+    
+    // Calculate improvement
+    let improvement = ((direct_success - batched_success) / direct_success) * 100.0;
+    println!("Correlation reduction improvement: {:.2}%", improvement);
+
+    // Ordering unpredictability of the deterministic shuffle, against the log2(n) ceiling
+    let ordering_entropy = measure_shuffle_entropy(&uuid::Uuid::new_v4().to_string(), batch_size);
+    println!("Ordering entropy per batch: {:.2} of {:.2} bits", ordering_entropy, (batch_size as f64).log2());
 }
 
 #[cfg(test)]
@@ -146,6 +307,14 @@ mod tests {
         assert!(reduction_ratio >= 0.0);
     }
     
+    #[test]
+    fn test_timing_variance_of_times_before_epoch() {
+        // A badly set clock can report times before the Unix epoch; only their spread matters
+        let before_epoch = SystemTime::UNIX_EPOCH - Duration::from_secs(10);
+        let times = [(0, before_epoch), (1, before_epoch + Duration::from_millis(200))];
+        assert_eq!(calculate_timing_variance(&times), 10_000.0);
+    }
+
     #[test]
     fn test_batch_entropy() {
         // Create a shuffled batch (simulating the deterministic shuffle in penum-ingress)
@@ -156,45 +325,70 @@ mod tests {
         assert!(entropy >= 0.0);
     }
     
+    #[test]
+    fn test_permutation_entropy_identity_vs_shuffled() {
+        let original: Vec<usize> = (0..256).collect();
+        assert_eq!(measure_permutation_entropy(&original, &original), 0.0);
+
+        // A rotation moves every transaction but keeps the order predictable
+        let mut rotated = original.clone();
+        rotated.rotate_left(7);
+        assert_eq!(measure_permutation_entropy(&original, &rotated), 0.0);
+
+        let mut shuffled = original.clone();
+        deterministic_shuffle(&mut shuffled, "batch-under-test", HashAlgo::default());
+        let entropy = measure_permutation_entropy(&original, &shuffled);
+        assert!(entropy > 0.75 * 8.0, "shuffled entropy {:.2} too low", entropy);
+        assert!(entropy <= 8.0);
+    }
+
+    #[test]
+    fn test_shuffle_entropy_grows_with_batch_size() {
+        let small = measure_shuffle_entropy("batch-under-test", 8);
+        let large = measure_shuffle_entropy("batch-under-test", 512);
+
+        assert!(large > small);
+        assert!(large > 0.75 * 9.0, "shuffle entropy {:.2} too low", large);
+    }
+
     #[test]
     fn test_correlation_attack_success_rates() {
         let base_time = SystemTime::now();
         let direct = simulate_direct_submission(100, base_time);
-        let batched = simulate_batched_submission(100, base_time, 10);
-        
-        let (direct_success, batched_success) = simulate_correlation_attack(&direct, &batched);
-        
-        assert!(direct_success > batched_success, 
+
+        let (direct_success, batched_success) = simulate_correlation_attack(&direct, 10, Duration::from_millis(20));
+
+        // Direct submissions are linked exactly; a batch of ten caps the adversary at one in ten
+        assert_eq!(direct_success, 1.0);
+        assert!(batched_success <= 0.1 + f64::EPSILON, "batched success {:.3} above 1/batch_size", batched_success);
+        assert!(direct_success > batched_success,
                 "Direct submission should have higher correlation success rate");
-        
-        println!("Direct correlation success: {:.2}%, Batching success: {:.2}%", 
-                 direct_success * 100.0, batched_success * 100.0);
     }
-}
 
-// Example usage and demonstration
-pub fn run_correlation_analysis() {
-    println!("Running off-chain correlation analysis tests...");
-    
-    let base_time = SystemTime::now();
-    let num_transactions = 1000;
-    let batch_size = 10;
-    
-    // Simulate both approaches
-    let direct_times = simulate_direct_submission(num_transactions, base_time);
-    let batched_times = simulate_batched_submission(num_transactions, base_time, batch_size);
-    
-    // Measure timing correlation reduction
-    let reduction_ratio = measure_timing_correlation_reduction(&direct_times, &batched_times);
-    println!("Timing correlation reduction ratio: {:.2}", reduction_ratio);
-    
-    // Simulate correlation attack success rates
-    let (direct_success, batched_success) = simulate_correlation_attack(&direct_times, &batched_times);
-    println!("Correlation attack success:");
-    println!("  Direct submission: {:.2}%", direct_success * 100.0);
-    println!("  With penum-ingress: {:.2}%", batched_success * 100.0);
-    
-    // Calculate improvement
-    let improvement = ((direct_success - batched_success) / direct_success) * 100.0;
-    println!("Correlation reduction improvement: {:.2}%", improvement);
-}
\ No newline at end of file
+    #[test]
+    fn test_larger_batches_lower_correlation_success() {
+        let direct = simulate_direct_submission(200, SystemTime::now());
+
+        for max_jitter in [Duration::ZERO, Duration::from_millis(30)] {
+            let rates: Vec<f64> = [2, 5, 10, 25, 50]
+                .iter()
+                .map(|&batch_size| simulate_correlation_attack(&direct, batch_size, max_jitter).1)
+                .collect();
+
+            for pair in rates.windows(2) {
+                assert!(pair[1] < pair[0], "success rates not decreasing with batch size: {:?}", rates);
+            }
+        }
+    }
+
+    #[test]
+    fn test_jitter_does_not_help_the_adversary() {
+        let direct = simulate_direct_submission(200, SystemTime::now());
+
+        let (_, without_jitter) = simulate_correlation_attack(&direct, 5, Duration::ZERO);
+        let (_, with_jitter) = simulate_correlation_attack(&direct, 5, Duration::from_millis(100));
+
+        assert!((without_jitter - 0.2).abs() < 1e-9);
+        assert!(with_jitter <= without_jitter);
+    }
+}
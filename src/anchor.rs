@@ -0,0 +1,151 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::crypto::Commitment;
+#[cfg(feature = "net")]
+use crate::crypto::HashAlgo;
+use crate::error::IngressError;
+#[cfg(feature = "net")]
+use crate::transaction::Address;
+
+// Future returned by an anchor; resolves to the hash of the anchoring transaction
+pub type AnchorFuture<'a> = Pin<Box<dyn Future<Output = Result<[u8; 32], IngressError>> + Send + 'a>>;
+
+// Publishes batch commitments somewhere outside the ingress, tying them to an external timestamp
+pub trait CommitmentAnchor: Send + Sync {
+    fn anchor<'a>(&'a self, commitment: &'a Commitment) -> AnchorFuture<'a>;
+}
+
+// Signature of the contract function receiving each commitment
+#[cfg(feature = "net")]
+const ANCHOR_FUNCTION: &str = "anchor(bytes32)";
+
+// Anchors commitments by calling anchor(bytes32) on a contract through eth_sendTransaction
+//
+// The node behind rpc_url signs for the from account, so no keys are held by the ingress.
+#[cfg(feature = "net")]
+#[derive(Clone)]
+pub struct EthereumAnchor {
+    rpc_url: String,
+    from: Address,
+    contract: Address,
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "net")]
+impl EthereumAnchor {
+    pub fn new(rpc_url: String, from: Address, contract: Address) -> Self {
+        Self {
+            rpc_url,
+            from,
+            contract,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    // ABI-encoded call: the 4-byte function selector followed by the commitment as a bytes32 word
+    pub fn calldata(commitment: &Commitment) -> Vec<u8> {
+        let mut calldata = HashAlgo::Keccak256.hash(ANCHOR_FUNCTION.as_bytes())[..4].to_vec();
+        calldata.extend_from_slice(commitment.as_bytes());
+        calldata
+    }
+
+    async fn send(&self, commitment: &Commitment) -> Result<[u8; 32], IngressError> {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "eth_sendTransaction",
+            "params": [{
+                "from": format!("0x{}", hex::encode(self.from)),
+                "to": format!("0x{}", hex::encode(self.contract)),
+                "data": format!("0x{}", hex::encode(Self::calldata(commitment))),
+            }],
+        });
+
+        let response = self
+            .client
+            .post(&self.rpc_url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| IngressError::Anchor(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(IngressError::Anchor(format!("HTTP {}", response.status())));
+        }
+
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| IngressError::Anchor(format!("Invalid JSON-RPC response: {}", e)))?;
+        if let Some(rpc_error) = body.get("error") {
+            return Err(IngressError::Anchor(rpc_error.to_string()));
+        }
+
+        // The result is the hash of the anchoring transaction
+        body.get("result")
+            .and_then(serde_json::Value::as_str)
+            .and_then(|result| result.strip_prefix("0x"))
+            .and_then(|hash| hex::decode(hash).ok())
+            .and_then(|hash| <[u8; 32]>::try_from(hash).ok())
+            .ok_or_else(|| IngressError::Anchor(format!("Invalid transaction hash in response: {}", body)))
+    }
+}
+
+#[cfg(feature = "net")]
+impl CommitmentAnchor for EthereumAnchor {
+    fn anchor<'a>(&'a self, commitment: &'a Commitment) -> AnchorFuture<'a> {
+        Box::pin(self.send(commitment))
+    }
+}
+
+#[cfg(all(test, feature = "net"))]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const FROM: Address = [0x11; 20];
+    const CONTRACT: Address = [0x22; 20];
+
+    #[tokio::test]
+    async fn test_commitment_sent_in_calldata() {
+        let rpc = MockServer::start().await;
+        let tx_hash = [0xab; 32];
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({"method": "eth_sendTransaction"})))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": format!("0x{}", hex::encode(tx_hash))})),
+            )
+            .expect(1)
+            .mount(&rpc)
+            .await;
+        let anchor = EthereumAnchor::new(rpc.uri(), FROM, CONTRACT);
+        let commitment = Commitment([0x5a; 32]);
+
+        assert_eq!(anchor.anchor(&commitment).await, Ok(tx_hash));
+
+        let requests = rpc.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        let call = &body["params"][0];
+        assert_eq!(call["from"], format!("0x{}", hex::encode(FROM)));
+        assert_eq!(call["to"], format!("0x{}", hex::encode(CONTRACT)));
+        let data = hex::decode(call["data"].as_str().unwrap().strip_prefix("0x").unwrap()).unwrap();
+        assert_eq!(data.len(), 4 + 32);
+        assert_eq!(&data[4..], commitment.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_rpc_error_surfaces_as_anchor_error() {
+        let rpc = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"jsonrpc": "2.0", "id": 0, "error": {"code": -32000, "message": "unknown account"}}),
+            ))
+            .mount(&rpc)
+            .await;
+        let anchor = EthereumAnchor::new(rpc.uri(), FROM, CONTRACT);
+
+        assert!(matches!(anchor.anchor(&Commitment([0; 32])).await, Err(IngressError::Anchor(_))));
+    }
+}
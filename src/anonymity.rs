@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use crate::batch::TransactionBatch;
+use crate::transaction::{transaction_fees, transaction_to};
+
+// Default width of a gas price bucket: 1 gwei
+pub const DEFAULT_GAS_PRICE_BUCKET: u128 = 1_000_000_000;
+
+// Public attribute an observer could use to single a transaction out of its batch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QuasiIdentifier {
+    #[default]
+    Destination,          // Recipient or called contract; contract creations form one group
+    GasPriceBucket(u128), // Max fee rounded down to a multiple of this many wei
+}
+
+impl QuasiIdentifier {
+    // Group key of one transaction; transactions that cannot be decoded share a group of their own
+    fn key(&self, tx_bytes: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            QuasiIdentifier::Destination => {
+                transaction_to(tx_bytes).ok().map(|to| to.map(Vec::from).unwrap_or_default())
+            }
+            QuasiIdentifier::GasPriceBucket(width) => transaction_fees(tx_bytes)
+                .ok()
+                .map(|fees| (fees.max_fee / (*width).max(1)).to_be_bytes().to_vec()),
+        }
+    }
+}
+
+// Size of the smallest group of a batch's transactions sharing the same quasi-identifier
+//
+// A k of 1 means some transaction is the only one with its value and so stands out despite the
+// batching. Decoys are never forwarded and don't count; a batch without real transactions has k = 0.
+pub fn k_anonymity(batch: &TransactionBatch, quasi_identifier: QuasiIdentifier) -> usize {
+    let mut groups: HashMap<Option<Vec<u8>>, usize> = HashMap::new();
+    for tx in batch.transactions.iter().filter(|tx| !tx.is_decoy()) {
+        *groups.entry(quasi_identifier.key(&tx.tx_bytes)).or_insert(0) += 1;
+    }
+    groups.into_values().min().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::TransactionEnvelope;
+    use crate::test_utils::{fee_bidding_tx, transfer_to};
+
+    fn batch_of(txs: Vec<Vec<u8>>) -> TransactionBatch {
+        TransactionBatch::new(txs.into_iter().map(|tx| TransactionEnvelope::new(tx, String::new())).collect()).unwrap()
+    }
+
+    #[test]
+    fn test_unique_destination_gives_k_of_one() {
+        let batch = batch_of(vec![
+            transfer_to(1, [0xaa; 20]),
+            transfer_to(2, [0xaa; 20]),
+            transfer_to(3, [0xaa; 20]),
+            transfer_to(4, [0xbb; 20]),
+        ]);
+
+        assert_eq!(k_anonymity(&batch, QuasiIdentifier::Destination), 1);
+        // The same batch is uniform by gas price
+        assert_eq!(k_anonymity(&batch, QuasiIdentifier::GasPriceBucket(DEFAULT_GAS_PRICE_BUCKET)), 4);
+    }
+
+    #[test]
+    fn test_gas_price_buckets_group_nearby_bids() {
+        let batch = batch_of(vec![
+            fee_bidding_tx(1, 1_000_000_000, 30_100_000_000),
+            fee_bidding_tx(2, 1_000_000_000, 30_900_000_000),
+            fee_bidding_tx(3, 1_000_000_000, 45_000_000_000),
+            fee_bidding_tx(4, 1_000_000_000, 45_200_000_000),
+        ]);
+
+        assert_eq!(k_anonymity(&batch, QuasiIdentifier::GasPriceBucket(DEFAULT_GAS_PRICE_BUCKET)), 2);
+        assert_eq!(k_anonymity(&batch, QuasiIdentifier::GasPriceBucket(100_000_000)), 1);
+        assert_eq!(k_anonymity(&batch, QuasiIdentifier::Destination), 4);
+        assert_eq!(k_anonymity(&batch_of(Vec::new()), QuasiIdentifier::Destination), 0);
+    }
+}
@@ -0,0 +1,382 @@
+use std::time::SystemTime;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{generate_nonce, Commitment, CommitmentScheme, HashAlgo, Nonce};
+use crate::entropy::{Entropy, OsEntropy};
+use crate::envelope::TransactionEnvelope;
+use crate::error::IngressError;
+use crate::merkle::{build_merkle_proof, canonical_commitment, MerkleProof};
+use crate::operator::OPERATOR_SIGNATURE_LEN;
+use crate::wire::BatchWire;
+
+// Batches at least this large hash their transactions across the rayon pool; below it the
+// thread hand-off costs more than it saves
+pub const PARALLEL_HASH_THRESHOLD: usize = 512;
+
+// How batch IDs, and with them the shuffle seeds, are chosen
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BatchIdMode {
+    #[default]
+    Random,           // Fresh UUID per batch, unlinkable to its contents
+    ContentAddressed, // Hex of the unsalted Merkle root, so the same transaction set always gets the same ID
+}
+
+// What made the batching engine cut a batch
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BatchTrigger {
+    SizeThreshold, // The pending pool reached the batch size
+    TimeWindow,    // The lane's time window elapsed
+    ForcedFlush,   // flush or flush_lanes, e.g. on shutdown
+    MaxAge,        // The oldest pending transaction waited max_pending_age
+}
+
+impl BatchTrigger {
+    pub const ALL: [BatchTrigger; 4] =
+        [BatchTrigger::SizeThreshold, BatchTrigger::TimeWindow, BatchTrigger::ForcedFlush, BatchTrigger::MaxAge];
+
+    // Name used for the trigger label in the Prometheus exposition
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BatchTrigger::SizeThreshold => "size_threshold",
+            BatchTrigger::TimeWindow => "time_window",
+            BatchTrigger::ForcedFlush => "forced_flush",
+            BatchTrigger::MaxAge => "max_age",
+        }
+    }
+}
+
+// Batch structure for grouping transactions; serializes to the versioned layout in wire
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(into = "BatchWire", try_from = "BatchWire")]
+pub struct TransactionBatch {
+    pub id: String,
+    pub transactions: Vec<TransactionEnvelope>,
+    pub commitment: Commitment,
+    pub timestamp: SystemTime,
+    pub nonce: Nonce,
+    pub hash_algo: HashAlgo,
+    pub commitment_scheme: CommitmentScheme, // How transactions are hashed into the commitment and content ID
+    pub operator_signature: Option<[u8; OPERATOR_SIGNATURE_LEN]>, // Operator's signature over the commitment, set at commit
+    pub permutation: Option<Vec<usize>>, // Ordering applied to the pre-ordering order, when the engine records it
+    pub sequence: u64, // Position among the engine's batches from 0, so auditors can spot a missing one
+    pub trigger: Option<BatchTrigger>, // Why the engine cut the batch; local only, never serialized
+}
+
+impl TransactionBatch {
+    pub fn new(transactions: Vec<TransactionEnvelope>) -> Result<Self, IngressError> {
+        Self::with_hash_algo(transactions, HashAlgo::default())
+    }
+
+    // Builds a batch whose commitment and proofs use the given hash function
+    pub fn with_hash_algo(transactions: Vec<TransactionEnvelope>, hash_algo: HashAlgo) -> Result<Self, IngressError> {
+        Self::with_id_mode(transactions, hash_algo, BatchIdMode::Random)
+    }
+
+    // Builds a batch whose ID follows id_mode; the commitment stays salted with a random nonce either way
+    pub fn with_id_mode(
+        transactions: Vec<TransactionEnvelope>,
+        hash_algo: HashAlgo,
+        id_mode: BatchIdMode,
+    ) -> Result<Self, IngressError> {
+        Self::with_entropy(transactions, hash_algo, id_mode, &OsEntropy)
+    }
+
+    // Builds a batch like with_id_mode, drawing its nonce and any random ID from entropy
+    pub fn with_entropy(
+        transactions: Vec<TransactionEnvelope>,
+        hash_algo: HashAlgo,
+        id_mode: BatchIdMode,
+        entropy: &dyn Entropy,
+    ) -> Result<Self, IngressError> {
+        let nonce = generate_nonce(entropy)?;
+        let mut id_bytes = [0u8; 16];
+        if id_mode == BatchIdMode::Random {
+            entropy.fill(&mut id_bytes)?;
+        }
+        let random_id = uuid::Builder::from_random_bytes(id_bytes).into_uuid();
+        Ok(Self::build(transactions, hash_algo, id_mode, nonce, random_id))
+    }
+
+    // Builds a batch salted with the caller's nonce, so its commitment is reproducible, e.g. against
+    // known test vectors
+    //
+    // A nonce that is ever reused or guessable lets anyone confirm a guessed transaction set against
+    // the commitment before the reveal; production batches should keep using new.
+    pub fn with_nonce(transactions: Vec<TransactionEnvelope>, nonce: Nonce) -> Self {
+        Self::build(transactions, HashAlgo::default(), BatchIdMode::Random, nonce, uuid::Uuid::new_v4())
+    }
+
+    fn build(
+        transactions: Vec<TransactionEnvelope>,
+        hash_algo: HashAlgo,
+        id_mode: BatchIdMode,
+        nonce: Nonce,
+        random_id: uuid::Uuid,
+    ) -> Self {
+        let scheme = CommitmentScheme::default();
+        let hashes = tx_hashes(&transactions, scheme, hash_algo);
+        let id = match id_mode {
+            BatchIdMode::Random => random_id.to_string(),
+            // The nonce is random, so the ID is taken from the root of the same sorted tree without salt
+            BatchIdMode::ContentAddressed => canonical_commitment(&hashes, &[], hash_algo).to_string(),
+        };
+
+        // Commitment is the Merkle root over the nonce-salted, sorted transaction hashes
+        let commitment = canonical_commitment(&hashes, nonce.as_bytes(), hash_algo);
+
+        Self {
+            id,
+            transactions,
+            commitment,
+            timestamp: SystemTime::now(),
+            nonce,
+            hash_algo,
+            commitment_scheme: scheme,
+            operator_signature: None,
+            permutation: None,
+            sequence: 0,
+            trigger: None,
+        }
+    }
+
+    // Recommits the batch under an older scheme, for verifiers that predate the current one
+    //
+    // Only the commitment is recomputed; a content-addressed ID keeps the current scheme's value.
+    pub fn with_commitment_scheme(mut self, scheme: CommitmentScheme) -> Self {
+        if scheme != self.commitment_scheme {
+            self.commitment_scheme = scheme;
+            self.commitment = self.recompute_commitment();
+        }
+        self
+    }
+
+    // Commitment over the batch's current contents, for checking a reveal or after replacing them
+    pub(crate) fn recompute_commitment(&self) -> Commitment {
+        canonical_commitment(
+            &tx_hashes(&self.transactions, self.commitment_scheme, self.hash_algo),
+            self.nonce.as_bytes(),
+            self.hash_algo,
+        )
+    }
+
+    // Earliest deadline among the batch's transactions, the last block the whole batch may land in
+    pub fn valid_until_block(&self) -> Option<u64> {
+        self.transactions.iter().filter_map(|tx| tx.valid_until_block).min()
+    }
+
+    // Bytes the batch's transactions take in a relay request, each as the 0x-prefixed hex JSON-RPC
+    // params carry; the request's own framing comes on top
+    pub fn payload_bytes(&self) -> usize {
+        self.transactions.iter().map(|tx| payload_len(&tx.tx_bytes)).sum()
+    }
+
+    // Splits the batch into consecutive parts of at most max_payload_bytes each, in forwarding order, for
+    // relays that refuse oversized requests; a batch that fits comes back whole
+    //
+    // Parts keep the batch's ID, sequence, commitment, nonce and operator signature, so relays and
+    // auditors still see one committed batch, and inclusion proofs come from the whole batch. A
+    // transaction over the cap on its own goes out as a part by itself.
+    pub fn split(&self, max_payload_bytes: usize) -> Vec<TransactionBatch> {
+        let mut parts: Vec<Vec<TransactionEnvelope>> = Vec::new();
+        let mut part_bytes = 0;
+        for tx in &self.transactions {
+            let len = payload_len(&tx.tx_bytes);
+            match parts.last_mut() {
+                Some(part) if part_bytes + len <= max_payload_bytes => {
+                    part.push(tx.clone());
+                    part_bytes += len;
+                }
+                _ => {
+                    parts.push(vec![tx.clone()]);
+                    part_bytes = len;
+                }
+            }
+        }
+        if parts.len() <= 1 {
+            return vec![self.clone()];
+        }
+
+        parts
+            .into_iter()
+            .map(|transactions| Self {
+                id: self.id.clone(),
+                transactions,
+                commitment: self.commitment,
+                timestamp: self.timestamp,
+                nonce: self.nonce,
+                hash_algo: self.hash_algo,
+                commitment_scheme: self.commitment_scheme,
+                operator_signature: self.operator_signature,
+                // The permutation describes the whole batch, not any one part
+                permutation: None,
+                sequence: self.sequence,
+                trigger: self.trigger,
+            })
+            .collect()
+    }
+
+    // Membership proof for a single transaction, letting it be disclosed without the rest of the batch
+    pub fn merkle_proof(&self, tx_bytes: &[u8]) -> Option<MerkleProof> {
+        let envelope_version = self.transactions.iter().find(|tx| tx.tx_bytes == tx_bytes)?.envelope_version;
+        build_merkle_proof(
+            &tx_hashes(&self.transactions, self.commitment_scheme, self.hash_algo),
+            self.nonce.as_bytes(),
+            &self.commitment_scheme.tx_hash(tx_bytes, envelope_version, self.hash_algo),
+            self.hash_algo,
+            self.commitment_scheme,
+            envelope_version,
+        )
+    }
+}
+
+// Helper function to measure a transaction as a relay request carries it, 0x-prefixed hex
+fn payload_len(tx_bytes: &[u8]) -> usize {
+    2 + 2 * tx_bytes.len()
+}
+
+// Helper function to hash every transaction in a batch, in parallel for large batches
+//
+// Hashes come back in transaction order either way, and the commitment sorts them anyway, so the
+// result never depends on which path ran.
+pub(crate) fn tx_hashes(transactions: &[TransactionEnvelope], scheme: CommitmentScheme, algo: HashAlgo) -> Vec<Vec<u8>> {
+    if transactions.len() >= PARALLEL_HASH_THRESHOLD {
+        transactions.par_iter().map(|tx| scheme.tx_hash(&tx.tx_bytes, tx.envelope_version, algo)).collect()
+    } else {
+        serial_tx_hashes(transactions, scheme, algo)
+    }
+}
+
+fn serial_tx_hashes(transactions: &[TransactionEnvelope], scheme: CommitmentScheme, algo: HashAlgo) -> Vec<Vec<u8>> {
+    transactions.iter().map(|tx| scheme.tx_hash(&tx.tx_bytes, tx.envelope_version, algo)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::verify_merkle_proof;
+
+    fn sample_batch() -> TransactionBatch {
+        let transactions = (0..5u8)
+            .map(|i| TransactionEnvelope::new(vec![0x02, i], i.to_string()))
+            .collect();
+        TransactionBatch::new(transactions).unwrap()
+    }
+
+    #[test]
+    fn test_merkle_proof_verifies_member() {
+        let batch = sample_batch();
+
+        for tx in &batch.transactions {
+            let proof = batch.merkle_proof(&tx.tx_bytes).expect("member should have a proof");
+            assert!(verify_merkle_proof(&batch.commitment, &tx.tx_bytes, &proof));
+        }
+    }
+
+    #[test]
+    fn test_merkle_proof_rejects_non_member() {
+        let batch = sample_batch();
+        let outsider = vec![0x02, 0xff];
+
+        assert!(batch.merkle_proof(&outsider).is_none());
+
+        // A member's proof must not validate a different transaction
+        let proof = batch.merkle_proof(&batch.transactions[0].tx_bytes).unwrap();
+        assert!(!verify_merkle_proof(&batch.commitment, &outsider, &proof));
+
+        // Nor validate against another batch's commitment
+        let other = sample_batch();
+        assert!(!verify_merkle_proof(&other.commitment, &batch.transactions[0].tx_bytes, &proof));
+    }
+
+    #[test]
+    fn test_content_addressed_id_depends_only_on_transaction_set() {
+        let transactions: Vec<TransactionEnvelope> = (0..5u8)
+            .map(|i| TransactionEnvelope::new(vec![0x02, i], i.to_string()))
+            .collect();
+        let mut reordered = transactions.clone();
+        reordered.reverse();
+
+        let content_addressed =
+            |transactions| TransactionBatch::with_id_mode(transactions, HashAlgo::Sha256, BatchIdMode::ContentAddressed).unwrap();
+        let first = content_addressed(transactions.clone());
+        let second = content_addressed(reordered);
+        assert_eq!(first.id, second.id);
+        // The commitment is still salted, so it is not reproducible from the transactions alone
+        assert_ne!(first.commitment, second.commitment);
+
+        assert_ne!(first.id, content_addressed(transactions[..4].to_vec()).id);
+        let random = TransactionBatch::with_hash_algo(transactions.clone(), HashAlgo::Sha256).unwrap();
+        assert_ne!(random.id, TransactionBatch::with_hash_algo(transactions, HashAlgo::Sha256).unwrap().id);
+    }
+
+    #[test]
+    fn test_keccak_batch_verifies_only_under_keccak() {
+        let transactions = (0..5u8)
+            .map(|i| TransactionEnvelope::new(vec![0x02, i], i.to_string()))
+            .collect();
+        let batch = TransactionBatch::with_hash_algo(transactions, HashAlgo::Keccak256).unwrap();
+        let tx = &batch.transactions[2].tx_bytes;
+
+        let mut proof = batch.merkle_proof(tx).unwrap();
+        assert_eq!(proof.hash_algo, HashAlgo::Keccak256);
+        assert!(verify_merkle_proof(&batch.commitment, tx, &proof));
+
+        proof.hash_algo = HashAlgo::Sha256;
+        assert!(!verify_merkle_proof(&batch.commitment, tx, &proof));
+    }
+
+    #[test]
+    fn test_fixed_nonce_commitment_matches_known_vector() {
+        let transactions: Vec<TransactionEnvelope> = (1..=3u8)
+            .map(|i| TransactionEnvelope::new(vec![0x02, i], String::new()))
+            .collect();
+        let v1 = |transactions, nonce| {
+            TransactionBatch::with_nonce(transactions, nonce).with_commitment_scheme(CommitmentScheme::V1)
+        };
+        let batch = v1(transactions.clone(), Nonce([0x11; 32]));
+
+        // SHA-256 root over sorted, salted, version-free leaves, computed independently of this crate
+        assert_eq!(
+            batch.commitment.to_string(),
+            "0x6f9f8c3d706808e84517f7b786d37979acfad5df8e51819eff1e1f098ec59eed"
+        );
+        assert_eq!(batch.nonce, Nonce([0x11; 32]));
+
+        // Reproducible across batches and orderings, and still bound to the nonce
+        let mut reordered = transactions.clone();
+        reordered.reverse();
+        assert_eq!(v1(reordered, Nonce([0x11; 32])).commitment, batch.commitment);
+        assert_ne!(v1(transactions.clone(), Nonce([0x12; 32])).commitment, batch.commitment);
+
+        // The current scheme binds envelope versions, so the same batch commits differently
+        assert_ne!(TransactionBatch::with_nonce(transactions, Nonce([0x11; 32])).commitment, batch.commitment);
+    }
+
+    #[test]
+    fn test_parallel_commitment_matches_serial() {
+        let transactions: Vec<TransactionEnvelope> = (0..4 * PARALLEL_HASH_THRESHOLD as u32)
+            .map(|i| TransactionEnvelope::new([&[0x02][..], &i.to_be_bytes()].concat(), String::new()))
+            .collect();
+        let batch = TransactionBatch::new(transactions.clone()).unwrap();
+
+        for algo in [HashAlgo::Sha256, HashAlgo::Keccak256] {
+            let parallel = tx_hashes(&transactions, CommitmentScheme::V2, algo);
+            let serial = serial_tx_hashes(&transactions, CommitmentScheme::V2, algo);
+            assert_eq!(parallel, serial);
+            assert_eq!(
+                canonical_commitment(&parallel, batch.nonce.as_bytes(), algo),
+                canonical_commitment(&serial, batch.nonce.as_bytes(), algo)
+            );
+        }
+        assert_eq!(
+            batch.commitment,
+            canonical_commitment(
+                &serial_tx_hashes(&transactions, batch.commitment_scheme, batch.hash_algo),
+                batch.nonce.as_bytes(),
+                batch.hash_algo
+            )
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+// How far back submissions are counted when estimating the arrival rate
+pub const DEFAULT_RATE_WINDOW: Duration = Duration::from_secs(10);
+// How long a batch should take to fill at the current arrival rate
+pub const DEFAULT_TARGET_FILL_TIME: Duration = Duration::from_secs(2);
+
+// Batch size policy that follows recent throughput: large batches under load, small ones when sparse
+pub struct AdaptiveBatchPolicy {
+    min_batch_size: usize,
+    max_batch_size: usize,
+    rate_window: Duration,
+    target_fill_time: Duration,
+    arrivals: VecDeque<SystemTime>, // Submission times inside the rate window, oldest first
+}
+
+impl AdaptiveBatchPolicy {
+    pub fn new(min_batch_size: usize, max_batch_size: usize) -> Self {
+        // A batch always holds at least one transaction and max never drops below min
+        let min_batch_size = min_batch_size.max(1);
+        Self {
+            min_batch_size,
+            max_batch_size: max_batch_size.max(min_batch_size),
+            rate_window: DEFAULT_RATE_WINDOW,
+            target_fill_time: DEFAULT_TARGET_FILL_TIME,
+            arrivals: VecDeque::new(),
+        }
+    }
+
+    // Sets the sliding window over which the arrival rate is measured
+    pub fn with_rate_window(mut self, rate_window: Duration) -> Self {
+        self.rate_window = rate_window;
+        self
+    }
+
+    // Sets how long a batch may take to fill, which bounds the wait of its first transaction
+    pub fn with_target_fill_time(mut self, target_fill_time: Duration) -> Self {
+        self.target_fill_time = target_fill_time;
+        self
+    }
+
+    pub fn record_arrival(&mut self, now: SystemTime) {
+        self.prune(now);
+        self.arrivals.push_back(now);
+    }
+
+    // Effective batch size: what the current arrival rate fills within the target time, kept within bounds
+    pub fn batch_size(&mut self, now: SystemTime) -> usize {
+        self.prune(now);
+
+        let window = self.rate_window.as_secs_f64();
+        let rate = if window > 0.0 { self.arrivals.len() as f64 / window } else { 0.0 };
+        let size = (rate * self.target_fill_time.as_secs_f64()).round() as usize;
+
+        size.clamp(self.min_batch_size, self.max_batch_size)
+    }
+
+    // Forgets arrivals that have left the rate window
+    fn prune(&mut self, now: SystemTime) {
+        while let Some(arrived_at) = self.arrivals.front() {
+            if now.duration_since(*arrived_at).unwrap_or_default() < self.rate_window {
+                break;
+            }
+            self.arrivals.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Records `count` arrivals evenly spaced by `interval`, returning the time of the last one
+    fn drive(policy: &mut AdaptiveBatchPolicy, start: SystemTime, count: u32, interval: Duration) -> SystemTime {
+        let mut now = start;
+        for i in 0..count {
+            now = start + interval * i;
+            policy.record_arrival(now);
+        }
+        now
+    }
+
+    #[test]
+    fn test_batch_size_grows_with_arrival_rate() {
+        let mut policy = AdaptiveBatchPolicy::new(2, 50)
+            .with_rate_window(Duration::from_secs(10))
+            .with_target_fill_time(Duration::from_secs(2));
+        let start = SystemTime::now();
+
+        // 10 tx/s fills 20 transactions in the target time
+        let now = drive(&mut policy, start, 100, Duration::from_millis(100));
+        assert_eq!(policy.batch_size(now), 20);
+
+        // A burst far above the rate the maximum can absorb is capped
+        let now = drive(&mut policy, now, 1000, Duration::from_millis(1));
+        assert_eq!(policy.batch_size(now), 50);
+    }
+
+    #[test]
+    fn test_batch_size_shrinks_when_sparse() {
+        let mut policy = AdaptiveBatchPolicy::new(2, 50)
+            .with_rate_window(Duration::from_secs(10))
+            .with_target_fill_time(Duration::from_secs(2));
+        let start = SystemTime::now();
+
+        let now = drive(&mut policy, start, 100, Duration::from_millis(100));
+        assert_eq!(policy.batch_size(now), 20);
+
+        // One transaction every five seconds never yields more than the minimum
+        let now = drive(&mut policy, now + Duration::from_secs(5), 6, Duration::from_secs(5));
+        assert_eq!(policy.batch_size(now), 2);
+
+        // Once the window has emptied the size rests at the minimum
+        assert_eq!(policy.batch_size(now + Duration::from_secs(60)), 2);
+    }
+
+    #[test]
+    fn test_bounds_are_normalized() {
+        let mut policy = AdaptiveBatchPolicy::new(0, 0);
+        assert_eq!(policy.batch_size(SystemTime::now()), 1);
+
+        let mut policy = AdaptiveBatchPolicy::new(8, 4);
+        assert_eq!(policy.batch_size(SystemTime::now()), 8);
+    }
+}
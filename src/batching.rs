@@ -0,0 +1,1861 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, SystemTime};
+
+
+use crate::batch::{BatchIdMode, BatchTrigger, TransactionBatch};
+use crate::batch_policy::AdaptiveBatchPolicy;
+use crate::clock::{Clock, SystemClock};
+use crate::crypto::{create_seed_from_batch_id, HashAlgo, ShuffleSecret};
+use crate::decoy::decoy_envelope;
+use crate::dedup::DedupCache;
+use crate::encryption::KeyShare;
+use crate::entropy::{Entropy, OsEntropy};
+use crate::envelope::TransactionEnvelope;
+use crate::error::{lock, IngressError};
+use crate::jitter::sample_poisson_window;
+use crate::ordering::{FeeDescendingPolicy, IdentityPolicy, OrderingPolicy, ShufflePolicy};
+use crate::pending::PendingPool;
+use crate::shuffle::shuffle_in_place;
+use crate::transaction::{
+    blob_sidecar_len, recover_sender, transaction_fees, transaction_hash, validate_transaction, Address,
+};
+use crate::wal::{Recovered, WriteAheadLog};
+
+// How long a submitted transaction is remembered for duplicate detection
+pub const DEFAULT_DEDUP_TTL: Duration = Duration::from_secs(600);
+
+// (key shares, plaintext hashes of the real members) of a batch the engine encrypted
+pub type SealedBatch = (Vec<KeyShare>, Vec<[u8; 32]>);
+
+// Upper bound on the combined size of transactions waiting for a batch
+pub const DEFAULT_MAX_PENDING_BYTES: usize = 64 * 1024 * 1024;
+
+// Upper bound on the combined size of blob sidecars waiting for a batch, budgeted apart from
+// DEFAULT_MAX_PENDING_BYTES since a single blob is larger than most whole transactions
+pub const DEFAULT_MAX_PENDING_BLOB_BYTES: usize = 256 * 1024 * 1024;
+
+// Built-in order in which a batch's transactions are forwarded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BatchOrdering {
+    #[default]
+    Shuffled,      // Deterministic privacy shuffle only
+    FeeDescending, // Shuffle, then highest fee bids first; trades ordering privacy for inclusion
+    Identity,      // Arrival order, unshuffled
+}
+
+// How long each lane waits between time-triggered batches
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowMode {
+    #[default]
+    Fixed,   // Every window lasts exactly the lane's time window
+    Poisson, // Each window is drawn afresh from an exponential distribution whose mean is the lane's time window
+}
+
+impl BatchOrdering {
+    // Policy implementing this ordering
+    pub fn policy(self) -> Arc<dyn OrderingPolicy> {
+        match self {
+            BatchOrdering::Shuffled => Arc::new(ShufflePolicy),
+            BatchOrdering::FeeDescending => Arc::new(FeeDescendingPolicy),
+            BatchOrdering::Identity => Arc::new(IdentityPolicy),
+        }
+    }
+}
+
+// Point-in-time view of every lane's pending transactions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PendingSnapshot {
+    pub count: usize,
+    pub total_bytes: usize,           // Blob sidecars included
+    pub oldest_age: Option<Duration>, // How long the longest-waiting transaction has been pending, None while empty
+    pub distinct_senders: usize,      // Senders recorded on the envelopes, counted once across lanes
+}
+
+// A batching lane with its own size threshold, time window and pending pool
+#[derive(Clone)]
+pub(crate) struct Lane {
+    max_batch_size: usize,
+    batch_time_window: Duration,
+    pub(crate) pending: Arc<PendingPool>,
+    last_batch_time: Arc<Mutex<SystemTime>>,
+    window: Arc<Mutex<Duration>>, // Length of the current window, batch_time_window unless drawn in Poisson mode
+}
+
+impl Lane {
+    // The lane's first window starts at now and lasts window
+    fn new(max_batch_size: usize, batch_time_window: Duration, now: SystemTime, window: Duration) -> Self {
+        Self {
+            max_batch_size,
+            batch_time_window,
+            pending: Arc::new(PendingPool::new()),
+            last_batch_time: Arc::new(Mutex::new(now)),
+            window: Arc::new(Mutex::new(window)),
+        }
+    }
+
+    // A poisoned lock still holds a valid time, at worst one batch older than it should be
+    fn last_batch_time(&self) -> MutexGuard<'_, SystemTime> {
+        self.last_batch_time.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // A poisoned lock still holds a valid length
+    fn window(&self) -> MutexGuard<'_, Duration> {
+        self.window.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Batching engine that batches transactions based on time window or size; clones share the same state
+//
+// Named lanes batch independently of the default lane and of each other, sharing deduplication,
+// the write-ahead log and the pending memory budget.
+#[derive(Clone)]
+pub struct BatchingEngine {
+    pub(crate) default_lane: Lane,
+    lanes: HashMap<String, Lane>,
+    pending_bytes: Arc<AtomicUsize>, // Combined size of the transactions pending in every lane, blob sidecars aside
+    pending_blob_bytes: Arc<AtomicUsize>, // Combined size of the blob sidecars pending in every lane
+    seen_transactions: Arc<Mutex<DedupCache>>,
+    batch_policy: Option<Arc<Mutex<AdaptiveBatchPolicy>>>, // Replaces the default lane's max_batch_size when set
+    wal: Option<Arc<WriteAheadLog>>,
+    hash_algo: HashAlgo,
+    decoy_target_size: Option<usize>,
+    max_pending_bytes: usize,
+    max_pending_blob_bytes: usize,
+    ordering: Arc<dyn OrderingPolicy>,
+    window_mode: WindowMode,
+    id_mode: BatchIdMode,
+    rng_seed: Option<[u8; 32]>, // Replaces the batch-ID-derived shuffle seed when set
+    shuffle_secret: Option<ShuffleSecret>, // Mixed into the batch-ID-derived shuffle seed when set
+    record_permutation: bool,
+    min_batch: Option<(usize, Duration)>, // (min_batch_size, max_wait) gating time-triggered batches
+    min_distinct_senders: usize,          // Batches from fewer senders stay pending, however they were triggered
+    max_pending_age: Option<Duration>,
+    nonce_gap_timeout: Option<Duration>, // Holds transactions past a sender's nonce gap for up to this long when set
+    nonce_gap_holds: Arc<Mutex<HashMap<(Address, u64), SystemTime>>>, // When each held (sender, nonce) was first held
+    next_sequence: Arc<Mutex<u64>>, // Sequence number of the next batch, across every lane
+    clock: Arc<dyn Clock>,
+    entropy: Arc<dyn Entropy>, // Randomness for batch nonces, random batch IDs, decoys and batch keys
+    encryption: Option<(u8, u8)>, // (threshold, share count) for batch encryption
+    sealed_batches: Arc<Mutex<HashMap<String, SealedBatch>>>, // Encrypted batches until the ingress commits them
+}
+
+impl BatchingEngine {
+    pub fn new(max_batch_size: usize, batch_time_window: Duration) -> Self {
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        Self {
+            default_lane: Lane::new(max_batch_size, batch_time_window, clock.now(), batch_time_window),
+            lanes: HashMap::new(),
+            pending_bytes: Arc::new(AtomicUsize::new(0)),
+            pending_blob_bytes: Arc::new(AtomicUsize::new(0)),
+            seen_transactions: Arc::new(Mutex::new(DedupCache::new(DEFAULT_DEDUP_TTL))),
+            batch_policy: None,
+            wal: None,
+            hash_algo: HashAlgo::default(),
+            decoy_target_size: None,
+            max_pending_bytes: DEFAULT_MAX_PENDING_BYTES,
+            max_pending_blob_bytes: DEFAULT_MAX_PENDING_BLOB_BYTES,
+            ordering: BatchOrdering::default().policy(),
+            window_mode: WindowMode::default(),
+            id_mode: BatchIdMode::default(),
+            rng_seed: None,
+            shuffle_secret: None,
+            record_permutation: false,
+            min_batch: None,
+            min_distinct_senders: 0,
+            max_pending_age: None,
+            nonce_gap_timeout: None,
+            nonce_gap_holds: Arc::new(Mutex::new(HashMap::new())),
+            next_sequence: Arc::new(Mutex::new(0)),
+            clock,
+            entropy: Arc::new(OsEntropy),
+            encryption: None,
+            sealed_batches: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Reads time from the given clock instead of the system clock; every lane's window restarts at its now
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now();
+        for lane in std::iter::once(&mut self.default_lane).chain(self.lanes.values_mut()) {
+            lane.last_batch_time = Arc::new(Mutex::new(now));
+        }
+        self.clock = clock;
+        self
+    }
+
+    // Draws batch nonces, random batch IDs, decoys and batch keys from the given source instead of the
+    // operating system
+    pub fn with_entropy(mut self, entropy: Arc<dyn Entropy>) -> Self {
+        self.entropy = entropy;
+        self
+    }
+
+    // Encrypts every batch once its contents and order are final, before it is logged as committed;
+    // the key shares are taken with take_sealed
+    pub fn with_envelope_encryption(mut self, threshold: u8, share_count: u8) -> Self {
+        self.encryption = Some((threshold, share_count));
+        self
+    }
+
+    // Randomness source the engine draws from, for encrypting batches it did not cut
+    pub fn entropy(&self) -> &dyn Entropy {
+        &*self.entropy
+    }
+
+    // Key shares and plaintext member hashes of a batch this engine encrypted, handed out once
+    pub fn take_sealed(&self, batch_id: &str) -> Result<Option<SealedBatch>, IngressError> {
+        Ok(lock(&self.sealed_batches)?.remove(batch_id))
+    }
+
+    // Sets how long a transaction is remembered for duplicate detection, within and across batches
+    pub fn with_dedup_ttl(mut self, ttl: Duration) -> Self {
+        self.seen_transactions = Arc::new(Mutex::new(DedupCache::new(ttl)));
+        self
+    }
+
+    // Adds a named lane that batches on its own size threshold and time window
+    pub fn with_lane(mut self, name: impl Into<String>, max_batch_size: usize, batch_time_window: Duration) -> Self {
+        let window = self.window_length(batch_time_window);
+        self.lanes.insert(name.into(), Lane::new(max_batch_size, batch_time_window, self.clock.now(), window));
+        self
+    }
+
+    // Sizes default-lane batches from recent throughput instead of the fixed max_batch_size
+    pub fn with_adaptive_policy(mut self, policy: AdaptiveBatchPolicy) -> Self {
+        self.batch_policy = Some(Arc::new(Mutex::new(policy)));
+        self
+    }
+
+    // Selects the hash function for batch commitments and the shuffle seed
+    pub fn with_hash_algo(mut self, hash_algo: HashAlgo) -> Self {
+        self.hash_algo = hash_algo;
+        self
+    }
+
+    // Pads every batch with decoy transactions up to target_size so its size reveals nothing
+    pub fn with_decoy_target_size(mut self, target_size: usize) -> Self {
+        self.decoy_target_size = Some(target_size);
+        self
+    }
+
+    // Rejects new transactions once the pending pool holds this many bytes, not counting blob sidecars
+    pub fn with_max_pending_bytes(mut self, max_pending_bytes: usize) -> Self {
+        self.max_pending_bytes = max_pending_bytes;
+        self
+    }
+
+    // Rejects new blob transactions once the pending pool holds this many bytes of blob sidecars
+    pub fn with_max_pending_blob_bytes(mut self, max_pending_blob_bytes: usize) -> Self {
+        self.max_pending_blob_bytes = max_pending_blob_bytes;
+        self
+    }
+
+    // Selects one of the built-in orderings for batch contents
+    pub fn with_ordering(mut self, ordering: BatchOrdering) -> Self {
+        self.ordering = ordering.policy();
+        self
+    }
+
+    // Orders batch contents with a custom policy instead of a built-in one
+    pub fn with_ordering_policy(mut self, ordering: Arc<dyn OrderingPolicy>) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    // Draws every lane's windows afresh after each batch in Poisson mode, so release times cannot be
+    // predicted from the last one; each lane's time window becomes the mean
+    pub fn with_window_mode(mut self, window_mode: WindowMode) -> Self {
+        self.window_mode = window_mode;
+        for lane in std::iter::once(&self.default_lane).chain(self.lanes.values()) {
+            *lane.window() = self.window_length(lane.batch_time_window);
+        }
+        self
+    }
+
+    // Content-addressed IDs make the shuffle reproducible from the transaction set, at the cost of
+    // linking batches with identical contents; decoys still make padded batches unique
+    pub fn with_batch_id_mode(mut self, id_mode: BatchIdMode) -> Self {
+        self.id_mode = id_mode;
+        self
+    }
+
+    // Holds time-triggered batches in every lane until min_batch_size transactions are pending,
+    // unless max_wait has passed since the lane's last batch
+    pub fn with_min_batch_size(mut self, min_batch_size: usize, max_wait: Duration) -> Self {
+        self.min_batch = Some((min_batch_size, max_wait));
+        self
+    }
+
+    // Keeps a lane's transactions pending until they come from at least min_distinct_senders senders
+    //
+    // Unlike the minimum batch size this has no escape: size thresholds, max_wait, max_pending_age and
+    // flushes all leave a batch below the floor pending, so it merges with the next window instead of
+    // going out trivially deanonymizable. Transactions past the floor only leave once more senders join.
+    pub fn with_min_distinct_senders(mut self, min_distinct_senders: usize) -> Self {
+        self.min_distinct_senders = min_distinct_senders;
+        self
+    }
+
+    // Force-batches a lane whose oldest transaction has been pending this long, regardless of its size
+    // threshold, time window or minimum batch size
+    //
+    // Checked whenever a transaction is added to the lane or its window is checked, so an operator
+    // that stops polling the windows still bounds how stale a transaction can get.
+    pub fn with_max_pending_age(mut self, max_pending_age: Duration) -> Self {
+        self.max_pending_age = Some(max_pending_age);
+        self
+    }
+
+    // Keeps a sender's transactions that follow a gap in its pending nonces out of batches until the
+    // missing nonce arrives, or until they have been held back for timeout
+    //
+    // Relays can't include anything past the gap, so forwarding it only wastes the slot and shows the
+    // sender's next moves early.
+    pub fn with_nonce_gap_hold(mut self, timeout: Duration) -> Self {
+        self.nonce_gap_timeout = Some(timeout);
+        self
+    }
+
+    // Pins the shuffle seed so tests and simulations get reproducible orderings
+    //
+    // Every batch of the same size is then permuted identically, so this is not for production use.
+    pub fn with_rng_seed(mut self, seed: [u8; 32]) -> Self {
+        self.rng_seed = Some(seed);
+        self
+    }
+
+    // Derives each batch's shuffle seed from the secret and the batch ID instead of the ID alone, so
+    // observers who learn a batch ID cannot reconstruct its ordering
+    //
+    // The operator reproduces a batch's order from ShuffleSecret::seed_for. A pinned rng seed still
+    // takes precedence.
+    pub fn with_shuffle_secret(mut self, secret: ShuffleSecret) -> Self {
+        self.shuffle_secret = Some(secret);
+        self
+    }
+
+    // Keeps the permutation each batch's ordering policy applied on TransactionBatch::permutation for auditing
+    //
+    // The permutation maps the forwarded order back to arrival order, which the shuffle exists to hide;
+    // only record it where batches never leave the operator's control.
+    pub fn with_record_permutation(mut self, record_permutation: bool) -> Self {
+        self.record_permutation = record_permutation;
+        self
+    }
+
+    // Persists the pending pool and batch numbering to the log, first reloading whatever a previous
+    // run left unbatched and resuming its sequence numbers
+    pub fn with_wal(mut self, wal: WriteAheadLog) -> Result<Self, IngressError> {
+        let Recovered { pending: recovered, next_sequence } = wal.recover()?;
+        self.next_sequence = Arc::new(Mutex::new(next_sequence));
+        {
+            let mut seen = lock(&self.seen_transactions)?;
+            let now = self.clock.now();
+            for tx in &recovered {
+                seen.insert(transaction_hash(&tx.tx_bytes).to_vec(), now);
+            }
+        }
+        // Recovered transactions return to the default lane
+        self.restore_bytes(footprint(&recovered));
+        self.default_lane.pending.extend(recovered, self.clock.now());
+
+        self.wal = Some(Arc::new(wal));
+        Ok(self)
+    }
+
+    // Adds a transaction to the default lane, returning a batch if the size threshold was hit
+    pub fn add_transaction(&self, tx: TransactionEnvelope) -> Result<Option<TransactionBatch>, IngressError> {
+        self.add_to_lane(&self.default_lane, tx, true)
+    }
+
+    // Adds a transaction to a named lane, returning a batch if that lane's size threshold was hit
+    pub fn add_transaction_to_lane(
+        &self,
+        tx: TransactionEnvelope,
+        lane: &str,
+    ) -> Result<Option<TransactionBatch>, IngressError> {
+        let named_lane = self.lanes.get(lane).ok_or_else(|| IngressError::UnknownLane(lane.to_string()))?;
+        self.add_to_lane(named_lane, tx, false)
+    }
+
+    // Adds transactions to the default lane in order, returning a result per transaction like
+    // add_transaction; sizes reached mid-way cut batches as usual
+    pub fn add_transactions(&self, txs: Vec<TransactionEnvelope>) -> Vec<Result<Option<TransactionBatch>, IngressError>> {
+        txs.into_iter().map(|tx| self.add_to_lane(&self.default_lane, tx, true)).collect()
+    }
+
+    // Admits one transaction into the lane's pending pool, cutting a batch if it reached the size threshold
+    //
+    // Only the transaction's shard is locked while it is admitted, so submissions from different
+    // senders proceed in parallel; the cut itself is serialised by the pool.
+    fn add_to_lane(
+        &self,
+        lane: &Lane,
+        tx: TransactionEnvelope,
+        adaptive: bool,
+    ) -> Result<Option<TransactionBatch>, IngressError> {
+        let batch_size = self.admit(lane, tx, adaptive)?;
+        if lane.pending.len() >= batch_size {
+            return self.batch_pending(lane, batch_size, BatchTrigger::SizeThreshold);
+        }
+        if self.is_stale(lane, self.clock.now()) {
+            return self.create_batch(lane, BatchTrigger::MaxAge);
+        }
+        Ok(None)
+    }
+
+    // Admits one transaction into its shard of the lane's pending pool, returning the size threshold it was admitted under
+    fn admit(&self, lane: &Lane, mut tx: TransactionEnvelope, adaptive: bool) -> Result<usize, IngressError> {
+        let (mut shard, seq) = lane.pending.shard_for(&tx)?;
+
+        // Malformed transactions never enter the pending pool
+        validate_transaction(&tx.tx_bytes)?;
+
+        // Bound uncommitted memory; reserved before dedup so a rejected transaction can be resubmitted
+        let size = footprint(std::slice::from_ref(&tx));
+        let reserved = self.pending_bytes.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |pending_bytes| {
+            (pending_bytes + size.0 <= self.max_pending_bytes).then_some(pending_bytes + size.0)
+        });
+        if let Err(pending_bytes) = reserved {
+            return Err(IngressError::PendingPoolFull {
+                pending_bytes,
+                max_pending_bytes: self.max_pending_bytes,
+            });
+        }
+        let reserved = self.pending_blob_bytes.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |blob_bytes| {
+            (blob_bytes + size.1 <= self.max_pending_blob_bytes).then_some(blob_bytes + size.1)
+        });
+        if let Err(pending_blob_bytes) = reserved {
+            self.pending_bytes.fetch_sub(size.0, Ordering::SeqCst);
+            return Err(IngressError::BlobPoolFull {
+                pending_blob_bytes,
+                max_pending_blob_bytes: self.max_pending_blob_bytes,
+            });
+        }
+        let release = || self.release_bytes(size);
+
+        // Repeats within the dedup window would be forwarded redundantly; keyed by the Ethereum hash, so a
+        // blob transaction resent with or without its sidecar is still a repeat
+        let now = self.clock.now();
+        let tx_hash = transaction_hash(&tx.tx_bytes).to_vec();
+        let fresh = lock(&self.seen_transactions).map(|mut seen| seen.insert(tx_hash, now));
+        match fresh {
+            Ok(true) => {}
+            Ok(false) => {
+                release();
+                return Err(IngressError::Duplicate);
+            }
+            Err(error) => {
+                release();
+                return Err(error);
+            }
+        }
+
+        let batch_size = if adaptive { self.effective_batch_size(now)? } else { lane.max_batch_size };
+
+        // A pending transaction with the same sender and nonce is only replaced by a strictly higher priority fee;
+        // a rejected replacement stays marked as seen, since resubmitting the same bytes cannot raise its fee
+        let replaced = match replacement_index(&shard, &tx) {
+            Some(index) if priority_fee(&tx) <= priority_fee(&shard[index].1) => {
+                release();
+                return Err(IngressError::ReplacementUnderpriced);
+            }
+            replaced => replaced,
+        };
+
+        if let Some(wal) = &self.wal
+            && let Err(error) = wal.record_accepted(&tx)
+        {
+            release();
+            return Err(error);
+        }
+        tx.received_at = Some(now);
+        match replaced {
+            Some(index) => {
+                let stale = std::mem::replace(&mut shard[index].1, tx);
+                self.release_bytes(footprint(std::slice::from_ref(&stale)));
+            }
+            None => lane.pending.push(&mut shard, seq, tx, now),
+        }
+        Ok(batch_size)
+    }
+
+    // Current size threshold, counting this arrival towards the adaptive policy's rate
+    fn effective_batch_size(&self, now: SystemTime) -> Result<usize, IngressError> {
+        match &self.batch_policy {
+            Some(policy) => {
+                let mut policy = lock(policy)?;
+                policy.record_arrival(now);
+                Ok(policy.batch_size(now))
+            }
+            None => Ok(self.default_lane.max_batch_size),
+        }
+    }
+
+    // Transactions pending across every lane
+    pub fn pending_count(&self) -> Result<usize, IngressError> {
+        let count = self.default_lane.pending.len() + self.lanes.values().map(|lane| lane.pending.len()).sum::<usize>();
+        Ok(count)
+    }
+
+    // Inspects the pending pools without disturbing them, e.g. to see why batches aren't forming
+    //
+    // Each lane is read with its pool locked, so its part of the view is consistent; submissions to
+    // other lanes may land in between. Senders are taken as recorded, never recovered under the locks.
+    pub fn pending_snapshot(&self) -> Result<PendingSnapshot, IngressError> {
+        let mut snapshot = PendingSnapshot::default();
+        let mut senders: HashSet<Address> = HashSet::new();
+        let mut oldest: Option<SystemTime> = None;
+        for lane in std::iter::once(&self.default_lane).chain(self.lanes.values()) {
+            let lane_oldest = lane.pending.inspect(|tx| {
+                snapshot.count += 1;
+                snapshot.total_bytes += tx.tx_bytes.len();
+                senders.extend(tx.sender);
+            })?;
+            oldest = oldest.into_iter().chain(lane_oldest).min();
+        }
+        snapshot.distinct_senders = senders.len();
+        snapshot.oldest_age = oldest.map(|oldest| self.clock.now().duration_since(oldest).unwrap_or_default());
+        Ok(snapshot)
+    }
+
+    // Whether any lane holds a transaction with an inclusion deadline, so callers only fetch the head when
+    // something could expire
+    pub fn has_deadlines(&self) -> Result<bool, IngressError> {
+        let mut found = false;
+        for lane in std::iter::once(&self.default_lane).chain(self.lanes.values()) {
+            lane.pending.inspect(|tx| found |= tx.valid_until_block.is_some())?;
+        }
+        Ok(found)
+    }
+
+    // Removes every pending transaction, in any lane, whose inclusion deadline has passed with the chain at head
+    //
+    // Expired transactions stay in the write-ahead log, deadline included, so a restart recovers
+    // them only for the next call to drop again. They also stay marked as seen, so resubmitting the
+    // same bytes is still refused as a duplicate.
+    pub fn drop_expired(&self, head: u64) -> Result<Vec<TransactionEnvelope>, IngressError> {
+        let mut expired = Vec::new();
+        for lane in std::iter::once(&self.default_lane).chain(self.lanes.values()) {
+            let removed = lane.pending.remove_where(|tx| tx.is_expired_at(head))?;
+            self.release_bytes(footprint(&removed));
+            expired.extend(removed);
+        }
+        Ok(expired)
+    }
+
+    // Current time on the engine's clock, so callers timestamp releases against the same clock as arrivals
+    pub fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+
+    // Batches the default lane if its time window has elapsed
+    pub fn check_time_window(&self) -> Result<Option<TransactionBatch>, IngressError> {
+        self.check_lane_window(&self.default_lane)
+    }
+
+    // Batches every named lane whose time window has elapsed
+    pub fn check_lane_windows(&self) -> Result<Vec<TransactionBatch>, IngressError> {
+        let mut batches = Vec::new();
+        for lane in self.lanes.values() {
+            batches.extend(self.check_lane_window(lane)?);
+        }
+        Ok(batches)
+    }
+
+    fn check_lane_window(&self, lane: &Lane) -> Result<Option<TransactionBatch>, IngressError> {
+        let now = self.clock.now();
+        if self.is_stale(lane, now) {
+            return self.create_batch(lane, BatchTrigger::MaxAge);
+        }
+        let last_batch_time = *lane.last_batch_time();
+        // A clock stepped backwards, e.g. by NTP, restarts the window rather than stalling it until the clock catches up
+        let Ok(elapsed) = now.duration_since(last_batch_time) else {
+            *lane.last_batch_time() = now;
+            return Ok(None);
+        };
+
+        if elapsed < *lane.window() {
+            return Ok(None);
+        }
+
+        // Tiny batches make poor anonymity sets, so wait for more unless the deadline has passed
+        if let Some((min_batch_size, max_wait)) = self.min_batch
+            && elapsed < max_wait
+            && lane.pending.len() < min_batch_size
+        {
+            return Ok(None);
+        }
+
+        self.create_batch(lane, BatchTrigger::TimeWindow)
+    }
+
+    // Batches everything still pending in the default lane regardless of the time window, e.g. on shutdown
+    pub fn flush(&self) -> Result<Option<TransactionBatch>, IngressError> {
+        self.create_batch(&self.default_lane, BatchTrigger::ForcedFlush)
+    }
+
+    // Batches everything still pending in the named lanes regardless of their time windows
+    pub fn flush_lanes(&self) -> Result<Vec<TransactionBatch>, IngressError> {
+        let mut batches = Vec::new();
+        for lane in self.lanes.values() {
+            batches.extend(self.create_batch(lane, BatchTrigger::ForcedFlush)?);
+        }
+        Ok(batches)
+    }
+
+    // Length of a lane's next window, drawn with batch_time_window as the mean in Poisson mode
+    fn window_length(&self, batch_time_window: Duration) -> Duration {
+        match self.window_mode {
+            WindowMode::Fixed => batch_time_window,
+            WindowMode::Poisson => sample_poisson_window(batch_time_window),
+        }
+    }
+
+    // Whether the lane's oldest pending transaction has waited at least max_pending_age
+    fn is_stale(&self, lane: &Lane, now: SystemTime) -> bool {
+        match (self.max_pending_age, lane.pending.oldest()) {
+            (Some(max_pending_age), Some(oldest)) => {
+                now.duration_since(oldest).is_ok_and(|age| age >= max_pending_age)
+            }
+            _ => false,
+        }
+    }
+
+    // Splits off each sender's transactions past the first gap in its nonces, unless they have already
+    // been held back for timeout
+    //
+    // A sender whose lowest pending transaction is itself still held has a gap below everything it
+    // has pending, since the missing nonce would otherwise be the lowest.
+    fn hold_nonce_gaps(
+        &self,
+        transactions: &mut Vec<TransactionEnvelope>,
+        timeout: Duration,
+    ) -> Result<Vec<TransactionEnvelope>, IngressError> {
+        let now = self.clock.now();
+        // Holds are advisory, so a poisoned lock keeps whatever hold times it has; a lost one restarts its timeout
+        let mut holds = self.nonce_gap_holds.lock().unwrap_or_else(PoisonError::into_inner);
+        let timed_out =
+            |held_since: SystemTime| now.duration_since(held_since).is_ok_and(|held_for| held_for >= timeout);
+
+        let mut nonces: HashMap<Address, Vec<u64>> = HashMap::new();
+        for tx in transactions.iter() {
+            if let (Some(sender), Some(nonce)) = (tx.sender, tx.nonce) {
+                nonces.entry(sender).or_default().push(nonce);
+            }
+        }
+        // Highest nonce of each sender's releasable run, None if the whole sender waits
+        let contiguous_until: HashMap<Address, Option<u64>> = nonces
+            .into_iter()
+            .map(|(sender, mut nonces)| {
+                nonces.sort_unstable();
+                if holds.get(&(sender, nonces[0])).is_some_and(|&held_since| !timed_out(held_since)) {
+                    return (sender, None);
+                }
+                let mut last = nonces[0];
+                for &nonce in &nonces[1..] {
+                    if nonce > last + 1 {
+                        break;
+                    }
+                    last = nonce;
+                }
+                (sender, Some(last))
+            })
+            .collect();
+
+        let (held, ready): (Vec<_>, Vec<_>) = std::mem::take(transactions).into_iter().partition(|tx| {
+            let (Some(sender), Some(nonce)) = (tx.sender, tx.nonce) else {
+                return false;
+            };
+            let key = (sender, nonce);
+            let in_run = contiguous_until[&sender].is_some_and(|last| nonce <= last);
+            if in_run || holds.get(&key).is_some_and(|&held_since| timed_out(held_since)) {
+                holds.remove(&key);
+                return false;
+            }
+            holds.entry(key).or_insert(now);
+            true
+        });
+        *transactions = ready;
+        Ok(held)
+    }
+
+    // Takes (bytes, blob bytes) off the pending budgets
+    fn release_bytes(&self, (bytes, blob_bytes): (usize, usize)) {
+        self.pending_bytes.fetch_sub(bytes, Ordering::SeqCst);
+        self.pending_blob_bytes.fetch_sub(blob_bytes, Ordering::SeqCst);
+    }
+
+    // Counts (bytes, blob bytes) against the pending budgets again, for transactions handed back to a pool
+    fn restore_bytes(&self, (bytes, blob_bytes): (usize, usize)) {
+        self.pending_bytes.fetch_add(bytes, Ordering::SeqCst);
+        self.pending_blob_bytes.fetch_add(blob_bytes, Ordering::SeqCst);
+    }
+
+    fn create_batch(&self, lane: &Lane, trigger: BatchTrigger) -> Result<Option<TransactionBatch>, IngressError> {
+        self.batch_pending(lane, 1, trigger)
+    }
+
+    // Batches everything in the lane's pending pool if it holds at least min_pending transactions
+    //
+    // Threads that reach the size threshold together cut one batch: the others find the pool
+    // drained once they get the cutover lock. Arrivals racing the cut may make it a little larger.
+    fn batch_pending(
+        &self,
+        lane: &Lane,
+        min_pending: usize,
+        trigger: BatchTrigger,
+    ) -> Result<Option<TransactionBatch>, IngressError> {
+        let _cutover = lane.pending.cutover()?;
+        if lane.pending.len() < min_pending.max(1) {
+            return Ok(None);
+        }
+
+        // Take all pending transactions
+        let arrived = lane.pending.oldest().unwrap_or_else(|| self.clock.now());
+        let mut transactions: Vec<TransactionEnvelope> = lane.pending.drain()?;
+        let mut drained_bytes = footprint(&transactions);
+        self.release_bytes(drained_bytes);
+
+        // Held transactions go straight back to the pool and wait for the next batch
+        if let Some(timeout) = self.nonce_gap_timeout {
+            let held = match self.hold_nonce_gaps(&mut transactions, timeout) {
+                Ok(held) => held,
+                Err(error) => {
+                    lane.pending.extend(transactions, arrived);
+                    self.restore_bytes(drained_bytes);
+                    return Err(error);
+                }
+            };
+            let held_bytes = footprint(&held);
+            drained_bytes = (drained_bytes.0 - held_bytes.0, drained_bytes.1 - held_bytes.1);
+            self.restore_bytes(held_bytes);
+            lane.pending.extend(held, arrived);
+            if transactions.is_empty() {
+                return Ok(None);
+            }
+        }
+
+        // Too few senders to hide among: everything waits to be cut with the next arrivals
+        if distinct_senders(&transactions) < self.min_distinct_senders {
+            lane.pending.extend(transactions, arrived);
+            self.restore_bytes(drained_bytes);
+            return Ok(None);
+        }
+
+        // Decoys are added before committing, so the commitment covers the padded batch
+        if let Some(target_size) = self.decoy_target_size {
+            while transactions.len() < target_size {
+                match decoy_envelope(&*self.entropy) {
+                    Ok(decoy) => transactions.push(decoy),
+                    Err(error) => {
+                        lane.pending.extend(transactions.into_iter().filter(|tx| !tx.decoy), arrived);
+                        self.restore_bytes(drained_bytes);
+                        return Err(error);
+                    }
+                }
+            }
+        }
+
+        // Update last batch time and start the next window
+        let now = self.clock.now();
+        *lane.last_batch_time() = now;
+        *lane.window() = self.window_length(lane.batch_time_window);
+
+        // Create batch with cryptographically secure shuffle
+        let mut batch = TransactionBatch::with_entropy(transactions, self.hash_algo, self.id_mode, &*self.entropy)?;
+        batch.timestamp = now;
+        batch.trigger = Some(trigger);
+
+        // Arrival order is dropped first, so the shuffle depends on nothing but the transaction set
+        if self.id_mode == BatchIdMode::ContentAddressed {
+            batch.transactions.sort_by_cached_key(|tx| self.hash_algo.hash(&tx.tx_bytes));
+        }
+
+        // Order transactions deterministically using a seed based on batch ID
+        let seed = self.rng_seed.unwrap_or_else(|| match &self.shuffle_secret {
+            Some(secret) => secret.seed_for(&batch.id, batch.hash_algo),
+            None => create_seed_from_batch_id(&batch.id, &[], batch.hash_algo),
+        });
+        let unordered = self.record_permutation.then(|| batch.transactions.clone());
+        self.ordering.order(&mut batch.transactions, seed);
+        order_nonces_per_sender(&mut batch.transactions);
+        if let Some(unordered) = unordered {
+            batch.permutation = Some(applied_permutation(&unordered, &batch.transactions));
+        }
+
+        // Encrypted batches commit to their ciphertexts, so a batch is sealed before it is logged; the
+        // plaintext stays in batch so a failure can put it back
+        let sealed = match self.encryption {
+            Some((threshold, share_count)) => {
+                let mut sealed = batch.clone();
+                match sealed.encrypt_batch_with_entropy(threshold, share_count, &*self.entropy) {
+                    Ok(shares) => Some((sealed, shares)),
+                    Err(error) => {
+                        lane.pending.extend(batch.transactions.into_iter().filter(|tx| !tx.decoy), arrived);
+                        self.restore_bytes(drained_bytes);
+                        return Err(error);
+                    }
+                }
+            }
+            None => None,
+        };
+
+        // The number is only used up once the batch is logged, so a failed batch leaves no gap
+        let mut next_sequence = match lock(&self.next_sequence) {
+            Ok(next_sequence) => next_sequence,
+            Err(error) => {
+                lane.pending.extend(batch.transactions.into_iter().filter(|tx| !tx.decoy), arrived);
+                self.restore_bytes(drained_bytes);
+                return Err(error);
+            }
+        };
+        batch.sequence = *next_sequence;
+
+        // Once this record is durable the batch is never recovered, so it cannot be forwarded twice;
+        // members are logged by their plaintext, which recovery matches against
+        if let Some(wal) = &self.wal
+            && let Err(error) = wal.record_committed(&batch)
+        {
+            lane.pending.extend(batch.transactions.into_iter().filter(|tx| !tx.decoy), arrived);
+            self.restore_bytes(drained_bytes);
+            return Err(error);
+        }
+        *next_sequence += 1;
+        drop(next_sequence);
+
+        let Some((mut sealed, shares)) = sealed else {
+            return Ok(Some(batch));
+        };
+        sealed.sequence = batch.sequence;
+        let members = batch.transactions.iter().filter(|tx| !tx.decoy).map(|tx| transaction_hash(&tx.tx_bytes));
+        // Only ever inserted into, so a poisoned map is still whole
+        self.sealed_batches
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(sealed.id.clone(), (shares, members.collect()));
+        Ok(Some(sealed))
+    }
+}
+
+// Helper function to measure what transactions hold against the pending budgets: (bytes, blob bytes)
+//
+// Blob sidecars are budgeted on their own, since relays and peers handle them apart from the
+// transactions that reference them.
+fn footprint(transactions: &[TransactionEnvelope]) -> (usize, usize) {
+    transactions.iter().fold((0, 0), |(bytes, blob_bytes), tx| {
+        let blob_len = blob_sidecar_len(&tx.tx_bytes);
+        (bytes + tx.tx_bytes.len() - blob_len, blob_bytes + blob_len)
+    })
+}
+
+// Position of the pending transaction from the same sender with the same nonce, if any
+fn replacement_index(pending: &[(u64, TransactionEnvelope)], tx: &TransactionEnvelope) -> Option<usize> {
+    let (Some(sender), Some(nonce)) = (tx.sender, tx.nonce) else {
+        return None;
+    };
+    pending
+        .iter()
+        .position(|(_, pending_tx)| pending_tx.sender == Some(sender) && pending_tx.nonce == Some(nonce))
+}
+
+// Helper function to count the distinct senders, i.e. the anonymity set, among a batch's transactions
+//
+// Envelopes without a recorded sender are recovered again; ones whose signature cannot be recovered, such
+// as decoys, do not count.
+pub(crate) fn distinct_senders(transactions: &[TransactionEnvelope]) -> usize {
+    let senders: HashSet<Address> = transactions
+        .iter()
+        .filter_map(|tx| tx.sender.or_else(|| recover_sender(&tx.tx_bytes).ok()))
+        .collect();
+    senders.len()
+}
+
+// Helper function to read the priority fee a replacement has to beat; undecodable fees count as zero
+fn priority_fee(tx: &TransactionEnvelope) -> u128 {
+    transaction_fees(&tx.tx_bytes).map(|fees| fees.max_priority_fee).unwrap_or_default()
+}
+
+// Helper function to recover the permutation an ordering policy applied: position k of ordered holds
+// unordered[permutation[k]]
+//
+// Each transaction is found by its bytes; the same bytes pending twice take their positions in turn.
+fn applied_permutation(unordered: &[TransactionEnvelope], ordered: &[TransactionEnvelope]) -> Vec<usize> {
+    let mut positions: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (index, tx) in unordered.iter().enumerate().rev() {
+        positions.entry(tx.tx_bytes.as_slice()).or_default().push(index);
+    }
+    ordered
+        .iter()
+        .filter_map(|tx| positions.get_mut(tx.tx_bytes.as_slice()).and_then(Vec::pop))
+        .collect()
+}
+
+// Permutes items with a seed derived from the batch ID, so anyone holding the ID can reproduce the order
+pub(crate) fn deterministic_shuffle<T>(items: &mut [T], batch_id: &str, algo: HashAlgo) {
+    shuffle_in_place(items, create_seed_from_batch_id(batch_id, &[], algo));
+}
+
+// Restores ascending nonce order within each sender, reusing the slots the shuffle gave that sender
+//
+// Relays reject a transaction placed ahead of its sender's lower nonce, while the interleaving
+// across senders (and so the deterministic shuffle) is left untouched.
+fn order_nonces_per_sender(transactions: &mut [TransactionEnvelope]) {
+    order_nonces_by(transactions, |tx| tx.sender.zip(tx.nonce));
+}
+
+// Restores ascending nonce order within each account as order_nonces_per_sender does, reading the
+// account and nonce of a member with account_nonce; members it returns None for keep their slots
+pub(crate) fn order_nonces_by<K: Eq + Hash>(
+    transactions: &mut [TransactionEnvelope],
+    account_nonce: impl Fn(&TransactionEnvelope) -> Option<(K, u64)>,
+) {
+    let mut slots: HashMap<K, Vec<(usize, u64)>> = HashMap::new();
+    for (index, tx) in transactions.iter().enumerate() {
+        if let Some((account, nonce)) = account_nonce(tx) {
+            slots.entry(account).or_default().push((index, nonce));
+        }
+    }
+
+    for positions in slots.values() {
+        let mut ordered: Vec<(u64, TransactionEnvelope)> =
+            positions.iter().map(|&(index, nonce)| (nonce, transactions[index].clone())).collect();
+        ordered.sort_by_key(|(nonce, _)| *nonce);
+        for (&(index, _), (_, tx)) in positions.iter().zip(ordered) {
+            transactions[index] = tx;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::entropy::SeededEntropy;
+    use crate::test_utils::{
+        blob_tx, dynamic_fee_tx, fee_bidding_tx, signed_dynamic_fee_tx, test_address, FailingEntropy, TEST_KEY,
+    };
+    use crate::transaction::transaction_nonce;
+    use crate::shuffle::{apply_permutation, shuffle_with_seed};
+    use crate::crypto::MIN_SHUFFLE_SECRET_LEN;
+
+    // Envelope carrying the sender and nonce the ingress would have attached
+    fn attributed(tx_bytes: Vec<u8>) -> TransactionEnvelope {
+        let mut tx = TransactionEnvelope::new(tx_bytes, "tx".to_string());
+        tx.sender = Some(recover_sender(&tx.tx_bytes).unwrap());
+        tx.nonce = Some(transaction_nonce(&tx.tx_bytes).unwrap());
+        tx
+    }
+
+    #[test]
+    fn test_add_transaction_returns_batch_at_size_threshold() {
+        let engine = BatchingEngine::new(3, Duration::from_secs(3600));
+
+        assert!(engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap().is_none());
+        assert!(engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(2), "b".to_string())).unwrap().is_none());
+
+        let batch = engine
+            .add_transaction(TransactionEnvelope::new(dynamic_fee_tx(3), "c".to_string()))
+            .unwrap()
+            .expect("size threshold should produce a batch");
+
+        assert_eq!(batch.transactions.len(), 3);
+        assert_eq!(engine.pending_count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_poisoned_pending_lock_surfaces_as_error() {
+        let engine = BatchingEngine::new(3, Duration::from_secs(3600));
+
+        // Panic while holding the pending locks to poison them
+        engine.default_lane.pending.poison();
+
+        let result = engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string()));
+        assert_eq!(result.unwrap_err(), IngressError::LockPoisoned);
+        assert_eq!(engine.default_lane.pending.drain().unwrap_err(), IngressError::LockPoisoned);
+    }
+
+    #[test]
+    fn test_poisoned_window_and_hold_locks_keep_batching() {
+        let clock = MockClock::default();
+        let engine = BatchingEngine::new(10, Duration::from_secs(1))
+            .with_clock(Arc::new(clock.clone()))
+            .with_nonce_gap_hold(Duration::from_secs(60));
+        std::thread::scope(|scope| {
+            let _ = scope
+                .spawn(|| {
+                    let _window = engine.default_lane.last_batch_time.lock().unwrap();
+                    let _holds = engine.nonce_gap_holds.lock().unwrap();
+                    panic!("poisoning the window and hold locks");
+                })
+                .join();
+        });
+
+        // Both still hold valid state, so the window keeps cutting batches
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(engine.check_time_window().unwrap().unwrap().transactions.len(), 1);
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(2), "b".to_string())).unwrap();
+        assert!(engine.check_time_window().unwrap().is_none());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(engine.check_time_window().unwrap().unwrap().transactions.len(), 1);
+    }
+
+    #[test]
+    fn test_pending_snapshot_leaves_pool_untouched() {
+        let clock = MockClock::default();
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600))
+            .with_clock(Arc::new(clock.clone()))
+            .with_lane("urgent", 10, Duration::from_secs(3600));
+        assert_eq!(engine.pending_snapshot().unwrap(), PendingSnapshot::default());
+
+        let txs = [signed_dynamic_fee_tx(1, 0), signed_dynamic_fee_tx(2, 0), signed_dynamic_fee_tx(1, 1)];
+        engine.add_transaction(attributed(txs[0].clone())).unwrap();
+        clock.advance(Duration::from_secs(5));
+        engine.add_transaction(attributed(txs[1].clone())).unwrap();
+        engine.add_transaction_to_lane(attributed(txs[2].clone()), "urgent").unwrap();
+        clock.advance(Duration::from_secs(2));
+
+        let snapshot = engine.pending_snapshot().unwrap();
+        assert_eq!(
+            snapshot,
+            PendingSnapshot {
+                count: 3,
+                total_bytes: txs.iter().map(Vec::len).sum(),
+                oldest_age: Some(Duration::from_secs(7)),
+                distinct_senders: 2,
+            }
+        );
+
+        // Reading it twice sees the same pool, still in arrival order
+        assert_eq!(engine.pending_snapshot().unwrap(), snapshot);
+        assert_eq!(engine.pending_count().unwrap(), 3);
+        let pending: Vec<Vec<u8>> = engine.default_lane.pending.snapshot().unwrap().into_iter().map(|tx| tx.tx_bytes).collect();
+        assert_eq!(pending, txs[..2]);
+    }
+
+    #[test]
+    fn test_flush_batches_transactions_below_threshold() {
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600));
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(2), "b".to_string())).unwrap();
+
+        let batch = engine.flush().unwrap().expect("pending transactions should be batched");
+
+        let mut flushed: Vec<Vec<u8>> = batch.transactions.into_iter().map(|tx| tx.tx_bytes).collect();
+        flushed.sort();
+        let mut expected = vec![dynamic_fee_tx(1), dynamic_fee_tx(2)];
+        expected.sort();
+        assert_eq!(flushed, expected);
+        assert_eq!(engine.pending_count().unwrap(), 0);
+        assert!(engine.flush().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_duplicate_rejected_within_and_across_batches() {
+        let engine = BatchingEngine::new(2, Duration::from_secs(3600));
+
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+        let repeat = engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "b".to_string()));
+        assert_eq!(repeat.unwrap_err(), IngressError::Duplicate);
+        assert_eq!(engine.pending_count().unwrap(), 1);
+
+        // Still rejected after the first copy has left in a batch
+        let batch = engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(2), "c".to_string())).unwrap();
+        assert!(batch.is_some());
+        let repeat = engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "d".to_string()));
+        assert_eq!(repeat.unwrap_err(), IngressError::Duplicate);
+    }
+
+    #[test]
+    fn test_fee_bump_replaces_pending_transaction() {
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600));
+        engine.add_transaction(attributed(fee_bidding_tx(TEST_KEY, 1_000_000_000, 30_000_000_000))).unwrap();
+
+        let bump = fee_bidding_tx(TEST_KEY, 2_000_000_000, 30_000_000_000);
+        engine.add_transaction(attributed(bump.clone())).unwrap();
+
+        // The bump takes the original's slot instead of being batched alongside it
+        let pending = engine.default_lane.pending.snapshot().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].tx_bytes, bump);
+        assert_eq!(engine.pending_bytes.load(Ordering::SeqCst), bump.len());
+    }
+
+    #[test]
+    fn test_replacement_without_higher_priority_fee_rejected() {
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600));
+        let original = fee_bidding_tx(TEST_KEY, 2_000_000_000, 30_000_000_000);
+        engine.add_transaction(attributed(original.clone())).unwrap();
+
+        // Same priority fee with a higher cap is not a bump, and neither is a lower priority fee
+        for (max_priority_fee, max_fee) in [(2_000_000_000, 40_000_000_000), (1_000_000_000, 30_000_000_000)] {
+            let replacement = engine.add_transaction(attributed(fee_bidding_tx(TEST_KEY, max_priority_fee, max_fee)));
+            assert_eq!(replacement.unwrap_err(), IngressError::ReplacementUnderpriced);
+        }
+
+        let pending = engine.default_lane.pending.snapshot().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].tx_bytes, original);
+        assert_eq!(engine.pending_bytes.load(Ordering::SeqCst), original.len());
+    }
+
+    #[test]
+    fn test_fee_bump_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ingress.wal");
+        let bump = fee_bidding_tx(TEST_KEY, 2_000_000_000, 30_000_000_000);
+
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600))
+            .with_wal(WriteAheadLog::open(&path).unwrap())
+            .unwrap();
+        engine.add_transaction(attributed(fee_bidding_tx(TEST_KEY, 1_000_000_000, 30_000_000_000))).unwrap();
+        engine.add_transaction(attributed(bump.clone())).unwrap();
+        drop(engine);
+
+        // Both acceptances are logged, but only the replacement is reloaded
+        let restarted = BatchingEngine::new(10, Duration::from_secs(3600))
+            .with_wal(WriteAheadLog::open(&path).unwrap())
+            .unwrap();
+        let pending = restarted.default_lane.pending.snapshot().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].tx_bytes, bump);
+    }
+
+    #[test]
+    fn test_pending_pool_rejects_beyond_byte_budget() {
+        let tx_len = dynamic_fee_tx(1).len();
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600)).with_max_pending_bytes(2 * tx_len);
+
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(2), "b".to_string())).unwrap();
+        let full = engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(3), "c".to_string()));
+        assert_eq!(
+            full.unwrap_err(),
+            IngressError::PendingPoolFull { pending_bytes: 2 * tx_len, max_pending_bytes: 2 * tx_len }
+        );
+
+        // Batching frees the budget, and the rejected transaction was not marked as seen
+        assert!(engine.flush().unwrap().is_some());
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(3), "d".to_string())).unwrap();
+        assert_eq!(engine.pending_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_expired_transactions_leave_every_lane_and_free_budget() {
+        let tx_len = dynamic_fee_tx(1).len();
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600))
+            .with_lane("bulk", 10, Duration::from_secs(3600))
+            .with_max_pending_bytes(2 * tx_len);
+        let with_deadline = |nonce: u64, valid_until_block: Option<u64>| {
+            let mut tx = TransactionEnvelope::new(dynamic_fee_tx(nonce), String::new());
+            tx.valid_until_block = valid_until_block;
+            tx
+        };
+        engine.add_transaction(with_deadline(1, Some(50))).unwrap();
+        engine.add_transaction_to_lane(with_deadline(2, None), "bulk").unwrap();
+        assert!(engine.has_deadlines().unwrap());
+
+        // Block 50 can still be built on top of head 49
+        assert!(engine.drop_expired(49).unwrap().is_empty());
+        let expired = engine.drop_expired(50).unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].tx_bytes, dynamic_fee_tx(1));
+        assert_eq!(engine.pending_count().unwrap(), 1);
+        assert!(!engine.has_deadlines().unwrap());
+        assert_eq!(engine.default_lane.pending.oldest(), None);
+
+        // The expired transaction's bytes no longer count against the budget
+        engine.add_transaction(with_deadline(3, None)).unwrap();
+    }
+
+    #[test]
+    fn test_blob_sidecars_budgeted_apart_from_pending_bytes() {
+        let wrapped = blob_tx(0, 1, true);
+        let sidecar_len = blob_sidecar_len(&wrapped);
+        let tx_len = dynamic_fee_tx(1).len();
+        let pending_len = wrapped.len() - sidecar_len + tx_len;
+        // A byte budget far below the size of a single blob
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600))
+            .with_max_pending_bytes(1024)
+            .with_max_pending_blob_bytes(sidecar_len);
+
+        // Only the transaction itself counts against the pending byte budget, however large its blobs
+        engine.add_transaction(TransactionEnvelope::new(wrapped, String::new())).unwrap();
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), String::new())).unwrap();
+        assert_eq!(engine.pending_bytes.load(Ordering::SeqCst), pending_len);
+        assert_eq!(engine.pending_blob_bytes.load(Ordering::SeqCst), sidecar_len);
+
+        // A second sidecar exceeds the blob budget, and the rejection reserves nothing
+        let full = engine.add_transaction(TransactionEnvelope::new(blob_tx(1, 1, true), String::new()));
+        assert_eq!(
+            full.unwrap_err(),
+            IngressError::BlobPoolFull { pending_blob_bytes: sidecar_len, max_pending_blob_bytes: sidecar_len }
+        );
+        assert_eq!(engine.pending_bytes.load(Ordering::SeqCst), pending_len);
+
+        assert!(engine.flush().unwrap().is_some());
+        assert_eq!(engine.pending_bytes.load(Ordering::SeqCst), 0);
+        assert_eq!(engine.pending_blob_bytes.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_filling_one_lane_does_not_batch_another() {
+        let engine = BatchingEngine::new(3, Duration::from_secs(3600)).with_lane("urgent", 2, Duration::from_secs(3600));
+
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(2), "b".to_string())).unwrap();
+        assert!(engine.add_transaction_to_lane(TransactionEnvelope::new(dynamic_fee_tx(3), "c".to_string()), "urgent").unwrap().is_none());
+
+        // The urgent lane fills first and its batch holds only its own transactions
+        let urgent = engine.add_transaction_to_lane(TransactionEnvelope::new(dynamic_fee_tx(4), "d".to_string()), "urgent").unwrap();
+        let mut urgent_txs: Vec<Vec<u8>> = urgent.unwrap().transactions.into_iter().map(|tx| tx.tx_bytes).collect();
+        urgent_txs.sort();
+        let mut expected = vec![dynamic_fee_tx(3), dynamic_fee_tx(4)];
+        expected.sort();
+        assert_eq!(urgent_txs, expected);
+        assert_eq!(engine.default_lane.pending.len(), 2);
+
+        // Filling the default lane leaves the empty urgent lane alone
+        engine.add_transaction_to_lane(TransactionEnvelope::new(dynamic_fee_tx(5), "e".to_string()), "urgent").unwrap();
+        let default = engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(6), "f".to_string())).unwrap();
+        assert_eq!(default.unwrap().transactions.len(), 3);
+        assert_eq!(engine.pending_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_lane_time_windows_are_independent() {
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600)).with_lane("urgent", 10, Duration::ZERO);
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+        engine.add_transaction_to_lane(TransactionEnvelope::new(dynamic_fee_tx(2), "b".to_string()), "urgent").unwrap();
+
+        assert!(engine.check_time_window().unwrap().is_none());
+        let batches = engine.check_lane_windows().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].transactions[0].tx_bytes, dynamic_fee_tx(2));
+
+        let unknown = engine.add_transaction_to_lane(TransactionEnvelope::new(dynamic_fee_tx(3), "c".to_string()), "bulk");
+        assert_eq!(unknown.unwrap_err(), IngressError::UnknownLane("bulk".to_string()));
+    }
+
+    #[test]
+    fn test_time_window_waits_for_min_batch_size() {
+        let engine = BatchingEngine::new(10, Duration::ZERO).with_min_batch_size(3, Duration::from_secs(3600));
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(2), "b".to_string())).unwrap();
+
+        // The window has elapsed but two transactions are too few
+        assert!(engine.check_time_window().unwrap().is_none());
+        assert_eq!(engine.pending_count().unwrap(), 2);
+
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(3), "c".to_string())).unwrap();
+        assert_eq!(engine.check_time_window().unwrap().unwrap().transactions.len(), 3);
+    }
+
+    #[test]
+    fn test_max_wait_forces_small_batch() {
+        let clock = MockClock::default();
+        let engine = BatchingEngine::new(10, Duration::ZERO)
+            .with_min_batch_size(3, Duration::from_millis(50))
+            .with_clock(Arc::new(clock.clone()));
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+        assert!(engine.check_time_window().unwrap().is_none());
+
+        clock.advance(Duration::from_millis(50));
+
+        assert_eq!(engine.check_time_window().unwrap().unwrap().transactions.len(), 1);
+    }
+
+    #[test]
+    fn test_injected_clock_and_entropy_reproduce_batches() {
+        let run = || {
+            let engine = BatchingEngine::new(3, Duration::from_secs(3600))
+                .with_decoy_target_size(5)
+                .with_clock(Arc::new(MockClock::default()))
+                .with_entropy(Arc::new(SeededEntropy::new([9; 32])));
+            let mut batch = None;
+            for key in 1..=3 {
+                batch = engine.add_transaction(attributed(signed_dynamic_fee_tx(key, 0))).unwrap();
+            }
+            batch.unwrap()
+        };
+
+        // Identical inputs, time and randomness give byte-identical batches, decoys included
+        let (first, second) = (run(), run());
+        assert_eq!(first.id, second.id);
+        assert_eq!(first.nonce.as_bytes(), second.nonce.as_bytes());
+        assert_eq!(first.commitment, second.commitment);
+        assert_eq!(first.timestamp, SystemTime::UNIX_EPOCH);
+        let bytes = |batch: &TransactionBatch| batch.transactions.iter().map(|tx| tx.tx_bytes.clone()).collect::<Vec<_>>();
+        assert_eq!(bytes(&first), bytes(&second));
+        assert_eq!(first.transactions.iter().filter(|tx| tx.is_decoy()).count(), 2);
+    }
+
+    #[test]
+    fn test_stale_transaction_force_batched_on_next_interaction() {
+        let clock = MockClock::default();
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600))
+            .with_max_pending_age(Duration::from_secs(30))
+            .with_clock(Arc::new(clock.clone()));
+        assert!(engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap().is_none());
+
+        clock.advance(Duration::from_secs(29));
+        assert!(engine.check_time_window().unwrap().is_none());
+        clock.advance(Duration::from_secs(1));
+
+        // Neither the size threshold nor the window is reached, yet the stale transaction is batched
+        let batch = engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(2), "a".to_string())).unwrap().unwrap();
+        assert_eq!(batch.transactions.len(), 2);
+        assert_eq!(engine.pending_count().unwrap(), 0);
+
+        // The age restarts with the next arrival, and polling the window enforces it too
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(3), "a".to_string())).unwrap();
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(engine.check_time_window().unwrap().unwrap().transactions.len(), 1);
+    }
+
+    #[test]
+    fn test_poisson_windows_average_the_target_mean() {
+        let clock = MockClock::default();
+        let mean = Duration::from_secs(10);
+        let engine =
+            BatchingEngine::new(10, mean).with_clock(Arc::new(clock.clone())).with_window_mode(WindowMode::Poisson);
+
+        let mut intervals = Vec::new();
+        let mut last_release = SystemTime::UNIX_EPOCH;
+        for nonce in 0..1000 {
+            engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(nonce), "a".to_string())).unwrap();
+            let window = *engine.default_lane.window();
+            if let Some(just_before) = window.checked_sub(Duration::from_nanos(1)) {
+                clock.advance(just_before);
+                assert!(engine.check_time_window().unwrap().is_none());
+                clock.advance(window - just_before);
+            }
+            let batch = engine.check_time_window().unwrap().expect("drawn window has elapsed");
+            intervals.push(batch.timestamp.duration_since(last_release).unwrap().as_secs_f64());
+            last_release = batch.timestamp;
+        }
+
+        // The standard error of the mean is about 3% of it over 1000 exponential draws
+        let average = intervals.iter().sum::<f64>() / intervals.len() as f64;
+        assert!((average - 10.0).abs() < 1.5, "average interval {}s", average);
+        // Exponential intervals spread as widely as their mean, unlike a fixed or jittered cadence
+        let variance =
+            intervals.iter().map(|interval| (interval - average).powi(2)).sum::<f64>() / intervals.len() as f64;
+        assert!((variance.sqrt() - 10.0).abs() < 3.0, "interval standard deviation {}s", variance.sqrt());
+    }
+
+    #[test]
+    fn test_batch_produced_exactly_at_window_boundary() {
+        let clock = MockClock::default();
+        let window = Duration::from_secs(10);
+        let engine = BatchingEngine::new(10, window).with_clock(Arc::new(clock.clone()));
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+
+        clock.advance(window - Duration::from_nanos(1));
+        assert!(engine.check_time_window().unwrap().is_none());
+
+        clock.advance(Duration::from_nanos(1));
+        let batch = engine.check_time_window().unwrap().expect("window has elapsed");
+        assert_eq!(batch.timestamp, SystemTime::UNIX_EPOCH + window);
+
+        // The next window runs from the last batch, not from its first transaction
+        clock.advance(Duration::from_secs(5));
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(2), "b".to_string())).unwrap();
+        clock.advance(Duration::from_secs(5) - Duration::from_nanos(1));
+        assert!(engine.check_time_window().unwrap().is_none());
+        clock.advance(Duration::from_nanos(1));
+        assert!(engine.check_time_window().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_clock_stepping_backwards_restarts_window() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(3600);
+        let clock = MockClock::new(start);
+        let window = Duration::from_secs(10);
+        let engine = BatchingEngine::new(10, window).with_clock(Arc::new(clock.clone()));
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+
+        // A correction steps the clock back a minute halfway through the window
+        clock.advance(Duration::from_secs(5));
+        clock.set(start - Duration::from_secs(60));
+        assert!(engine.check_time_window().unwrap().is_none());
+
+        // The window restarts from the stepped-back time instead of waiting out the minute
+        clock.advance(window - Duration::from_nanos(1));
+        assert!(engine.check_time_window().unwrap().is_none());
+        clock.advance(Duration::from_nanos(1));
+        let batch = engine.check_time_window().unwrap().expect("restarted window has elapsed");
+        assert_eq!(batch.transactions.len(), 1);
+    }
+
+    #[test]
+    fn test_duplicate_accepted_after_dedup_ttl() {
+        let clock = MockClock::default();
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600))
+            .with_dedup_ttl(Duration::from_millis(50))
+            .with_clock(Arc::new(clock.clone()));
+
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+        assert!(engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "b".to_string())).is_err());
+
+        clock.advance(Duration::from_millis(50));
+
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "c".to_string())).unwrap();
+        assert_eq!(engine.pending_count().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_add_transactions_cuts_batches_in_order() {
+        let engine = BatchingEngine::new(2, Duration::from_secs(3600));
+        let txs = (1..=5).map(|nonce| TransactionEnvelope::new(dynamic_fee_tx(nonce), String::new())).collect();
+
+        let results = engine.add_transactions(txs);
+
+        let cut: Vec<Option<usize>> = results
+            .into_iter()
+            .map(|result| result.unwrap().map(|batch| batch.transactions.len()))
+            .collect();
+        assert_eq!(cut, vec![None, Some(2), None, Some(2), None]);
+        assert_eq!(engine.pending_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_adaptive_policy_replaces_fixed_batch_size() {
+        // Sparse traffic keeps the policy at its minimum of 2, well below the fixed size of 10
+        let policy = AdaptiveBatchPolicy::new(2, 10).with_rate_window(Duration::from_secs(3600));
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600)).with_adaptive_policy(policy);
+
+        assert!(engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap().is_none());
+        let batch = engine
+            .add_transaction(TransactionEnvelope::new(dynamic_fee_tx(2), "b".to_string()))
+            .unwrap()
+            .expect("adaptive minimum should produce a batch");
+        assert_eq!(batch.transactions.len(), 2);
+    }
+
+    #[test]
+    fn test_encrypted_batch_logged_only_once_sealed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ingress.wal");
+
+        // The batch nonce and ID are drawn, then the batch key cannot be
+        let engine = BatchingEngine::new(1, Duration::from_secs(3600))
+            .with_wal(WriteAheadLog::open(&path).unwrap())
+            .unwrap()
+            .with_entropy(Arc::new(FailingEntropy::after(2)))
+            .with_envelope_encryption(2, 3);
+        let failed = engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string()));
+        assert_eq!(failed.unwrap_err(), IngressError::RngFailure);
+        assert_eq!(engine.pending_count().unwrap(), 1);
+        drop(engine);
+
+        // Nothing was logged as committed, so the transaction is recovered
+        let engine = BatchingEngine::new(1, Duration::from_secs(3600))
+            .with_wal(WriteAheadLog::open(&path).unwrap())
+            .unwrap()
+            .with_envelope_encryption(2, 3);
+        assert_eq!(engine.pending_count().unwrap(), 1);
+        let batch = engine.flush().unwrap().unwrap();
+        assert!(batch.transactions.iter().all(|tx| tx.encrypted));
+        assert_eq!(batch.sequence, 0);
+
+        // The shares open the batch and the members are known by their plaintext hashes
+        let (shares, members) = engine.take_sealed(&batch.id).unwrap().unwrap();
+        assert_eq!(members, vec![transaction_hash(&dynamic_fee_tx(1))]);
+        assert!(engine.take_sealed(&batch.id).unwrap().is_none());
+        let mut opened = batch;
+        opened.decrypt_batch(&shares[..2]).unwrap();
+        assert_eq!(opened.transactions[0].tx_bytes, dynamic_fee_tx(1));
+        drop(engine);
+
+        // The committed record matches the plaintext, so nothing is recovered again
+        let restarted = BatchingEngine::new(1, Duration::from_secs(3600))
+            .with_wal(WriteAheadLog::open(&path).unwrap())
+            .unwrap();
+        assert_eq!(restarted.pending_count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_pending_transactions_recovered_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ingress.wal");
+
+        let engine = BatchingEngine::new(2, Duration::from_secs(3600))
+            .with_wal(WriteAheadLog::open(&path).unwrap())
+            .unwrap();
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+        let batch = engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(2), "b".to_string())).unwrap();
+        assert!(batch.is_some());
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(3), "c".to_string())).unwrap();
+        drop(engine);
+
+        // A fresh engine over the same log only reloads the transaction that was never batched
+        let restarted = BatchingEngine::new(2, Duration::from_secs(3600))
+            .with_wal(WriteAheadLog::open(&path).unwrap())
+            .unwrap();
+        let pending = restarted.default_lane.pending.snapshot().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].tx_bytes, dynamic_fee_tx(3));
+
+        // Recovered transactions still count as seen
+        let repeat = restarted.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(3), "d".to_string()));
+        assert_eq!(repeat.unwrap_err(), IngressError::Duplicate);
+    }
+
+    #[test]
+    fn test_batch_sequence_increments_and_resumes_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ingress.wal");
+        let engine = BatchingEngine::new(1, Duration::from_secs(3600))
+            .with_lane("urgent", 1, Duration::from_secs(3600))
+            .with_wal(WriteAheadLog::open(&path).unwrap())
+            .unwrap();
+
+        // One numbering across lanes
+        let first = engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap().unwrap();
+        let second = engine
+            .add_transaction_to_lane(TransactionEnvelope::new(dynamic_fee_tx(2), "b".to_string()), "urgent")
+            .unwrap()
+            .unwrap();
+        let third = engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(3), "c".to_string())).unwrap().unwrap();
+        assert_eq!((first.sequence, second.sequence, third.sequence), (0, 1, 2));
+        drop(engine);
+
+        // A restarted engine continues after the last logged batch instead of starting over
+        let restarted = BatchingEngine::new(1, Duration::from_secs(3600))
+            .with_wal(WriteAheadLog::open(&path).unwrap())
+            .unwrap();
+        let batch = restarted.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(4), "d".to_string())).unwrap().unwrap();
+        assert_eq!(batch.sequence, 3);
+
+        // Without a log the numbering starts from 0 on every run
+        let fresh = BatchingEngine::new(1, Duration::from_secs(3600));
+        let batch = fresh.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(4), "d".to_string())).unwrap().unwrap();
+        assert_eq!(batch.sequence, 0);
+    }
+
+    #[test]
+    fn test_concurrent_submissions_neither_lost_nor_duplicated() {
+        let engine = BatchingEngine::new(32, Duration::from_secs(3600));
+        let per_sender: Vec<Vec<Vec<u8>>> = (1..=8u8)
+            .map(|key| (0..100).map(|nonce| signed_dynamic_fee_tx(key, nonce)).collect())
+            .collect();
+
+        // Every thread keeps the batches its own submissions cut
+        let mut batched: Vec<Vec<u8>> = std::thread::scope(|scope| {
+            let workers: Vec<_> = per_sender
+                .iter()
+                .map(|txs| {
+                    let engine = &engine;
+                    scope.spawn(move || {
+                        let mut batched = Vec::new();
+                        for tx in txs {
+                            let batch = engine.add_transaction(attributed(tx.clone())).unwrap();
+                            batched.extend(batch.into_iter().flat_map(|batch| batch.transactions));
+                        }
+                        batched
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .map(|tx| tx.tx_bytes)
+                .collect()
+        });
+        batched.extend(engine.flush().unwrap().into_iter().flat_map(|batch| batch.transactions).map(|tx| tx.tx_bytes));
+
+        let submitted: HashSet<Vec<u8>> = per_sender.into_iter().flatten().collect();
+        assert_eq!(batched.len(), submitted.len());
+        assert_eq!(batched.into_iter().collect::<HashSet<_>>(), submitted);
+        assert_eq!(engine.pending_count().unwrap(), 0);
+        assert_eq!(engine.pending_bytes.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_batch_preserves_nonce_order_per_sender() {
+        let engine = BatchingEngine::new(24, Duration::from_secs(3600));
+
+        // Three senders with eight transactions each, submitted interleaved
+        let mut batch = None;
+        for nonce in 0..8 {
+            for key in [0x01, 0x02, 0x03] {
+                batch = engine.add_transaction(attributed(signed_dynamic_fee_tx(key, nonce))).unwrap();
+            }
+        }
+        let batch = batch.expect("size threshold should produce a batch");
+
+        for key in [0x01, 0x02, 0x03] {
+            let nonces: Vec<u64> = batch
+                .transactions
+                .iter()
+                .filter(|tx| tx.sender == Some(test_address(key)))
+                .map(|tx| tx.nonce.unwrap())
+                .collect();
+            assert_eq!(nonces, (0..8).collect::<Vec<u64>>(), "sender {:#04x} out of order", key);
+        }
+    }
+
+    #[test]
+    fn test_nonce_gap_held_until_filled_or_timed_out() {
+        let clock = MockClock::default();
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600))
+            .with_nonce_gap_hold(Duration::from_secs(10))
+            .with_clock(Arc::new(clock.clone()));
+        let nonces = |batch: &TransactionBatch, key: u8| -> Vec<u64> {
+            batch.transactions.iter().filter(|tx| tx.sender == Some(test_address(key))).map(|tx| tx.nonce.unwrap()).collect()
+        };
+        for (key, nonce) in [(0x01, 0), (0x01, 2), (0x02, 0), (0x02, 1)] {
+            engine.add_transaction(attributed(signed_dynamic_fee_tx(key, nonce))).unwrap();
+        }
+
+        // The contiguous sender goes out whole; the gapped tail waits
+        let batch = engine.flush().unwrap().unwrap();
+        assert_eq!(nonces(&batch, 0x01), vec![0]);
+        assert_eq!(nonces(&batch, 0x02), vec![0, 1]);
+        assert_eq!(engine.pending_count().unwrap(), 1);
+
+        // Filling the gap releases the tail with it
+        engine.add_transaction(attributed(signed_dynamic_fee_tx(0x01, 1))).unwrap();
+        assert_eq!(nonces(&engine.flush().unwrap().unwrap(), 0x01), vec![1, 2]);
+
+        // A gap that is never filled holds its tail only until the timeout
+        engine.add_transaction(attributed(signed_dynamic_fee_tx(0x01, 3))).unwrap();
+        engine.add_transaction(attributed(signed_dynamic_fee_tx(0x01, 5))).unwrap();
+        assert_eq!(nonces(&engine.flush().unwrap().unwrap(), 0x01), vec![3]);
+        clock.advance(Duration::from_secs(9));
+        assert!(engine.flush().unwrap().is_none());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(nonces(&engine.flush().unwrap().unwrap(), 0x01), vec![5]);
+    }
+
+    #[test]
+    fn test_gapped_tail_batched_without_nonce_gap_hold() {
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600));
+        engine.add_transaction(attributed(signed_dynamic_fee_tx(0x01, 0))).unwrap();
+        engine.add_transaction(attributed(signed_dynamic_fee_tx(0x01, 2))).unwrap();
+
+        let batch = engine.flush().unwrap().unwrap();
+        let nonces: Vec<u64> = batch.transactions.iter().map(|tx| tx.nonce.unwrap()).collect();
+        assert_eq!(nonces, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_nonce_ordering_keeps_sender_slots() {
+        let mut transactions = vec![
+            attributed(signed_dynamic_fee_tx(0x01, 2)),
+            attributed(signed_dynamic_fee_tx(0x02, 5)),
+            attributed(signed_dynamic_fee_tx(0x01, 0)),
+            TransactionEnvelope::new(vec![0x02, 0x01], "unattributed".to_string()),
+            attributed(signed_dynamic_fee_tx(0x01, 1)),
+            attributed(signed_dynamic_fee_tx(0x02, 4)),
+        ];
+
+        order_nonces_per_sender(&mut transactions);
+
+        // Each sender keeps the positions it was shuffled into; only its own nonces are reordered
+        let layout: Vec<(Option<Address>, Option<u64>)> = transactions.iter().map(|tx| (tx.sender, tx.nonce)).collect();
+        assert_eq!(
+            layout,
+            vec![
+                (Some(test_address(0x01)), Some(0)),
+                (Some(test_address(0x02)), Some(4)),
+                (Some(test_address(0x01)), Some(1)),
+                (None, None),
+                (Some(test_address(0x01)), Some(2)),
+                (Some(test_address(0x02)), Some(5)),
+            ]
+        );
+    }
+
+    // Sixteen transactions from distinct senders bidding distinct fees, in ascending fee order
+    fn fee_ladder() -> Vec<Vec<u8>> {
+        (1..=16u64).map(|i| fee_bidding_tx(i as u8, i * 100, i * 1_000)).collect()
+    }
+
+    #[test]
+    fn test_fee_ordering_sorts_descending() {
+        let engine = BatchingEngine::new(16, Duration::from_secs(3600)).with_ordering(BatchOrdering::FeeDescending);
+
+        let mut batch = None;
+        for tx in fee_ladder() {
+            batch = engine.add_transaction(attributed(tx)).unwrap();
+        }
+        let batch = batch.unwrap();
+
+        let fees: Vec<u128> = batch.transactions.iter().map(|tx| transaction_fees(&tx.tx_bytes).unwrap().max_fee).collect();
+        let expected: Vec<u128> = (1..=16u128).rev().map(|i| i * 1_000).collect();
+        assert_eq!(fees, expected);
+    }
+
+    #[test]
+    fn test_content_addressed_batches_reproducible() {
+        let build = |order: &[u8]| {
+            let engine = BatchingEngine::new(order.len(), Duration::from_secs(3600))
+                .with_batch_id_mode(BatchIdMode::ContentAddressed);
+            let mut batch = None;
+            for &key in order {
+                batch = engine.add_transaction(attributed(fee_bidding_tx(key, 100, 1_000))).unwrap();
+            }
+            batch.unwrap()
+        };
+
+        // Same transactions in a different arrival order: same ID, and so the same shuffled order
+        let first = build(&[1, 2, 3, 4, 5, 6]);
+        let second = build(&[6, 5, 4, 3, 2, 1]);
+        assert_eq!(first.id, second.id);
+        let forwarded = |batch: &TransactionBatch| batch.transactions.iter().map(|tx| tx.tx_bytes.clone()).collect::<Vec<_>>();
+        assert_eq!(forwarded(&first), forwarded(&second));
+
+        assert_ne!(first.id, build(&[1, 2, 3, 4, 5, 7]).id);
+    }
+
+    #[test]
+    fn test_default_ordering_preserves_shuffle() {
+        let engine = BatchingEngine::new(16, Duration::from_secs(3600));
+
+        let mut batch = None;
+        for tx in fee_ladder() {
+            batch = engine.add_transaction(attributed(tx)).unwrap();
+        }
+        let batch = batch.unwrap();
+
+        // Exactly the batch-id shuffle of the arrival order, not sorted by fee
+        let mut expected = fee_ladder();
+        deterministic_shuffle(&mut expected, &batch.id, batch.hash_algo);
+        let forwarded: Vec<Vec<u8>> = batch.transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        assert_eq!(forwarded, expected);
+    }
+
+    #[test]
+    fn test_injected_seed_pins_shuffle() {
+        let shuffled = |seed: [u8; 32]| {
+            let engine = BatchingEngine::new(16, Duration::from_secs(3600)).with_rng_seed(seed);
+            let mut batch = None;
+            for tx in fee_ladder() {
+                batch = engine.add_transaction(attributed(tx)).unwrap();
+            }
+            batch.unwrap().transactions.into_iter().map(|tx| tx.tx_bytes).collect::<Vec<_>>()
+        };
+
+        // Batch IDs differ between runs, yet the order only depends on the seed
+        assert_eq!(shuffled([7; 32]), shuffled([7; 32]));
+        assert_ne!(shuffled([7; 32]), shuffled([8; 32]));
+        assert_ne!(shuffled([7; 32]), fee_ladder());
+    }
+
+    #[test]
+    fn test_recorded_permutation_reproduces_forwarded_order() {
+        let engine = BatchingEngine::new(16, Duration::from_secs(3600)).with_record_permutation(true);
+        let mut batch = None;
+        for tx in fee_ladder() {
+            batch = engine.add_transaction(attributed(tx)).unwrap();
+        }
+        let batch = batch.unwrap();
+
+        // Arrival order, the permutation and the seed from the batch ID each reproduce the forwarded order
+        let permutation = batch.permutation.clone().unwrap();
+        let forwarded: Vec<Vec<u8>> = batch.transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        assert_eq!(apply_permutation(&fee_ladder(), &permutation), forwarded);
+        let seed = create_seed_from_batch_id(&batch.id, &[], batch.hash_algo);
+        assert_eq!(shuffle_with_seed(&fee_ladder(), seed), permutation);
+
+        // Not recorded unless asked for
+        let engine = BatchingEngine::new(1, Duration::from_secs(3600));
+        let batch = engine.add_transaction(attributed(fee_ladder().remove(0))).unwrap().unwrap();
+        assert!(batch.permutation.is_none());
+    }
+
+    #[test]
+    fn test_recorded_permutation_covers_nonce_reordering() {
+        // One sender's nonces 0, 1 and 2 among other senders: the shuffle alone would scatter the
+        // sender's nonces, so the permutation has to account for putting them back in sequence
+        let arrival: Vec<Vec<u8>> = [(1, 2), (2, 0), (1, 0), (3, 0), (1, 1), (4, 0)]
+            .into_iter()
+            .map(|(key, nonce)| signed_dynamic_fee_tx(key, nonce))
+            .collect();
+        let engine = BatchingEngine::new(arrival.len(), Duration::from_secs(3600)).with_record_permutation(true);
+        let mut batch = None;
+        for tx in &arrival {
+            batch = engine.add_transaction(attributed(tx.clone())).unwrap();
+        }
+        let batch = batch.unwrap();
+
+        let forwarded: Vec<Vec<u8>> = batch.transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        let nonces: Vec<u64> = batch
+            .transactions
+            .iter()
+            .filter(|tx| tx.sender == Some(recover_sender(&arrival[0]).unwrap()))
+            .map(|tx| tx.nonce.unwrap())
+            .collect();
+        assert_eq!(nonces, vec![0, 1, 2]);
+        assert_eq!(apply_permutation(&arrival, batch.permutation.as_ref().unwrap()), forwarded);
+    }
+
+    #[test]
+    fn test_shuffle_secret_hides_order_from_batch_id() {
+        let secret = |byte: u8| ShuffleSecret::new(vec![byte; 32]).unwrap();
+        let (first, second) = (secret(1), secret(2));
+
+        // Same batch ID, different secrets: different seeds and different orderings
+        let permutation = |seed: [u8; 32]| shuffle_with_seed(&fee_ladder(), seed);
+        assert_ne!(first.seed_for("batch-1", HashAlgo::Sha256), second.seed_for("batch-1", HashAlgo::Sha256));
+        assert_ne!(
+            permutation(first.seed_for("batch-1", HashAlgo::Sha256)),
+            permutation(second.seed_for("batch-1", HashAlgo::Sha256))
+        );
+
+        // The batch ID alone no longer predicts the order, the secret and the ID together do
+        let engine = BatchingEngine::new(16, Duration::from_secs(3600))
+            .with_shuffle_secret(first.clone())
+            .with_record_permutation(true);
+        let mut batch = None;
+        for tx in fee_ladder() {
+            batch = engine.add_transaction(attributed(tx)).unwrap();
+        }
+        let batch = batch.unwrap();
+        let recorded = batch.permutation.clone().unwrap();
+        assert_eq!(permutation(first.seed_for(&batch.id, batch.hash_algo)), recorded);
+        assert_ne!(permutation(create_seed_from_batch_id(&batch.id, &[], batch.hash_algo)), recorded);
+
+        assert!(ShuffleSecret::new(vec![1; MIN_SHUFFLE_SECRET_LEN - 1]).is_err());
+        assert_eq!(format!("{:?}", first), "ShuffleSecret(..)");
+    }
+
+    #[test]
+    fn test_batch_padded_to_decoy_target_size() {
+        let engine = BatchingEngine::new(10, Duration::from_secs(3600)).with_decoy_target_size(8);
+
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(2), "b".to_string())).unwrap();
+        let batch = engine.flush().unwrap().unwrap();
+
+        assert_eq!(batch.transactions.len(), 8);
+        assert_eq!(batch.transactions.iter().filter(|tx| !tx.is_decoy()).count(), 2);
+        // Every entry, decoy or not, is a valid transaction covered by the commitment
+        for tx in &batch.transactions {
+            assert!(validate_transaction(&tx.tx_bytes).is_ok());
+            assert!(batch.merkle_proof(&tx.tx_bytes).is_some());
+        }
+
+        // Batches already at the target are left alone
+        let engine = BatchingEngine::new(2, Duration::from_secs(3600)).with_decoy_target_size(2);
+        engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(1), "a".to_string())).unwrap();
+        let batch = engine.add_transaction(TransactionEnvelope::new(dynamic_fee_tx(2), "b".to_string())).unwrap().unwrap();
+        assert!(batch.transactions.iter().all(|tx| !tx.is_decoy()));
+    }
+
+    #[test]
+    fn test_invalid_transaction_never_enters_pending() {
+        let engine = BatchingEngine::new(3, Duration::from_secs(3600));
+
+        let result = engine.add_transaction(TransactionEnvelope::new(vec![0x02, 0x01, 0x02, 0x03], "a".to_string()));
+
+        assert!(matches!(result, Err(IngressError::InvalidTransaction(_))));
+        assert_eq!(engine.pending_count().unwrap(), 0);
+    }
+}
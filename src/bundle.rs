@@ -0,0 +1,262 @@
+use crate::batch::TransactionBatch;
+use crate::error::IngressError;
+use crate::relay::{post_json_rpc, RelayFuture, RelayResult, RelayTransport, RetryPolicy};
+use crate::transaction::transaction_hash;
+
+// Blocks ahead of the current head that bundles target by default: the next block
+pub const DEFAULT_BLOCK_OFFSET: u64 = 1;
+
+// Relay speaking the Flashbots eth_sendBundle API, sending each batch as one atomic bundle
+//
+// A bundle is included whole and in order or not at all, so the shuffled order is exactly what
+// lands on chain. The head block comes from a separate node, since bundle relays don't serve
+// eth_blockNumber. A bundle targets one block, never one past the batch's inclusion deadline.
+#[derive(Clone)]
+pub struct BundleRelay {
+    relay_url: String,
+    node_url: String,
+    block_offset: u64,
+    allow_reverts: bool, // List every transaction in revertingTxHashes
+    client: reqwest::Client,
+    retry_policy: RetryPolicy,
+}
+
+impl BundleRelay {
+    pub fn new(relay_url: String, node_url: String) -> Self {
+        Self {
+            relay_url,
+            node_url,
+            block_offset: DEFAULT_BLOCK_OFFSET,
+            allow_reverts: false,
+            client: reqwest::Client::new(),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    // Targets the block this many blocks after the current head
+    pub fn with_block_offset(mut self, block_offset: u64) -> Self {
+        self.block_offset = block_offset;
+        self
+    }
+
+    // Lets any transaction revert without the bundle being dropped
+    pub fn with_allow_reverts(mut self, allow_reverts: bool) -> Self {
+        self.allow_reverts = allow_reverts;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    // eth_sendBundle request for the batch, targeting target_block
+    pub fn bundle_request(&self, batch: &TransactionBatch, target_block: u64) -> serde_json::Value {
+        let txs: Vec<String> = batch
+            .transactions
+            .iter()
+            .map(|tx| format!("0x{}", hex::encode(&tx.tx_bytes)))
+            .collect();
+        let mut bundle = serde_json::json!({
+            "txs": txs,
+            "blockNumber": format!("0x{:x}", target_block),
+        });
+        if self.allow_reverts {
+            let reverting: Vec<String> = batch
+                .transactions
+                .iter()
+                .map(|tx| format!("0x{}", hex::encode(transaction_hash(&tx.tx_bytes))))
+                .collect();
+            bundle["revertingTxHashes"] = serde_json::json!(reverting);
+        }
+
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_sendBundle",
+            "params": [bundle],
+        })
+    }
+
+    async fn send_bundle(&self, batch: &TransactionBatch) -> RelayResult {
+        let mut result = RelayResult::new(self.relay_url.as_str());
+        let target = head_block(&self.client, &self.node_url)
+            .await
+            .and_then(|head| target_block(head, self.block_offset, batch));
+        let target_block = match target {
+            Ok(target_block) => target_block,
+            Err(error) => {
+                result.error = Some(error);
+                return result;
+            }
+        };
+
+        let request = self.bundle_request(batch, target_block);
+        post_json_rpc(&self.client, &self.relay_url, &request, batch, &self.retry_policy, &mut result).await;
+        result
+    }
+}
+
+// Helper function to pick the block a bundle targets: block_offset past head, pulled in to the batch's
+// inclusion deadline if that comes sooner
+pub(crate) fn target_block(head: u64, block_offset: u64, batch: &TransactionBatch) -> Result<u64, IngressError> {
+    let target_block = head + block_offset;
+    match batch.valid_until_block() {
+        Some(valid_until_block) if valid_until_block <= head => {
+            Err(IngressError::InclusionDeadlinePassed { valid_until_block, head })
+        }
+        Some(valid_until_block) => Ok(target_block.min(valid_until_block)),
+        None => Ok(target_block),
+    }
+}
+
+// Helper function to read the node's current head block, which bundles are targeted relative to
+pub(crate) async fn head_block(client: &reqwest::Client, node_url: &str) -> Result<u64, IngressError> {
+    let request = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber", "params": []});
+    let body: serde_json::Value = client
+        .post(node_url)
+        .json(&request)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| IngressError::RelayUnreachable(format!("head block: {}", e)))?
+        .json()
+        .await
+        .map_err(|e| IngressError::RelayUnreachable(format!("head block: {}", e)))?;
+
+    body.get("result")
+        .and_then(serde_json::Value::as_str)
+        .and_then(|number| number.strip_prefix("0x"))
+        .and_then(|number| u64::from_str_radix(number, 16).ok())
+        .ok_or_else(|| IngressError::RelayUnreachable(format!("head block: invalid response {}", body)))
+}
+
+impl RelayTransport for BundleRelay {
+    fn name(&self) -> &str {
+        &self.relay_url
+    }
+
+    fn submit<'a>(&'a self, batch: &'a TransactionBatch) -> RelayFuture<'a> {
+        Box::pin(self.send_bundle(batch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batching::BatchingEngine;
+    use crate::envelope::TransactionEnvelope;
+    use crate::relay::RelayForwarder;
+    use crate::test_utils::{node_at_block, signed_dynamic_fee_tx};
+    use std::time::Duration;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn shuffled_batch() -> TransactionBatch {
+        let engine = BatchingEngine::new(4, Duration::from_secs(3600));
+        let mut batch = None;
+        for key in 1..=4 {
+            batch = engine.add_transaction(TransactionEnvelope::new(signed_dynamic_fee_tx(key, 0), String::new())).unwrap();
+        }
+        batch.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_batch_sent_as_one_bundle_in_shuffled_order() {
+        let node = node_at_block(0x1000).await;
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": {"bundleHash": format!("0x{}", "ab".repeat(32))}}),
+            ))
+            .expect(1)
+            .mount(&relay)
+            .await;
+        let forwarder = RelayForwarder::from_transports(vec![Box::new(
+            BundleRelay::new(relay.uri(), node.uri()).with_block_offset(2),
+        )]);
+        let batch = shuffled_batch();
+
+        let results = forwarder.forward_batch(&batch).await;
+        assert!(results[0].is_success());
+
+        let requests = relay.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["jsonrpc"], "2.0");
+        assert_eq!(body["method"], "eth_sendBundle");
+        let params = body["params"].as_array().unwrap();
+        assert_eq!(params.len(), 1);
+        let bundle = params[0].as_object().unwrap();
+        assert_eq!(bundle["blockNumber"], "0x1002");
+        assert!(!bundle.contains_key("revertingTxHashes"));
+
+        // Every transaction, exactly in the batch's shuffled order
+        let expected: Vec<String> = batch
+            .transactions
+            .iter()
+            .map(|tx| format!("0x{}", hex::encode(&tx.tx_bytes)))
+            .collect();
+        assert_eq!(bundle["txs"], serde_json::json!(expected));
+    }
+
+    #[test]
+    fn test_reverting_hashes_listed_when_allowed() {
+        let batch = shuffled_batch();
+        let request = BundleRelay::new(String::new(), String::new())
+            .with_allow_reverts(true)
+            .bundle_request(&batch, 17);
+
+        let expected: Vec<String> = batch
+            .transactions
+            .iter()
+            .map(|tx| format!("0x{}", hex::encode(transaction_hash(&tx.tx_bytes))))
+            .collect();
+        assert_eq!(request["params"][0]["revertingTxHashes"], serde_json::json!(expected));
+        assert_eq!(request["params"][0]["blockNumber"], "0x11");
+    }
+
+    #[tokio::test]
+    async fn test_bundle_target_kept_within_inclusion_deadline() {
+        let node = node_at_block(0x1000).await;
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": {"bundleHash": format!("0x{}", "ab".repeat(32))}}),
+            ))
+            .mount(&relay)
+            .await;
+        let transport = BundleRelay::new(relay.uri(), node.uri()).with_block_offset(3);
+        let mut batch = shuffled_batch();
+
+        // The earliest deadline in the batch pulls the target in from 0x1003
+        batch.transactions[0].valid_until_block = Some(0x1005);
+        batch.transactions[1].valid_until_block = Some(0x1001);
+        assert!(transport.submit(&batch).await.is_success());
+        let requests = relay.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["params"][0]["blockNumber"], "0x1001");
+
+        // A deadline the head has reached leaves no block to target
+        batch.transactions[1].valid_until_block = Some(0x1000);
+        let result = transport.submit(&batch).await;
+        assert_eq!(
+            result.error,
+            Some(IngressError::InclusionDeadlinePassed { valid_until_block: 0x1000, head: 0x1000 })
+        );
+        assert_eq!(relay.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_node_fails_without_sending_bundle() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(200)).expect(0).mount(&relay).await;
+        let node = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(503)).mount(&node).await;
+        let transport = BundleRelay::new(relay.uri(), node.uri());
+
+        let result = transport.submit(&shuffled_batch()).await;
+
+        assert!(matches!(result.error, Some(IngressError::RelayUnreachable(_))));
+        assert!(!result.is_success());
+    }
+}
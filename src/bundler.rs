@@ -0,0 +1,519 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use tracing::{info, warn};
+
+use crate::batch::TransactionBatch;
+use crate::batching::{order_nonces_by, DEFAULT_DEDUP_TTL, DEFAULT_MAX_PENDING_BYTES};
+use crate::clock::{Clock, SystemClock};
+use crate::commit_reveal::CommitRevealPipeline;
+use crate::crypto::{create_seed_from_batch_id, HashAlgo, ShuffleSecret};
+use crate::dedup::DedupCache;
+use crate::envelope::TransactionEnvelope;
+use crate::error::{lock, IngressError};
+use crate::ingress::DEFAULT_POLL_INTERVAL;
+use crate::ordering::{OrderingPolicy, ShufflePolicy};
+use crate::relay::{post_body, RelayForwarder, RelayFuture, RelayResult, RelayTransport, RetryPolicy};
+use crate::transaction::{transaction_hash, Address};
+use crate::user_operation::UserOperation;
+
+// Method ERC-4337 bundlers take UserOperations over
+pub const USER_OPERATION_METHOD: &str = "eth_sendUserOperation";
+
+// Relay speaking the ERC-4337 bundler API: every UserOperation of a batch goes out as its own
+// eth_sendUserOperation call for the configured EntryPoint, in batch order
+//
+// Batch members carry a UserOperation's JSON in place of raw transaction bytes, as UserOpIngress
+// builds them; a member that does not decode as one is refused without being sent. Accepted and
+// refused operations are reported by userOpHash, as bundlers and clients know them.
+#[derive(Clone)]
+pub struct BundlerRelay {
+    url: String,
+    entry_point: Address,
+    chain_id: u64, // Part of every userOpHash the results are reported by
+    client: reqwest::Client,
+    retry_policy: RetryPolicy,
+}
+
+impl BundlerRelay {
+    pub fn new(url: String, entry_point: Address, chain_id: u64) -> Self {
+        Self {
+            url,
+            entry_point,
+            chain_id,
+            client: reqwest::Client::new(),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    async fn send_batch(&self, batch: &TransactionBatch) -> RelayResult {
+        let mut result = RelayResult::new(self.url.as_str());
+        let entry_point = format!("0x{}", hex::encode(self.entry_point));
+
+        for (index, tx) in batch.transactions.iter().enumerate() {
+            // A member that is no UserOperation has no userOpHash, so it is named by its Keccak-256 hash
+            let op = match UserOperation::decode(&tx.tx_bytes) {
+                Ok(op) => op,
+                Err(error) => {
+                    result.rejected.push((transaction_hash(&tx.tx_bytes), error.to_string()));
+                    result.error.get_or_insert(error);
+                    continue;
+                }
+            };
+            let op_hash = op.hash(&self.entry_point, self.chain_id);
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": index,
+                "method": USER_OPERATION_METHOD,
+                "params": [op, entry_point],
+            });
+            let body = request.to_string().into_bytes();
+            match post_body(&self.client, &self.url, body, None, batch, &self.retry_policy, &mut result).await {
+                Ok(_) => result.accepted_txs.push(op_hash),
+                Err(error) => result.rejected.push((op_hash, error.to_string())),
+            }
+        }
+        result
+    }
+}
+
+impl RelayTransport for BundlerRelay {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    fn submit<'a>(&'a self, batch: &'a TransactionBatch) -> RelayFuture<'a> {
+        Box::pin(self.send_batch(batch))
+    }
+}
+
+// Ingress path for ERC-4337 UserOperations alongside PenumIngress, with the same privacy: operations
+// are pooled, cut into batches by size or time window, shuffled under a seed derived from the batch
+// ID, committed, and only forwarded to the bundler once revealed; clones share the same state
+//
+// Each operation becomes a batch member carrying its JSON, so commitments and inclusion proofs
+// work as they do for transactions. Operations are not simulated; bundlers do that on receipt.
+#[derive(Clone)]
+pub struct UserOpIngress {
+    entry_point: Address,
+    chain_id: u64, // Part of every userOpHash, so operations cannot be replayed on another network
+    max_batch_size: usize,
+    batch_time_window: Duration,
+    hash_algo: HashAlgo,
+    shuffle_secret: Option<ShuffleSecret>,
+    poll_interval: Duration,
+    max_pending_bytes: usize, // Bound on the encoded size of the operations waiting for a batch
+    pending: Arc<Mutex<PendingOps>>,
+    last_batch_time: Arc<Mutex<SystemTime>>,
+    seen: Arc<Mutex<DedupCache>>, // userOpHashes submitted within the dedup TTL
+    commit_reveal_pipeline: Arc<CommitRevealPipeline>,
+    awaiting_reveal: Arc<Mutex<Vec<TransactionBatch>>>, // Committed batches, oldest first
+    relay_forwarder: Arc<RelayForwarder>,
+    clock: Arc<dyn Clock>, // Times batching windows, dedup and commitments
+}
+
+// Operations waiting for the next batch, and their total encoded size
+#[derive(Default)]
+struct PendingOps {
+    ops: Vec<UserOperation>,
+    bytes: usize,
+}
+
+impl UserOpIngress {
+    pub fn new(
+        bundler_url: String,
+        entry_point: Address,
+        chain_id: u64,
+        max_batch_size: usize,
+        batch_time_window: Duration,
+    ) -> Self {
+        let bundler = BundlerRelay::new(bundler_url, entry_point, chain_id);
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        Self {
+            entry_point,
+            chain_id,
+            max_batch_size,
+            batch_time_window,
+            hash_algo: HashAlgo::default(),
+            shuffle_secret: None,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            max_pending_bytes: DEFAULT_MAX_PENDING_BYTES,
+            pending: Arc::new(Mutex::new(PendingOps::default())),
+            last_batch_time: Arc::new(Mutex::new(clock.now())),
+            seen: Arc::new(Mutex::new(DedupCache::new(DEFAULT_DEDUP_TTL))),
+            commit_reveal_pipeline: Arc::new(CommitRevealPipeline::new()),
+            awaiting_reveal: Arc::new(Mutex::new(Vec::new())),
+            relay_forwarder: Arc::new(RelayForwarder::from_transports(vec![Box::new(bundler)])),
+            clock,
+        }
+    }
+
+    // Reads time from the given clock for batching windows, dedup, commitments and reveal delays
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.last_batch_time = Arc::new(Mutex::new(clock.now()));
+        self.commit_reveal_pipeline = Arc::new((*self.commit_reveal_pipeline).clone().with_clock(clock.clone()));
+        self.clock = clock;
+        self
+    }
+
+    // Refuses operations with IngressError::PendingPoolFull once those waiting for a batch encode
+    // to more than max_pending_bytes, instead of 64 MiB
+    pub fn with_max_pending_bytes(mut self, max_pending_bytes: usize) -> Self {
+        self.max_pending_bytes = max_pending_bytes;
+        self
+    }
+
+    // Replaces the single bundler, e.g. with several BundlerRelays or a forwarder with circuit breakers
+    pub fn with_relay_forwarder(mut self, relay_forwarder: RelayForwarder) -> Self {
+        self.relay_forwarder = Arc::new(relay_forwarder);
+        self
+    }
+
+    // Sets how long a committed batch waits before it is revealed and forwarded
+    pub fn with_reveal_delay(mut self, reveal_delay: Duration) -> Self {
+        self.commit_reveal_pipeline = Arc::new((*self.commit_reveal_pipeline).clone().with_reveal_delay(reveal_delay));
+        self
+    }
+
+    // Mixes an operator secret into every shuffle seed, as PenumIngress::with_shuffle_secret does
+    pub fn with_shuffle_secret(mut self, shuffle_secret: ShuffleSecret) -> Self {
+        self.shuffle_secret = Some(shuffle_secret);
+        self
+    }
+
+    pub fn with_hash_algo(mut self, hash_algo: HashAlgo) -> Self {
+        self.hash_algo = hash_algo;
+        self
+    }
+
+    // Sets how often drain checks for batches whose reveal delay has passed
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    // Validates the operation and pools it, returning its userOpHash; forwards a batch once the pool
+    // reaches the batch size
+    pub async fn submit_user_operation(&self, op: UserOperation) -> Result<[u8; 32], IngressError> {
+        op.validate()?;
+        let op_hash = op.hash(&self.entry_point, self.chain_id);
+
+        let full = {
+            // Room is checked before dedup, so an operation refused for a full pool can be resubmitted
+            let mut pending = lock(&self.pending)?;
+            let size = op.encode().len();
+            if pending.bytes + size > self.max_pending_bytes {
+                return Err(IngressError::PendingPoolFull {
+                    pending_bytes: pending.bytes,
+                    max_pending_bytes: self.max_pending_bytes,
+                });
+            }
+            if !lock(&self.seen)?.insert(op_hash.to_vec(), self.clock.now()) {
+                return Err(IngressError::Duplicate);
+            }
+            pending.ops.push(op);
+            pending.bytes += size;
+            pending.ops.len() >= self.max_batch_size
+        };
+        if full {
+            self.process_batch().await?;
+        }
+        Ok(op_hash)
+    }
+
+    // Operations waiting for the next batch
+    pub fn pending_count(&self) -> Result<usize, IngressError> {
+        Ok(lock(&self.pending)?.ops.len())
+    }
+
+    // Cuts a batch if the time window has passed and forwards every batch whose reveal delay has
+    pub async fn process_batches(&self) -> Result<(), IngressError> {
+        let elapsed = self.clock.now().duration_since(*lock(&self.last_batch_time)?).unwrap_or_default();
+        if elapsed >= self.batch_time_window {
+            return self.process_batch().await;
+        }
+        self.reveal_ready_batches().await
+    }
+
+    // Forwards everything still pending, waiting out the reveal delay of batches already committed
+    pub async fn drain(&self) -> Result<(), IngressError> {
+        let mut result = self.process_batch().await;
+        while !lock(&self.awaiting_reveal)?.is_empty() {
+            tokio::time::sleep(self.poll_interval).await;
+            let cycle = self.reveal_ready_batches().await;
+            if result.is_ok() {
+                result = cycle;
+            }
+        }
+        result
+    }
+
+    // Cuts and commits a batch of everything pending, then forwards every batch that is ready
+    async fn process_batch(&self) -> Result<(), IngressError> {
+        if let Some(batch) = self.cut_batch()? {
+            self.commit_reveal_pipeline.commit_batch(&batch).await?;
+            info!(batch_id = %batch.id, ops = batch.transactions.len(), "user operation batch committed");
+            lock(&self.awaiting_reveal)?.push(batch);
+        }
+        self.reveal_ready_batches().await
+    }
+
+    fn cut_batch(&self) -> Result<Option<TransactionBatch>, IngressError> {
+        let PendingOps { ops, .. } = std::mem::take(&mut *lock(&self.pending)?);
+        *lock(&self.last_batch_time)? = self.clock.now();
+        if ops.is_empty() {
+            return Ok(None);
+        }
+
+        let envelopes = ops
+            .into_iter()
+            .map(|op| {
+                let mut envelope = TransactionEnvelope::new(op.encode(), String::new());
+                envelope.sender = Some(op.sender);
+                envelope
+            })
+            .collect();
+        let mut batch = TransactionBatch::with_hash_algo(envelopes, self.hash_algo)?;
+        batch.timestamp = self.clock.now();
+        for envelope in &mut batch.transactions {
+            envelope.batch_id = batch.id.clone();
+        }
+
+        // The commitment sorts its leaves, so shuffling afterwards leaves it unchanged
+        let seed = match &self.shuffle_secret {
+            Some(secret) => secret.seed_for(&batch.id, batch.hash_algo),
+            None => create_seed_from_batch_id(&batch.id, &[], batch.hash_algo),
+        };
+        ShufflePolicy.order(&mut batch.transactions, seed);
+
+        // The EntryPoint refuses an operation ahead of a lower sequence under the same account and nonce key
+        order_nonces_by(&mut batch.transactions, |tx| {
+            let op = UserOperation::decode(&tx.tx_bytes).ok()?;
+            Some(((op.sender, op.nonce_key()), op.nonce_sequence()))
+        });
+        Ok(Some(batch))
+    }
+
+    // Reveals and forwards, oldest first, every committed batch whose reveal delay has passed
+    //
+    // A batch no bundler accepted is not retried; the first such failure is returned once every
+    // ready batch has been tried.
+    async fn reveal_ready_batches(&self) -> Result<(), IngressError> {
+        let ready = self.commit_reveal_pipeline.ready_to_reveal()?;
+        let batches: Vec<TransactionBatch> = {
+            let mut awaiting = lock(&self.awaiting_reveal)?;
+            let (ready, held) = std::mem::take(&mut *awaiting).into_iter().partition(|batch| ready.contains(&batch.id));
+            *awaiting = held;
+            ready
+        };
+
+        let mut first_error = None;
+        for batch in batches {
+            if let Err(error) = self.forward(&batch).await {
+                warn!(batch_id = %batch.id, %error, "user operation batch not forwarded");
+                first_error.get_or_insert(error);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    async fn forward(&self, batch: &TransactionBatch) -> Result<(), IngressError> {
+        self.commit_reveal_pipeline.verify_reveal(batch)?;
+        let results = self.relay_forwarder.forward_batch(batch).await;
+        let accepted = results.iter().filter(|result| result.is_success()).count();
+        info!(batch_id = %batch.id, accepted, bundlers = results.len(), "user operation batch forwarded");
+        if accepted == 0 {
+            return Err(results
+                .into_iter()
+                .find_map(|result| result.error)
+                .unwrap_or(IngressError::QuorumNotMet { accepted, required: 1 }));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::relay::RelayTransport;
+
+    const ENTRY_POINT: Address = [0x5f; 20];
+
+    fn user_operation(sender: u8) -> UserOperation {
+        UserOperation {
+            sender: [sender; 20],
+            nonce: [0; 32],
+            init_code: Vec::new(),
+            call_data: vec![0xb6, 0x1d, 0x27, 0xf6, sender],
+            call_gas_limit: 100_000,
+            verification_gas_limit: 150_000,
+            pre_verification_gas: 45_000,
+            max_fee_per_gas: 30_000_000_000,
+            max_priority_fee_per_gas: 1_000_000_000,
+            paymaster_and_data: Vec::new(),
+            signature: vec![0x5a; 65],
+        }
+    }
+
+    // Operation of sender under the given nonce key and sequence
+    fn sequenced_operation(sender: u8, key: u8, sequence: u64) -> UserOperation {
+        let mut op = user_operation(sender);
+        op.nonce[23] = key;
+        op.nonce[24..].copy_from_slice(&sequence.to_be_bytes());
+        op
+    }
+
+    async fn bundler() -> MockServer {
+        let bundler = MockServer::start().await;
+        let response = serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": format!("0x{}", "ab".repeat(32))});
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({"method": USER_OPERATION_METHOD})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response))
+            .mount(&bundler)
+            .await;
+        bundler
+    }
+
+    #[tokio::test]
+    async fn test_user_operations_batched_and_forwarded_to_bundler() {
+        let bundler = bundler().await;
+        let ingress = UserOpIngress::new(bundler.uri(), ENTRY_POINT, 1, 3, Duration::from_secs(3600));
+
+        // Refused operations never reach the pool
+        let mut unsigned = user_operation(1);
+        unsigned.signature.clear();
+        assert!(matches!(
+            ingress.submit_user_operation(unsigned).await,
+            Err(IngressError::InvalidUserOperation(_))
+        ));
+        let op_hash = ingress.submit_user_operation(user_operation(1)).await.unwrap();
+        assert_eq!(op_hash, user_operation(1).hash(&ENTRY_POINT, 1));
+        assert_eq!(ingress.submit_user_operation(user_operation(1)).await, Err(IngressError::Duplicate));
+        ingress.submit_user_operation(user_operation(2)).await.unwrap();
+        assert!(bundler.received_requests().await.unwrap().is_empty());
+
+        // The third operation fills the batch, which goes out one call per operation
+        ingress.submit_user_operation(user_operation(3)).await.unwrap();
+        assert_eq!(ingress.pending_count().unwrap(), 0);
+        let requests = bundler.received_requests().await.unwrap();
+        let calls: Vec<serde_json::Value> = requests.iter().map(|request| request.body_json().unwrap()).collect();
+        assert_eq!(calls.len(), 3);
+        let entry_point = format!("0x{}", hex::encode(ENTRY_POINT));
+        assert!(calls.iter().all(|call| call["params"][1] == entry_point));
+        let mut senders: Vec<UserOperation> =
+            calls.iter().map(|call| serde_json::from_value(call["params"][0].clone()).unwrap()).collect();
+        senders.sort_by_key(|op| op.sender);
+        assert_eq!(senders, vec![user_operation(1), user_operation(2), user_operation(3)]);
+
+        // A bundler refusing the operation fails the batch
+        bundler.reset().await;
+        let refusal = serde_json::json!({"jsonrpc": "2.0", "id": 0, "error": {"code": -32602, "message": "AA21"}});
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(refusal))
+            .mount(&bundler)
+            .await;
+        ingress.submit_user_operation(user_operation(4)).await.unwrap();
+        assert!(matches!(ingress.drain().await, Err(IngressError::RelayRejected(_))));
+        assert_eq!(bundler.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reveal_delay_holds_user_operations() {
+        let bundler = bundler().await;
+        let ingress = UserOpIngress::new(bundler.uri(), ENTRY_POINT, 1, 10, Duration::from_millis(10))
+            .with_reveal_delay(Duration::from_millis(30))
+            .with_poll_interval(Duration::from_millis(10));
+        ingress.submit_user_operation(user_operation(1)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // The window cuts and commits the batch, but it stays held until the delay has passed
+        ingress.process_batches().await.unwrap();
+        assert_eq!(ingress.pending_count().unwrap(), 0);
+        assert!(bundler.received_requests().await.unwrap().is_empty());
+        ingress.drain().await.unwrap();
+        assert_eq!(bundler.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sequences_ordered_within_each_nonce_key() {
+        let bundler = bundler().await;
+        // Sender 1's key-0 sequences arrive newest first, among other senders and another key of its own
+        let mut ops: Vec<UserOperation> = (0..8).rev().map(|sequence| sequenced_operation(1, 0, sequence)).collect();
+        ops.extend([sequenced_operation(1, 1, 0), sequenced_operation(2, 0, 0), sequenced_operation(3, 0, 0)]);
+        let ingress = UserOpIngress::new(bundler.uri(), ENTRY_POINT, 1, ops.len(), Duration::from_secs(3600));
+        for op in ops {
+            ingress.submit_user_operation(op).await.unwrap();
+        }
+
+        // They go out in order; the key-1 operation is sequenced on its own
+        let requests = bundler.received_requests().await.unwrap();
+        let calls: Vec<serde_json::Value> = requests.iter().map(|request| request.body_json().unwrap()).collect();
+        let forwarded: Vec<UserOperation> =
+            calls.iter().map(|call| serde_json::from_value(call["params"][0].clone()).unwrap()).collect();
+        assert_eq!(forwarded.len(), 11);
+        let sequences: Vec<u64> = forwarded
+            .iter()
+            .filter(|op| op.sender == [1; 20] && op.nonce_key() == [0; 24])
+            .map(UserOperation::nonce_sequence)
+            .collect();
+        assert_eq!(sequences, (0..8).collect::<Vec<u64>>());
+    }
+
+    #[tokio::test]
+    async fn test_bundler_reports_operations_by_user_op_hash() {
+        let bundler = bundler().await;
+        let relay = BundlerRelay::new(bundler.uri(), ENTRY_POINT, 10);
+        let envelopes = vec![
+            TransactionEnvelope::new(user_operation(1).encode(), String::new()),
+            TransactionEnvelope::new(b"not an operation".to_vec(), String::new()),
+        ];
+        let batch = TransactionBatch::new(envelopes).unwrap();
+
+        let result = relay.submit(&batch).await;
+
+        assert_eq!(result.accepted_txs, vec![user_operation(1).hash(&ENTRY_POINT, 10)]);
+        assert_eq!(result.rejected.len(), 1);
+        assert_eq!(result.rejected[0].0, transaction_hash(b"not an operation"));
+    }
+
+    #[tokio::test]
+    async fn test_window_and_pool_bound_follow_injected_clock() {
+        let bundler = bundler().await;
+        let clock = MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let op_len = user_operation(1).encode().len();
+        let ingress = UserOpIngress::new(bundler.uri(), ENTRY_POINT, 1, 10, Duration::from_secs(60))
+            .with_clock(Arc::new(clock.clone()))
+            .with_reveal_delay(Duration::from_secs(5))
+            .with_max_pending_bytes(op_len);
+
+        // The pool holds one operation; the refused one is not remembered as seen
+        ingress.submit_user_operation(user_operation(1)).await.unwrap();
+        assert!(matches!(
+            ingress.submit_user_operation(user_operation(2)).await,
+            Err(IngressError::PendingPoolFull { .. })
+        ));
+
+        // Only the mock clock moves the window and the reveal delay
+        ingress.process_batches().await.unwrap();
+        assert_eq!(ingress.pending_count().unwrap(), 1);
+        clock.advance(Duration::from_secs(60));
+        ingress.process_batches().await.unwrap();
+        assert_eq!(ingress.pending_count().unwrap(), 0);
+        assert!(bundler.received_requests().await.unwrap().is_empty());
+        clock.advance(Duration::from_secs(5));
+        ingress.process_batches().await.unwrap();
+        assert_eq!(bundler.received_requests().await.unwrap().len(), 1);
+
+        ingress.submit_user_operation(user_operation(2)).await.unwrap();
+        assert_eq!(ingress.pending_count().unwrap(), 1);
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use crate::bundle::head_block;
+use crate::envelope::TransactionEnvelope;
+use crate::error::IngressError;
+use crate::transaction::{recover_sender, transaction_nonce, Address};
+
+// Read-only view of account state through a node's JSON-RPC endpoint
+#[derive(Clone)]
+pub struct ChainState {
+    rpc_url: String,
+    client: reqwest::Client,
+}
+
+impl ChainState {
+    pub fn new(rpc_url: String) -> Self {
+        Self {
+            rpc_url,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    // Number of the node's latest block
+    pub async fn head_block(&self) -> Result<u64, IngressError> {
+        head_block(&self.client, &self.rpc_url).await
+    }
+
+    // Nonce the account's next transaction must carry, i.e. its count of mined transactions
+    pub async fn account_nonce(&self, address: &Address) -> Result<u64, IngressError> {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_getTransactionCount",
+            "params": [format!("0x{}", hex::encode(address)), "latest"],
+        });
+        let body: serde_json::Value = self
+            .client
+            .post(&self.rpc_url)
+            .json(&request)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| IngressError::RelayUnreachable(format!("chain RPC: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| IngressError::RelayUnreachable(format!("chain RPC: {}", e)))?;
+
+        body.get("result")
+            .and_then(serde_json::Value::as_str)
+            .and_then(|count| count.strip_prefix("0x"))
+            .and_then(|count| u64::from_str_radix(count, 16).ok())
+            .ok_or_else(|| IngressError::RelayRejected(format!("chain RPC: invalid response {}", body)))
+    }
+
+    // Removes transactions whose nonce is below their sender's account nonce, returning how many were dropped
+    //
+    // Each sender is looked up once. A sender whose nonce cannot be fetched keeps its transactions,
+    // so an unavailable node never stops forwarding.
+    pub async fn drop_mined(&self, transactions: &mut Vec<TransactionEnvelope>) -> usize {
+        let keyed: Vec<Option<(Address, u64)>> = transactions.iter().map(sender_and_nonce).collect();
+        let mut senders: Vec<Address> = keyed.iter().flatten().map(|(sender, _)| *sender).collect();
+        senders.sort();
+        senders.dedup();
+
+        let lookups = senders.iter().map(|sender| self.account_nonce(sender));
+        let account_nonces: HashMap<Address, u64> = senders
+            .iter()
+            .zip(futures::future::join_all(lookups).await)
+            .filter_map(|(sender, nonce)| nonce.ok().map(|nonce| (*sender, nonce)))
+            .collect();
+
+        let before = transactions.len();
+        let mut keys = keyed.into_iter();
+        transactions.retain(|_| match keys.next().flatten() {
+            Some((sender, nonce)) => account_nonces.get(&sender).is_none_or(|&account_nonce| nonce >= account_nonce),
+            None => true,
+        });
+        before - transactions.len()
+    }
+}
+
+// Helper function to read an envelope's sender and nonce, decoding them again if they were not recorded
+fn sender_and_nonce(tx: &TransactionEnvelope) -> Option<(Address, u64)> {
+    let sender = tx.sender.or_else(|| recover_sender(&tx.tx_bytes).ok())?;
+    let nonce = tx.nonce.or_else(|| transaction_nonce(&tx.tx_bytes).ok())?;
+    Some((sender, nonce))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{signed_dynamic_fee_tx, test_address};
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn mount_nonce(rpc: &MockServer, address: Address, nonce: u64) {
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "method": "eth_getTransactionCount",
+                "params": [format!("0x{}", hex::encode(address))],
+            })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": format!("0x{:x}", nonce)})),
+            )
+            .mount(rpc)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_drops_transactions_below_account_nonce() {
+        let rpc = MockServer::start().await;
+        mount_nonce(&rpc, test_address(1), 3).await;
+        mount_nonce(&rpc, test_address(2), 0).await;
+        let chain = ChainState::new(rpc.uri());
+
+        let mut transactions: Vec<TransactionEnvelope> = [(1, 2), (2, 0), (1, 3)]
+            .into_iter()
+            .map(|(key, nonce)| TransactionEnvelope::new(signed_dynamic_fee_tx(key, nonce), String::new()))
+            .collect();
+
+        // Sender 1 already mined nonce 2, so only its nonce-3 transaction is still live
+        assert_eq!(chain.drop_mined(&mut transactions).await, 1);
+        let remaining: Vec<Vec<u8>> = transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        assert_eq!(remaining, vec![signed_dynamic_fee_tx(2, 0), signed_dynamic_fee_tx(1, 3)]);
+
+        // One lookup per sender, not per transaction
+        assert_eq!(rpc.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_node_keeps_transactions() {
+        let rpc = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(503)).mount(&rpc).await;
+        let chain = ChainState::new(rpc.uri());
+        let mut transactions = vec![TransactionEnvelope::new(signed_dynamic_fee_tx(1, 0), String::new())];
+
+        assert_eq!(chain.drop_mined(&mut transactions).await, 0);
+        assert_eq!(transactions.len(), 1);
+    }
+}
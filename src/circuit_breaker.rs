@@ -0,0 +1,131 @@
+use std::time::{Duration, Instant};
+
+// Where a relay's circuit breaker stands
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakerState {
+    Closed,   // Batches are sent as usual
+    Open,     // The relay is skipped until its cool-off ends
+    HalfOpen, // The cool-off ended and one probe batch decides whether the relay is back
+}
+
+// When a breaker opens and how long it stays open
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BreakerPolicy {
+    pub failure_threshold: u32, // Consecutive failed batches that open the breaker
+    pub cool_off: Duration,     // How long an open breaker skips the relay before probing it
+}
+
+impl Default for BreakerPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cool_off: Duration::from_secs(30),
+        }
+    }
+}
+
+// Circuit breaker for one relay, so a relay that keeps failing stops costing every batch its retries
+//
+// A closed breaker opens after failure_threshold consecutive failures. Once the cool-off has passed,
+// the next batch is let through as a probe: its success closes the breaker, its failure reopens it
+// for another cool-off. Batches are refused while the probe is in flight.
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    policy: BreakerPolicy,
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(policy: BreakerPolicy) -> Self {
+        Self {
+            policy,
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.state
+    }
+
+    // Whether a batch at now would be refused, without claiming the probe
+    pub fn blocks(&self, now: Instant) -> bool {
+        match self.state {
+            BreakerState::Closed => false,
+            BreakerState::Open => !self.cool_off_elapsed(now),
+            BreakerState::HalfOpen => true,
+        }
+    }
+
+    // Whether to send a batch at now; an open breaker whose cool-off has passed lets this one through as the probe
+    pub fn allow(&mut self, now: Instant) -> bool {
+        if self.blocks(now) {
+            return false;
+        }
+        if self.state == BreakerState::Open {
+            self.state = BreakerState::HalfOpen;
+        }
+        true
+    }
+
+    // Records the outcome of a batch that allow let through
+    pub fn record(&mut self, success: bool, now: Instant) {
+        if success {
+            self.state = BreakerState::Closed;
+            self.consecutive_failures = 0;
+            self.opened_at = None;
+            return;
+        }
+
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        let tripped = self.state == BreakerState::Closed && self.consecutive_failures >= self.policy.failure_threshold;
+        if tripped || self.state == BreakerState::HalfOpen {
+            self.state = BreakerState::Open;
+            self.opened_at = Some(now);
+        }
+    }
+
+    fn cool_off_elapsed(&self, now: Instant) -> bool {
+        self.opened_at.is_none_or(|opened_at| now.saturating_duration_since(opened_at) >= self.policy.cool_off)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker_opens_probes_and_closes() {
+        let policy = BreakerPolicy { failure_threshold: 3, cool_off: Duration::from_secs(10) };
+        let mut breaker = CircuitBreaker::new(policy);
+        let start = Instant::now();
+
+        // A success in between resets the count
+        for success in [false, false, true, false, false] {
+            assert!(breaker.allow(start));
+            breaker.record(success, start);
+        }
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        breaker.record(false, start);
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.allow(start + Duration::from_secs(9)));
+
+        // One probe after the cool-off; its failure reopens the breaker for a full cool-off
+        let probe_at = start + Duration::from_secs(10);
+        assert!(breaker.allow(probe_at));
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(!breaker.allow(probe_at));
+        breaker.record(false, probe_at);
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(breaker.blocks(probe_at + Duration::from_secs(9)));
+
+        let second_probe_at = probe_at + Duration::from_secs(10);
+        assert!(breaker.allow(second_probe_at));
+        breaker.record(true, second_probe_at);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.allow(second_probe_at));
+    }
+}
@@ -0,0 +1,57 @@
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+
+// Source of the current time for batching windows, deduplication and batch timestamps
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+// Wall-clock time, used unless a test or simulation substitutes its own clock
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+// Clock that only moves when told to, so time-dependent behaviour can be tested without sleeping;
+// clones share the same time
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new(SystemTime::UNIX_EPOCH)
+    }
+}
+
+impl MockClock {
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.time() += by;
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.time() = now;
+    }
+
+    // A poisoned lock still holds a valid time
+    fn time(&self) -> std::sync::MutexGuard<'_, SystemTime> {
+        self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.time()
+    }
+}
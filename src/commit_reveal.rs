@@ -0,0 +1,437 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use subtle::ConstantTimeEq;
+use tracing::info;
+
+use crate::anchor::CommitmentAnchor;
+use crate::batch::TransactionBatch;
+use crate::clock::{Clock, SystemClock};
+use crate::crypto::Commitment;
+use crate::error::{lock, IngressError};
+
+// How long a batch can still be revealed once its reveal delay has elapsed
+pub const DEFAULT_REVEAL_WINDOW: Duration = Duration::from_secs(600);
+
+struct CommitmentEntry {
+    commitment: Commitment,
+    committed_at: SystemTime,
+}
+
+// Commitments by batch ID, each dropped once its reveal window closes
+//
+// The IDs of dropped commitments are kept for one more window, so a late reveal is told it expired
+// rather than that it was never committed.
+struct CommitmentLog {
+    entries: HashMap<String, CommitmentEntry>,
+    expiry_order: VecDeque<(SystemTime, String)>, // (committed at, batch ID), oldest first
+    expired: HashSet<String>,
+    forget_order: VecDeque<(SystemTime, String)>, // (committed at, batch ID) of expired commitments, oldest first
+}
+
+impl CommitmentLog {
+    fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            expiry_order: VecDeque::new(),
+            expired: HashSet::new(),
+            forget_order: VecDeque::new(),
+        }
+    }
+
+    // Drops commitments committed more than lifetime ago, then forgets IDs expired for another lifetime
+    fn prune(&mut self, now: SystemTime, lifetime: Duration) {
+        let older_than =
+            |committed_at: SystemTime, age: Duration| now.duration_since(committed_at).unwrap_or_default() >= age;
+
+        while let Some((committed_at, _)) = self.expiry_order.front() {
+            if !older_than(*committed_at, lifetime) {
+                break;
+            }
+            if let Some((committed_at, batch_id)) = self.expiry_order.pop_front() {
+                self.entries.remove(&batch_id);
+                self.expired.insert(batch_id.clone());
+                self.forget_order.push_back((committed_at, batch_id));
+            }
+        }
+        while let Some((committed_at, _)) = self.forget_order.front() {
+            if !older_than(*committed_at, lifetime.saturating_mul(2)) {
+                break;
+            }
+            if let Some((_, batch_id)) = self.forget_order.pop_front() {
+                self.expired.remove(&batch_id);
+            }
+        }
+    }
+}
+
+// Commit-Reveal Pipeline; clones share the commitment log
+#[derive(Clone)]
+pub struct CommitRevealPipeline {
+    commitments: Arc<Mutex<CommitmentLog>>,
+    reveal_delay: Duration,                    // Minimum time between commit and reveal
+    reveal_window: Duration,                   // How long after the delay a batch may still be revealed
+    anchor: Option<Arc<dyn CommitmentAnchor>>, // Publishes each commitment before it is recorded
+    clock: Arc<dyn Clock>,                     // Times commitments, reveal delays and expiry
+}
+
+impl Default for CommitRevealPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommitRevealPipeline {
+    pub fn new() -> Self {
+        Self {
+            commitments: Arc::new(Mutex::new(CommitmentLog::new())),
+            reveal_delay: Duration::ZERO,
+            reveal_window: DEFAULT_REVEAL_WINDOW,
+            anchor: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    // Reads time from the given clock instead of the system clock, e.g. the batching engine's
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    // Sets how long a committed batch must wait before it can be revealed
+    pub fn with_reveal_delay(mut self, reveal_delay: Duration) -> Self {
+        self.reveal_delay = reveal_delay;
+        self
+    }
+
+    // Sets how long after its reveal delay a committed batch may be revealed before its commitment is dropped
+    //
+    // Bounds the log to the commitments of one window, and refuses replays of a reveal long after the fact.
+    pub fn with_reveal_window(mut self, reveal_window: Duration) -> Self {
+        self.reveal_window = reveal_window;
+        self
+    }
+
+    // Anchors every commitment, e.g. on-chain, so its timestamp can be checked independently of the ingress
+    pub fn with_anchor(mut self, anchor: Arc<dyn CommitmentAnchor>) -> Self {
+        self.anchor = Some(anchor);
+        self
+    }
+
+    // Records the commitment, anchoring it first when an anchor is set; returns the anchoring tx hash
+    //
+    // A batch whose commitment could not be anchored is not recorded, so it can never be revealed.
+    pub async fn commit_batch(&self, batch: &TransactionBatch) -> Result<Option<[u8; 32]>, IngressError> {
+        let anchor_tx = match &self.anchor {
+            Some(anchor) => {
+                let anchor_tx = anchor.anchor(&batch.commitment).await?;
+                info!(anchor_tx = %hex::encode(anchor_tx), "commitment anchored");
+                Some(anchor_tx)
+            }
+            None => None,
+        };
+
+        let mut commitments = lock(&self.commitments)?;
+        let now = self.clock.now();
+        commitments.prune(now, self.lifetime());
+        // The first commitment recorded for a batch ID stands
+        if !commitments.entries.contains_key(&batch.id) {
+            let entry = CommitmentEntry { commitment: batch.commitment, committed_at: now };
+            commitments.entries.insert(batch.id.clone(), entry);
+            commitments.expiry_order.push_back((now, batch.id.clone()));
+        }
+        Ok(anchor_tx)
+    }
+
+    // Batch IDs, in commit order, whose reveal delay has elapsed and whose reveal window is still open
+    pub fn ready_to_reveal(&self) -> Result<Vec<String>, IngressError> {
+        let mut commitments = lock(&self.commitments)?;
+        let now = self.clock.now();
+        commitments.prune(now, self.lifetime());
+
+        Ok(commitments
+            .expiry_order
+            .iter()
+            .filter(|(committed_at, _)| self.delay_elapsed(*committed_at, now))
+            .map(|(_, batch_id)| batch_id.clone())
+            .collect())
+    }
+
+    pub fn verify_reveal(&self, batch: &TransactionBatch) -> Result<(), IngressError> {
+        let mut commitments = lock(&self.commitments)?;
+        let now = self.clock.now();
+        commitments.prune(now, self.lifetime());
+
+        let Some(entry) = commitments.entries.get(&batch.id) else {
+            return Err(if commitments.expired.contains(&batch.id) {
+                IngressError::CommitmentExpired(batch.id.clone())
+            } else {
+                IngressError::CommitmentNotFound(batch.id.clone())
+            });
+        };
+
+        // Revealing before the delay would defeat the commitment
+        if !self.delay_elapsed(entry.committed_at, now) {
+            return Err(IngressError::RevealTooEarly(batch.id.clone()));
+        }
+
+        // Recalculate commitment to verify, with the hash function the batch was committed under;
+        // Nonce is always full length, so a truncated salt never reaches this comparison
+        let calculated_commitment = batch.recompute_commitment();
+
+        if bool::from(calculated_commitment.ct_eq(&entry.commitment)) {
+            Ok(())
+        } else {
+            Err(IngressError::CommitmentMismatch)
+        }
+    }
+
+    // Whether the batch's commitment is still recorded, i.e. committed and its reveal window not yet closed
+    pub fn is_open(&self, batch_id: &str) -> Result<bool, IngressError> {
+        let mut commitments = lock(&self.commitments)?;
+        commitments.prune(self.clock.now(), self.lifetime());
+        Ok(commitments.entries.contains_key(batch_id))
+    }
+
+    // How long a commitment is kept after it is recorded, the reveal delay plus the reveal window
+    pub fn lifetime(&self) -> Duration {
+        self.reveal_delay.saturating_add(self.reveal_window)
+    }
+
+    fn delay_elapsed(&self, committed_at: SystemTime, now: SystemTime) -> bool {
+        now.duration_since(committed_at).unwrap_or_default() >= self.reveal_delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "net")]
+    use crate::anchor::EthereumAnchor;
+    use crate::batch::tx_hashes;
+    use crate::clock::MockClock;
+    use crate::crypto::{CommitmentScheme, HashAlgo, COMMITMENT_LEN};
+    use crate::envelope::TransactionEnvelope;
+    use crate::merkle::canonical_commitment;
+    #[cfg(feature = "net")]
+    use wiremock::matchers::method;
+    #[cfg(feature = "net")]
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn sample_batch() -> TransactionBatch {
+        TransactionBatch::new(vec![
+            TransactionEnvelope::new(vec![0x02, 0x01], "a".to_string()),
+            TransactionEnvelope::new(vec![0x02, 0x02], "b".to_string()),
+        ])
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_verify_reveal_errors() {
+        let pipeline = CommitRevealPipeline::new();
+        let mut batch = sample_batch();
+
+        assert_eq!(
+            pipeline.verify_reveal(&batch),
+            Err(IngressError::CommitmentNotFound(batch.id.clone()))
+        );
+
+        pipeline.commit_batch(&batch).await.unwrap();
+        assert_eq!(pipeline.verify_reveal(&batch), Ok(()));
+
+        // Tampering with the revealed contents must be detected
+        batch.transactions[0].tx_bytes = vec![0x02, 0xff];
+        assert_eq!(pipeline.verify_reveal(&batch), Err(IngressError::CommitmentMismatch));
+    }
+
+    #[tokio::test]
+    async fn test_commitment_differing_in_last_byte_mismatches() {
+        let pipeline = CommitRevealPipeline::new();
+        let mut batch = sample_batch();
+        batch.commitment.0[COMMITMENT_LEN - 1] ^= 0x01;
+
+        pipeline.commit_batch(&batch).await.unwrap();
+        assert_eq!(pipeline.verify_reveal(&batch), Err(IngressError::CommitmentMismatch));
+    }
+
+    #[tokio::test]
+    async fn test_commit_and_reveal_paths_agree_byte_for_byte() {
+        for algo in [HashAlgo::Sha256, HashAlgo::Keccak256] {
+            let pipeline = CommitRevealPipeline::new();
+            let transactions = (0..5u8).map(|i| TransactionEnvelope::new(vec![0x02, i], String::new())).collect();
+            let mut batch = TransactionBatch::with_hash_algo(transactions, algo).unwrap();
+            pipeline.commit_batch(&batch).await.unwrap();
+
+            // The reveal recomputes from the shuffled order the relays see
+            batch.transactions.reverse();
+            let recomputed = canonical_commitment(
+                &tx_hashes(&batch.transactions, batch.commitment_scheme, algo),
+                batch.nonce.as_bytes(),
+                algo,
+            );
+            assert_eq!(recomputed.as_bytes(), batch.commitment.as_bytes());
+            assert_eq!(pipeline.verify_reveal(&batch), Ok(()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keccak_commitment_rejects_sha256_reveal() {
+        let pipeline = CommitRevealPipeline::new();
+        let mut batch = TransactionBatch::with_hash_algo(sample_batch().transactions, HashAlgo::Keccak256).unwrap();
+
+        pipeline.commit_batch(&batch).await.unwrap();
+        assert_eq!(pipeline.verify_reveal(&batch), Ok(()));
+
+        // Recomputing under SHA-256 cannot reproduce a Keccak-256 commitment
+        batch.hash_algo = HashAlgo::Sha256;
+        assert_eq!(pipeline.verify_reveal(&batch), Err(IngressError::CommitmentMismatch));
+    }
+
+    #[tokio::test]
+    async fn test_changed_envelope_version_fails_reveal() {
+        let pipeline = CommitRevealPipeline::new();
+        let mut batch = sample_batch();
+        pipeline.commit_batch(&batch).await.unwrap();
+
+        batch.transactions[0].envelope_version += 1;
+        assert_eq!(pipeline.verify_reveal(&batch), Err(IngressError::CommitmentMismatch));
+
+        // The version-free scheme is kept for old commitments and cannot notice the change
+        let mut legacy = sample_batch().with_commitment_scheme(CommitmentScheme::V1);
+        pipeline.commit_batch(&legacy).await.unwrap();
+        legacy.transactions[0].envelope_version += 1;
+        assert_eq!(pipeline.verify_reveal(&legacy), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_reveal_refused_before_delay() {
+        let clock = MockClock::default();
+        let pipeline = CommitRevealPipeline::new()
+            .with_reveal_delay(Duration::from_millis(50))
+            .with_clock(Arc::new(clock.clone()));
+        let batch = sample_batch();
+
+        pipeline.commit_batch(&batch).await.unwrap();
+        clock.advance(Duration::from_millis(49));
+        assert_eq!(pipeline.verify_reveal(&batch), Err(IngressError::RevealTooEarly(batch.id.clone())));
+        assert!(pipeline.ready_to_reveal().unwrap().is_empty());
+
+        clock.advance(Duration::from_millis(1));
+
+        assert_eq!(pipeline.ready_to_reveal().unwrap(), vec![batch.id.clone()]);
+        assert_eq!(pipeline.verify_reveal(&batch), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_reveal_looked_up_among_many_commitments() {
+        let pipeline = CommitRevealPipeline::new();
+        let batches: Vec<TransactionBatch> = (0..2000u16)
+            .map(|i| TransactionEnvelope::new(i.to_be_bytes().to_vec(), String::new()))
+            .map(|tx| TransactionBatch::new(vec![tx]).unwrap())
+            .collect();
+        for batch in &batches {
+            pipeline.commit_batch(batch).await.unwrap();
+        }
+
+        // Each reveal finds its own commitment by batch ID
+        for batch in batches.iter().rev() {
+            assert_eq!(pipeline.verify_reveal(batch), Ok(()));
+        }
+        let mut swapped = batches[0].clone();
+        swapped.commitment = batches[1].commitment;
+        swapped.transactions = batches[1].transactions.clone();
+        assert_eq!(pipeline.verify_reveal(&swapped), Err(IngressError::CommitmentMismatch));
+        assert_eq!(pipeline.ready_to_reveal().unwrap().len(), 2000);
+        assert_eq!(pipeline.ready_to_reveal().unwrap()[0], batches[0].id);
+    }
+
+    #[tokio::test]
+    async fn test_reveal_after_window_rejected_as_expired() {
+        let clock = MockClock::default();
+        let pipeline = CommitRevealPipeline::new()
+            .with_reveal_delay(Duration::from_millis(20))
+            .with_reveal_window(Duration::from_millis(30))
+            .with_clock(Arc::new(clock.clone()));
+        let batch = sample_batch();
+        pipeline.commit_batch(&batch).await.unwrap();
+
+        clock.advance(Duration::from_millis(49));
+        assert_eq!(pipeline.ready_to_reveal().unwrap(), vec![batch.id.clone()]);
+        assert!(pipeline.is_open(&batch.id).unwrap());
+
+        // Past the delay plus the window the reveal is refused, unlike a batch never committed
+        clock.advance(Duration::from_millis(1));
+        assert!(pipeline.ready_to_reveal().unwrap().is_empty());
+        assert!(!pipeline.is_open(&batch.id).unwrap());
+        assert_eq!(pipeline.verify_reveal(&batch), Err(IngressError::CommitmentExpired(batch.id.clone())));
+        let uncommitted = sample_batch();
+        assert_eq!(
+            pipeline.verify_reveal(&uncommitted),
+            Err(IngressError::CommitmentNotFound(uncommitted.id.clone()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_commitment_log_bounded_by_window() {
+        let clock = MockClock::default();
+        let pipeline = CommitRevealPipeline::new()
+            .with_reveal_window(Duration::from_millis(30))
+            .with_clock(Arc::new(clock.clone()));
+        for _ in 0..100 {
+            pipeline.commit_batch(&sample_batch()).await.unwrap();
+        }
+        assert_eq!(pipeline.commitments.lock().unwrap().entries.len(), 100);
+
+        // Expired commitments are dropped, and their IDs once a second window has passed; only the
+        // last batch's ID is left by the end
+        clock.advance(Duration::from_millis(30));
+        pipeline.commit_batch(&sample_batch()).await.unwrap();
+        {
+            let log = pipeline.commitments.lock().unwrap();
+            assert_eq!((log.entries.len(), log.expired.len()), (1, 100));
+        }
+        clock.advance(Duration::from_millis(30));
+        pipeline.ready_to_reveal().unwrap();
+        let log = pipeline.commitments.lock().unwrap();
+        assert_eq!((log.entries.len(), log.expired.len()), (0, 1));
+        assert_eq!((log.expiry_order.len(), log.forget_order.len()), (0, 1));
+    }
+
+    #[cfg(feature = "net")]
+    #[tokio::test]
+    async fn test_commit_anchors_commitment_in_calldata() {
+        let rpc = MockServer::start().await;
+        let anchor_tx = [0xcd; 32];
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": format!("0x{}", hex::encode(anchor_tx))})),
+            )
+            .mount(&rpc)
+            .await;
+        let pipeline = CommitRevealPipeline::new().with_anchor(Arc::new(EthereumAnchor::new(rpc.uri(), [0x11; 20], [0x22; 20])));
+        let batch = sample_batch();
+
+        assert_eq!(pipeline.commit_batch(&batch).await, Ok(Some(anchor_tx)));
+        assert_eq!(pipeline.verify_reveal(&batch), Ok(()));
+
+        let requests = rpc.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["method"], "eth_sendTransaction");
+        assert!(body["params"][0]["data"].as_str().unwrap().ends_with(&hex::encode(batch.commitment.as_bytes())));
+    }
+
+    #[cfg(feature = "net")]
+    #[tokio::test]
+    async fn test_failed_anchor_leaves_batch_uncommitted() {
+        let rpc = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(503)).mount(&rpc).await;
+        let pipeline = CommitRevealPipeline::new().with_anchor(Arc::new(EthereumAnchor::new(rpc.uri(), [0x11; 20], [0x22; 20])));
+        let batch = sample_batch();
+
+        assert!(matches!(pipeline.commit_batch(&batch).await, Err(IngressError::Anchor(_))));
+        assert_eq!(pipeline.verify_reveal(&batch), Err(IngressError::CommitmentNotFound(batch.id.clone())));
+    }
+}
@@ -0,0 +1,479 @@
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::batching::{BatchOrdering, WindowMode};
+use crate::circuit_breaker::BreakerPolicy;
+use crate::compression::Compression;
+use crate::crypto::{HashAlgo, ShuffleSecret};
+use crate::error::IngressError;
+use crate::ingress::PenumIngress;
+use crate::jitter::JitterDistribution;
+use crate::metrics::DEFAULT_SAMPLE_WINDOW;
+use crate::relay::DEFAULT_RELAY_REQUEST_TIMEOUT;
+
+// Defaults follow the batching configuration in TECHNICAL-SPEC.md
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+pub const DEFAULT_BATCH_INTERVAL: Duration = Duration::from_millis(1000);
+
+// Deployment settings for PenumIngress::from_config
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IngressConfig {
+    pub max_batch_size: usize,
+    pub batch_time_window: Duration,
+    pub relay_urls: Vec<String>,
+    pub reveal_delay: Duration,
+    pub max_release_jitter: Duration,
+    pub jitter_distribution: JitterDistribution,
+    pub min_relay_quorum: usize,
+    pub hash_algo: HashAlgo,
+    pub chain_rpc_url: Option<String>, // Node used to drop already-mined transactions before forwarding
+    pub metrics_window: usize,         // Recent samples kept per metrics series
+    pub chain_id: Option<u64>,         // Network submissions must be bound to, unchecked if None
+    pub allow_unprotected_txs: bool,   // Accept pre-EIP-155 transactions when chain_id is set
+    pub max_acceptable_latency: Option<Duration>, // Forwarding slower than this is warned about and counted
+    pub dry_run: bool,                            // Run the pipeline without sending anything to relays
+    pub nonce_gap_timeout: Option<Duration>,      // Hold transactions past a sender's nonce gap for up to this long
+    pub compression: Option<Compression>,         // Content-Encoding for batch bodies sent to HTTP relays
+    pub allow_blob_txs: bool,                     // Accept EIP-4844 blob transactions
+    pub relay_request_timeout: Duration,          // Longest a relay may take over one batch
+    pub max_payload_bytes: Option<usize>,         // Batches encoding larger reach relays in parts, never split if None
+    pub ordering: BatchOrdering,                  // Built-in ordering of batch contents
+    pub min_distinct_senders: usize,              // Batches from fewer senders are held, 0 never holds
+    pub window_mode: WindowMode,                  // Fixed windows, or Poisson windows averaging batch_time_window
+    pub circuit_breaker: Option<BreakerPolicy>,   // Per-relay circuit breakers, off if None
+    pub shuffle_secret: Option<ShuffleSecret>,    // Mixed into shuffle seeds, which derive from batch IDs alone if None
+}
+
+impl Default for IngressConfig {
+    fn default() -> Self {
+        Self {
+            max_batch_size: DEFAULT_BATCH_SIZE,
+            batch_time_window: DEFAULT_BATCH_INTERVAL,
+            relay_urls: Vec::new(),
+            reveal_delay: Duration::ZERO,
+            max_release_jitter: Duration::ZERO,
+            jitter_distribution: JitterDistribution::default(),
+            min_relay_quorum: 0,
+            hash_algo: HashAlgo::default(),
+            chain_rpc_url: None,
+            metrics_window: DEFAULT_SAMPLE_WINDOW,
+            chain_id: None,
+            allow_unprotected_txs: false,
+            max_acceptable_latency: None,
+            dry_run: false,
+            nonce_gap_timeout: None,
+            compression: None,
+            allow_blob_txs: true,
+            relay_request_timeout: DEFAULT_RELAY_REQUEST_TIMEOUT,
+            max_payload_bytes: None,
+            ordering: BatchOrdering::default(),
+            min_distinct_senders: 0,
+            window_mode: WindowMode::default(),
+            circuit_breaker: None,
+            shuffle_secret: None,
+        }
+    }
+}
+
+// On-disk layout; durations are whole milliseconds and every key is optional
+//
+// Not Debug, since it holds the shuffle secret as written.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    batch_size: Option<usize>,
+    batch_interval_ms: Option<u64>,
+    relays: Option<Vec<String>>,
+    reveal_delay_ms: Option<u64>,
+    max_release_jitter_ms: Option<u64>,
+    jitter_distribution: Option<String>,
+    min_relay_quorum: Option<usize>,
+    hash_algo: Option<String>,
+    chain_rpc: Option<String>,
+    metrics_window: Option<usize>,
+    chain_id: Option<u64>,
+    allow_unprotected_txs: Option<bool>,
+    max_acceptable_latency_ms: Option<u64>,
+    dry_run: Option<bool>,
+    nonce_gap_timeout_ms: Option<u64>,
+    compression: Option<String>,
+    allow_blob_txs: Option<bool>,
+    relay_request_timeout_ms: Option<u64>,
+    max_payload_bytes: Option<usize>,
+    ordering: Option<String>,
+    min_distinct_senders: Option<usize>,
+    window_mode: Option<String>,
+    circuit_breaker_failures: Option<u32>,
+    circuit_breaker_cool_off_ms: Option<u64>,
+    shuffle_secret: Option<String>, // Hex, with or without 0x
+}
+
+impl IngressConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size;
+        self
+    }
+
+    pub fn with_batch_time_window(mut self, batch_time_window: Duration) -> Self {
+        self.batch_time_window = batch_time_window;
+        self
+    }
+
+    pub fn with_relay_urls(mut self, relay_urls: Vec<String>) -> Self {
+        self.relay_urls = relay_urls;
+        self
+    }
+
+    pub fn with_reveal_delay(mut self, reveal_delay: Duration) -> Self {
+        self.reveal_delay = reveal_delay;
+        self
+    }
+
+    pub fn with_release_jitter(mut self, max_release_jitter: Duration, distribution: JitterDistribution) -> Self {
+        self.max_release_jitter = max_release_jitter;
+        self.jitter_distribution = distribution;
+        self
+    }
+
+    pub fn with_min_relay_quorum(mut self, min_relay_quorum: usize) -> Self {
+        self.min_relay_quorum = min_relay_quorum;
+        self
+    }
+
+    pub fn with_hash_algo(mut self, hash_algo: HashAlgo) -> Self {
+        self.hash_algo = hash_algo;
+        self
+    }
+
+    pub fn with_chain_rpc_url(mut self, chain_rpc_url: String) -> Self {
+        self.chain_rpc_url = Some(chain_rpc_url);
+        self
+    }
+
+    pub fn with_metrics_window(mut self, metrics_window: usize) -> Self {
+        self.metrics_window = metrics_window;
+        self
+    }
+
+    pub fn with_chain_id(mut self, chain_id: u64, allow_unprotected_txs: bool) -> Self {
+        self.chain_id = Some(chain_id);
+        self.allow_unprotected_txs = allow_unprotected_txs;
+        self
+    }
+
+    pub fn with_max_acceptable_latency(mut self, max_acceptable_latency: Duration) -> Self {
+        self.max_acceptable_latency = Some(max_acceptable_latency);
+        self
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_nonce_gap_timeout(mut self, nonce_gap_timeout: Duration) -> Self {
+        self.nonce_gap_timeout = Some(nonce_gap_timeout);
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    pub fn with_allow_blob_txs(mut self, allow_blob_txs: bool) -> Self {
+        self.allow_blob_txs = allow_blob_txs;
+        self
+    }
+
+    pub fn with_relay_request_timeout(mut self, relay_request_timeout: Duration) -> Self {
+        self.relay_request_timeout = relay_request_timeout;
+        self
+    }
+
+    pub fn with_max_payload_bytes(mut self, max_payload_bytes: usize) -> Self {
+        self.max_payload_bytes = Some(max_payload_bytes);
+        self
+    }
+
+    pub fn with_ordering(mut self, ordering: BatchOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    pub fn with_min_distinct_senders(mut self, min_distinct_senders: usize) -> Self {
+        self.min_distinct_senders = min_distinct_senders;
+        self
+    }
+
+    pub fn with_window_mode(mut self, window_mode: WindowMode) -> Self {
+        self.window_mode = window_mode;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, circuit_breaker: BreakerPolicy) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    pub fn with_shuffle_secret(mut self, shuffle_secret: ShuffleSecret) -> Self {
+        self.shuffle_secret = Some(shuffle_secret);
+        self
+    }
+
+    // Loads a TOML file, taking defaults for any omitted key
+    pub fn from_toml_path(path: impl AsRef<Path>) -> Result<Self, IngressError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| IngressError::Config(format!("{}: {}", path.display(), error)))?;
+        Self::from_toml_str(&contents)
+    }
+
+    pub fn from_toml_str(contents: &str) -> Result<Self, IngressError> {
+        let file: ConfigFile = toml::from_str(contents).map_err(|error| IngressError::Config(error.to_string()))?;
+        let defaults = Self::default();
+
+        Ok(Self {
+            max_batch_size: file.batch_size.unwrap_or(defaults.max_batch_size),
+            batch_time_window: file.batch_interval_ms.map_or(defaults.batch_time_window, Duration::from_millis),
+            relay_urls: file.relays.unwrap_or(defaults.relay_urls),
+            reveal_delay: file.reveal_delay_ms.map_or(defaults.reveal_delay, Duration::from_millis),
+            max_release_jitter: file.max_release_jitter_ms.map_or(defaults.max_release_jitter, Duration::from_millis),
+            jitter_distribution: match file.jitter_distribution.as_deref() {
+                None => defaults.jitter_distribution,
+                Some("uniform") => JitterDistribution::Uniform,
+                Some("exponential") => JitterDistribution::Exponential,
+                Some(other) => return Err(IngressError::Config(format!("unknown jitter_distribution {:?}", other))),
+            },
+            min_relay_quorum: file.min_relay_quorum.unwrap_or(defaults.min_relay_quorum),
+            hash_algo: match file.hash_algo.as_deref() {
+                None => defaults.hash_algo,
+                Some("sha256") => HashAlgo::Sha256,
+                Some("keccak256") => HashAlgo::Keccak256,
+                Some(other) => return Err(IngressError::Config(format!("unknown hash_algo {:?}", other))),
+            },
+            chain_rpc_url: file.chain_rpc,
+            metrics_window: file.metrics_window.unwrap_or(defaults.metrics_window),
+            chain_id: file.chain_id,
+            allow_unprotected_txs: file.allow_unprotected_txs.unwrap_or(defaults.allow_unprotected_txs),
+            max_acceptable_latency: file.max_acceptable_latency_ms.map(Duration::from_millis),
+            dry_run: file.dry_run.unwrap_or(defaults.dry_run),
+            nonce_gap_timeout: file.nonce_gap_timeout_ms.map(Duration::from_millis),
+            compression: match file.compression.as_deref() {
+                None => defaults.compression,
+                Some("gzip") => Some(Compression::Gzip),
+                Some("zstd") => Some(Compression::Zstd),
+                Some(other) => return Err(IngressError::Config(format!("unknown compression {:?}", other))),
+            },
+            allow_blob_txs: file.allow_blob_txs.unwrap_or(defaults.allow_blob_txs),
+            relay_request_timeout: file
+                .relay_request_timeout_ms
+                .map_or(defaults.relay_request_timeout, Duration::from_millis),
+            max_payload_bytes: file.max_payload_bytes,
+            ordering: match file.ordering.as_deref() {
+                None => defaults.ordering,
+                Some("shuffled") => BatchOrdering::Shuffled,
+                Some("fee_descending") => BatchOrdering::FeeDescending,
+                Some("identity") => BatchOrdering::Identity,
+                Some(other) => return Err(IngressError::Config(format!("unknown ordering {:?}", other))),
+            },
+            min_distinct_senders: file.min_distinct_senders.unwrap_or(defaults.min_distinct_senders),
+            window_mode: match file.window_mode.as_deref() {
+                None => defaults.window_mode,
+                Some("fixed") => WindowMode::Fixed,
+                Some("poisson") => WindowMode::Poisson,
+                Some(other) => return Err(IngressError::Config(format!("unknown window mode {:?}", other))),
+            },
+            // Either key turns the breakers on, the other taking its default
+            circuit_breaker: match (file.circuit_breaker_failures, file.circuit_breaker_cool_off_ms) {
+                (None, None) => defaults.circuit_breaker,
+                (failures, cool_off_ms) => {
+                    let policy = BreakerPolicy::default();
+                    Some(BreakerPolicy {
+                        failure_threshold: failures.unwrap_or(policy.failure_threshold),
+                        cool_off: cool_off_ms.map_or(policy.cool_off, Duration::from_millis),
+                    })
+                }
+            },
+            // Errors name the key but never echo the value
+            shuffle_secret: match file.shuffle_secret {
+                None => defaults.shuffle_secret,
+                Some(secret) => {
+                    let bytes = hex::decode(secret.strip_prefix("0x").unwrap_or(&secret))
+                        .map_err(|_| IngressError::Config("shuffle_secret is not valid hex".to_string()))?;
+                    Some(ShuffleSecret::new(bytes)?)
+                }
+            },
+        })
+    }
+}
+
+impl PenumIngress {
+    // Fails if the settings contradict each other, e.g. a release jitter outlasting the commitment
+    pub fn from_config(config: IngressConfig) -> Result<Self, IngressError> {
+        let mut ingress = PenumIngress::new(config.max_batch_size, config.batch_time_window, config.relay_urls)
+            .with_reveal_delay(config.reveal_delay)
+            .with_release_jitter(config.max_release_jitter, config.jitter_distribution)?
+            .with_min_relay_quorum(config.min_relay_quorum)
+            .with_hash_algo(config.hash_algo)
+            .with_metrics_window(config.metrics_window)
+            .with_dry_run(config.dry_run)
+            .with_allow_blob_txs(config.allow_blob_txs)
+            .with_relay_request_timeout(config.relay_request_timeout)
+            .with_batch_ordering(config.ordering)
+            .with_min_distinct_senders(config.min_distinct_senders)
+            .with_window_mode(config.window_mode);
+        if let Some(chain_id) = config.chain_id {
+            ingress = ingress.with_chain_id(chain_id, config.allow_unprotected_txs);
+        }
+        if let Some(max_acceptable_latency) = config.max_acceptable_latency {
+            ingress = ingress.with_max_acceptable_latency(max_acceptable_latency);
+        }
+        if let Some(nonce_gap_timeout) = config.nonce_gap_timeout {
+            ingress = ingress.with_nonce_gap_hold(nonce_gap_timeout);
+        }
+        if let Some(max_payload_bytes) = config.max_payload_bytes {
+            ingress = ingress.with_max_payload_bytes(max_payload_bytes);
+        }
+        if let Some(compression) = config.compression {
+            ingress = ingress.with_compression(compression);
+        }
+        if let Some(circuit_breaker) = config.circuit_breaker {
+            ingress = ingress.with_circuit_breaker(circuit_breaker);
+        }
+        if let Some(shuffle_secret) = config.shuffle_secret {
+            ingress = ingress.with_shuffle_secret(shuffle_secret);
+        }
+        Ok(match config.chain_rpc_url {
+            Some(chain_rpc_url) => ingress.with_chain_rpc(chain_rpc_url),
+            None => ingress,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_CONFIG: &str = r#"
+batch_size = 25
+batch_interval_ms = 2500
+relays = ["https://relay.flashbots.net", "https://relay.ultrasound.money"]
+reveal_delay_ms = 200
+max_release_jitter_ms = 750
+jitter_distribution = "exponential"
+min_relay_quorum = 2
+hash_algo = "keccak256"
+chain_rpc = "http://localhost:8545"
+metrics_window = 500
+chain_id = 1
+allow_unprotected_txs = true
+max_acceptable_latency_ms = 400
+dry_run = true
+nonce_gap_timeout_ms = 12000
+compression = "zstd"
+allow_blob_txs = false
+relay_request_timeout_ms = 1500
+max_payload_bytes = 1048576
+ordering = "fee_descending"
+min_distinct_senders = 3
+window_mode = "poisson"
+circuit_breaker_failures = 4
+circuit_breaker_cool_off_ms = 60000
+shuffle_secret = "0x5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e"
+"#;
+
+    #[test]
+    fn test_toml_fields_map_to_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ingress.toml");
+        std::fs::write(&path, SAMPLE_CONFIG).unwrap();
+
+        let config = IngressConfig::from_toml_path(&path).unwrap();
+        let expected = IngressConfig::new()
+            .with_max_batch_size(25)
+            .with_batch_time_window(Duration::from_millis(2500))
+            .with_relay_urls(vec![
+                "https://relay.flashbots.net".to_string(),
+                "https://relay.ultrasound.money".to_string(),
+            ])
+            .with_reveal_delay(Duration::from_millis(200))
+            .with_release_jitter(Duration::from_millis(750), JitterDistribution::Exponential)
+            .with_min_relay_quorum(2)
+            .with_hash_algo(HashAlgo::Keccak256)
+            .with_chain_rpc_url("http://localhost:8545".to_string())
+            .with_metrics_window(500)
+            .with_chain_id(1, true)
+            .with_max_acceptable_latency(Duration::from_millis(400))
+            .with_dry_run(true)
+            .with_nonce_gap_timeout(Duration::from_secs(12))
+            .with_compression(Compression::Zstd)
+            .with_allow_blob_txs(false)
+            .with_relay_request_timeout(Duration::from_millis(1500))
+            .with_max_payload_bytes(1024 * 1024)
+            .with_ordering(BatchOrdering::FeeDescending)
+            .with_min_distinct_senders(3)
+            .with_window_mode(WindowMode::Poisson)
+            .with_circuit_breaker(BreakerPolicy { failure_threshold: 4, cool_off: Duration::from_secs(60) })
+            .with_shuffle_secret(ShuffleSecret::new(vec![0x5e; 32]).unwrap());
+        assert_eq!(config, expected);
+
+        // Setting only the threshold keeps the default cool-off
+        let config = IngressConfig::from_toml_str("circuit_breaker_failures = 2").unwrap();
+        let policy = config.circuit_breaker.unwrap();
+        assert_eq!((policy.failure_threshold, policy.cool_off), (2, BreakerPolicy::default().cool_off));
+    }
+
+    #[test]
+    fn test_omitted_keys_take_defaults() {
+        let config = IngressConfig::from_toml_str("relays = [\"https://relay.flashbots.net\"]").unwrap();
+
+        assert_eq!(config.max_batch_size, DEFAULT_BATCH_SIZE);
+        assert_eq!(config.batch_time_window, DEFAULT_BATCH_INTERVAL);
+        assert_eq!(config.relay_urls, vec!["https://relay.flashbots.net".to_string()]);
+        assert_eq!(config.reveal_delay, Duration::ZERO);
+        assert_eq!(config.max_release_jitter, Duration::ZERO);
+        assert_eq!(config.jitter_distribution, JitterDistribution::Uniform);
+        assert_eq!(config.min_relay_quorum, 0);
+        assert_eq!(config.hash_algo, HashAlgo::Sha256);
+        assert_eq!(config.chain_rpc_url, None);
+        assert_eq!(config.metrics_window, DEFAULT_SAMPLE_WINDOW);
+        assert_eq!((config.chain_id, config.allow_unprotected_txs), (None, false));
+        assert_eq!(config.max_acceptable_latency, None);
+        assert!(!config.dry_run);
+        assert_eq!(config.nonce_gap_timeout, None);
+        assert_eq!(config.compression, None);
+        assert!(config.allow_blob_txs);
+        assert_eq!(config.relay_request_timeout, DEFAULT_RELAY_REQUEST_TIMEOUT);
+        assert_eq!(config.max_payload_bytes, None);
+        assert_eq!(config.ordering, BatchOrdering::Shuffled);
+        assert_eq!(config.min_distinct_senders, 0);
+        assert_eq!(config.window_mode, WindowMode::Fixed);
+        assert_eq!(config.circuit_breaker, None);
+        assert_eq!(config.shuffle_secret, None);
+        assert_eq!(IngressConfig::from_toml_str("").unwrap(), IngressConfig::default());
+    }
+
+    #[test]
+    fn test_invalid_config_rejected() {
+        assert!(matches!(IngressConfig::from_toml_str("hash_algo = \"md5\""), Err(IngressError::Config(_))));
+        assert!(matches!(IngressConfig::from_toml_str("compression = \"brotli\""), Err(IngressError::Config(_))));
+        assert!(matches!(IngressConfig::from_toml_str("ordering = \"random\""), Err(IngressError::Config(_))));
+        assert!(matches!(IngressConfig::from_toml_str("window_mode = \"gaussian\""), Err(IngressError::Config(_))));
+        assert!(matches!(IngressConfig::from_toml_str("batch_sise = 10"), Err(IngressError::Config(_))));
+        assert!(matches!(IngressConfig::from_toml_str("circuit_breaker_failures = -1"), Err(IngressError::Config(_))));
+        assert!(matches!(IngressConfig::from_toml_path("/nonexistent/ingress.toml"), Err(IngressError::Config(_))));
+
+        // A short or malformed secret is refused without the error repeating it
+        for secret in ["0x5e5e5e5e", "not-hex-5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e5e"] {
+            let error = IngressConfig::from_toml_str(&format!("shuffle_secret = \"{}\"", secret)).unwrap_err();
+            assert!(matches!(&error, IngressError::Config(message) if !message.contains("5e5e")));
+        }
+    }
+}
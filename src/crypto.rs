@@ -0,0 +1,240 @@
+use std::fmt;
+use std::str::FromStr;
+
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use subtle::{Choice, ConstantTimeEq};
+
+use crate::entropy::Entropy;
+use crate::error::IngressError;
+use crate::transaction::transaction_hash;
+
+// Hash function used for batch commitments, Merkle proofs and the shuffle seed; transactions are
+// always identified by their Keccak-256 Ethereum hash underneath
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashAlgo {
+    #[default]
+    Sha256,
+    Keccak256, // Matches Ethereum-native tooling and on-chain verification
+}
+
+impl HashAlgo {
+    pub fn hash(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgo::Sha256 => sha256_hash(data),
+            HashAlgo::Keccak256 => Keccak256::digest(data).to_vec(),
+        }
+    }
+}
+
+// How each transaction is hashed into a batch commitment, recorded on the batch so commitments made
+// under an older scheme still verify
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommitmentScheme {
+    V1, // H(tx_bytes); the envelope version can change without detection
+    V2, // H(envelope_version as big-endian u32 || tx_bytes)
+    #[default]
+    V3, // H(envelope_version as big-endian u32 || Keccak-256 Ethereum transaction hash)
+}
+
+impl CommitmentScheme {
+    // Hash the transaction is committed under, before the per-leaf salt
+    pub fn tx_hash(self, tx_bytes: &[u8], envelope_version: u32, algo: HashAlgo) -> Vec<u8> {
+        match self {
+            CommitmentScheme::V1 => algo.hash(tx_bytes),
+            CommitmentScheme::V2 => algo.hash(&[&envelope_version.to_be_bytes()[..], tx_bytes].concat()),
+            CommitmentScheme::V3 => {
+                algo.hash(&[&envelope_version.to_be_bytes()[..], &transaction_hash(tx_bytes)[..]].concat())
+            }
+        }
+    }
+}
+
+// Length in bytes of batch nonces and commitments (both hash functions produce 32-byte digests)
+pub const COMMITMENT_LEN: usize = 32;
+
+// Batch commitment (Merkle root), displayed and parsed as 0x-prefixed hex
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Commitment(pub [u8; COMMITMENT_LEN]);
+
+// Random per-batch salt mixed into every commitment leaf, displayed and parsed as 0x-prefixed hex
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Nonce(pub [u8; COMMITMENT_LEN]);
+
+impl Commitment {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// Reveals compare commitments in constant time, so timing never says how much of a forgery matched
+impl ConstantTimeEq for Commitment {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl Nonce {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl TryFrom<&[u8]> for Commitment {
+    type Error = IngressError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        exact_length(bytes).map(Self).map_err(IngressError::InvalidCommitment)
+    }
+}
+
+impl TryFrom<&[u8]> for Nonce {
+    type Error = IngressError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        exact_length(bytes).map(Self).map_err(IngressError::InvalidNonce)
+    }
+}
+
+impl FromStr for Commitment {
+    type Err = IngressError;
+
+    fn from_str(hex_str: &str) -> Result<Self, Self::Err> {
+        decode_hex(hex_str).and_then(|bytes| exact_length(&bytes)).map(Self).map_err(IngressError::InvalidCommitment)
+    }
+}
+
+impl FromStr for Nonce {
+    type Err = IngressError;
+
+    fn from_str(hex_str: &str) -> Result<Self, Self::Err> {
+        decode_hex(hex_str).and_then(|bytes| exact_length(&bytes)).map(Self).map_err(IngressError::InvalidNonce)
+    }
+}
+
+impl fmt::Display for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl fmt::Display for Nonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+// Helper function to reject anything but a full-length digest, so truncated values never compare
+fn exact_length(bytes: &[u8]) -> Result<[u8; COMMITMENT_LEN], String> {
+    bytes
+        .try_into()
+        .map_err(|_| format!("expected {} bytes, got {}", COMMITMENT_LEN, bytes.len()))
+}
+
+// Helper function to decode hex with an optional 0x prefix
+fn decode_hex(hex_str: &str) -> Result<Vec<u8>, String> {
+    hex::decode(hex_str.strip_prefix("0x").unwrap_or(hex_str)).map_err(|error| error.to_string())
+}
+
+// Helper function to generate a random nonce
+pub(crate) fn generate_nonce(entropy: &dyn Entropy) -> Result<Nonce, IngressError> {
+    let mut nonce = [0u8; COMMITMENT_LEN];
+    entropy.fill(&mut nonce)?;
+    Ok(Nonce(nonce))
+}
+
+// Helper function for SHA-256 hashing
+pub(crate) fn sha256_hash(data: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.finalize().to_vec()
+}
+
+// Shortest shuffle secret accepted, so the seed cannot be recovered by guessing the secret
+pub const MIN_SHUFFLE_SECRET_LEN: usize = 16;
+
+// Operator-held secret mixed into every shuffle seed, so a batch ID alone no longer reveals the order
+// while the operator can still reproduce it for an audit; Debug never shows the bytes
+#[derive(Clone, PartialEq, Eq)]
+pub struct ShuffleSecret(Vec<u8>);
+
+impl ShuffleSecret {
+    pub fn new(secret: Vec<u8>) -> Result<Self, IngressError> {
+        if secret.len() < MIN_SHUFFLE_SECRET_LEN {
+            return Err(IngressError::Config(format!(
+                "shuffle secret must be at least {} bytes",
+                MIN_SHUFFLE_SECRET_LEN
+            )));
+        }
+        Ok(Self(secret))
+    }
+
+    // Seed the shuffle of the batch with this ID used, for reproducing its order with shuffle_with_seed
+    pub fn seed_for(&self, batch_id: &str, algo: HashAlgo) -> [u8; 32] {
+        create_seed_from_batch_id(batch_id, &self.0, algo)
+    }
+}
+
+impl fmt::Debug for ShuffleSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ShuffleSecret(..)")
+    }
+}
+
+// Helper function to create a deterministic seed from batch ID, as hash(secret || batch_id)
+//
+// With an empty secret this is the hash of the batch ID alone, which anyone holding the ID can
+// reproduce.
+pub(crate) fn create_seed_from_batch_id(batch_id: &str, secret: &[u8], algo: HashAlgo) -> [u8; 32] {
+    let mut seed = [0u8; 32];
+    let hash = algo.hash(&[secret, batch_id.as_bytes()].concat());
+
+    // Copy hash bytes to seed (truncating if necessary)
+    let len = std::cmp::min(32, hash.len());
+    seed[..len].copy_from_slice(&hash[..len]);
+
+    seed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entropy::OsEntropy;
+
+    #[test]
+    fn test_hex_round_trip() {
+        let nonce = generate_nonce(&OsEntropy).unwrap();
+        let commitment = Commitment([0xab; COMMITMENT_LEN]);
+
+        assert_eq!(nonce.to_string().parse::<Nonce>(), Ok(nonce));
+        assert_eq!(commitment.to_string(), format!("0x{}", "ab".repeat(COMMITMENT_LEN)));
+        assert_eq!(commitment.to_string().parse::<Commitment>(), Ok(commitment));
+
+        // The 0x prefix is optional when parsing
+        assert_eq!("ab".repeat(COMMITMENT_LEN).parse::<Commitment>(), Ok(commitment));
+    }
+
+    #[test]
+    fn test_wrong_length_rejected() {
+        let nonce = generate_nonce(&OsEntropy).unwrap();
+
+        // A truncated reveal nonce can never be used to recompute a commitment
+        let truncated = Nonce::try_from(&nonce.as_bytes()[..16]);
+        assert_eq!(truncated, Err(IngressError::InvalidNonce("expected 32 bytes, got 16".to_string())));
+        assert!(matches!(format!("0x{}", "00".repeat(33)).parse::<Nonce>(), Err(IngressError::InvalidNonce(_))));
+        assert!(matches!("0xzz".parse::<Nonce>(), Err(IngressError::InvalidNonce(_))));
+
+        let commitment = Commitment::try_from(&[0u8; 31][..]);
+        assert_eq!(commitment, Err(IngressError::InvalidCommitment("expected 32 bytes, got 31".to_string())));
+    }
+
+    #[test]
+    fn test_constant_time_commitment_equality() {
+        let commitment = Commitment([0xab; COMMITMENT_LEN]);
+        let mut last_byte_differs = commitment;
+        last_byte_differs.0[COMMITMENT_LEN - 1] ^= 0x01;
+
+        assert!(bool::from(commitment.ct_eq(&Commitment([0xab; COMMITMENT_LEN]))));
+        assert!(!bool::from(commitment.ct_eq(&last_byte_differs)));
+    }
+}
@@ -0,0 +1,114 @@
+use k256::ecdsa::SigningKey;
+
+use crate::entropy::Entropy;
+use crate::envelope::TransactionEnvelope;
+use crate::error::IngressError;
+use crate::rlp::{encode_bytes, encode_list, encode_u64};
+use crate::transaction::{keccak256, recover_sender, transaction_nonce, Address};
+
+// Unsigned EIP-1559 transfer fields
+pub(crate) struct DynamicFeeTx {
+    pub(crate) chain_id: u64,
+    pub(crate) nonce: u64,
+    pub(crate) max_priority_fee: u64,
+    pub(crate) max_fee: u64,
+    pub(crate) gas_limit: u64,
+    pub(crate) to: Address,
+    pub(crate) value: u64,
+}
+
+impl DynamicFeeTx {
+    // Signs the transaction and returns its EIP-2718 encoding (0x02 || rlp(fields))
+    pub(crate) fn sign(&self, key: &SigningKey) -> Result<Vec<u8>, IngressError> {
+        let mut fields = vec![
+            encode_u64(self.chain_id),
+            encode_u64(self.nonce),
+            encode_u64(self.max_priority_fee),
+            encode_u64(self.max_fee),
+            encode_u64(self.gas_limit),
+            encode_bytes(&self.to),
+            encode_u64(self.value),
+            encode_bytes(&[]), // data
+            encode_list(&[]),  // access list
+        ];
+
+        let mut signing_payload = vec![0x02];
+        signing_payload.extend(encode_list(&fields));
+        let (signature, recovery_id) = key
+            .sign_prehash_recoverable(&keccak256(&signing_payload))
+            .map_err(|_| IngressError::InvalidSignature)?;
+
+        fields.extend([
+            encode_u64(recovery_id.to_byte() as u64),
+            encode_bytes(trim(&signature.r().to_bytes())),
+            encode_bytes(trim(&signature.s().to_bytes())),
+        ]);
+        let mut tx = vec![0x02];
+        tx.extend(encode_list(&fields));
+        Ok(tx)
+    }
+}
+
+// Helper function to strip leading zeros from a big-endian scalar
+fn trim(bytes: &[u8]) -> &[u8] {
+    let first = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    &bytes[first..]
+}
+
+fn random_u64(entropy: &dyn Entropy, bound: u64) -> Result<u64, IngressError> {
+    let mut bytes = [0u8; 8];
+    entropy.fill(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes) % bound)
+}
+
+// A validly signed transfer from a throwaway key, marked as a decoy so it is never forwarded
+pub(crate) fn decoy_envelope(entropy: &dyn Entropy) -> Result<TransactionEnvelope, IngressError> {
+    let key = loop {
+        let mut secret = [0u8; 32];
+        entropy.fill(&mut secret)?;
+        // Out-of-range scalars are astronomically rare, just draw again
+        if let Ok(key) = SigningKey::from_slice(&secret) {
+            break key;
+        }
+    };
+    let mut to = [0u8; 20];
+    entropy.fill(&mut to)?;
+
+    let tx_bytes = DynamicFeeTx {
+        chain_id: 1,
+        nonce: random_u64(entropy, 1024)?,
+        max_priority_fee: 1_000_000_000,
+        max_fee: 30_000_000_000,
+        gas_limit: 21_000,
+        to,
+        value: random_u64(entropy, 1_000_000_000_000_000_000)?,
+    }
+    .sign(&key)?;
+
+    // Same metadata as a real submission so batching treats it identically
+    let mut id_bytes = [0u8; 16];
+    entropy.fill(&mut id_bytes)?;
+    let mut envelope = TransactionEnvelope::new(tx_bytes, uuid::Builder::from_random_bytes(id_bytes).into_uuid().to_string());
+    envelope.sender = Some(recover_sender(&envelope.tx_bytes)?);
+    envelope.nonce = Some(transaction_nonce(&envelope.tx_bytes)?);
+    envelope.decoy = true;
+    Ok(envelope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entropy::OsEntropy;
+    use crate::transaction::{validate_transaction, TxType};
+
+    #[test]
+    fn test_decoys_are_valid_and_unique() {
+        let first = decoy_envelope(&OsEntropy).unwrap();
+        let second = decoy_envelope(&OsEntropy).unwrap();
+
+        assert!(first.is_decoy());
+        assert_eq!(validate_transaction(&first.tx_bytes), Ok(TxType::DynamicFee));
+        assert_ne!(first.tx_bytes, second.tx_bytes);
+        assert_ne!(first.sender, second.sender);
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, SystemTime};
+
+// Set of recently seen transaction hashes, each remembered for a fixed TTL
+pub(crate) struct DedupCache {
+    ttl: Duration,
+    seen: HashSet<Vec<u8>>,
+    expiry_order: VecDeque<(SystemTime, Vec<u8>)>, // (first seen, hash), oldest first
+}
+
+impl DedupCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            seen: HashSet::new(),
+            expiry_order: VecDeque::new(),
+        }
+    }
+
+    // Records the hash, returning false if it was already seen within the TTL
+    pub(crate) fn insert(&mut self, hash: Vec<u8>, now: SystemTime) -> bool {
+        self.prune(now);
+
+        if self.seen.contains(&hash) {
+            return false;
+        }
+        self.seen.insert(hash.clone());
+        self.expiry_order.push_back((now, hash));
+        true
+    }
+
+    // Drops every hash whose TTL has elapsed so memory stays bounded by the window
+    fn prune(&mut self, now: SystemTime) {
+        while let Some((seen_at, _)) = self.expiry_order.front() {
+            let age = now.duration_since(*seen_at).unwrap_or_default();
+            if age < self.ttl {
+                break;
+            }
+            if let Some((_, hash)) = self.expiry_order.pop_front() {
+                self.seen.remove(&hash);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prunes_expired_hashes() {
+        let mut cache = DedupCache::new(Duration::from_secs(10));
+        let start = SystemTime::now();
+
+        assert!(cache.insert(vec![1], start));
+        assert!(cache.insert(vec![2], start + Duration::from_secs(5)));
+        assert!(!cache.insert(vec![1], start + Duration::from_secs(9)));
+
+        // The first hash expires, the second is still inside its window
+        assert!(cache.insert(vec![3], start + Duration::from_secs(10)));
+        assert_eq!(cache.seen.len(), 2);
+        assert!(!cache.insert(vec![2], start + Duration::from_secs(14)));
+        assert!(cache.insert(vec![1], start + Duration::from_secs(15)));
+    }
+}
@@ -0,0 +1,222 @@
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::warn;
+
+use crate::error::IngressError;
+
+// Source of the relay URLs a RelayForwarder sends each batch to, for deployments whose relays come
+// from service discovery rather than from code or config
+pub trait RelayProvider: Send + Sync {
+    // Relays for the next batch; called once per batch, so it must not block
+    fn relay_urls(&self) -> Vec<String>;
+}
+
+// A fixed list of relays
+#[derive(Clone, Debug)]
+pub struct StaticRelayProvider {
+    relay_urls: Vec<String>,
+}
+
+impl StaticRelayProvider {
+    pub fn new(relay_urls: Vec<String>) -> Self {
+        Self { relay_urls }
+    }
+}
+
+impl RelayProvider for StaticRelayProvider {
+    fn relay_urls(&self) -> Vec<String> {
+        self.relay_urls.clone()
+    }
+}
+
+// Relays from a comma-separated environment variable, read again for every batch
+//
+// An unset variable, or one that is not valid Unicode, yields no relays.
+#[derive(Clone, Debug)]
+pub struct EnvRelayProvider {
+    var: String,
+}
+
+impl EnvRelayProvider {
+    pub fn new(var: impl Into<String>) -> Self {
+        Self { var: var.into() }
+    }
+}
+
+impl RelayProvider for EnvRelayProvider {
+    fn relay_urls(&self) -> Vec<String> {
+        std::env::var(&self.var).map(|value| parse_relay_list(&value)).unwrap_or_default()
+    }
+}
+
+// Splits a comma-separated relay list, dropping surrounding whitespace, empty entries and repeats
+pub fn parse_relay_list(value: &str) -> Vec<String> {
+    unique_urls(value.split(','))
+}
+
+// Helper function to keep the first of each repeated URL, in order, skipping blank ones
+fn unique_urls<'a>(urls: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut relay_urls: Vec<String> = Vec::new();
+    for url in urls.into_iter().map(str::trim).filter(|url| !url.is_empty()) {
+        if !relay_urls.iter().any(|known| known == url) {
+            relay_urls.push(url.to_string());
+        }
+    }
+    relay_urls
+}
+
+// Relays fetched from an HTTP endpoint answering GET with a JSON array of URLs, refreshed by the task
+// spawn starts; clones share the fetched set
+//
+// Until the first successful fetch there are no relays. A failed refresh keeps the last set, so an
+// unavailable discovery service never empties it.
+#[derive(Clone)]
+pub struct HttpRelayDiscovery {
+    endpoint: String,
+    refresh_interval: Duration,
+    client: reqwest::Client,
+    relay_urls: Arc<Mutex<Vec<String>>>,
+}
+
+impl HttpRelayDiscovery {
+    pub fn new(endpoint: String, refresh_interval: Duration) -> Self {
+        Self {
+            endpoint,
+            refresh_interval,
+            client: reqwest::Client::new(),
+            relay_urls: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    // Fetches the relay set once, replacing the current one on success
+    pub async fn refresh(&self) -> Result<(), IngressError> {
+        let body: serde_json::Value = self
+            .client
+            .get(&self.endpoint)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| IngressError::RelayUnreachable(format!("relay discovery: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| IngressError::RelayUnreachable(format!("relay discovery: {}", e)))?;
+
+        let relay_urls: Option<Vec<&str>> =
+            body.as_array().and_then(|urls| urls.iter().map(serde_json::Value::as_str).collect());
+        let relay_urls = relay_urls
+            .ok_or_else(|| IngressError::RelayRejected(format!("relay discovery: invalid response {}", body)))?;
+        *self.urls() = unique_urls(relay_urls);
+        Ok(())
+    }
+
+    // Refreshes right away and then every refresh_interval until the returned task is aborted
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.refresh_interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                if let Err(error) = self.refresh().await {
+                    warn!(endpoint = %self.endpoint, %error, "relay discovery refresh failed, keeping last relay set");
+                }
+            }
+        })
+    }
+
+    // The set is only ever replaced whole, so a poisoned lock still holds a valid one
+    fn urls(&self) -> std::sync::MutexGuard<'_, Vec<String>> {
+        self.relay_urls.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl RelayProvider for HttpRelayDiscovery {
+    fn relay_urls(&self) -> Vec<String> {
+        self.urls().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::batch::TransactionBatch;
+    use crate::circuit_breaker::BreakerPolicy;
+    use crate::envelope::TransactionEnvelope;
+    use crate::relay::{RelayForwarder, RetryPolicy};
+
+    async fn relay(status: u16) -> MockServer {
+        let relay = MockServer::start().await;
+        let body = serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"});
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(status).set_body_json(body))
+            .mount(&relay)
+            .await;
+        relay
+    }
+
+    async fn serve_relays(discovery: &MockServer, relays: &[&MockServer]) {
+        let relay_urls: Vec<String> = relays.iter().map(|relay| relay.uri()).collect();
+        discovery.reset().await;
+        Mock::given(method("GET"))
+            .and(path("/relays"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!(relay_urls)))
+            .mount(discovery)
+            .await;
+    }
+
+    #[test]
+    fn test_relay_list_parsed_from_env_value() {
+        assert_eq!(
+            parse_relay_list(" https://a.example , https://b.example,,https://a.example,\thttps://c.example\n"),
+            vec!["https://a.example", "https://b.example", "https://c.example"]
+        );
+        assert!(parse_relay_list("").is_empty());
+        assert!(parse_relay_list(" , ,").is_empty());
+
+        // An unset variable means no relays rather than an error
+        assert!(EnvRelayProvider::new("PENUM_TEST_UNSET_RELAY_URLS").relay_urls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_forwarder_follows_discovered_relay_set() {
+        let (failing, healthy) = (relay(500).await, relay(200).await);
+        let discovery_server = MockServer::start().await;
+        serve_relays(&discovery_server, &[&failing]).await;
+
+        let discovery = HttpRelayDiscovery::new(format!("{}/relays", discovery_server.uri()), Duration::from_secs(60));
+        let policy = BreakerPolicy { failure_threshold: 1, cool_off: Duration::from_secs(60) };
+        let forwarder = RelayForwarder::new(Vec::new())
+            .with_retry_policy(RetryPolicy { max_attempts: 1, ..RetryPolicy::default() })
+            .with_circuit_breaker(policy)
+            .with_provider(Arc::new(discovery.clone()));
+        let batch = TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, 0xaa], "a".to_string())]).unwrap();
+
+        // Nothing is sent before the first fetch
+        assert!(forwarder.forward_batch(&batch).await.is_empty());
+        discovery.refresh().await.unwrap();
+        let results = forwarder.forward_batch(&batch).await;
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].is_success());
+
+        // A relay joins; the one already listed keeps its open breaker across the change
+        serve_relays(&discovery_server, &[&healthy, &failing]).await;
+        discovery.refresh().await.unwrap();
+        let results = forwarder.forward_batch(&batch).await;
+        let outcomes: Vec<(String, bool, bool)> =
+            results.iter().map(|result| (result.relay_url.clone(), result.is_success(), result.circuit_open)).collect();
+        assert_eq!(outcomes, vec![(healthy.uri(), true, false), (failing.uri(), false, true)]);
+        assert_eq!(failing.received_requests().await.unwrap().len(), 1);
+
+        // An unavailable discovery service leaves the last set in place
+        discovery_server.reset().await;
+        assert!(discovery.refresh().await.is_err());
+        assert_eq!(discovery.relay_urls(), vec![healthy.uri(), failing.uri()]);
+        forwarder.forward_batch(&batch).await;
+        assert_eq!(healthy.received_requests().await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,275 @@
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce};
+
+use crate::batch::TransactionBatch;
+use crate::entropy::{Entropy, OsEntropy};
+use crate::error::IngressError;
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+// One Shamir share of a batch key; any `threshold` distinct shares reconstruct it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyShare {
+    pub index: u8, // Evaluation point, never zero
+    pub value: Vec<u8>,
+}
+
+impl TransactionBatch {
+    // Encrypts every transaction under a fresh batch key and splits the key into share_count shares
+    //
+    // The commitment is recomputed over the ciphertexts, so a reveal can be verified before decryption.
+    pub fn encrypt_batch(&mut self, threshold: u8, share_count: u8) -> Result<Vec<KeyShare>, IngressError> {
+        self.encrypt_batch_with_entropy(threshold, share_count, &OsEntropy)
+    }
+
+    // Encrypts the batch like encrypt_batch, drawing the key, nonces and share polynomials from entropy
+    //
+    // Everything is sealed before the batch is touched, so a failure leaves it in plaintext.
+    pub fn encrypt_batch_with_entropy(
+        &mut self,
+        threshold: u8,
+        share_count: u8,
+        entropy: &dyn Entropy,
+    ) -> Result<Vec<KeyShare>, IngressError> {
+        if threshold == 0 || threshold > share_count {
+            return Err(IngressError::Encryption(format!(
+                "threshold {} must be between 1 and the share count {}",
+                threshold, share_count
+            )));
+        }
+        if self.transactions.iter().any(|tx| tx.encrypted) {
+            return Err(IngressError::Encryption("batch is already encrypted".to_string()));
+        }
+
+        let key = random_bytes::<KEY_LEN>(entropy)?;
+        let cipher = ChaCha20Poly1305::new(&key.into());
+        let mut sealed = Vec::with_capacity(self.transactions.len());
+        for tx in &self.transactions {
+            let nonce = random_bytes::<NONCE_LEN>(entropy)?;
+            // The batch id is authenticated so ciphertexts cannot be moved between batches
+            let ciphertext = cipher
+                .encrypt(Nonce::from_slice(&nonce), Payload { msg: &tx.tx_bytes, aad: self.id.as_bytes() })
+                .map_err(|_| IngressError::Encryption("encryption failed".to_string()))?;
+
+            let mut sealed_bytes = nonce.to_vec();
+            sealed_bytes.extend(ciphertext);
+            sealed.push(sealed_bytes);
+        }
+        let shares = split_secret(&key, threshold, share_count, entropy)?;
+
+        for (tx, sealed_bytes) in self.transactions.iter_mut().zip(sealed) {
+            tx.tx_bytes = sealed_bytes;
+            tx.encrypted = true;
+        }
+        self.commitment = self.recompute_commitment();
+        Ok(shares)
+    }
+
+    // Restores the plaintext transactions; fails unless at least threshold distinct shares are given
+    pub fn decrypt_batch(&mut self, shares: &[KeyShare]) -> Result<(), IngressError> {
+        let key = combine_shares(shares)?;
+        let cipher = ChaCha20Poly1305::new(&key.into());
+
+        // Decrypt everything before touching the batch so a failure leaves it intact
+        let mut plaintexts = Vec::with_capacity(self.transactions.len());
+        for tx in &self.transactions {
+            if !tx.encrypted || tx.tx_bytes.len() < NONCE_LEN {
+                return Err(IngressError::Encryption("transaction is not encrypted".to_string()));
+            }
+            let (nonce, ciphertext) = tx.tx_bytes.split_at(NONCE_LEN);
+            let plaintext = cipher
+                .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: self.id.as_bytes() })
+                .map_err(|_| IngressError::Encryption("decryption failed, not enough valid key shares".to_string()))?;
+            plaintexts.push(plaintext);
+        }
+
+        for (tx, plaintext) in self.transactions.iter_mut().zip(plaintexts) {
+            tx.tx_bytes = plaintext;
+            tx.encrypted = false;
+        }
+        Ok(())
+    }
+}
+
+fn random_bytes<const N: usize>(entropy: &dyn Entropy) -> Result<[u8; N], IngressError> {
+    let mut bytes = [0u8; N];
+    entropy.fill(&mut bytes)?;
+    Ok(bytes)
+}
+
+// Helper function to split a secret byte-wise with a random degree threshold-1 polynomial over GF(256)
+fn split_secret(
+    secret: &[u8],
+    threshold: u8,
+    share_count: u8,
+    entropy: &dyn Entropy,
+) -> Result<Vec<KeyShare>, IngressError> {
+    let mut shares: Vec<KeyShare> = (1..=share_count)
+        .map(|index| KeyShare { index, value: Vec::with_capacity(secret.len()) })
+        .collect();
+
+    let mut coefficients = vec![0u8; threshold as usize];
+    for &byte in secret {
+        coefficients[0] = byte;
+        entropy.fill(&mut coefficients[1..])?;
+
+        for share in &mut shares {
+            // Horner evaluation at x = share.index
+            let y = coefficients.iter().rev().fold(0u8, |acc, &c| gf_mul(acc, share.index) ^ c);
+            share.value.push(y);
+        }
+    }
+    Ok(shares)
+}
+
+// Helper function to recover the secret by Lagrange interpolation at x = 0
+fn combine_shares(shares: &[KeyShare]) -> Result<[u8; KEY_LEN], IngressError> {
+    let invalid = |reason: &str| IngressError::Encryption(reason.to_string());
+    if shares.is_empty() {
+        return Err(invalid("no key shares provided"));
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if share.index == 0 || share.value.len() != KEY_LEN {
+            return Err(invalid("malformed key share"));
+        }
+        if shares[..i].iter().any(|other| other.index == share.index) {
+            return Err(invalid("duplicate key share"));
+        }
+    }
+
+    let mut key = [0u8; KEY_LEN];
+    for share in shares {
+        // Lagrange basis at zero: prod(x_j / (x_j - x_i)), and subtraction is xor in GF(256)
+        let basis = shares
+            .iter()
+            .filter(|other| other.index != share.index)
+            .fold(1u8, |acc, other| gf_mul(acc, gf_mul(other.index, gf_inv(other.index ^ share.index))));
+        for (byte, &y) in key.iter_mut().zip(&share.value) {
+            *byte ^= gf_mul(y, basis);
+        }
+    }
+    Ok(key)
+}
+
+// Multiplication in GF(256) with the AES reduction polynomial x^8 + x^4 + x^3 + x + 1
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+// Multiplicative inverse in GF(256), a^254; only called with non-zero a
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1;
+    for _ in 0..254 {
+        result = gf_mul(result, a);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit_reveal::CommitRevealPipeline;
+    use crate::entropy::SeededEntropy;
+    use crate::test_utils::FailingEntropy;
+    use crate::envelope::TransactionEnvelope;
+
+    fn sample_batch() -> TransactionBatch {
+        let transactions = (0..4u8)
+            .map(|i| TransactionEnvelope::new(vec![0x02, i, i], i.to_string()))
+            .collect();
+        TransactionBatch::new(transactions).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_decrypt_round_trip() {
+        let mut batch = sample_batch();
+        let plaintexts: Vec<Vec<u8>> = batch.transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        let plaintext_commitment = batch.commitment;
+
+        let shares = batch.encrypt_batch(3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert!(batch.transactions.iter().all(|tx| tx.encrypted));
+        assert!(batch.transactions.iter().zip(&plaintexts).all(|(tx, plain)| tx.tx_bytes != *plain));
+
+        // The commitment now binds to the ciphertexts and verifies before decryption
+        assert_ne!(batch.commitment, plaintext_commitment);
+        let pipeline = CommitRevealPipeline::new();
+        pipeline.commit_batch(&batch).await.unwrap();
+        assert_eq!(pipeline.verify_reveal(&batch), Ok(()));
+
+        // Any three of the five shares reconstruct the key
+        batch.decrypt_batch(&[shares[4].clone(), shares[0].clone(), shares[2].clone()]).unwrap();
+        let decrypted: Vec<Vec<u8>> = batch.transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        assert_eq!(decrypted, plaintexts);
+        assert!(batch.transactions.iter().all(|tx| !tx.encrypted));
+    }
+
+    #[test]
+    fn test_decrypt_fails_below_threshold() {
+        let mut batch = sample_batch();
+        let shares = batch.encrypt_batch(3, 5).unwrap();
+        let sealed: Vec<Vec<u8>> = batch.transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+
+        let result = batch.decrypt_batch(&shares[..2]);
+        assert!(matches!(result, Err(IngressError::Encryption(_))));
+
+        // A failed attempt leaves the ciphertexts untouched
+        let unchanged: Vec<Vec<u8>> = batch.transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        assert_eq!(unchanged, sealed);
+        assert!(batch.decrypt_batch(&shares[1..4]).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_share_parameters_rejected() {
+        let mut batch = sample_batch();
+
+        assert!(matches!(batch.encrypt_batch(0, 3), Err(IngressError::Encryption(_))));
+        assert!(matches!(batch.encrypt_batch(4, 3), Err(IngressError::Encryption(_))));
+
+        let shares = batch.encrypt_batch(2, 3).unwrap();
+        assert!(matches!(batch.encrypt_batch(2, 3), Err(IngressError::Encryption(_))));
+        let duplicated = [shares[0].clone(), shares[0].clone()];
+        assert!(matches!(batch.decrypt_batch(&duplicated), Err(IngressError::Encryption(_))));
+    }
+
+    #[test]
+    fn test_failed_encryption_leaves_batch_in_plaintext() {
+        let mut batch = sample_batch();
+        let plaintexts: Vec<Vec<u8>> = batch.transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        let commitment = batch.commitment;
+
+        // The key and two nonces are drawn before the third transaction's nonce fails
+        let entropy = FailingEntropy::after(3);
+        assert_eq!(batch.encrypt_batch_with_entropy(2, 3, &entropy), Err(IngressError::RngFailure));
+        let unchanged: Vec<Vec<u8>> = batch.transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        assert_eq!(unchanged, plaintexts);
+        assert!(batch.transactions.iter().all(|tx| !tx.encrypted));
+        assert_eq!(batch.commitment, commitment);
+
+        // Injected entropy backs the whole encryption
+        let shares = batch.encrypt_batch_with_entropy(2, 3, &SeededEntropy::new([3; 32])).unwrap();
+        batch.decrypt_batch(&shares[..2]).unwrap();
+        let decrypted: Vec<Vec<u8>> = batch.transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        assert_eq!(decrypted, plaintexts);
+    }
+
+    #[test]
+    fn test_gf_inverse() {
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::IngressError;
+
+// Source of the randomness behind batch nonces, random batch IDs and decoy transactions
+pub trait Entropy: Send + Sync {
+    fn fill(&self, dest: &mut [u8]) -> Result<(), IngressError>;
+}
+
+// Operating system randomness, used unless an embedder or test substitutes its own source
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OsEntropy;
+
+impl Entropy for OsEntropy {
+    fn fill(&self, dest: &mut [u8]) -> Result<(), IngressError> {
+        getrandom::getrandom(dest).map_err(|_| IngressError::RngFailure)
+    }
+}
+
+// Deterministic stream of SHA-256(seed || counter) blocks, so batches can be reproduced in tests and
+// simulations; clones share the same stream
+//
+// Anyone who knows the seed can predict every batch nonce, so this must never back a production engine.
+#[derive(Clone, Debug)]
+pub struct SeededEntropy {
+    seed: [u8; 32],
+    counter: Arc<Mutex<u64>>,
+}
+
+impl SeededEntropy {
+    pub fn new(seed: [u8; 32]) -> Self {
+        Self {
+            seed,
+            counter: Arc::new(Mutex::new(0)),
+        }
+    }
+}
+
+impl Entropy for SeededEntropy {
+    fn fill(&self, dest: &mut [u8]) -> Result<(), IngressError> {
+        // A poisoned lock still holds a valid counter
+        let mut counter = self.counter.lock().unwrap_or_else(PoisonError::into_inner);
+        for chunk in dest.chunks_mut(32) {
+            let block = Sha256::new().chain_update(self.seed).chain_update(counter.to_be_bytes()).finalize();
+            chunk.copy_from_slice(&block[..chunk.len()]);
+            *counter += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_entropy_is_reproducible_and_shared_by_clones() {
+        let (mut first, mut second) = ([0u8; 40], [0u8; 40]);
+        SeededEntropy::new([7; 32]).fill(&mut first).unwrap();
+        SeededEntropy::new([7; 32]).fill(&mut second).unwrap();
+        assert_eq!(first, second);
+
+        // A clone continues the stream instead of restarting it
+        let entropy = SeededEntropy::new([7; 32]);
+        let mut head = [0u8; 32];
+        entropy.clone().fill(&mut head).unwrap();
+        let mut next = [0u8; 32];
+        entropy.fill(&mut next).unwrap();
+        assert_eq!(head, first[..32]);
+        assert_ne!(next, head);
+    }
+}
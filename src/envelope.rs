@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::transaction::Address;
+use crate::wire::{ConditionsWire, EnvelopeWire};
+
+// Transaction fields a MEV-Share relay may show searchers; the default shares nothing
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrivacyHints {
+    pub calldata: bool,
+    pub contract_address: bool,
+    pub function_selector: bool,
+    pub logs: bool,
+}
+
+impl PrivacyHints {
+    // Hints shared only where both allow them
+    pub fn intersect(self, other: PrivacyHints) -> PrivacyHints {
+        PrivacyHints {
+            calldata: self.calldata && other.calldata,
+            contract_address: self.contract_address && other.contract_address,
+            function_selector: self.function_selector && other.function_selector,
+            logs: self.logs && other.logs,
+        }
+    }
+}
+
+// Chain state a transaction may only be included under, as eth_sendRawTransactionConditional takes it;
+// serializes to that method's options object, so it passes through to relays unchanged
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "ConditionsWire", try_from = "ConditionsWire")]
+pub struct TransactionConditions {
+    pub known_accounts: BTreeMap<Address, KnownAccount>, // Accounts whose storage must be exactly as given
+    pub block_number_min: Option<u64>,                   // First block it may be included in
+    pub block_number_max: Option<u64>,                   // Last block it may be included in
+}
+
+// What a known account's storage must hold for a conditional transaction to be included
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KnownAccount {
+    StorageRoot([u8; 32]),               // Root of the account's whole storage trie
+    Slots(BTreeMap<[u8; 32], [u8; 32]>), // Values of the listed slots
+}
+
+// Transaction envelope containing raw transaction bytes; serializes to the versioned layout in wire
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(into = "EnvelopeWire", try_from = "EnvelopeWire")]
+pub struct TransactionEnvelope {
+    pub tx_bytes: Vec<u8>,
+    pub batch_id: String,
+    pub envelope_version: u32,
+    pub sender: Option<Address>, // Recovered signer, used for dedup and nonce tracking (never logged)
+    pub nonce: Option<u64>,      // Account nonce, keeps a sender's transactions in order within a batch
+    pub encrypted: bool,         // tx_bytes holds nonce || ciphertext under the batch key until decrypt_batch
+    pub privacy_hints: PrivacyHints, // What the sender lets MEV-Share searchers see; not kept in the write-ahead log
+    pub valid_until_block: Option<u64>, // Last block the sender wants it included in; dropped once the head reaches it
+    pub conditions: Option<TransactionConditions>, // Sent along to relays that take conditional transactions
+    pub(crate) decoy: bool,      // Padding that is committed to but never forwarded
+    pub(crate) received_at: Option<SystemTime>, // When the batching engine accepted it, for the privacy report
+}
+
+impl TransactionEnvelope {
+    pub fn new(tx_bytes: Vec<u8>, batch_id: String) -> Self {
+        Self {
+            tx_bytes,
+            batch_id,
+            envelope_version: 1,
+            sender: None,
+            nonce: None,
+            encrypted: false,
+            privacy_hints: PrivacyHints::default(),
+            valid_until_block: None,
+            conditions: None,
+            decoy: false,
+            received_at: None,
+        }
+    }
+
+    pub fn is_decoy(&self) -> bool {
+        self.decoy
+    }
+
+    // Whether the sender's deadline has passed with the chain at head; the earliest block it could
+    // still land in is head + 1
+    pub fn is_expired_at(&self, head: u64) -> bool {
+        self.valid_until_block.is_some_and(|valid_until_block| valid_until_block <= head)
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use thiserror::Error;
+
+// Errors surfaced by the ingress layer
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum IngressError {
+    #[error("Transaction bytes cannot be empty")]
+    EmptyTransaction,
+
+    #[error("Invalid transaction: {0}")]
+    InvalidTransaction(String),
+
+    #[error("Transaction signature is invalid")]
+    InvalidSignature,
+
+    #[error("Invalid user operation: {0}")]
+    InvalidUserOperation(String),
+
+    #[error("Transaction was already submitted")]
+    Duplicate,
+
+    #[error("Replacement transaction does not raise the priority fee")]
+    ReplacementUnderpriced,
+
+    #[error("Transaction chain ID {} does not match network chain ID {expected}", chain_id_label(.found))]
+    WrongChainId { expected: u64, found: Option<u64> }, // found is None for a pre-EIP-155 transaction
+
+    #[error("Transaction is {size} bytes, limit is {max_tx_bytes}")]
+    TxTooLarge { size: usize, max_tx_bytes: usize },
+
+    #[error("Blob transactions are not accepted")]
+    BlobTransactionsDisabled,
+
+    #[error("No batching lane named {0}")]
+    UnknownLane(String),
+
+    #[error("Pending pool holds {pending_bytes} bytes, limit is {max_pending_bytes}")]
+    PendingPoolFull { pending_bytes: usize, max_pending_bytes: usize },
+
+    #[error("Pending pool holds {pending_blob_bytes} bytes of blobs, limit is {max_pending_blob_bytes}")]
+    BlobPoolFull { pending_blob_bytes: usize, max_pending_blob_bytes: usize },
+
+    #[error("Submission rate limit exceeded")]
+    RateLimited,
+
+    #[error("Idempotency key {0} was already used for a different transaction")]
+    IdempotencyKeyConflict(String),
+
+    #[error("Relay unreachable: {0}")]
+    RelayUnreachable(String),
+
+    #[error("Relay rejected submission: {0}")]
+    RelayRejected(String),
+
+    #[error("Relay did not respond within {0:?}")]
+    RelayTimeout(Duration),
+
+    #[error("Batch had to land by block {valid_until_block}, head is already at {head}")]
+    InclusionDeadlinePassed { valid_until_block: u64, head: u64 },
+
+    #[error("Revealed batch does not match its commitment")]
+    CommitmentMismatch,
+
+    #[error("No commitment recorded for batch {0}")]
+    CommitmentNotFound(String),
+
+    #[error("Reveal delay has not elapsed for batch {0}")]
+    RevealTooEarly(String),
+
+    #[error("Reveal window has closed for batch {0}")]
+    CommitmentExpired(String),
+
+    #[error("Invalid batch nonce: {0}")]
+    InvalidNonce(String),
+
+    #[error("Invalid batch commitment: {0}")]
+    InvalidCommitment(String),
+
+    #[error("Batch operator signature is missing or invalid")]
+    InvalidOperatorSignature,
+
+    #[error("Failed to generate random nonce")]
+    RngFailure,
+
+    #[error("Internal lock poisoned")]
+    LockPoisoned,
+
+    #[error("Only {accepted} relays accepted the batch, {required} required")]
+    QuorumNotMet { accepted: usize, required: usize },
+
+    #[error("Only {accepted} relays in group {group} accepted its part of the batch, {required} required")]
+    GroupQuorumNotMet { group: String, accepted: usize, required: usize },
+
+    #[error("Envelope encryption error: {0}")]
+    Encryption(String),
+
+    #[error("Write-ahead log error: {0}")]
+    Storage(String),
+
+    #[error("Commitment anchoring failed: {0}")]
+    Anchor(String),
+
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+}
+
+impl IngressError {
+    // Name of the variant, without its fields, e.g. to count rejections by reason
+    pub fn kind(&self) -> &'static str {
+        match self {
+            IngressError::EmptyTransaction => "EmptyTransaction",
+            IngressError::InvalidTransaction(_) => "InvalidTransaction",
+            IngressError::InvalidSignature => "InvalidSignature",
+            IngressError::InvalidUserOperation(_) => "InvalidUserOperation",
+            IngressError::Duplicate => "Duplicate",
+            IngressError::ReplacementUnderpriced => "ReplacementUnderpriced",
+            IngressError::WrongChainId { .. } => "WrongChainId",
+            IngressError::TxTooLarge { .. } => "TxTooLarge",
+            IngressError::BlobTransactionsDisabled => "BlobTransactionsDisabled",
+            IngressError::UnknownLane(_) => "UnknownLane",
+            IngressError::PendingPoolFull { .. } => "PendingPoolFull",
+            IngressError::BlobPoolFull { .. } => "BlobPoolFull",
+            IngressError::RateLimited => "RateLimited",
+            IngressError::IdempotencyKeyConflict(_) => "IdempotencyKeyConflict",
+            IngressError::RelayUnreachable(_) => "RelayUnreachable",
+            IngressError::RelayRejected(_) => "RelayRejected",
+            IngressError::RelayTimeout(_) => "RelayTimeout",
+            IngressError::InclusionDeadlinePassed { .. } => "InclusionDeadlinePassed",
+            IngressError::CommitmentMismatch => "CommitmentMismatch",
+            IngressError::CommitmentNotFound(_) => "CommitmentNotFound",
+            IngressError::RevealTooEarly(_) => "RevealTooEarly",
+            IngressError::CommitmentExpired(_) => "CommitmentExpired",
+            IngressError::InvalidNonce(_) => "InvalidNonce",
+            IngressError::InvalidCommitment(_) => "InvalidCommitment",
+            IngressError::InvalidOperatorSignature => "InvalidOperatorSignature",
+            IngressError::RngFailure => "RngFailure",
+            IngressError::LockPoisoned => "LockPoisoned",
+            IngressError::QuorumNotMet { .. } => "QuorumNotMet",
+            IngressError::GroupQuorumNotMet { .. } => "GroupQuorumNotMet",
+            IngressError::Encryption(_) => "Encryption",
+            IngressError::Storage(_) => "Storage",
+            IngressError::Anchor(_) => "Anchor",
+            IngressError::Config(_) => "Config",
+        }
+    }
+
+    // Whether the submission itself was refused, so resubmitting the same bytes gets the same answer;
+    // capacity, rate limits and internal failures can clear up on a retry
+    pub fn is_validation_failure(&self) -> bool {
+        matches!(
+            self,
+            IngressError::EmptyTransaction
+                | IngressError::InvalidTransaction(_)
+                | IngressError::InvalidSignature
+                | IngressError::InvalidUserOperation(_)
+                | IngressError::Duplicate
+                | IngressError::ReplacementUnderpriced
+                | IngressError::WrongChainId { .. }
+                | IngressError::TxTooLarge { .. }
+                | IngressError::BlobTransactionsDisabled
+                | IngressError::UnknownLane(_)
+        )
+    }
+}
+
+// Helper function to name a transaction's chain ID in error messages
+fn chain_id_label(chain_id: &Option<u64>) -> String {
+    chain_id.map_or_else(|| "missing".to_string(), |chain_id| chain_id.to_string())
+}
+
+// Helper function to lock a mutex, surfacing poisoning as an error instead of panicking
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, IngressError> {
+    mutex.lock().map_err(|_| IngressError::LockPoisoned)
+}
+
+impl From<std::io::Error> for IngressError {
+    fn from(error: std::io::Error) -> Self {
+        IngressError::Storage(error.to_string())
+    }
+}
@@ -0,0 +1,247 @@
+// C-compatible entry points, so services written in other languages can submit transactions in-process
+//
+// Ownership: penum_ingress_new hands the caller a handle that only penum_ingress_free releases; every
+// other pointer stays owned by the caller and is only read or written for the duration of the call.
+// Each handle runs its own tokio runtime with the batch processing task on it, so calls block the
+// calling thread and must not be made from inside a tokio runtime. Build the shared library with
+// `cargo rustc --release --lib --features ffi --crate-type cdylib`.
+
+use std::ffi::{c_char, c_int, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+
+use crate::config::IngressConfig;
+use crate::error::IngressError;
+use crate::ingress::PenumIngress;
+
+// Return codes; everything other than PENUM_OK leaves the output arguments untouched
+pub const PENUM_OK: c_int = 0;
+pub const PENUM_ERR_NULL_POINTER: c_int = -1;
+pub const PENUM_ERR_INVALID_CONFIG: c_int = -2; // Not UTF-8, not TOML, an unknown key or value, or conflicting values
+pub const PENUM_ERR_BUFFER_TOO_SMALL: c_int = -3; // The hash buffer holds fewer than PENUM_TX_HASH_LEN bytes
+pub const PENUM_ERR_INVALID_TRANSACTION: c_int = -4; // Malformed, badly signed, wrong chain or too large
+pub const PENUM_ERR_DUPLICATE: c_int = -5; // Already submitted, or a replacement without a higher fee
+pub const PENUM_ERR_BACKPRESSURE: c_int = -6; // Rate limited or the pending pool is full; retry later
+pub const PENUM_ERR_INTERNAL: c_int = -7; // Runtime, relay or lock failure inside the ingress
+pub const PENUM_ERR_PANIC: c_int = -8; // A panic was caught at the boundary; the handle should be freed
+
+// Length of the transaction hash written by penum_ingress_submit
+pub const PENUM_TX_HASH_LEN: usize = 32;
+
+// Opaque to foreign callers, who only ever hold a pointer to it
+pub struct PenumIngressHandle {
+    runtime: Runtime,
+    ingress: PenumIngress,
+    processor: JoinHandle<()>,
+}
+
+/// Creates an ingress from a NUL-terminated TOML configuration, or the defaults when `config_toml`
+/// is null, and stores its handle in `*out`.
+///
+/// # Safety
+///
+/// `config_toml` must be null or point to a NUL-terminated string, and `out` must be valid for a
+/// pointer-sized write. The handle written to `*out` must be released with `penum_ingress_free`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn penum_ingress_new(config_toml: *const c_char, out: *mut *mut PenumIngressHandle) -> c_int {
+    if out.is_null() {
+        return PENUM_ERR_NULL_POINTER;
+    }
+
+    guard(|| {
+        let config = if config_toml.is_null() {
+            IngressConfig::default()
+        } else {
+            // SAFETY: the caller guarantees a NUL-terminated string
+            let Ok(contents) = unsafe { CStr::from_ptr(config_toml) }.to_str() else {
+                return PENUM_ERR_INVALID_CONFIG;
+            };
+            match IngressConfig::from_toml_str(contents) {
+                Ok(config) => config,
+                Err(_) => return PENUM_ERR_INVALID_CONFIG,
+            }
+        };
+        let Ok(runtime) = Runtime::new() else {
+            return PENUM_ERR_INTERNAL;
+        };
+
+        // The ingress and its processing task are tied to this handle's runtime
+        let (ingress, processor) = {
+            let _context = runtime.enter();
+            let Ok(ingress) = PenumIngress::from_config(config) else {
+                return PENUM_ERR_INVALID_CONFIG;
+            };
+            let processor = ingress.clone().spawn();
+            (ingress, processor)
+        };
+
+        let handle = Box::new(PenumIngressHandle { runtime, ingress, processor });
+        // SAFETY: out was checked for null and the caller guarantees it is writable
+        unsafe { *out = Box::into_raw(handle) };
+        PENUM_OK
+    })
+}
+
+/// Submits the `tx_len` raw transaction bytes at `tx` and, once accepted, writes the transaction's
+/// 32-byte Keccak-256 hash to the start of `hash_out`.
+///
+/// # Safety
+///
+/// `handle` must come from `penum_ingress_new` and not yet be freed, `tx` must be readable for
+/// `tx_len` bytes and `hash_out` writable for `hash_out_len` bytes. The bytes are copied, so both
+/// buffers may be reused as soon as the call returns.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn penum_ingress_submit(
+    handle: *const PenumIngressHandle,
+    tx: *const u8,
+    tx_len: usize,
+    hash_out: *mut u8,
+    hash_out_len: usize,
+) -> c_int {
+    if handle.is_null() || tx.is_null() || hash_out.is_null() {
+        return PENUM_ERR_NULL_POINTER;
+    }
+    if hash_out_len < PENUM_TX_HASH_LEN {
+        return PENUM_ERR_BUFFER_TOO_SMALL;
+    }
+
+    guard(|| {
+        // SAFETY: the caller guarantees a live handle and a readable transaction buffer
+        let (handle, tx_bytes) = unsafe { (&*handle, std::slice::from_raw_parts(tx, tx_len).to_vec()) };
+        match handle.runtime.block_on(handle.ingress.submit_transaction(tx_bytes)) {
+            Ok(tx_hash) => {
+                // SAFETY: hash_out was checked to hold at least PENUM_TX_HASH_LEN bytes
+                unsafe { std::ptr::copy_nonoverlapping(tx_hash.as_ptr(), hash_out, PENUM_TX_HASH_LEN) };
+                PENUM_OK
+            }
+            Err(error) => error_code(&error),
+        }
+    })
+}
+
+/// Flushes and forwards every pending transaction, then releases the handle and its runtime.
+/// Passing null does nothing.
+///
+/// # Safety
+///
+/// `handle` must be null or come from `penum_ingress_new`, and must not be used again afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn penum_ingress_free(handle: *mut PenumIngressHandle) {
+    if handle.is_null() {
+        return;
+    }
+
+    // SAFETY: the caller hands back ownership of a handle created by penum_ingress_new
+    let handle = unsafe { Box::from_raw(handle) };
+    let _ = catch_unwind(AssertUnwindSafe(move || {
+        let PenumIngressHandle { runtime, ingress, processor } = *handle;
+        ingress.shutdown();
+        let _ = runtime.block_on(processor);
+    }));
+}
+
+/// Static, NUL-terminated description of a return code; never null and never to be freed.
+#[unsafe(no_mangle)]
+pub extern "C" fn penum_ingress_error_message(code: c_int) -> *const c_char {
+    let message = match code {
+        PENUM_OK => c"ok",
+        PENUM_ERR_NULL_POINTER => c"a required pointer argument was null",
+        PENUM_ERR_INVALID_CONFIG => c"invalid configuration",
+        PENUM_ERR_BUFFER_TOO_SMALL => c"hash buffer is smaller than 32 bytes",
+        PENUM_ERR_INVALID_TRANSACTION => c"transaction rejected as invalid",
+        PENUM_ERR_DUPLICATE => c"transaction was already submitted",
+        PENUM_ERR_BACKPRESSURE => c"ingress is at capacity, retry later",
+        PENUM_ERR_INTERNAL => c"internal ingress error",
+        PENUM_ERR_PANIC => c"ingress panicked",
+        _ => c"unknown error code",
+    };
+    message.as_ptr()
+}
+
+// Helper function to keep a panic from unwinding into foreign frames
+fn guard(body: impl FnOnce() -> c_int) -> c_int {
+    catch_unwind(AssertUnwindSafe(body)).unwrap_or(PENUM_ERR_PANIC)
+}
+
+fn error_code(error: &IngressError) -> c_int {
+    match error {
+        IngressError::EmptyTransaction
+        | IngressError::InvalidTransaction(_)
+        | IngressError::InvalidSignature
+        | IngressError::WrongChainId { .. }
+        | IngressError::TxTooLarge { .. }
+        | IngressError::BlobTransactionsDisabled
+        | IngressError::UnknownLane(_) => PENUM_ERR_INVALID_TRANSACTION,
+        IngressError::Duplicate | IngressError::ReplacementUnderpriced => PENUM_ERR_DUPLICATE,
+        IngressError::RateLimited | IngressError::PendingPoolFull { .. } | IngressError::BlobPoolFull { .. } => {
+            PENUM_ERR_BACKPRESSURE
+        }
+        _ => PENUM_ERR_INTERNAL,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::dynamic_fee_tx;
+    use crate::transaction::transaction_hash;
+
+    // Creates a handle the way a C caller would, through an out-pointer
+    fn new_handle(config_toml: &CStr) -> *mut PenumIngressHandle {
+        let mut handle: *mut PenumIngressHandle = std::ptr::null_mut();
+        assert_eq!(unsafe { penum_ingress_new(config_toml.as_ptr(), &mut handle) }, PENUM_OK);
+        assert!(!handle.is_null());
+        handle
+    }
+
+    #[test]
+    fn test_foreign_caller_submits_and_frees_handle() {
+        let handle = new_handle(c"batch_size = 100\nbatch_interval_ms = 3600000\n");
+        let tx = dynamic_fee_tx(0);
+
+        // The hash lands at the start of a larger caller buffer, leaving the rest alone
+        let mut hash = [0xaa; 40];
+        let code = unsafe { penum_ingress_submit(handle, tx.as_ptr(), tx.len(), hash.as_mut_ptr(), hash.len()) };
+        assert_eq!(code, PENUM_OK);
+        assert_eq!(hash[..PENUM_TX_HASH_LEN], transaction_hash(&tx));
+        assert_eq!(hash[PENUM_TX_HASH_LEN..], [0xaa; 8]);
+
+        let mut again = [0; PENUM_TX_HASH_LEN];
+        let code = unsafe { penum_ingress_submit(handle, tx.as_ptr(), tx.len(), again.as_mut_ptr(), again.len()) };
+        assert_eq!(code, PENUM_ERR_DUPLICATE);
+        assert_eq!(again, [0; PENUM_TX_HASH_LEN]);
+
+        // Freeing flushes the pending transaction and releases the runtime; null is ignored
+        unsafe { penum_ingress_free(handle) };
+        unsafe { penum_ingress_free(std::ptr::null_mut()) };
+    }
+
+    #[test]
+    fn test_foreign_caller_gets_error_codes() {
+        let mut handle: *mut PenumIngressHandle = std::ptr::null_mut();
+        assert_eq!(unsafe { penum_ingress_new(c"batch_sise = 10".as_ptr(), &mut handle) }, PENUM_ERR_INVALID_CONFIG);
+        assert!(handle.is_null());
+        assert_eq!(unsafe { penum_ingress_new(std::ptr::null(), std::ptr::null_mut()) }, PENUM_ERR_NULL_POINTER);
+
+        let handle = new_handle(c"");
+        let garbage = [0x02, 0xff, 0x00];
+        let mut hash = [0; PENUM_TX_HASH_LEN];
+        let submit = |tx: &[u8], hash: &mut [u8]| unsafe {
+            penum_ingress_submit(handle, tx.as_ptr(), tx.len(), hash.as_mut_ptr(), hash.len())
+        };
+        assert_eq!(submit(&garbage, &mut hash), PENUM_ERR_INVALID_TRANSACTION);
+        assert_eq!(submit(&dynamic_fee_tx(0), &mut hash[..31]), PENUM_ERR_BUFFER_TOO_SMALL);
+        assert_eq!(
+            unsafe { penum_ingress_submit(handle, std::ptr::null(), 0, hash.as_mut_ptr(), hash.len()) },
+            PENUM_ERR_NULL_POINTER
+        );
+        assert_eq!(hash, [0; PENUM_TX_HASH_LEN]);
+        unsafe { penum_ingress_free(handle) };
+
+        let message = unsafe { CStr::from_ptr(penum_ingress_error_message(PENUM_ERR_BUFFER_TOO_SMALL)) };
+        assert_eq!(message, c"hash buffer is smaller than 32 bytes");
+        assert!(!penum_ingress_error_message(42).is_null());
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, SystemTime};
+
+use crate::error::IngressError;
+
+// How long the outcome of a submission is remembered under its idempotency key
+pub const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(600);
+
+// Outcome of a keyed submission, with a digest of the bytes it was made with
+struct Entry {
+    fingerprint: Vec<u8>,
+    result: Result<[u8; 32], IngressError>,
+}
+
+// Outcomes of recent submissions by client-chosen idempotency key, each remembered for a fixed TTL
+//
+// Unlike the batching engine's content dedup, a repeat gets the first outcome back, hash or error,
+// rather than a Duplicate error.
+pub(crate) struct IdempotencyCache {
+    ttl: Duration,
+    entries: HashMap<String, Entry>,
+    expiry_order: VecDeque<(SystemTime, String)>, // (first submitted, key), oldest first
+}
+
+impl IdempotencyCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: HashMap::new(),
+            expiry_order: VecDeque::new(),
+        }
+    }
+
+    // Outcome recorded for the key within the TTL, or a conflict if it was used for other bytes
+    pub(crate) fn get(
+        &mut self,
+        key: &str,
+        fingerprint: &[u8],
+        now: SystemTime,
+    ) -> Option<Result<[u8; 32], IngressError>> {
+        self.prune(now);
+
+        let entry = self.entries.get(key)?;
+        if entry.fingerprint != fingerprint {
+            return Some(Err(IngressError::IdempotencyKeyConflict(key.to_string())));
+        }
+        Some(entry.result.clone())
+    }
+
+    // Records the outcome of the first submission under the key
+    pub(crate) fn insert(
+        &mut self,
+        key: String,
+        fingerprint: Vec<u8>,
+        result: Result<[u8; 32], IngressError>,
+        now: SystemTime,
+    ) {
+        self.prune(now);
+
+        if self.entries.insert(key.clone(), Entry { fingerprint, result }).is_none() {
+            self.expiry_order.push_back((now, key));
+        }
+    }
+
+    // Drops every key whose TTL has elapsed so memory stays bounded by the window
+    fn prune(&mut self, now: SystemTime) {
+        while let Some((submitted_at, _)) = self.expiry_order.front() {
+            let age = now.duration_since(*submitted_at).unwrap_or_default();
+            if age < self.ttl {
+                break;
+            }
+            if let Some((_, key)) = self.expiry_order.pop_front() {
+                self.entries.remove(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcomes_expire_after_ttl() {
+        let mut cache = IdempotencyCache::new(Duration::from_secs(10));
+        let start = SystemTime::now();
+
+        cache.insert("a".to_string(), vec![1], Ok([7; 32]), start);
+        cache.insert("b".to_string(), vec![2], Err(IngressError::InvalidSignature), start + Duration::from_secs(5));
+        assert_eq!(cache.get("a", &[1], start + Duration::from_secs(9)), Some(Ok([7; 32])));
+        assert_eq!(
+            cache.get("a", &[9], start + Duration::from_secs(9)),
+            Some(Err(IngressError::IdempotencyKeyConflict("a".to_string())))
+        );
+
+        // The first key expires, the second is still inside its window
+        assert_eq!(cache.get("a", &[1], start + Duration::from_secs(10)), None);
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.get("b", &[2], start + Duration::from_secs(14)), Some(Err(IngressError::InvalidSignature)));
+        assert_eq!(cache.get("b", &[2], start + Duration::from_secs(15)), None);
+    }
+}
@@ -0,0 +1,2331 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::anchor::CommitmentAnchor;
+use crate::anonymity::{k_anonymity, QuasiIdentifier};
+use crate::batch::{BatchIdMode, TransactionBatch};
+use crate::batching::{distinct_senders, BatchOrdering, BatchingEngine, PendingSnapshot, WindowMode};
+use crate::chain::ChainState;
+use crate::circuit_breaker::BreakerPolicy;
+use crate::clock::Clock;
+use crate::commit_reveal::CommitRevealPipeline;
+use crate::compression::Compression;
+use crate::crypto::{sha256_hash, Commitment, HashAlgo, ShuffleSecret};
+use crate::encryption::KeyShare;
+use crate::envelope::{PrivacyHints, TransactionConditions, TransactionEnvelope};
+use crate::error::{lock, IngressError};
+use crate::idempotency::{IdempotencyCache, DEFAULT_IDEMPOTENCY_TTL};
+use crate::jitter::{sample_release_jitter, JitterDistribution};
+use crate::metrics::MetricsCollector;
+use crate::operator::OperatorKey;
+use crate::ordering::OrderingPolicy;
+use crate::rate_limit::{RateLimitKey, RateLimiter};
+use crate::registry::{BatchRegistry, TxStatus};
+use crate::relay::{RelayForwarder, RelayResult, RetryPolicy};
+use crate::routing::RelayRouter;
+use crate::transaction::{
+    blob_sidecar_len, recover_sender, transaction_chain_id, transaction_hash, transaction_nonce, validate_transaction,
+    TxType,
+};
+use crate::wal::WriteAheadLog;
+use tokio::sync::{broadcast, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, info_span, warn, Instrument, Span};
+
+// How often the background task checks the time window and pending reveals
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Largest accepted raw transaction, matching the usual relay and txpool limit
+pub const DEFAULT_MAX_TX_BYTES: usize = 128 * 1024;
+
+// Batches a subscriber may fall behind by before it starts missing them
+pub const DEFAULT_BATCH_CHANNEL_CAPACITY: usize = 64;
+
+// A committed batch with its jittered release time and the span that follows it to the relays
+type HeldBatch = (TransactionBatch, SystemTime, Span);
+
+// Receipt for a batch committed with PenumIngress::commit, redeemed with reveal
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitHandle {
+    pub batch_id: String,
+    pub commitment: Commitment,      // Root the reveal is checked against; over ciphertexts when encrypted
+    pub anchor_tx: Option<[u8; 32]>, // Anchoring transaction, when a commitment anchor is set
+}
+
+// Main ingress service; clones share the same state, so one can be handed to spawn()
+#[derive(Clone)]
+pub struct PenumIngress {
+    batching_engine: Arc<BatchingEngine>,
+    commit_reveal_pipeline: Arc<CommitRevealPipeline>,
+    relay_forwarder: Arc<RelayForwarder>,
+    relay_router: Option<Arc<RelayRouter>>, // Splits batches between relay groups instead of using relay_forwarder
+    metrics_collector: Arc<MetricsCollector>,
+    registry: Arc<BatchRegistry>,
+    awaiting_reveal: Arc<Mutex<HashMap<String, HeldBatch>>>, // Committed batches waiting for reveal
+    quorum_retries: Arc<Mutex<Vec<(TransactionBatch, Span)>>>, // Batches that missed quorum, forwarded once more next cycle
+    min_relay_quorum: usize,
+    requeue_below_quorum: bool,
+    max_release_jitter: Duration,
+    jitter_distribution: JitterDistribution,
+    encryption: Option<(u8, u8)>,                            // (threshold, share count) for batch encryption
+    key_shares: Arc<Mutex<HashMap<String, Vec<KeyShare>>>>, // Key shares of encrypted batches awaiting reveal
+    poll_interval: Duration,
+    max_tx_bytes: usize,
+    min_anonymity_set: usize, // Batches with fewer distinct senders log a warning
+    max_acceptable_latency: Option<Duration>, // Batches forwarded slower than this log a warning
+    quasi_identifier: QuasiIdentifier,        // Attribute the k-anonymity of forwarded batches is measured over
+    rate_limiter: Option<Arc<Mutex<RateLimiter>>>, // Per-client token buckets, shared by clones
+    idempotency: Arc<Mutex<IdempotencyCache>>,     // Outcomes of keyed submissions, shared by clones
+    chain_state: Option<Arc<ChainState>>,          // Drops already-mined transactions before forwarding
+    operator_key: Option<Arc<OperatorKey>>,        // Signs every commitment so relays can authenticate batches
+    chain_id: Option<u64>,                         // Network submissions must be bound to, unchecked if None
+    allow_unprotected: bool,                       // Accept pre-EIP-155 transactions, which carry no chain ID
+    allow_blob_txs: bool,                          // Accept EIP-4844 blob transactions
+    batch_events: broadcast::Sender<Arc<TransactionBatch>>, // Every committed batch, for subscribe_batches
+    pool_space: Arc<Notify>, // Woken whenever a batch leaves the pending pools, for submit_transaction_async
+    shutdown: CancellationToken,
+}
+
+impl PenumIngress {
+    pub fn new(
+        max_batch_size: usize,
+        batch_time_window: Duration,
+        relay_urls: Vec<String>,
+    ) -> Self {
+        Self {
+            batching_engine: Arc::new(BatchingEngine::new(max_batch_size, batch_time_window)),
+            commit_reveal_pipeline: Arc::new(CommitRevealPipeline::new()),
+            relay_forwarder: Arc::new(RelayForwarder::new(relay_urls)),
+            relay_router: None,
+            metrics_collector: Arc::new(MetricsCollector::new()),
+            registry: Arc::new(BatchRegistry::new()),
+            awaiting_reveal: Arc::new(Mutex::new(HashMap::new())),
+            quorum_retries: Arc::new(Mutex::new(Vec::new())),
+            min_relay_quorum: 0,
+            requeue_below_quorum: false,
+            max_release_jitter: Duration::ZERO,
+            jitter_distribution: JitterDistribution::default(),
+            encryption: None,
+            key_shares: Arc::new(Mutex::new(HashMap::new())),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            max_tx_bytes: DEFAULT_MAX_TX_BYTES,
+            min_anonymity_set: 0,
+            max_acceptable_latency: None,
+            quasi_identifier: QuasiIdentifier::default(),
+            rate_limiter: None,
+            idempotency: Arc::new(Mutex::new(IdempotencyCache::new(DEFAULT_IDEMPOTENCY_TTL))),
+            chain_state: None,
+            operator_key: None,
+            chain_id: None,
+            allow_unprotected: false,
+            allow_blob_txs: true,
+            batch_events: broadcast::channel(DEFAULT_BATCH_CHANNEL_CAPACITY).0,
+            pool_space: Arc::new(Notify::new()),
+            shutdown: CancellationToken::new(),
+        }
+    }
+
+    // Reads time from the given clock for batching windows, commitments, reveal delays, release jitter and
+    // status retention
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.batching_engine = Arc::new((*self.batching_engine).clone().with_clock(clock.clone()));
+        self.commit_reveal_pipeline = Arc::new((*self.commit_reveal_pipeline).clone().with_clock(clock.clone()));
+        self.registry = Arc::new((*self.registry).clone().with_clock(clock));
+        self
+    }
+
+    // Holds committed batches back from relays until the reveal delay has elapsed
+    pub fn with_reveal_delay(mut self, reveal_delay: Duration) -> Self {
+        self.commit_reveal_pipeline = Arc::new((*self.commit_reveal_pipeline).clone().with_reveal_delay(reveal_delay));
+        self
+    }
+
+    // Publishes every batch commitment through the anchor before the batch is held for reveal
+    pub fn with_commitment_anchor(mut self, anchor: Arc<dyn CommitmentAnchor>) -> Self {
+        self.commit_reveal_pipeline = Arc::new((*self.commit_reveal_pipeline).clone().with_anchor(anchor));
+        self
+    }
+
+    // Requires at least this many relays to accept a batch before it counts as submitted
+    pub fn with_min_relay_quorum(mut self, min_relay_quorum: usize) -> Self {
+        self.min_relay_quorum = min_relay_quorum;
+        self
+    }
+
+    // Gives a batch that missed the relay quorum one more forwarding attempt on the next cycle
+    pub fn with_requeue_below_quorum(mut self, requeue_below_quorum: bool) -> Self {
+        self.requeue_below_quorum = requeue_below_quorum;
+        self
+    }
+
+    // Delays each batch's release by a random amount up to max_release_jitter, so forwarding
+    // no longer lines up with the batching window
+    //
+    // The jitter must be shorter than the reveal delay plus the reveal window, or a batch could be held
+    // past its commitment's expiry; set the reveal delay first.
+    pub fn with_release_jitter(
+        mut self,
+        max_release_jitter: Duration,
+        distribution: JitterDistribution,
+    ) -> Result<Self, IngressError> {
+        let lifetime = self.commit_reveal_pipeline.lifetime();
+        if max_release_jitter >= lifetime {
+            return Err(IngressError::Config(format!(
+                "max release jitter {:?} must be shorter than the reveal delay plus reveal window {:?}",
+                max_release_jitter, lifetime
+            )));
+        }
+        self.max_release_jitter = max_release_jitter;
+        self.jitter_distribution = distribution;
+        self.metrics_collector = Arc::new((*self.metrics_collector).clone().with_max_release_jitter(max_release_jitter));
+        Ok(self)
+    }
+
+    // Holds committed batches encrypted, splitting each batch key into share_count shares of which
+    // threshold are needed to decrypt; decryption only happens once the reveal has been verified
+    pub fn with_envelope_encryption(mut self, threshold: u8, share_count: u8) -> Self {
+        self.encryption = Some((threshold, share_count));
+        self.batching_engine =
+            Arc::new((*self.batching_engine).clone().with_envelope_encryption(threshold, share_count));
+        self
+    }
+
+    // Replaces the forwarder built from relay_urls, e.g. with weighted top-N selection or custom transports
+    pub fn with_relay_forwarder(mut self, relay_forwarder: RelayForwarder) -> Self {
+        self.relay_forwarder = Arc::new(relay_forwarder);
+        self
+    }
+
+    // Splits every batch between relay groups, each judged on its own quorum, instead of sending it whole
+    // to one forwarder
+    //
+    // Each group's forwarder keeps its own transports and timeout, so with_compression, with_retry_policy,
+    // with_relay_request_timeout, with_max_payload_bytes and with_circuit_breaker do not reach it; dry-run mode
+    // does. min_relay_quorum and requeue_below_quorum only apply without groups, since a resend would repeat the
+    // parts that got through.
+    pub fn with_relay_router(mut self, relay_router: RelayRouter) -> Self {
+        let dry_run = self.relay_forwarder.is_dry_run();
+        self.relay_router = Some(Arc::new(if dry_run { relay_router.with_dry_run(true) } else { relay_router }));
+        self
+    }
+
+    // Runs the whole pipeline but never sends batches to the relays, which are reported as accepting
+    //
+    // Batches are still committed, broadcast to subscribe_batches and counted in the batch, latency
+    // and anonymity metrics; per-relay metrics stay empty. Anchoring and the chain RPC still make
+    // their own calls.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.relay_forwarder = Arc::new((*self.relay_forwarder).clone().with_dry_run(dry_run));
+        self.relay_router = self.relay_router.map(|router| Arc::new((*router).clone().with_dry_run(dry_run)));
+        self
+    }
+
+    // Compresses batch bodies sent to HTTP relays; relays refusing the encoding get them uncompressed
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.relay_forwarder = Arc::new((*self.relay_forwarder).clone().with_compression(compression));
+        self
+    }
+
+    // Sets how often and how patiently transient relay failures are retried
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.relay_forwarder = Arc::new((*self.relay_forwarder).clone().with_retry_policy(retry_policy));
+        self
+    }
+
+    // Skips a relay for a cool-off once it fails enough batches in a row, then probes it with one batch
+    pub fn with_circuit_breaker(mut self, policy: BreakerPolicy) -> Self {
+        self.relay_forwarder = Arc::new((*self.relay_forwarder).clone().with_circuit_breaker(policy));
+        self
+    }
+
+    // Reports a relay as timed out once it has spent this long on a batch, retries included
+    pub fn with_relay_request_timeout(mut self, relay_request_timeout: Duration) -> Self {
+        self.relay_forwarder = Arc::new((*self.relay_forwarder).clone().with_request_timeout(relay_request_timeout));
+        self
+    }
+
+    // Sends batches too large for relays' request limits in consecutive parts under max_payload_bytes
+    pub fn with_max_payload_bytes(mut self, max_payload_bytes: usize) -> Self {
+        self.relay_forwarder = Arc::new((*self.relay_forwarder).clone().with_max_payload_bytes(max_payload_bytes));
+        self
+    }
+
+    // Bounds how many recent samples each metrics series keeps
+    pub fn with_metrics_window(mut self, sample_window: usize) -> Self {
+        let metrics_collector = MetricsCollector::with_sample_window(sample_window);
+        self.metrics_collector = Arc::new(metrics_collector.with_max_release_jitter(self.max_release_jitter));
+        self
+    }
+
+    // Rejects transactions bound to any other chain; pre-EIP-155 transactions are replayable on every
+    // chain and only accepted with allow_unprotected
+    pub fn with_chain_id(mut self, chain_id: u64, allow_unprotected: bool) -> Self {
+        self.chain_id = Some(chain_id);
+        self.allow_unprotected = allow_unprotected;
+        self
+    }
+
+    // Signs each batch commitment with the operator key; relays check it with verify_operator_signature
+    pub fn with_operator_key(mut self, operator_key: OperatorKey) -> Self {
+        self.operator_key = Some(Arc::new(operator_key));
+        self
+    }
+
+    // Selects the hash function batches are committed with
+    pub fn with_hash_algo(mut self, hash_algo: HashAlgo) -> Self {
+        self.batching_engine = Arc::new((*self.batching_engine).clone().with_hash_algo(hash_algo));
+        self
+    }
+
+    // Holds time-triggered batches until min_batch_size transactions are pending or max_wait has passed
+    pub fn with_min_batch_size(mut self, min_batch_size: usize, max_wait: Duration) -> Self {
+        self.batching_engine =
+            Arc::new((*self.batching_engine).clone().with_min_batch_size(min_batch_size, max_wait));
+        self
+    }
+
+    // Force-batches transactions that have been pending this long, even if the windows are never polled
+    pub fn with_max_pending_age(mut self, max_pending_age: Duration) -> Self {
+        self.batching_engine = Arc::new((*self.batching_engine).clone().with_max_pending_age(max_pending_age));
+        self
+    }
+
+    // Holds back a sender's transactions past a nonce gap until the gap is filled, for at most timeout
+    pub fn with_nonce_gap_hold(mut self, timeout: Duration) -> Self {
+        self.batching_engine = Arc::new((*self.batching_engine).clone().with_nonce_gap_hold(timeout));
+        self
+    }
+
+    // Adds a named lane with its own size threshold and time window, e.g. for urgent transactions
+    pub fn with_lane(mut self, name: impl Into<String>, max_batch_size: usize, batch_time_window: Duration) -> Self {
+        self.batching_engine =
+            Arc::new((*self.batching_engine).clone().with_lane(name, max_batch_size, batch_time_window));
+        self
+    }
+
+    // Mixes an operator-held secret into every batch's shuffle seed, so the batch ID alone no longer
+    // reveals the order; see BatchingEngine::with_shuffle_secret
+    pub fn with_shuffle_secret(mut self, secret: ShuffleSecret) -> Self {
+        self.batching_engine = Arc::new((*self.batching_engine).clone().with_shuffle_secret(secret));
+        self
+    }
+
+    // Orders batch contents with one of the built-in orderings, the privacy shuffle unless set
+    pub fn with_batch_ordering(mut self, ordering: BatchOrdering) -> Self {
+        self.batching_engine = Arc::new((*self.batching_engine).clone().with_ordering(ordering));
+        self
+    }
+
+    // Orders batch contents with a custom policy
+    pub fn with_ordering_policy(mut self, ordering: Arc<dyn OrderingPolicy>) -> Self {
+        self.batching_engine = Arc::new((*self.batching_engine).clone().with_ordering_policy(ordering));
+        self
+    }
+
+    // Draws each window from an exponential distribution around the lane's time window in Poisson mode
+    pub fn with_window_mode(mut self, window_mode: WindowMode) -> Self {
+        self.batching_engine = Arc::new((*self.batching_engine).clone().with_window_mode(window_mode));
+        self
+    }
+
+    // Derives batch IDs, and so shuffle seeds, from batch contents instead of random UUIDs
+    pub fn with_batch_id_mode(mut self, id_mode: BatchIdMode) -> Self {
+        self.batching_engine = Arc::new((*self.batching_engine).clone().with_batch_id_mode(id_mode));
+        self
+    }
+
+    // Pads batches with decoy transactions up to decoy_target_size; decoys are committed but never forwarded
+    pub fn with_decoy_target_size(mut self, decoy_target_size: usize) -> Self {
+        self.batching_engine = Arc::new((*self.batching_engine).clone().with_decoy_target_size(decoy_target_size));
+        self
+    }
+
+    // Limits each client, or each sender when no client ID is given, to burst submissions at once
+    // and rate submissions per second after that
+    pub fn with_rate_limit(mut self, rate: f64, burst: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(Mutex::new(RateLimiter::new(rate, burst))));
+        self
+    }
+
+    // Answers status_of for a transaction until its status has been final for retention instead of ten minutes
+    pub fn with_status_retention(mut self, retention: Duration) -> Self {
+        self.registry = Arc::new((*self.registry).clone().with_retention(retention));
+        self
+    }
+
+    // Remembers the outcome of each keyed submission for ttl instead of ten minutes
+    pub fn with_idempotency_ttl(mut self, ttl: Duration) -> Self {
+        self.idempotency = Arc::new(Mutex::new(IdempotencyCache::new(ttl)));
+        self
+    }
+
+    // Checks released transactions against account nonces from this node and drops the ones already mined,
+    // and drops pending and released transactions whose inclusion deadline its head has reached
+    pub fn with_chain_rpc(mut self, rpc_url: String) -> Self {
+        self.chain_state = Some(Arc::new(ChainState::new(rpc_url)));
+        self
+    }
+
+    // Warns about every forwarded batch whose transactions come from fewer than this many distinct senders
+    pub fn with_min_anonymity_set(mut self, min_anonymity_set: usize) -> Self {
+        self.min_anonymity_set = min_anonymity_set;
+        self
+    }
+
+    // Keeps transactions pending until a batch would come from at least this many distinct senders, so no
+    // batch is forwarded below the floor; see BatchingEngine::with_min_distinct_senders
+    pub fn with_min_distinct_senders(mut self, min_distinct_senders: usize) -> Self {
+        self.batching_engine =
+            Arc::new((*self.batching_engine).clone().with_min_distinct_senders(min_distinct_senders));
+        self
+    }
+
+    // Measures the k-anonymity of forwarded batches over this attribute instead of the destination
+    pub fn with_quasi_identifier(mut self, quasi_identifier: QuasiIdentifier) -> Self {
+        self.quasi_identifier = quasi_identifier;
+        self
+    }
+
+    // Warns and counts a latency breach whenever forwarding a batch to the relays takes longer than this
+    pub fn with_max_acceptable_latency(mut self, max_acceptable_latency: Duration) -> Self {
+        self.max_acceptable_latency = Some(max_acceptable_latency);
+        self
+    }
+
+    // Rejects raw transactions larger than max_tx_bytes, not counting a blob transaction's sidecar
+    pub fn with_max_tx_bytes(mut self, max_tx_bytes: usize) -> Self {
+        self.max_tx_bytes = max_tx_bytes;
+        self
+    }
+
+    // Caps the combined size of transactions waiting for a batch, blob sidecars aside
+    pub fn with_max_pending_bytes(mut self, max_pending_bytes: usize) -> Self {
+        self.batching_engine = Arc::new((*self.batching_engine).clone().with_max_pending_bytes(max_pending_bytes));
+        self
+    }
+
+    // Caps the combined size of the blob sidecars waiting for a batch
+    pub fn with_max_pending_blob_bytes(mut self, max_pending_blob_bytes: usize) -> Self {
+        self.batching_engine =
+            Arc::new((*self.batching_engine).clone().with_max_pending_blob_bytes(max_pending_blob_bytes));
+        self
+    }
+
+    // Accepts or rejects EIP-4844 blob transactions; accepted by default
+    pub fn with_allow_blob_txs(mut self, allow_blob_txs: bool) -> Self {
+        self.allow_blob_txs = allow_blob_txs;
+        self
+    }
+
+    // Persists pending transactions to a write-ahead log at wal_path, recovering any left by a previous run
+    pub fn with_wal_path(mut self, wal_path: impl Into<PathBuf>) -> Result<Self, IngressError> {
+        let wal = WriteAheadLog::open(wal_path)?;
+        self.batching_engine = Arc::new((*self.batching_engine).clone().with_wal(wal)?);
+        Ok(self)
+    }
+
+    // Sets how often the background task started by spawn() polls for work
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn metrics(&self) -> &MetricsCollector {
+        &self.metrics_collector
+    }
+
+    // Current contents of the pending pools at a glance, without removing or reordering anything
+    pub fn pending_snapshot(&self) -> Result<PendingSnapshot, IngressError> {
+        self.batching_engine.pending_snapshot()
+    }
+
+    // Receives every batch from the moment it is committed, before it is revealed to the relays
+    //
+    // Batches are sent as committed: signed if an operator key is set, and still encrypted when
+    // envelope encryption is on. A subscriber that falls more than DEFAULT_BATCH_CHANNEL_CAPACITY
+    // batches behind gets RecvError::Lagged and skips ahead.
+    pub fn subscribe_batches(&self) -> broadcast::Receiver<Arc<TransactionBatch>> {
+        self.batch_events.subscribe()
+    }
+
+    // Where the transaction with this Keccak-256 hash is in the pipeline, if it was submitted here
+    pub fn status_of(&self, tx_hash: &[u8]) -> Option<TxStatus> {
+        self.registry.status_of(tx_hash).ok().flatten()
+    }
+
+    pub async fn submit_transaction(&self, tx_bytes: Vec<u8>) -> Result<[u8; 32], IngressError> {
+        self.submit(tx_bytes, None, None, PrivacyHints::default(), None, None).await
+    }
+
+    // Submits like submit_transaction, but waits up to timeout for room instead of failing on a full pending pool
+    //
+    // The submission is retried each time a batch leaves the pending pools, so producers that can
+    // tolerate waiting get backpressure rather than a hard rejection; the pool-full error is returned
+    // once timeout has passed. Every other outcome is returned as soon as it happens.
+    pub async fn submit_transaction_async(
+        &self,
+        tx_bytes: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<[u8; 32], IngressError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Registered before the attempt, so a batch cut between a failed attempt and the wait still wakes it
+            let space = self.pool_space.notified();
+            tokio::pin!(space);
+            space.as_mut().enable();
+
+            match self.submit(tx_bytes.clone(), None, None, PrivacyHints::default(), None, None).await {
+                Err(error @ (IngressError::PendingPoolFull { .. } | IngressError::BlobPoolFull { .. })) => {
+                    if tokio::time::timeout_at(deadline, space).await.is_err() {
+                        return Err(error);
+                    }
+                }
+                outcome => return outcome,
+            }
+        }
+    }
+
+    // Submits on behalf of an identified client, e.g. an API key, which the rate limit is charged to
+    pub async fn submit_transaction_from(&self, tx_bytes: Vec<u8>, client_id: &str) -> Result<[u8; 32], IngressError> {
+        self.submit(tx_bytes, None, Some(client_id), PrivacyHints::default(), None, None).await
+    }
+
+    // Submits to a named lane added with with_lane, batched independently of the default lane
+    pub async fn submit_transaction_to_lane(&self, tx_bytes: Vec<u8>, lane: &str) -> Result<[u8; 32], IngressError> {
+        self.submit(tx_bytes, Some(lane), None, PrivacyHints::default(), None, None).await
+    }
+
+    // Submits with the fields a MEV-Share relay may show searchers; the other entry points share nothing
+    pub async fn submit_transaction_with_hints(
+        &self,
+        tx_bytes: Vec<u8>,
+        hints: PrivacyHints,
+    ) -> Result<[u8; 32], IngressError> {
+        self.submit(tx_bytes, None, None, hints, None, None).await
+    }
+
+    // Submits with an inclusion deadline: once the chain head reaches valid_until_block the transaction
+    // is dropped wherever it is still waiting and marked Expired
+    //
+    // Deadlines are only enforced with a chain RPC set through with_chain_rpc. Bundle relays target
+    // no block past the batch's earliest deadline.
+    pub async fn submit_transaction_with_expiry(
+        &self,
+        tx_bytes: Vec<u8>,
+        valid_until_block: u64,
+    ) -> Result<[u8; 32], IngressError> {
+        self.submit(tx_bytes, None, None, PrivacyHints::default(), Some(valid_until_block), None).await
+    }
+
+    // Submits with the chain state its inclusion depends on, for relays taking
+    // eth_sendRawTransactionConditional; other relays get the transaction without it
+    //
+    // block_number_max doubles as the inclusion deadline, so the transaction is dropped as it would
+    // be after submit_transaction_with_expiry once it can no longer land.
+    pub async fn submit_transaction_with_conditions(
+        &self,
+        tx_bytes: Vec<u8>,
+        conditions: TransactionConditions,
+    ) -> Result<[u8; 32], IngressError> {
+        if let (Some(min), Some(max)) = (conditions.block_number_min, conditions.block_number_max)
+            && min > max
+        {
+            let error = IngressError::InvalidTransaction(format!("block number range {}..={} is empty", min, max));
+            self.metrics_collector.record_rejection(&error);
+            return Err(error);
+        }
+        let valid_until_block = conditions.block_number_max;
+        self.submit(tx_bytes, None, None, PrivacyHints::default(), valid_until_block, Some(conditions)).await
+    }
+
+    // Submits under a client-chosen idempotency key, so a retry after a lost response cannot enqueue twice
+    //
+    // A repeat of the key within its TTL returns the first call's outcome, the hash or the rejection,
+    // without touching the pending pool; reusing it for other bytes fails with IdempotencyKeyConflict.
+    // Rate limiting and a full pending pool say nothing about the transaction and are not remembered,
+    // and neither is a failure to forward the batch this submission cut, since the transaction was
+    // accepted either way.
+    pub async fn submit_transaction_idempotent(
+        &self,
+        tx_bytes: Vec<u8>,
+        idempotency_key: &str,
+    ) -> Result<[u8; 32], IngressError> {
+        let fingerprint = sha256_hash(&tx_bytes);
+        let (tx_hash, batch) = {
+            // Held until the outcome is recorded, so concurrent retries of one key enqueue at most once
+            let mut cache = lock(&self.idempotency)?;
+            let now = self.batching_engine.now();
+            if let Some(result) = cache.get(idempotency_key, &fingerprint, now) {
+                return result;
+            }
+
+            // Only answers a retry would get again are remembered, never a full pool or an internal failure
+            let enqueued = self.enqueue(tx_bytes, None, None, PrivacyHints::default(), None, None);
+            match &enqueued {
+                Ok((tx_hash, _)) => cache.insert(idempotency_key.to_string(), fingerprint, Ok(*tx_hash), now),
+                Err(error) if error.is_validation_failure() => {
+                    cache.insert(idempotency_key.to_string(), fingerprint, Err(error.clone()), now)
+                }
+                Err(_) => {}
+            }
+            enqueued?
+        };
+
+        if let Some(batch) = batch {
+            self.process_batch(batch).await?;
+        }
+        Ok(tx_hash)
+    }
+
+    // Submits many transactions at once, e.g. from an aggregator, returning a result per transaction in order
+    //
+    // Every transaction is checked as submit_transaction would, then all accepted ones enter the
+    // default lane together. An error from forwarding a batch this cut is
+    // reported for the transaction that completed it.
+    pub async fn submit_transactions(&self, txs: Vec<Vec<u8>>) -> Vec<Result<[u8; 32], IngressError>> {
+        let mut results: Vec<Result<[u8; 32], IngressError>> = Vec::with_capacity(txs.len());
+        let mut admitted = Vec::new(); // (result index, tx hash) of every transaction that passed the checks
+        let mut envelopes = Vec::new();
+        for tx_bytes in txs {
+            match self.prepare(tx_bytes, None) {
+                Ok((tx_hash, envelope)) => {
+                    admitted.push((results.len(), tx_hash));
+                    envelopes.push(envelope);
+                    results.push(Ok(tx_hash));
+                }
+                Err(error) => {
+                    self.metrics_collector.record_rejection(&error);
+                    results.push(Err(error));
+                }
+            }
+        }
+
+        let mut batches = Vec::new();
+        for ((index, tx_hash), outcome) in admitted.into_iter().zip(self.batching_engine.add_transactions(envelopes)) {
+            let outcome = outcome.inspect_err(|error| self.metrics_collector.record_rejection(error));
+            match outcome.and_then(|batch| self.registry.record_pending(tx_hash).map(|()| batch)) {
+                Ok(batch) => batches.extend(batch.map(|batch| (index, batch))),
+                Err(error) => results[index] = Err(error),
+            }
+        }
+        for (index, batch) in batches {
+            if let Err(error) = self.process_batch(batch).await {
+                results[index] = Err(error);
+            }
+        }
+        results
+    }
+
+    async fn submit(
+        &self,
+        tx_bytes: Vec<u8>,
+        lane: Option<&str>,
+        client_id: Option<&str>,
+        hints: PrivacyHints,
+        valid_until_block: Option<u64>,
+        conditions: Option<TransactionConditions>,
+    ) -> Result<[u8; 32], IngressError> {
+        let (tx_hash, batch) = self.enqueue(tx_bytes, lane, client_id, hints, valid_until_block, conditions)?;
+
+        // Forward right away if the size threshold was hit
+        if let Some(batch) = batch {
+            self.process_batch(batch).await?;
+        }
+
+        // Callers track their transaction by its standard hash
+        Ok(tx_hash)
+    }
+
+    // Checks a submission and adds it to its lane, returning its hash and the batch it completed, if any
+    fn enqueue(
+        &self,
+        tx_bytes: Vec<u8>,
+        lane: Option<&str>,
+        client_id: Option<&str>,
+        hints: PrivacyHints,
+        valid_until_block: Option<u64>,
+        conditions: Option<TransactionConditions>,
+    ) -> Result<([u8; 32], Option<TransactionBatch>), IngressError> {
+        let rejected = |error: &IngressError| self.metrics_collector.record_rejection(error);
+        let (tx_hash, mut envelope) = self.prepare(tx_bytes, client_id).inspect_err(rejected)?;
+        envelope.privacy_hints = hints;
+        envelope.valid_until_block = valid_until_block;
+        envelope.conditions = conditions;
+
+        // Add to batching engine
+        let batch = match lane {
+            Some(lane) => self.batching_engine.add_transaction_to_lane(envelope, lane),
+            None => self.batching_engine.add_transaction(envelope),
+        }
+        .inspect_err(rejected)?;
+        self.registry.record_pending(tx_hash)?;
+        Ok((tx_hash, batch))
+    }
+
+    // Checks a submission and wraps it in an envelope carrying its sender and nonce
+    fn prepare(&self, tx_bytes: Vec<u8>, client_id: Option<&str>) -> Result<([u8; 32], TransactionEnvelope), IngressError> {
+        // Validate that this is a properly formatted Ethereum transaction
+        if tx_bytes.is_empty() {
+            return Err(IngressError::EmptyTransaction);
+        }
+        // Blob sidecars are budgeted by the pending pool instead; they are only decoded for oversized submissions
+        if tx_bytes.len() > self.max_tx_bytes {
+            let size = tx_bytes.len() - blob_sidecar_len(&tx_bytes);
+            if size > self.max_tx_bytes {
+                return Err(IngressError::TxTooLarge { size, max_tx_bytes: self.max_tx_bytes });
+            }
+        }
+        if !self.allow_blob_txs && validate_transaction(&tx_bytes)? == TxType::Blob {
+            return Err(IngressError::BlobTransactionsDisabled);
+        }
+
+        if let Some(expected) = self.chain_id {
+            match transaction_chain_id(&tx_bytes)? {
+                Some(found) if found == expected => {}
+                None if self.allow_unprotected => {}
+                found => return Err(IngressError::WrongChainId { expected, found }),
+            }
+        }
+
+        // Identified clients are throttled before any signature work is spent on them
+        if let Some(client_id) = client_id {
+            self.check_rate_limit(RateLimitKey::Client(client_id.to_string()))?;
+        }
+
+        // Only relay transactions carrying a valid signature
+        let sender = recover_sender(&tx_bytes)?;
+        if client_id.is_none() {
+            self.check_rate_limit(RateLimitKey::Sender(sender))?;
+        }
+
+        // Create envelope
+        let tx_hash = transaction_hash(&tx_bytes);
+        let batch_id = uuid::Uuid::new_v4().to_string();
+        let mut envelope = TransactionEnvelope::new(tx_bytes, batch_id);
+        envelope.sender = Some(sender);
+        envelope.nonce = Some(transaction_nonce(&envelope.tx_bytes)?);
+        Ok((tx_hash, envelope))
+    }
+
+    fn check_rate_limit(&self, key: RateLimitKey) -> Result<(), IngressError> {
+        match &self.rate_limiter {
+            Some(limiter) if !lock(limiter)?.try_acquire(key, Instant::now()) => Err(IngressError::RateLimited),
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn process_batches(&self) -> Result<(), IngressError> {
+        self.expire_pending().await?;
+
+        // Check if any lane's time window has passed and create batches if needed
+        let mut batches: Vec<TransactionBatch> = self.batching_engine.check_time_window()?.into_iter().collect();
+        batches.extend(self.batching_engine.check_lane_windows()?);
+        if batches.is_empty() {
+            return self.reveal_ready_batches().await;
+        }
+        self.process_all(batches).await
+    }
+
+    // Drops pending transactions whose inclusion deadline the chain head has reached, marking them Expired
+    //
+    // The head is only fetched while some transaction carries a deadline. An unavailable node keeps
+    // everything pending, and relays still get no bundle past a batch's deadline.
+    async fn expire_pending(&self) -> Result<(), IngressError> {
+        let Some(chain_state) = &self.chain_state else {
+            return Ok(());
+        };
+        if !self.batching_engine.has_deadlines()? {
+            return Ok(());
+        }
+        let head = match chain_state.head_block().await {
+            Ok(head) => head,
+            Err(error) => {
+                warn!(%error, "head block unavailable, not expiring pending transactions");
+                return Ok(());
+            }
+        };
+
+        let expired = self.batching_engine.drop_expired(head)?;
+        if !expired.is_empty() {
+            for tx in &expired {
+                self.registry.record_expired(transaction_hash(&tx.tx_bytes))?;
+            }
+            info!(expired = expired.len(), head, "dropped expired transactions");
+            self.pool_space.notify_waiters();
+        }
+        Ok(())
+    }
+
+    // Processes every batch even if an earlier one fails; the first error is returned
+    async fn process_all(&self, batches: Vec<TransactionBatch>) -> Result<(), IngressError> {
+        let mut first_error = None;
+        for batch in batches {
+            if let Err(error) = self.process_batch(batch).await {
+                first_error.get_or_insert(error);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    // Runs batch processing on a tokio task until shutdown() is called, then flushes what is still pending
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.poll_interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                tokio::select! {
+                    _ = self.shutdown.cancelled() => break,
+                    _ = interval.tick() => {
+                        if let Err(error) = self.process_batches().await {
+                            error!(%error, "background batch processing failed");
+                        }
+                    }
+                }
+            }
+
+            if let Err(error) = self.drain().await {
+                error!(%error, "final batch flush failed");
+            }
+        })
+    }
+
+    // Signals the task started by spawn() to flush pending transactions and exit
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    // Forwards everything still pending, waiting out the reveal delay of batches already committed
+    pub(crate) async fn drain(&self) -> Result<(), IngressError> {
+        let mut batches: Vec<TransactionBatch> = self.batching_engine.flush()?.into_iter().collect();
+        batches.extend(self.batching_engine.flush_lanes()?);
+        let mut result = self.process_all(batches).await;
+
+        while !lock(&self.awaiting_reveal)?.is_empty() || !lock(&self.quorum_retries)?.is_empty() {
+            tokio::time::sleep(self.poll_interval).await;
+            let cycle = self.reveal_ready_batches().await;
+            if result.is_ok() {
+                result = cycle;
+            }
+        }
+        result
+    }
+
+    // Commits the batch and releases every held batch that is ready, this one included once its delay allows
+    async fn process_batch(&self, batch: TransactionBatch) -> Result<(), IngressError> {
+        // The batch's transactions no longer count against the pending budgets
+        self.pool_space.notify_waiters();
+        if let Some(trigger) = batch.trigger {
+            self.metrics_collector.record_batch_trigger(trigger);
+        }
+        self.commit(batch).await?;
+        self.reveal_ready_batches().await
+    }
+
+    // Commits the batch and holds it until it is revealed, either by a later call to reveal or
+    // automatically once its reveal delay and release jitter have passed
+    pub async fn commit(&self, batch: TransactionBatch) -> Result<CommitHandle, IngressError> {
+        // One span follows the batch through commit, reveal and forwarding; it carries counts and IDs only
+        let span = info_span!("batch", batch_id = %batch.id, tx_count = batch.transactions.len());
+        self.commit_and_hold(batch, span.clone()).instrument(span).await
+    }
+
+    // Reveals and forwards a committed batch now, without waiting for its release jitter
+    //
+    // The reveal delay still applies; a batch revealed too early stays held and can be revealed again.
+    pub async fn reveal(&self, handle: CommitHandle) -> Result<(), IngressError> {
+        if !self.commit_reveal_pipeline.ready_to_reveal()?.contains(&handle.batch_id) {
+            let held = lock(&self.awaiting_reveal)?.contains_key(&handle.batch_id);
+            return Err(if held {
+                IngressError::RevealTooEarly(handle.batch_id)
+            } else {
+                IngressError::CommitmentNotFound(handle.batch_id)
+            });
+        }
+        let Some((batch, _, span)) = lock(&self.awaiting_reveal)?.remove(&handle.batch_id) else {
+            return Err(IngressError::CommitmentNotFound(handle.batch_id));
+        };
+        self.reveal_batch(batch, span.clone(), true).instrument(span).await
+    }
+
+    async fn commit_and_hold(&self, mut batch: TransactionBatch, span: Span) -> Result<CommitHandle, IngressError> {
+        // Encrypted batches commit to their ciphertexts; the engine seals the batches it cuts, and
+        // batches committed directly are sealed here, after their members are registered
+        if let Some((shares, members)) = self.batching_engine.take_sealed(&batch.id)? {
+            self.registry.record_members(&batch.id, members)?;
+            lock(&self.key_shares)?.insert(batch.id.clone(), shares);
+        } else {
+            self.registry.record_batched(&batch)?;
+            if let Some((threshold, share_count)) = self.encryption {
+                let entropy = self.batching_engine.entropy();
+                let shares = match batch.encrypt_batch_with_entropy(threshold, share_count, entropy) {
+                    Ok(shares) => shares,
+                    Err(error) => {
+                        self.registry.update_batch(&batch.id, TxStatus::Failed)?;
+                        return Err(error);
+                    }
+                };
+                lock(&self.key_shares)?.insert(batch.id.clone(), shares);
+            }
+        }
+
+        // Commit the batch first (commit-reveal), then hold it until it may be revealed
+        let anchor_tx = match self.commit_reveal_pipeline.commit_batch(&batch).await {
+            Ok(anchor_tx) => anchor_tx,
+            Err(error) => {
+                warn!(%error, "batch commit failed");
+                lock(&self.key_shares)?.remove(&batch.id);
+                self.registry.update_batch(&batch.id, TxStatus::Failed)?;
+                return Err(error);
+            }
+        };
+        if let Some(operator_key) = &self.operator_key {
+            batch.operator_signature = Some(operator_key.sign_commitment(&batch.commitment)?);
+        }
+        self.registry.update_batch(&batch.id, TxStatus::Committed)?;
+        let release_jitter = sample_release_jitter(self.max_release_jitter, self.jitter_distribution);
+        info!(encrypted = self.encryption.is_some(), release_jitter_ms = release_jitter.as_millis() as u64, "batch committed");
+
+        let handle = CommitHandle {
+            batch_id: batch.id.clone(),
+            commitment: batch.commitment,
+            anchor_tx,
+        };
+        // Sending only fails when nobody is subscribed
+        let _ = self.batch_events.send(Arc::new(batch.clone()));
+        let release_at = self.batching_engine.now() + release_jitter;
+        lock(&self.awaiting_reveal)?.insert(batch.id.clone(), (batch, release_at, span));
+        Ok(handle)
+    }
+
+    // Reveals and forwards every held batch whose reveal delay and release jitter have elapsed, plus any quorum retries
+    //
+    // Every batch is attempted even if an earlier one fails; the first error is returned.
+    async fn reveal_ready_batches(&self) -> Result<(), IngressError> {
+        let retries: Vec<(TransactionBatch, Span)> = lock(&self.quorum_retries)?.drain(..).collect();
+        let ready = self.commit_reveal_pipeline.ready_to_reveal()?;
+        let now = self.batching_engine.now();
+        let mut first_error = self.evict_expired().err();
+        let batches: Vec<(TransactionBatch, Span)> = {
+            let mut awaiting = lock(&self.awaiting_reveal)?;
+            let released: Vec<String> = ready
+                .into_iter()
+                .filter(|batch_id| awaiting.get(batch_id).is_some_and(|(_, release_at, _)| *release_at <= now))
+                .collect();
+            released
+                .iter()
+                .filter_map(|batch_id| awaiting.remove(batch_id))
+                .map(|(batch, _, span)| (batch, span))
+                .collect()
+        };
+
+        let attempts = retries
+            .into_iter()
+            .map(|(batch, span)| (batch, span, false))
+            .chain(batches.into_iter().map(|(batch, span)| (batch, span, true)));
+        for (batch, span, may_requeue) in attempts {
+            if let Err(error) = self.reveal_batch(batch, span.clone(), may_requeue).instrument(span).await {
+                first_error.get_or_insert(error);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    // Fails every held batch whose commitment expired before it was revealed, e.g. one committed by
+    // hand and never revealed, since it could otherwise be held forever; returns the first such error
+    fn evict_expired(&self) -> Result<(), IngressError> {
+        let held: Vec<String> = lock(&self.awaiting_reveal)?.keys().cloned().collect();
+        let mut first_error = None;
+        for batch_id in held {
+            if self.commit_reveal_pipeline.is_open(&batch_id)? {
+                continue;
+            }
+            let Some((_, _, span)) = lock(&self.awaiting_reveal)?.remove(&batch_id) else {
+                continue;
+            };
+            span.in_scope(|| warn!("commitment expired before the batch was revealed"));
+            lock(&self.key_shares)?.remove(&batch_id);
+            self.registry.update_batch(&batch_id, TxStatus::Failed)?;
+            first_error.get_or_insert(IngressError::CommitmentExpired(batch_id));
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    async fn reveal_batch(&self, batch: TransactionBatch, span: Span, may_requeue: bool) -> Result<(), IngressError> {
+        // Verify the revealed contents against the commitment before anything reaches a relay
+        let reveal = self.commit_reveal_pipeline.verify_reveal(&batch);
+        match &reveal {
+            Ok(()) => info!("batch revealed"),
+            Err(error) => warn!(%error, "batch reveal verification failed"),
+        }
+        if reveal.is_err() {
+            lock(&self.key_shares)?.remove(&batch.id);
+            self.registry.update_batch(&batch.id, TxStatus::Failed)?;
+        }
+        reveal?;
+
+        // Relays need plaintext; the held batch stays encrypted in case it is requeued
+        let shares = lock(&self.key_shares)?.get(&batch.id).cloned();
+        let mut prepared = match shares {
+            Some(shares) => {
+                let mut plaintext = batch.clone();
+                if let Err(error) = plaintext.decrypt_batch(&shares) {
+                    self.registry.update_batch(&batch.id, TxStatus::Failed)?;
+                    return Err(error);
+                }
+                Some(plaintext)
+            }
+            None => None,
+        };
+
+        // Decoys only pad the committed batch and never reach a relay
+        let decoys = batch.transactions.iter().filter(|tx| tx.is_decoy()).count();
+        if decoys > 0 {
+            prepared
+                .get_or_insert_with(|| batch.clone())
+                .transactions
+                .retain(|tx| !tx.is_decoy());
+        }
+
+        // Transactions that landed while the batch was held would waste relay capacity and show we watch the chain
+        if let Some(chain_state) = &self.chain_state {
+            let mut live = prepared.take().unwrap_or_else(|| batch.clone());
+            let held: Vec<[u8; 32]> = live.transactions.iter().map(|tx| transaction_hash(&tx.tx_bytes)).collect();
+            let dropped = chain_state.drop_mined(&mut live.transactions).await;
+            if dropped > 0 {
+                let kept: HashSet<[u8; 32]> =
+                    live.transactions.iter().map(|tx| transaction_hash(&tx.tx_bytes)).collect();
+                for tx_hash in held.into_iter().filter(|tx_hash| !kept.contains(tx_hash)) {
+                    self.registry.record_mined(tx_hash)?;
+                }
+                info!(dropped, "dropped already-mined transactions");
+            }
+            // Deadlines can pass while a batch is held; the rest of the batch still goes out
+            if live.valid_until_block().is_some()
+                && let Ok(head) = chain_state.head_block().await
+            {
+                let (expired, unexpired): (Vec<_>, Vec<_>) =
+                    live.transactions.into_iter().partition(|tx| tx.is_expired_at(head));
+                live.transactions = unexpired;
+                for tx in &expired {
+                    self.registry.record_expired(transaction_hash(&tx.tx_bytes))?;
+                }
+                if !expired.is_empty() {
+                    info!(expired = expired.len(), head, "dropped expired transactions");
+                }
+            }
+            prepared = Some(live);
+        }
+        let outgoing = prepared.as_ref().unwrap_or(&batch);
+
+        // Every member was mined or expired, so there is nothing to send and no relay to count as accepting;
+        // finishing the batch leaves its members marked Mined or Expired
+        if outgoing.transactions.is_empty() {
+            info!("nothing left to forward");
+            lock(&self.key_shares)?.remove(&batch.id);
+            self.registry.update_batch(&batch.id, TxStatus::Forwarded)?;
+            return Ok(());
+        }
+        let relay_results = self.forward(outgoing).await;
+
+        // Record metrics; only real transactions count towards the batch size
+        self.metrics_collector.record_batch_size(outgoing.transactions.len());
+        self.metrics_collector.record_batch_composition(outgoing.transactions.len(), decoys);
+
+        let accepted = relay_results.iter().filter(|result| result.is_success()).count();
+        let quorum = match &self.relay_router {
+            Some(router) => router.check_quorums(&relay_results),
+            None if accepted < self.min_relay_quorum => Err(IngressError::QuorumNotMet {
+                accepted,
+                required: self.min_relay_quorum,
+            }),
+            None => Ok(()),
+        };
+        let requeue = quorum.is_err() && may_requeue && self.requeue_below_quorum && self.relay_router.is_none();
+        if !requeue {
+            lock(&self.key_shares)?.remove(&batch.id);
+
+            // Reaching no relay at all counts as a failure even without a quorum requirement
+            let forwarded = accepted > 0 && quorum.is_ok();
+            let status = if forwarded { TxStatus::Forwarded } else { TxStatus::Failed };
+            self.registry.update_batch(&batch.id, status)?;
+        } else {
+            lock(&self.quorum_retries)?.push((batch, span));
+        }
+        quorum
+    }
+
+    // Sends a plaintext batch to the relays as is, recording relay outcomes, latency and anonymity set
+    //
+    // No commitment is checked here; commit and reveal call this once the batch has been verified.
+    pub async fn forward(&self, batch: &TransactionBatch) -> Vec<RelayResult> {
+        let start_time = tokio::time::Instant::now();
+        let (relay_results, dry_run) = match &self.relay_router {
+            Some(router) => (router.forward_batch(batch).await, router.is_dry_run()),
+            None => (self.relay_forwarder.forward_batch(batch).await, self.relay_forwarder.is_dry_run()),
+        };
+        let latency = start_time.elapsed();
+
+        // A dry run reached no relay, so there is no relay outcome to record
+        for result in relay_results.iter().filter(|_| !dry_run) {
+            if let Some(state) = result.breaker {
+                self.metrics_collector.record_breaker_state(&result.relay_url, state);
+            }
+            if result.circuit_open {
+                info!(relay = %result.relay_url, "relay skipped while its circuit breaker is open");
+                continue;
+            }
+            if result.skipped {
+                self.metrics_collector.record_relay_skipped(&result.relay_url);
+                info!(relay = %result.relay_url, "relay skipped past its submission deadline");
+                continue;
+            }
+            self.metrics_collector.record_relay_outcome(&result.relay_url, result.is_success());
+            if matches!(result.error, Some(IngressError::RelayTimeout(_))) {
+                self.metrics_collector.record_relay_timeout(&result.relay_url);
+            }
+            self.metrics_collector.record_relay_retries(&result.relay_url, result.retries);
+            if let Some((uncompressed, sent)) = result.body_bytes {
+                self.metrics_collector.record_compression(uncompressed, sent);
+            }
+            let relay_latency_ms = result.latency.as_millis() as u64;
+            match &result.error {
+                None => info!(relay = %result.relay_url, latency_ms = relay_latency_ms, retries = result.retries, "relay accepted batch"),
+                // Counts only; which transactions were refused is in the returned results, never the logs
+                Some(error) => warn!(
+                    relay = %result.relay_url,
+                    latency_ms = relay_latency_ms,
+                    retries = result.retries,
+                    accepted = result.accepted_txs.len(),
+                    rejected = result.rejected.len(),
+                    %error,
+                    "relay failed batch"
+                ),
+            }
+        }
+
+        self.metrics_collector.record_forwarding_latency(latency);
+        if let Some(max_acceptable_latency) = self.max_acceptable_latency
+            && latency > max_acceptable_latency
+        {
+            self.metrics_collector.record_latency_breach();
+            warn!(
+                latency_ms = latency.as_millis() as u64,
+                max_acceptable_latency_ms = max_acceptable_latency.as_millis() as u64,
+                "batch forwarding exceeded maximum acceptable latency"
+            );
+        }
+        let anonymity_set = distinct_senders(&batch.transactions);
+        self.metrics_collector.record_anonymity_set(anonymity_set);
+        if anonymity_set < self.min_anonymity_set {
+            warn!(anonymity_set, min_anonymity_set = self.min_anonymity_set, "batch anonymity set below floor");
+        }
+        let k = k_anonymity(batch, self.quasi_identifier);
+        self.metrics_collector.record_k_anonymity(k);
+        let received = batch.transactions.iter().filter(|tx| !tx.is_decoy()).filter_map(|tx| tx.received_at);
+        self.metrics_collector.record_release(received, self.batching_engine.now());
+        if k == 1 {
+            warn!(quasi_identifier = ?self.quasi_identifier, "batch holds a uniquely identifiable transaction");
+        }
+
+        info!(
+            forwarded_count = batch.transactions.len(),
+            accepted = relay_results.iter().filter(|result| result.is_success()).count(),
+            relays = relay_results.iter().filter(|result| !result.skipped && !result.circuit_open).count(),
+            latency_ms = latency.as_millis() as u64,
+            dry_run,
+            "batch forwarded"
+        );
+        relay_results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        blob_tx, dynamic_fee_tx, dynamic_fee_tx_on_chain, fee_bidding_tx, legacy_tx, node_at_block, set_head,
+        signed_dynamic_fee_tx, test_address, transfer_to, unprotected_legacy_tx, FailingEntropy, EIP155_EXAMPLE_TX,
+        EIP155_EXAMPLE_TX_HASH,
+    };
+    use crate::anchor::EthereumAnchor;
+    use crate::batch::BatchTrigger;
+    use crate::clock::MockClock;
+    use crate::commit_reveal::DEFAULT_REVEAL_WINDOW;
+    use crate::crypto::CommitmentScheme;
+    use crate::envelope::KnownAccount;
+    use crate::merkle::verify_merkle_proof;
+    use crate::operator::verify_operator_signature;
+    use crate::relay::{BlockSchedule, InMemoryRelay};
+    use crate::routing::{PriorityFeeRule, RelayGroup};
+    use crate::simulated_relay::{SimulatedRelay, SimulatedResponse};
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_size_triggered_batch_is_forwarded_once() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .expect(3)
+            .mount(&relay)
+            .await;
+        let ingress = PenumIngress::new(3, Duration::from_secs(3600), vec![relay.uri()]);
+
+        for i in 0..3 {
+            ingress.submit_transaction(dynamic_fee_tx(i)).await.unwrap();
+        }
+
+        // Exactly one forward happened and nothing is left pending
+        assert_eq!(ingress.metrics_collector.forwarding_latencies.lock().unwrap().len(), 1);
+        assert_eq!(ingress.batching_engine.pending_count().unwrap(), 0);
+
+        // The time window has not elapsed, so polling must not forward again
+        ingress.process_batches().await.unwrap();
+        assert_eq!(ingress.metrics_collector.forwarding_latencies.lock().unwrap().len(), 1);
+        let accepted = format!("penum_relay_accepted_total{{relay=\"{}\"}} 1", relay.uri());
+        assert!(ingress.metrics().render_prometheus().contains(&accepted));
+    }
+
+    #[tokio::test]
+    async fn test_forwarding_waits_for_reveal_delay() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .mount(&relay)
+            .await;
+        let ingress = PenumIngress::new(2, Duration::from_secs(3600), vec![relay.uri()])
+            .with_reveal_delay(Duration::from_millis(50));
+
+        ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        ingress.submit_transaction(dynamic_fee_tx(1)).await.unwrap();
+
+        // Committed but held back
+        assert_eq!(ingress.awaiting_reveal.lock().unwrap().len(), 1);
+        assert!(relay.received_requests().await.unwrap().is_empty());
+        ingress.process_batches().await.unwrap();
+        assert!(relay.received_requests().await.unwrap().is_empty());
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+
+        ingress.process_batches().await.unwrap();
+        assert!(ingress.awaiting_reveal.lock().unwrap().is_empty());
+        assert_eq!(relay.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_relay_outcomes_recorded_per_batch() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .up_to_n_times(1)
+            .mount(&relay)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"jsonrpc": "2.0", "id": 0, "error": {"code": -32000, "message": "nonce too low"}}),
+            ))
+            .mount(&relay)
+            .await;
+        let ingress = PenumIngress::new(1, Duration::from_secs(3600), vec![relay.uri()]);
+
+        // First batch is accepted, the second rejected
+        ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        ingress.submit_transaction(dynamic_fee_tx(1)).await.unwrap();
+
+        assert_eq!(ingress.metrics().acceptance_rate(&relay.uri()), Some(0.5));
+    }
+
+    // Polls the mock relay until it has seen the expected number of requests
+    async fn wait_for_requests(relay: &MockServer, expected: usize) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while relay.received_requests().await.unwrap().len() < expected {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("relay did not receive the batch in time");
+    }
+
+    #[tokio::test]
+    async fn test_spawned_task_forwards_on_time_window() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .mount(&relay)
+            .await;
+        let ingress = PenumIngress::new(10, Duration::from_millis(50), vec![relay.uri()])
+            .with_poll_interval(Duration::from_millis(10));
+        let handle = ingress.clone().spawn();
+
+        ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        ingress.submit_transaction(dynamic_fee_tx(1)).await.unwrap();
+
+        // Below the size threshold, so only the background task can forward these
+        wait_for_requests(&relay, 2).await;
+        assert_eq!(ingress.batching_engine.pending_count().unwrap(), 0);
+
+        ingress.shutdown();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_flushes_pending_transactions() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .expect(1)
+            .mount(&relay)
+            .await;
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), vec![relay.uri()])
+            .with_reveal_delay(Duration::from_millis(30))
+            .with_poll_interval(Duration::from_millis(10));
+        let handle = ingress.clone().spawn();
+
+        ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        ingress.shutdown();
+        handle.await.unwrap();
+
+        // The final batch was committed, held for the reveal delay and forwarded before exit
+        assert_eq!(relay.received_requests().await.unwrap().len(), 1);
+        assert!(ingress.awaiting_reveal.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_restart_recovers_unforwarded_transactions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ingress.wal");
+
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), Vec::new()).with_wal_path(&path).unwrap();
+        ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        ingress.submit_transaction(dynamic_fee_tx(1)).await.unwrap();
+        drop(ingress);
+
+        let restarted = PenumIngress::new(10, Duration::from_secs(3600), Vec::new()).with_wal_path(&path).unwrap();
+        assert_eq!(restarted.batching_engine.pending_count().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_batch_held_as_ciphertext_until_reveal() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .mount(&relay)
+            .await;
+        let ingress = PenumIngress::new(1, Duration::from_secs(3600), vec![relay.uri()])
+            .with_reveal_delay(Duration::from_millis(30))
+            .with_envelope_encryption(2, 3);
+
+        ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        {
+            let awaiting = ingress.awaiting_reveal.lock().unwrap();
+            let (held, _, _) = awaiting.values().next().unwrap();
+            assert!(held.transactions[0].encrypted);
+            assert_ne!(held.transactions[0].tx_bytes, dynamic_fee_tx(0));
+        }
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        ingress.process_batches().await.unwrap();
+
+        // Relays receive the original signed transaction and the key shares are discarded
+        let requests = relay.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["params"][0], format!("0x{}", hex::encode(dynamic_fee_tx(0))));
+        assert!(ingress.key_shares.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_commit_discards_key_shares() {
+        let rpc = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(503)).mount(&rpc).await;
+        let ingress = PenumIngress::new(1, Duration::from_secs(3600), Vec::new())
+            .with_envelope_encryption(2, 3)
+            .with_commitment_anchor(Arc::new(EthereumAnchor::new(rpc.uri(), [0x11; 20], [0x22; 20])));
+
+        let submitted = ingress.submit_transaction(dynamic_fee_tx(0)).await;
+
+        // The batch never reaches a reveal, so nothing would ever discard its shares otherwise
+        assert!(matches!(submitted, Err(IngressError::Anchor(_))), "{:?}", submitted);
+        assert!(ingress.key_shares.lock().unwrap().is_empty());
+        assert!(ingress.awaiting_reveal.lock().unwrap().is_empty());
+        assert_eq!(ingress.status_of(&transaction_hash(&dynamic_fee_tx(0))), Some(TxStatus::Failed));
+    }
+
+    #[tokio::test]
+    async fn test_release_jitter_spreads_batches() {
+        let max_jitter = Duration::from_secs(10);
+        let clock = MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        // The clock stands still while submitting, so no batch is released by a later submission
+        let ingress = PenumIngress::new(1, Duration::from_secs(3600), Vec::new())
+            .with_clock(Arc::new(clock.clone()))
+            .with_reveal_delay(max_jitter / 4)
+            .with_release_jitter(max_jitter, JitterDistribution::Uniform)
+            .unwrap();
+        let start = clock.now();
+
+        for nonce in 0..40 {
+            ingress.submit_transaction(dynamic_fee_tx(nonce)).await.unwrap();
+        }
+
+        // Nothing is released early, and release times cover the range instead of clustering
+        let offsets: Vec<f64> = ingress
+            .awaiting_reveal
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(_, release_at, _)| release_at.duration_since(start).unwrap().as_secs_f64())
+            .collect();
+        assert_eq!(offsets.len(), 40);
+        let mut quarters = [0; 4];
+        for offset in &offsets {
+            assert!(*offset <= max_jitter.as_secs_f64());
+            quarters[((offset / 2.5) as usize).min(3)] += 1;
+        }
+        assert!(quarters.iter().all(|&count| count > 0), "release times clustered: {:?}", quarters);
+
+        // Once the reveal delay has passed, batches leave as their own jitter runs out
+        clock.advance(max_jitter / 2);
+        let _ = ingress.process_batches().await;
+        let held = ingress.awaiting_reveal.lock().unwrap().len();
+        assert_eq!(held, offsets.iter().filter(|&&offset| offset > max_jitter.as_secs_f64() / 2.0).count());
+    }
+
+    #[test]
+    fn test_release_jitter_outlasting_commitment_rejected() {
+        let ingress =
+            PenumIngress::new(1, Duration::from_secs(3600), Vec::new()).with_reveal_delay(Duration::from_secs(60));
+        let lifetime = Duration::from_secs(60) + DEFAULT_REVEAL_WINDOW;
+        let rejected = ingress.clone().with_release_jitter(lifetime, JitterDistribution::Uniform);
+        assert!(matches!(rejected, Err(IngressError::Config(_))));
+        assert!(ingress.with_release_jitter(lifetime - Duration::from_millis(1), JitterDistribution::Uniform).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_drain_fails_batch_whose_commitment_expired_while_held() {
+        let clock = MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let ingress = PenumIngress::new(1, Duration::from_secs(3600), Vec::new())
+            .with_clock(Arc::new(clock.clone()))
+            .with_reveal_delay(Duration::from_secs(1))
+            .with_poll_interval(Duration::from_millis(10));
+        let tx_hash = ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+
+        // Nothing ran while held, so the batch is past both its release time and its reveal window
+        clock.advance(Duration::from_secs(1) + DEFAULT_REVEAL_WINDOW);
+        let drained = tokio::time::timeout(Duration::from_secs(5), ingress.drain()).await;
+        assert!(matches!(drained, Ok(Err(IngressError::CommitmentExpired(_)))), "{:?}", drained);
+        assert!(ingress.awaiting_reveal.lock().unwrap().is_empty());
+        assert_eq!(ingress.status_of(&tx_hash), Some(TxStatus::Failed));
+    }
+
+    #[tokio::test]
+    async fn test_decoys_committed_but_not_forwarded() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .mount(&relay)
+            .await;
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), vec![relay.uri()])
+            .with_reveal_delay(Duration::from_millis(30))
+            .with_decoy_target_size(6);
+
+        ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        let batch = ingress.batching_engine.flush().unwrap().unwrap();
+        ingress.process_batch(batch).await.unwrap();
+
+        // The committed batch reaches the target size
+        let committed_size = ingress.awaiting_reveal.lock().unwrap().values().next().unwrap().0.transactions.len();
+        assert_eq!(committed_size, 6);
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        ingress.process_batches().await.unwrap();
+
+        let requests = relay.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["params"][0], format!("0x{}", hex::encode(dynamic_fee_tx(0))));
+        assert_eq!(ingress.metrics().transaction_counts(), (1, 5));
+        // The batch size metric counts what went out, once per batch
+        assert_eq!(ingress.metrics().get_aggregate_metrics().avg_batch_size, 1.0);
+    }
+
+    // Three relays of which only the first accepts
+    async fn one_of_three_relays() -> Vec<MockServer> {
+        let mut relays = Vec::new();
+        for accepts in [true, false, false] {
+            let relay = MockServer::start().await;
+            let body = if accepts {
+                serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})
+            } else {
+                serde_json::json!({"jsonrpc": "2.0", "id": 0, "error": {"code": -32000, "message": "rejected"}})
+            };
+            Mock::given(method("POST"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&relay)
+                .await;
+            relays.push(relay);
+        }
+        relays
+    }
+
+    #[tokio::test]
+    async fn test_batch_below_quorum_is_reported() {
+        let relays = one_of_three_relays().await;
+        let ingress = PenumIngress::new(1, Duration::from_secs(3600), relays.iter().map(|relay| relay.uri()).collect())
+            .with_min_relay_quorum(2);
+
+        let result = ingress.submit_transaction(dynamic_fee_tx(0)).await;
+
+        assert_eq!(result, Err(IngressError::QuorumNotMet { accepted: 1, required: 2 }));
+        assert!(ingress.quorum_retries.lock().unwrap().is_empty());
+        assert_eq!(ingress.status_of(&transaction_hash(&dynamic_fee_tx(0))), Some(TxStatus::Failed));
+
+        // Without requeueing nothing is forwarded again
+        ingress.process_batches().await.unwrap();
+        assert_eq!(relays[0].received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_batch_below_quorum_is_requeued_once() {
+        let relays = one_of_three_relays().await;
+        let ingress = PenumIngress::new(1, Duration::from_secs(3600), relays.iter().map(|relay| relay.uri()).collect())
+            .with_min_relay_quorum(2)
+            .with_requeue_below_quorum(true);
+
+        let result = ingress.submit_transaction(dynamic_fee_tx(0)).await;
+        assert_eq!(result, Err(IngressError::QuorumNotMet { accepted: 1, required: 2 }));
+        assert_eq!(ingress.quorum_retries.lock().unwrap().len(), 1);
+        assert_eq!(ingress.status_of(&transaction_hash(&dynamic_fee_tx(0))), Some(TxStatus::Committed));
+
+        // The retry cycle forwards the same batch again, then gives up on it
+        let result = ingress.process_batches().await;
+        assert_eq!(result, Err(IngressError::QuorumNotMet { accepted: 1, required: 2 }));
+        for relay in &relays {
+            assert_eq!(relay.received_requests().await.unwrap().len(), 2);
+        }
+        assert!(ingress.quorum_retries.lock().unwrap().is_empty());
+        assert_eq!(ingress.status_of(&transaction_hash(&dynamic_fee_tx(0))), Some(TxStatus::Failed));
+    }
+
+    #[tokio::test]
+    async fn test_batch_split_between_relay_groups() {
+        let (private, public) = (SimulatedRelay::new("private"), SimulatedRelay::new("public"));
+        let groups = vec![
+            RelayGroup::new("public", RelayForwarder::from_transports(vec![Box::new(public.clone())])),
+            RelayGroup::new("private", RelayForwarder::from_transports(vec![Box::new(private.clone())]))
+                .with_min_quorum(1),
+        ];
+        let router = RelayRouter::new(groups, Arc::new(PriorityFeeRule::new(50, "private", "public"))).unwrap();
+        let ingress = PenumIngress::new(2, Duration::from_secs(3600), Vec::new()).with_relay_router(router);
+        let (low, high) = (fee_bidding_tx(1, 10, 100), fee_bidding_tx(2, 80, 800));
+
+        ingress.submit_transaction(low.clone()).await.unwrap();
+        ingress.submit_transaction(high.clone()).await.unwrap();
+        let sent = |relay: &SimulatedRelay| relay.received_batches().unwrap()[0].transactions[0].tx_bytes.clone();
+        assert_eq!((sent(&public), sent(&private)), (low.clone(), high.clone()));
+        assert_eq!(ingress.status_of(&transaction_hash(&high)), Some(TxStatus::Forwarded));
+
+        // The private group missing its quorum fails the batch, even though the public group took its part
+        let failing = SimulatedRelay::new("private").with_default_response(SimulatedResponse::reject(503));
+        let groups = vec![
+            RelayGroup::new("public", RelayForwarder::from_transports(vec![Box::new(public.clone())])),
+            RelayGroup::new("private", RelayForwarder::from_transports(vec![Box::new(failing)])).with_min_quorum(1),
+        ];
+        let router = RelayRouter::new(groups, Arc::new(PriorityFeeRule::new(50, "private", "public"))).unwrap();
+        let ingress = PenumIngress::new(2, Duration::from_secs(3600), Vec::new())
+            .with_relay_router(router)
+            .with_requeue_below_quorum(true);
+        let (low, high) = (fee_bidding_tx(3, 10, 100), fee_bidding_tx(4, 80, 800));
+        ingress.submit_transaction(low).await.unwrap();
+        assert_eq!(
+            ingress.submit_transaction(high.clone()).await,
+            Err(IngressError::GroupQuorumNotMet { group: "private".to_string(), accepted: 0, required: 1 })
+        );
+        assert_eq!(public.received_batches().unwrap().len(), 2);
+        assert!(ingress.quorum_retries.lock().unwrap().is_empty());
+        assert_eq!(ingress.status_of(&transaction_hash(&high)), Some(TxStatus::Failed));
+    }
+
+    #[tokio::test]
+    async fn test_status_follows_transaction_to_relay() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .mount(&relay)
+            .await;
+        let ingress = PenumIngress::new(2, Duration::from_secs(3600), vec![relay.uri()])
+            .with_reveal_delay(Duration::from_millis(30));
+
+        let first = ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        assert_eq!(ingress.status_of(&first), Some(TxStatus::Pending));
+
+        // Filling the batch commits it, and the reveal delay holds it back from the relay
+        let second = ingress.submit_transaction(dynamic_fee_tx(1)).await.unwrap();
+        assert_eq!(ingress.status_of(&first), Some(TxStatus::Committed));
+        assert_eq!(ingress.status_of(&second), Some(TxStatus::Committed));
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        ingress.process_batches().await.unwrap();
+        assert_eq!(ingress.status_of(&first), Some(TxStatus::Forwarded));
+        assert_eq!(ingress.status_of(&second), Some(TxStatus::Forwarded));
+        assert_eq!(ingress.status_of(&transaction_hash(&dynamic_fee_tx(2))), None);
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_batch_lifecycle_traced_without_payloads() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .mount(&relay)
+            .await;
+        let ingress = PenumIngress::new(2, Duration::from_secs(3600), vec![relay.uri()]);
+
+        ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        ingress.submit_transaction(dynamic_fee_tx(1)).await.unwrap();
+
+        assert!(logs_contain("batch_id="));
+        assert!(logs_contain("tx_count=2"));
+        assert!(logs_contain("batch committed"));
+        assert!(logs_contain("batch revealed"));
+        assert!(logs_contain("relay accepted batch"));
+        assert!(logs_contain(&format!("relay={}", relay.uri())));
+        assert!(logs_contain("latency_ms="));
+        assert!(logs_contain("batch forwarded"));
+        // Only identifiers and counts are logged, never transaction bytes
+        assert!(!logs_contain(&hex::encode(dynamic_fee_tx(0))));
+        assert!(!logs_contain(&hex::encode(dynamic_fee_tx(1))));
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_slow_forward_counts_latency_breach() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"}))
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .mount(&relay)
+            .await;
+        let tolerant = PenumIngress::new(1, Duration::from_secs(3600), vec![relay.uri()])
+            .with_max_acceptable_latency(Duration::from_secs(10));
+        let ingress = PenumIngress::new(1, Duration::from_secs(3600), vec![relay.uri()])
+            .with_max_acceptable_latency(Duration::from_millis(50));
+
+        tolerant.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        assert_eq!(tolerant.metrics().latency_breach_count(), 0);
+        assert!(!logs_contain("exceeded maximum acceptable latency"));
+
+        ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        assert_eq!(ingress.metrics().latency_breach_count(), 1);
+        assert!(logs_contain("batch forwarding exceeded maximum acceptable latency"));
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_unique_destination_reported_as_k_of_one() {
+        let ingress = PenumIngress::new(4, Duration::from_secs(3600), Vec::new());
+
+        for key in 1..=4 {
+            ingress.submit_transaction(transfer_to(key, [0xaa; 20])).await.unwrap();
+        }
+        assert_eq!(ingress.metrics().get_aggregate_metrics().min_k_anonymity, 4);
+        assert!(!logs_contain("uniquely identifiable"));
+
+        for (key, to) in [(5, [0xaa; 20]), (6, [0xaa; 20]), (7, [0xaa; 20]), (8, [0xbb; 20])] {
+            ingress.submit_transaction(transfer_to(key, to)).await.unwrap();
+        }
+        assert_eq!(ingress.metrics().get_aggregate_metrics().min_k_anonymity, 1);
+        assert!(logs_contain("batch holds a uniquely identifiable transaction"));
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_anonymity_set_counts_distinct_senders() {
+        let ingress = PenumIngress::new(3, Duration::from_secs(3600), Vec::new()).with_min_anonymity_set(2);
+
+        // One sender filling a whole batch hides among nobody
+        for nonce in 0..3 {
+            ingress.submit_transaction(signed_dynamic_fee_tx(1, nonce)).await.unwrap();
+        }
+        assert!(logs_contain("batch anonymity set below floor"));
+
+        for (key, nonce) in [(2, 0), (2, 1), (3, 0), (4, 0), (5, 0), (6, 0)] {
+            ingress.submit_transaction(signed_dynamic_fee_tx(key, nonce)).await.unwrap();
+        }
+
+        let aggregate = ingress.metrics().get_aggregate_metrics();
+        assert_eq!(aggregate.min_anonymity_set, 1);
+        assert_eq!(aggregate.avg_anonymity_set, 2.0);
+        assert_eq!(aggregate.max_anonymity_set, 3);
+
+        // Every transaction was released among two others
+        let report = ingress.metrics().privacy_report();
+        assert_eq!(report.avg_anonymity_set, 2.0);
+        assert!(report.estimated_attack_success > 0.0 && report.estimated_attack_success <= 1.0 / 3.0 + 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_batch_below_min_distinct_senders_held_until_more_join() {
+        let ingress = PenumIngress::new(3, Duration::from_millis(10), Vec::new()).with_min_distinct_senders(2);
+
+        // A full batch from one sender stays pending, through the size threshold, the window and a flush
+        for nonce in 0..3 {
+            ingress.submit_transaction(signed_dynamic_fee_tx(1, nonce)).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        ingress.process_batches().await.unwrap();
+        assert!(ingress.batching_engine.flush().unwrap().is_none());
+        assert_eq!(ingress.batching_engine.pending_count().unwrap(), 3);
+        assert!(ingress.metrics().forwarding_latencies.lock().unwrap().is_empty());
+
+        // A second sender joining lets the whole lot out as one batch
+        ingress.submit_transaction(signed_dynamic_fee_tx(2, 0)).await.unwrap();
+        assert_eq!(ingress.batching_engine.pending_count().unwrap(), 0);
+        assert_eq!(ingress.metrics().get_aggregate_metrics().max_anonymity_set, 2);
+        assert_eq!(*ingress.metrics().batch_sizes.lock().unwrap().back().unwrap(), 4);
+        assert_eq!(ingress.metrics().forwarding_latencies.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_batches_counted_by_trigger() {
+        let ingress = PenumIngress::new(2, Duration::from_millis(10), Vec::new());
+        for nonce in 0..2 {
+            ingress.submit_transaction(signed_dynamic_fee_tx(1, nonce)).await.unwrap();
+        }
+        ingress.submit_transaction(signed_dynamic_fee_tx(2, 0)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        ingress.process_batches().await.unwrap();
+        // Nothing pending, so the window passing again cuts no batch
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        ingress.process_batches().await.unwrap();
+        ingress.submit_transaction(signed_dynamic_fee_tx(3, 0)).await.unwrap();
+        ingress.drain().await.unwrap();
+
+        let counts = ingress.metrics().batch_trigger_counts();
+        let expected = HashMap::from([
+            (BatchTrigger::SizeThreshold, 1),
+            (BatchTrigger::TimeWindow, 1),
+            (BatchTrigger::ForcedFlush, 1),
+        ]);
+        assert_eq!(counts, expected);
+        assert!(ingress.metrics().render_prometheus().contains("penum_batches_total{trigger=\"max_age\"} 0"));
+    }
+
+    // Node answering eth_getTransactionCount with the given account nonce for each test key's address
+    async fn node_with_account_nonces(account_nonces: &[(u8, u64)]) -> MockServer {
+        let rpc = MockServer::start().await;
+        for &(key, account_nonce) in account_nonces {
+            Mock::given(method("POST"))
+                .and(body_partial_json(serde_json::json!({
+                    "method": "eth_getTransactionCount",
+                    "params": [format!("0x{}", hex::encode(test_address(key)))],
+                })))
+                .respond_with(ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": format!("0x{:x}", account_nonce)}),
+                ))
+                .mount(&rpc)
+                .await;
+        }
+        rpc
+    }
+
+    #[tokio::test]
+    async fn test_already_mined_transaction_not_forwarded() {
+        let rpc = node_with_account_nonces(&[(1, 0), (2, 5)]).await;
+        let relay = InMemoryRelay::new("memory");
+        let ingress = PenumIngress::new(2, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]))
+            .with_chain_rpc(rpc.uri());
+
+        // Sender 2 is already at nonce 5, so its nonce-4 transaction has been mined
+        let live = ingress.submit_transaction(signed_dynamic_fee_tx(1, 0)).await.unwrap();
+        let mined = ingress.submit_transaction(signed_dynamic_fee_tx(2, 4)).await.unwrap();
+
+        let forwarded = relay.forwarded().unwrap();
+        assert_eq!(forwarded.len(), 1);
+        let payloads: Vec<Vec<u8>> = forwarded[0].transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        assert_eq!(payloads, vec![signed_dynamic_fee_tx(1, 0)]);
+        assert_eq!(ingress.status_of(&live), Some(TxStatus::Forwarded));
+        assert_eq!(ingress.status_of(&mined), Some(TxStatus::Mined));
+    }
+
+    #[tokio::test]
+    async fn test_batch_of_mined_transactions_reaches_no_relay() {
+        let rpc = node_with_account_nonces(&[(1, 3), (2, 5)]).await;
+        let relay = InMemoryRelay::new("memory");
+        let ingress = PenumIngress::new(2, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]))
+            .with_chain_rpc(rpc.uri());
+
+        let first = ingress.submit_transaction(signed_dynamic_fee_tx(1, 2)).await.unwrap();
+        let second = ingress.submit_transaction(signed_dynamic_fee_tx(2, 4)).await.unwrap();
+
+        // Nothing is sent, no relay is credited with accepting it, and neither member counts as forwarded
+        assert!(relay.forwarded().unwrap().is_empty());
+        assert_eq!(ingress.metrics().acceptance_rate("memory"), None);
+        assert_eq!(ingress.status_of(&first), Some(TxStatus::Mined));
+        assert_eq!(ingress.status_of(&second), Some(TxStatus::Mined));
+        assert!(ingress.key_shares.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_transaction_past_inclusion_deadline_dropped_and_marked_expired() {
+        let node = node_at_block(100).await;
+        let relay = InMemoryRelay::new("memory");
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]))
+            .with_chain_rpc(node.uri());
+
+        let expiring = ingress.submit_transaction_with_expiry(signed_dynamic_fee_tx(1, 0), 102).await.unwrap();
+        let lasting = ingress.submit_transaction_with_expiry(signed_dynamic_fee_tx(2, 0), 110).await.unwrap();
+        let open = ingress.submit_transaction(signed_dynamic_fee_tx(3, 0)).await.unwrap();
+        ingress.process_batches().await.unwrap();
+        assert_eq!(ingress.batching_engine.pending_count().unwrap(), 3);
+
+        // Block 102 is mined, so the first transaction can no longer land in time
+        set_head(&node, 102).await;
+        ingress.process_batches().await.unwrap();
+        assert_eq!(ingress.batching_engine.pending_count().unwrap(), 2);
+        assert_eq!(ingress.status_of(&expiring), Some(TxStatus::Expired));
+        assert_eq!(ingress.status_of(&lasting), Some(TxStatus::Pending));
+
+        ingress.drain().await.unwrap();
+        let forwarded = relay.forwarded().unwrap();
+        let mut payloads: Vec<Vec<u8>> = forwarded[0].transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        payloads.sort();
+        let mut expected = vec![signed_dynamic_fee_tx(2, 0), signed_dynamic_fee_tx(3, 0)];
+        expected.sort();
+        assert_eq!(payloads, expected);
+        assert_eq!(ingress.status_of(&expiring), Some(TxStatus::Expired));
+        assert_eq!(ingress.status_of(&open), Some(TxStatus::Forwarded));
+    }
+
+    #[tokio::test]
+    async fn test_conditions_carried_to_relay_and_bound_deadline() {
+        let relay = InMemoryRelay::new("memory");
+        let ingress = PenumIngress::new(1, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]));
+        let conditions = TransactionConditions {
+            known_accounts: [([0x11; 20], KnownAccount::StorageRoot([0x22; 32]))].into(),
+            block_number_min: Some(90),
+            block_number_max: Some(100),
+        };
+
+        ingress.submit_transaction_with_conditions(signed_dynamic_fee_tx(1, 0), conditions.clone()).await.unwrap();
+        let forwarded = relay.forwarded().unwrap();
+        assert_eq!(forwarded[0].transactions[0].conditions, Some(conditions));
+        assert_eq!(forwarded[0].transactions[0].valid_until_block, Some(100));
+
+        // No block satisfies an empty range, so the transaction is refused up front
+        let empty =
+            TransactionConditions { block_number_min: Some(101), block_number_max: Some(100), ..Default::default() };
+        let refused = ingress.submit_transaction_with_conditions(signed_dynamic_fee_tx(2, 0), empty).await;
+        assert!(matches!(refused, Err(IngressError::InvalidTransaction(_))));
+        assert_eq!(relay.forwarded().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_relay_past_deadline_recorded_as_skipped() {
+        let (early, open) = (InMemoryRelay::new("early"), InMemoryRelay::new("open"));
+        let schedule = BlockSchedule::new(SystemTime::now() - Duration::from_secs(11), Duration::from_secs(12));
+        let forwarder = RelayForwarder::from_transports(vec![Box::new(early.clone()), Box::new(open.clone())])
+            .with_block_schedule(schedule)
+            .with_submission_cutoff("early", Duration::from_secs(4));
+        let ingress = PenumIngress::new(1, Duration::from_secs(3600), Vec::new()).with_relay_forwarder(forwarder);
+
+        ingress.submit_transaction(signed_dynamic_fee_tx(1, 0)).await.unwrap();
+
+        assert!(early.forwarded().unwrap().is_empty());
+        assert_eq!(open.forwarded().unwrap().len(), 1);
+        assert_eq!(ingress.metrics().skipped_count("early"), 1);
+        assert_eq!(ingress.metrics().acceptance_rate("early"), None);
+        assert_eq!(ingress.metrics().acceptance_rate("open"), Some(1.0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slow_relay_times_out_without_holding_up_fast_ones() {
+        let slow = SimulatedRelay::new("slow").with_default_response(SimulatedResponse::accept().after(Duration::from_secs(30)));
+        let fast = SimulatedRelay::new("fast").with_default_response(SimulatedResponse::accept().after(Duration::from_millis(200)));
+        let forwarder = RelayForwarder::from_transports(vec![Box::new(slow), Box::new(fast)]);
+        let ingress = PenumIngress::new(1, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(forwarder)
+            .with_relay_request_timeout(Duration::from_secs(1));
+
+        let start = tokio::time::Instant::now();
+        let results = ingress.forward(&signed_batch(&[1])).await;
+
+        // The batch waited for the timeout, not for the slow relay
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert_eq!(results[0].error, Some(IngressError::RelayTimeout(Duration::from_secs(1))));
+        assert!(results[1].is_success());
+        assert_eq!(results[1].latency, Duration::from_millis(200));
+        assert_eq!(ingress.metrics().timeout_count("slow"), 1);
+        assert_eq!(ingress.metrics().timeout_count("fast"), 0);
+        assert_eq!(ingress.metrics().acceptance_rate("slow"), Some(0.0));
+        assert_eq!(ingress.metrics().acceptance_rate("fast"), Some(1.0));
+    }
+
+    fn signed_batch(keys: &[u8]) -> TransactionBatch {
+        let transactions = keys
+            .iter()
+            .map(|&key| TransactionEnvelope::new(signed_dynamic_fee_tx(key, 0), String::new()))
+            .collect();
+        TransactionBatch::new(transactions).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_commit_reveal_driven_manually() {
+        let relay = InMemoryRelay::new("memory");
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]));
+        let batch = signed_batch(&[1, 2]);
+        let tx_hash = transaction_hash(&batch.transactions[0].tx_bytes);
+
+        let handle = ingress.commit(batch.clone()).await.unwrap();
+        assert_eq!(handle.batch_id, batch.id);
+        assert_eq!(handle.commitment, batch.commitment);
+        assert_eq!(handle.anchor_tx, None);
+        assert_eq!(ingress.status_of(&tx_hash), Some(TxStatus::Committed));
+        assert!(relay.forwarded().unwrap().is_empty());
+
+        ingress.reveal(handle.clone()).await.unwrap();
+        assert_eq!(ingress.status_of(&tx_hash), Some(TxStatus::Forwarded));
+        assert_eq!(relay.forwarded().unwrap().len(), 1);
+
+        // A handle is redeemed once
+        assert_eq!(ingress.reveal(handle).await, Err(IngressError::CommitmentNotFound(batch.id)));
+        assert_eq!(relay.forwarded().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_receive_committed_batches() {
+        let relay = InMemoryRelay::new("memory");
+        let ingress = PenumIngress::new(2, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]));
+        let mut first = ingress.subscribe_batches();
+        let mut second = ingress.clone().subscribe_batches();
+
+        ingress.submit_transaction(signed_dynamic_fee_tx(1, 0)).await.unwrap();
+        assert!(first.try_recv().is_err());
+        ingress.submit_transaction(signed_dynamic_fee_tx(2, 0)).await.unwrap();
+
+        let forwarded = relay.forwarded().unwrap();
+        for receiver in [&mut first, &mut second] {
+            let batch = receiver.try_recv().unwrap();
+            assert_eq!(batch.id, forwarded[0].id);
+            assert_eq!(batch.commitment, forwarded[0].commitment);
+            assert_eq!(batch.transactions.len(), 2);
+            assert!(receiver.try_recv().is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_records_batches_without_calling_relays() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(200)).expect(0).mount(&relay).await;
+        let ingress = PenumIngress::new(2, Duration::from_secs(3600), vec![relay.uri()]).with_dry_run(true);
+        let mut batches = ingress.subscribe_batches();
+        let tx_hash = transaction_hash(&signed_dynamic_fee_tx(1, 0));
+
+        ingress.submit_transaction(signed_dynamic_fee_tx(1, 0)).await.unwrap();
+        ingress.submit_transaction(signed_dynamic_fee_tx(2, 0)).await.unwrap();
+
+        assert_eq!(batches.try_recv().unwrap().transactions.len(), 2);
+        assert_eq!(ingress.status_of(&tx_hash), Some(TxStatus::Forwarded));
+        assert_eq!(ingress.metrics().forwarding_latencies.lock().unwrap().len(), 1);
+        assert_eq!(ingress.metrics().get_aggregate_metrics().max_anonymity_set, 2);
+        assert_eq!(ingress.metrics().acceptance_rate(&relay.uri()), None);
+        assert!(relay.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reveal_fails_without_commit() {
+        let relay = InMemoryRelay::new("memory");
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]));
+        let batch = signed_batch(&[1]);
+        let handle = CommitHandle {
+            batch_id: batch.id.clone(),
+            commitment: batch.commitment,
+            anchor_tx: None,
+        };
+
+        assert_eq!(ingress.reveal(handle).await, Err(IngressError::CommitmentNotFound(batch.id)));
+        assert!(relay.forwarded().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reveal_before_delay_keeps_batch_held() {
+        let relay = InMemoryRelay::new("memory");
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]))
+            .with_reveal_delay(Duration::from_millis(50));
+        let handle = ingress.commit(signed_batch(&[1])).await.unwrap();
+
+        assert_eq!(ingress.reveal(handle.clone()).await, Err(IngressError::RevealTooEarly(handle.batch_id.clone())));
+        assert!(relay.forwarded().unwrap().is_empty());
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        ingress.reveal(handle).await.unwrap();
+        assert_eq!(relay.forwarded().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_batch_carries_operator_signature() {
+        let relay = InMemoryRelay::new("memory");
+        let operator_key = OperatorKey::from_bytes(&[9; 32]).unwrap();
+        let public_key = operator_key.public_key();
+        let ingress = PenumIngress::new(2, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]))
+            .with_envelope_encryption(2, 3)
+            .with_operator_key(operator_key);
+
+        ingress.submit_transaction(signed_dynamic_fee_tx(1, 0)).await.unwrap();
+        ingress.submit_transaction(signed_dynamic_fee_tx(2, 0)).await.unwrap();
+
+        let forwarded = relay.forwarded().unwrap();
+        assert_eq!(verify_operator_signature(&forwarded[0], &public_key), Ok(()));
+
+        let mut spoofed = forwarded[0].clone();
+        spoofed.commitment.0[31] ^= 0xff;
+        assert_eq!(verify_operator_signature(&spoofed, &public_key), Err(IngressError::InvalidOperatorSignature));
+    }
+
+    #[tokio::test]
+    async fn test_forward_sends_batch_without_commitment() {
+        let relay = InMemoryRelay::new("memory");
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]));
+        let batch = signed_batch(&[1, 2, 3]);
+
+        let results = ingress.forward(&batch).await;
+
+        assert!(results.iter().all(|result| result.is_success()));
+        assert_eq!(relay.forwarded().unwrap()[0].id, batch.id);
+        assert_eq!(ingress.metrics().acceptance_rate("memory"), Some(1.0));
+        assert_eq!(ingress.metrics().get_aggregate_metrics().max_anonymity_set, 3);
+    }
+
+    #[tokio::test]
+    async fn test_urgent_lane_forwards_without_waiting_for_default_lane() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .mount(&relay)
+            .await;
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), vec![relay.uri()])
+            .with_lane("urgent", 1, Duration::from_secs(3600));
+
+        ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        let urgent = ingress.submit_transaction_to_lane(dynamic_fee_tx(1), "urgent").await.unwrap();
+
+        assert_eq!(ingress.status_of(&urgent), Some(TxStatus::Forwarded));
+        assert_eq!(ingress.status_of(&transaction_hash(&dynamic_fee_tx(0))), Some(TxStatus::Pending));
+        let requests = relay.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["params"][0], format!("0x{}", hex::encode(dynamic_fee_tx(1))));
+    }
+
+    #[tokio::test]
+    async fn test_submit_rejects_empty_transaction() {
+        let ingress = PenumIngress::new(3, Duration::from_secs(3600), Vec::new());
+
+        assert_eq!(ingress.submit_transaction(Vec::new()).await, Err(IngressError::EmptyTransaction));
+    }
+
+    #[tokio::test]
+    async fn test_submit_rejects_malformed_transaction() {
+        let ingress = PenumIngress::new(3, Duration::from_secs(3600), Vec::new());
+
+        let result = ingress.submit_transaction(vec![0x02, 0x01, 0x02, 0x03]).await;
+
+        assert!(matches!(result, Err(IngressError::InvalidTransaction(_))));
+    }
+
+    #[tokio::test]
+    async fn test_submit_rate_limited_until_bucket_refills() {
+        let ingress = PenumIngress::new(100, Duration::from_secs(3600), Vec::new()).with_rate_limit(20.0, 2);
+
+        ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        ingress.submit_transaction(dynamic_fee_tx(1)).await.unwrap();
+        assert_eq!(ingress.submit_transaction(dynamic_fee_tx(2)).await, Err(IngressError::RateLimited));
+
+        // A client ID gets its own bucket, separate from the sender's
+        ingress.submit_transaction_from(dynamic_fee_tx(2), "wallet").await.unwrap();
+        ingress.submit_transaction_from(dynamic_fee_tx(3), "wallet").await.unwrap();
+        assert_eq!(ingress.submit_transaction_from(dynamic_fee_tx(4), "wallet").await, Err(IngressError::RateLimited));
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        ingress.submit_transaction(dynamic_fee_tx(4)).await.unwrap();
+        assert_eq!(ingress.batching_engine.pending_count().unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_submit_rejects_oversized_transaction() {
+        let tx = dynamic_fee_tx(0);
+        let ingress = PenumIngress::new(3, Duration::from_secs(3600), Vec::new()).with_max_tx_bytes(tx.len() - 1);
+
+        let result = ingress.submit_transaction(tx.clone()).await;
+        assert_eq!(result, Err(IngressError::TxTooLarge { size: tx.len(), max_tx_bytes: tx.len() - 1 }));
+        assert_eq!(ingress.batching_engine.pending_count().unwrap(), 0);
+
+        // The default limit leaves room for ordinary transactions but not for huge payloads
+        let ingress = PenumIngress::new(3, Duration::from_secs(3600), Vec::new());
+        ingress.submit_transaction(tx).await.unwrap();
+        let huge = vec![0x02; DEFAULT_MAX_TX_BYTES + 1];
+        assert!(matches!(ingress.submit_transaction(huge).await, Err(IngressError::TxTooLarge { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_blob_transactions_accepted_unless_disabled() {
+        // The sidecar alone is larger than the default limit, but only the transaction itself is measured
+        let wrapped = blob_tx(0, 1, true);
+        assert!(wrapped.len() > DEFAULT_MAX_TX_BYTES);
+        let ingress = PenumIngress::new(3, Duration::from_secs(3600), Vec::new());
+        ingress.submit_transaction(wrapped.clone()).await.unwrap();
+        assert_eq!(ingress.batching_engine.pending_count().unwrap(), 1);
+
+        let ingress = PenumIngress::new(3, Duration::from_secs(3600), Vec::new()).with_allow_blob_txs(false);
+        assert_eq!(ingress.submit_transaction(wrapped).await, Err(IngressError::BlobTransactionsDisabled));
+        assert_eq!(ingress.submit_transaction(blob_tx(0, 1, false)).await, Err(IngressError::BlobTransactionsDisabled));
+        ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_submit_rejects_invalid_signature() {
+        let ingress = PenumIngress::new(3, Duration::from_secs(3600), Vec::new());
+
+        // Corrupt r so the signature no longer recovers to a valid point
+        let mut tx = dynamic_fee_tx(1);
+        let r_index = tx.len() - 65;
+        tx[r_index..r_index + 32].fill(0xff);
+
+        assert_eq!(ingress.submit_transaction(tx).await, Err(IngressError::InvalidSignature));
+        assert_eq!(ingress.batching_engine.pending_count().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_submit_records_recovered_sender() {
+        let ingress = PenumIngress::new(3, Duration::from_secs(3600), Vec::new());
+
+        ingress.submit_transaction(signed_dynamic_fee_tx(0x07, 0)).await.unwrap();
+
+        let pending = ingress.batching_engine.default_lane.pending.snapshot().unwrap();
+        assert_eq!(pending[0].sender, Some(test_address(0x07)));
+    }
+
+    #[tokio::test]
+    async fn test_submit_transactions_reports_each_result() {
+        let relay = InMemoryRelay::new("memory");
+        let ingress = PenumIngress::new(2, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]));
+        let (first, second) = (signed_dynamic_fee_tx(1, 0), signed_dynamic_fee_tx(2, 0));
+
+        let results = ingress
+            .submit_transactions(vec![first.clone(), Vec::new(), second.clone(), first.clone(), vec![0x02, 0x01]])
+            .await;
+
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0], Ok(transaction_hash(&first)));
+        assert_eq!(results[1], Err(IngressError::EmptyTransaction));
+        assert_eq!(results[2], Ok(transaction_hash(&second)));
+        assert_eq!(results[3], Err(IngressError::Duplicate));
+        assert!(matches!(results[4], Err(IngressError::InvalidTransaction(_))));
+
+        // The two accepted transactions filled one batch, which was forwarded
+        let forwarded = relay.forwarded().unwrap();
+        assert_eq!(forwarded.len(), 1);
+        assert_eq!(forwarded[0].transactions.len(), 2);
+        assert_eq!(ingress.status_of(&transaction_hash(&second)), Some(TxStatus::Forwarded));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_idempotency_key_returns_cached_hash_without_second_enqueue() {
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), Vec::new());
+        let tx = signed_dynamic_fee_tx(1, 0);
+
+        let first = ingress.submit_transaction_idempotent(tx.clone(), "order-1").await;
+        assert_eq!(first, Ok(transaction_hash(&tx)));
+        assert_eq!(ingress.submit_transaction_idempotent(tx.clone(), "order-1").await, first);
+        assert_eq!(ingress.pending_snapshot().unwrap().count, 1);
+
+        // Content dedup still rejects the same bytes without the key, or under another key
+        assert_eq!(ingress.submit_transaction(tx.clone()).await, Err(IngressError::Duplicate));
+        assert_eq!(ingress.submit_transaction_idempotent(tx, "order-2").await, Err(IngressError::Duplicate));
+
+        // Rejections are remembered too, and a key cannot be reused for other bytes
+        let rejected = ingress.submit_transaction_idempotent(vec![0x02, 0x01], "order-3").await;
+        assert!(matches!(rejected, Err(IngressError::InvalidTransaction(_))));
+        assert_eq!(ingress.submit_transaction_idempotent(vec![0x02, 0x01], "order-3").await, rejected);
+        assert_eq!(
+            ingress.submit_transaction_idempotent(signed_dynamic_fee_tx(2, 0), "order-1").await,
+            Err(IngressError::IdempotencyKeyConflict("order-1".to_string()))
+        );
+
+        assert_eq!(ingress.pending_snapshot().unwrap().count, 1);
+
+        // Being rate limited is not remembered, so a retry under the same key gets in once tokens refill
+        let limited = PenumIngress::new(10, Duration::from_secs(3600), Vec::new()).with_rate_limit(20.0, 1);
+        limited.submit_transaction(signed_dynamic_fee_tx(3, 0)).await.unwrap();
+        let next = signed_dynamic_fee_tx(3, 1);
+        assert_eq!(limited.submit_transaction_idempotent(next.clone(), "order-4").await, Err(IngressError::RateLimited));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(limited.submit_transaction_idempotent(next.clone(), "order-4").await, Ok(transaction_hash(&next)));
+
+        // Nor is an internal failure, here drawing randomness for the batch this submission completes
+        let mut failing = PenumIngress::new(1, Duration::from_secs(3600), Vec::new());
+        failing.batching_engine =
+            Arc::new((*failing.batching_engine).clone().with_entropy(Arc::new(FailingEntropy::after(0))));
+        let tx = signed_dynamic_fee_tx(4, 0);
+        assert_eq!(failing.submit_transaction_idempotent(tx.clone(), "order-5").await, Err(IngressError::RngFailure));
+        let now = failing.batching_engine.now();
+        assert!(failing.idempotency.lock().unwrap().get("order-5", &sha256_hash(&tx), now).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_submit_waits_for_pool_space() {
+        let relay = InMemoryRelay::new("memory");
+        let (first, second) = (signed_dynamic_fee_tx(1, 0), signed_dynamic_fee_tx(2, 0));
+        let ingress = PenumIngress::new(10, Duration::from_millis(100), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]))
+            .with_max_pending_bytes(first.len() + 10);
+        ingress.submit_transaction(first.clone()).await.unwrap();
+
+        // The pool is full, so the plain submit is refused and the async one waits
+        assert!(matches!(
+            ingress.submit_transaction(second.clone()).await,
+            Err(IngressError::PendingPoolFull { .. })
+        ));
+        let waiting = tokio::spawn({
+            let (ingress, second) = (ingress.clone(), second.clone());
+            async move { ingress.submit_transaction_async(second, Duration::from_secs(5)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        // The time window drains the pool into a batch, which lets the waiting submit in
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        ingress.process_batches().await.unwrap();
+        assert_eq!(waiting.await.unwrap(), Ok(transaction_hash(&second)));
+        assert_eq!(relay.forwarded().unwrap()[0].transactions[0].tx_bytes, first);
+        assert_eq!(ingress.pending_snapshot().unwrap().count, 1);
+
+        // Without a batch to make room, the wait ends with the pool-full error
+        let third = signed_dynamic_fee_tx(3, 0);
+        assert!(matches!(
+            ingress.submit_transaction_async(third, Duration::from_millis(30)).await,
+            Err(IngressError::PendingPoolFull { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_submit_checks_chain_id() {
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), Vec::new()).with_chain_id(1, false);
+
+        ingress.submit_transaction(dynamic_fee_tx_on_chain(1)).await.unwrap();
+        ingress.submit_transaction(legacy_tx(1)).await.unwrap();
+        assert_eq!(
+            ingress.submit_transaction(dynamic_fee_tx_on_chain(11155111)).await,
+            Err(IngressError::WrongChainId { expected: 1, found: Some(11155111) })
+        );
+        let unprotected = ingress.submit_transaction(unprotected_legacy_tx(2)).await.unwrap_err();
+        assert_eq!(unprotected, IngressError::WrongChainId { expected: 1, found: None });
+        assert_eq!(unprotected.to_string(), "Transaction chain ID missing does not match network chain ID 1");
+    }
+
+    #[tokio::test]
+    async fn test_returned_and_dedup_hash_is_the_ethereum_hash() {
+        let relay = InMemoryRelay::new("memory");
+        let ingress = PenumIngress::new(3, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]));
+        let tx = hex::decode(EIP155_EXAMPLE_TX).unwrap();
+
+        let tx_hash = ingress.submit_transaction(tx.clone()).await.unwrap();
+        assert_eq!(hex::encode(tx_hash), EIP155_EXAMPLE_TX_HASH);
+        assert_eq!(ingress.submit_transaction(tx.clone()).await, Err(IngressError::Duplicate));
+
+        // Dedup goes by the same hash, so a blob transaction resent without its sidecar is a repeat
+        ingress.submit_transaction(blob_tx(0, 1, true)).await.unwrap();
+        assert_eq!(ingress.submit_transaction(blob_tx(0, 1, false)).await, Err(IngressError::Duplicate));
+
+        // The batch's Merkle leaves are built on the Ethereum hash as well
+        ingress.submit_transaction(signed_dynamic_fee_tx(2, 0)).await.unwrap();
+        let batch = &relay.forwarded().unwrap()[0];
+        let leaf = CommitmentScheme::V3.tx_hash(&tx, 1, HashAlgo::Sha256);
+        assert_eq!(leaf, HashAlgo::Sha256.hash(&[&[0, 0, 0, 1][..], &tx_hash[..]].concat()));
+        let proof = batch.merkle_proof(&tx).unwrap();
+        assert_eq!(proof.scheme, CommitmentScheme::V3);
+        assert!(verify_merkle_proof(&batch.commitment, &tx, &proof));
+    }
+
+    #[tokio::test]
+    async fn test_rejections_counted_by_reason() {
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), Vec::new())
+            .with_chain_id(1, false)
+            .with_max_tx_bytes(200);
+        let mut bad_signature = dynamic_fee_tx(1);
+        let r_index = bad_signature.len() - 65;
+        bad_signature[r_index..r_index + 32].fill(0xff);
+
+        ingress.submit_transaction(dynamic_fee_tx(0)).await.unwrap();
+        for tx in [Vec::new(), vec![0xab; 300], dynamic_fee_tx(0), dynamic_fee_tx(0), bad_signature] {
+            assert!(ingress.submit_transaction(tx).await.is_err());
+        }
+        let wrong_chains = vec![dynamic_fee_tx_on_chain(5), dynamic_fee_tx_on_chain(10)];
+        assert!(ingress.submit_transactions(wrong_chains).await.iter().all(Result::is_err));
+
+        let expected = HashMap::from([
+            ("EmptyTransaction", 1),
+            ("TxTooLarge", 1),
+            ("Duplicate", 2),
+            ("InvalidSignature", 1),
+            ("WrongChainId", 2),
+        ]);
+        assert_eq!(ingress.metrics().rejection_counts(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_unprotected_transaction_accepted_when_allowed() {
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), Vec::new()).with_chain_id(1, true);
+
+        ingress.submit_transaction(unprotected_legacy_tx(0)).await.unwrap();
+        assert!(matches!(
+            ingress.submit_transaction(dynamic_fee_tx_on_chain(5)).await,
+            Err(IngressError::WrongChainId { expected: 1, found: Some(5) })
+        ));
+
+        // Without a configured network every chain is accepted
+        let unchecked = PenumIngress::new(10, Duration::from_secs(3600), Vec::new());
+        unchecked.submit_transaction(dynamic_fee_tx_on_chain(5)).await.unwrap();
+        unchecked.submit_transaction(unprotected_legacy_tx(0)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_submit_returns_transaction_hash() {
+        use sha3::{Digest, Keccak256};
+
+        let ingress = PenumIngress::new(3, Duration::from_secs(3600), Vec::new());
+        let tx = dynamic_fee_tx(0);
+
+        let tx_hash = ingress.submit_transaction(tx.clone()).await.unwrap();
+
+        let expected: [u8; 32] = Keccak256::digest(&tx).into();
+        assert_eq!(tx_hash, expected);
+    }
+
+    #[tokio::test]
+    async fn test_poisoned_lock_surfaces_from_submit() {
+        let ingress = PenumIngress::new(3, Duration::from_secs(3600), Vec::new());
+
+        ingress.batching_engine.default_lane.pending.poison();
+
+        assert_eq!(ingress.submit_transaction(dynamic_fee_tx(1)).await, Err(IngressError::LockPoisoned));
+    }
+}
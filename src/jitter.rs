@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use rand::rngs::OsRng;
+use rand::Rng;
+
+// Shape of the random delay added before a batch is released to relays
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JitterDistribution {
+    #[default]
+    Uniform,
+    Exponential, // Mostly short delays with a long tail, truncated at the maximum
+}
+
+// Draws a release delay in [0, max_release_jitter] from OS entropy
+pub fn sample_release_jitter(max_release_jitter: Duration, distribution: JitterDistribution) -> Duration {
+    if max_release_jitter.is_zero() {
+        return Duration::ZERO;
+    }
+
+    let fraction = match distribution {
+        JitterDistribution::Uniform => OsRng.gen_range(0.0..=1.0),
+        JitterDistribution::Exponential => {
+            // Mean of a third of the range keeps truncation at the maximum rare (about 5%)
+            let uniform: f64 = OsRng.gen_range(f64::EPSILON..1.0);
+            (-uniform.ln() / 3.0).min(1.0)
+        }
+    };
+    max_release_jitter.mul_f64(fraction)
+}
+
+// Draws the length of a batch window from an exponential distribution with the given mean, so releases
+// form a Poisson process and the time since the last one says nothing about when the next comes
+pub fn sample_poisson_window(mean: Duration) -> Duration {
+    // Excluding zero keeps the logarithm finite; the tail is cut off where -ln(EPSILON) lands, about 36 means
+    let uniform: f64 = OsRng.gen_range(f64::EPSILON..1.0);
+    mean.mul_f64(-uniform.ln())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Counts how many samples fall into each tenth of the jitter range
+    fn deciles(distribution: JitterDistribution) -> [usize; 10] {
+        let max = Duration::from_secs(10);
+        let mut buckets = [0; 10];
+        for _ in 0..2000 {
+            let jitter = sample_release_jitter(max, distribution);
+            assert!(jitter <= max);
+            buckets[((jitter.as_secs_f64() / 10.0 * 10.0) as usize).min(9)] += 1;
+        }
+        buckets
+    }
+
+    #[test]
+    fn test_uniform_jitter_spreads_across_range() {
+        // Roughly 200 per decile; a clustered draw would leave some nearly empty
+        for count in deciles(JitterDistribution::Uniform) {
+            assert!(count > 100, "decile count {} too low", count);
+        }
+    }
+
+    #[test]
+    fn test_exponential_jitter_favours_short_delays() {
+        let buckets = deciles(JitterDistribution::Exponential);
+        assert!(buckets[0] > buckets[5]);
+        assert!(buckets[..5].iter().all(|&count| count > 0));
+    }
+
+    #[test]
+    fn test_zero_jitter() {
+        assert_eq!(sample_release_jitter(Duration::ZERO, JitterDistribution::Exponential), Duration::ZERO);
+    }
+}
@@ -1 +1,122 @@
-pub mod analysis;
\ No newline at end of file
+pub mod analysis;
+pub mod anchor;
+pub mod anonymity;
+pub mod batch;
+pub mod batch_policy;
+pub mod batching;
+#[cfg(feature = "net")]
+pub mod bundle;
+#[cfg(feature = "net")]
+pub mod bundler;
+#[cfg(feature = "net")]
+pub mod chain;
+pub mod circuit_breaker;
+pub mod clock;
+pub mod commit_reveal;
+#[cfg(feature = "net")]
+pub mod compression;
+#[cfg(feature = "net")]
+pub mod config;
+mod crypto;
+mod decoy;
+#[cfg(feature = "net")]
+pub mod discovery;
+mod dedup;
+pub mod encryption;
+pub mod entropy;
+pub mod envelope;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "net")]
+mod idempotency;
+#[cfg(feature = "net")]
+pub mod ingress;
+pub mod jitter;
+pub mod merkle;
+pub mod metrics;
+#[cfg(feature = "net")]
+pub mod mev_share;
+#[cfg(feature = "metrics-server")]
+pub mod metrics_server;
+pub mod operator;
+pub mod ordering;
+mod pending;
+pub mod registry;
+#[cfg(feature = "net")]
+pub mod relay;
+#[cfg(feature = "net")]
+mod rate_limit;
+mod rlp;
+#[cfg(feature = "net")]
+pub mod routing;
+#[cfg(feature = "net")]
+pub mod replay;
+#[cfg(feature = "rpc-server")]
+pub mod rpc_server;
+#[cfg(test)]
+mod test_utils;
+pub mod service;
+pub mod shuffle;
+#[cfg(feature = "net")]
+pub mod simulated_relay;
+pub mod transaction;
+pub mod user_operation;
+pub mod wal;
+mod wire;
+#[cfg(feature = "ws-server")]
+pub mod ws_server;
+
+pub use anchor::CommitmentAnchor;
+#[cfg(feature = "net")]
+pub use anchor::EthereumAnchor;
+pub use anonymity::{k_anonymity, QuasiIdentifier};
+pub use batch::{BatchIdMode, BatchTrigger, TransactionBatch};
+pub use batch_policy::AdaptiveBatchPolicy;
+pub use batching::{BatchOrdering, BatchingEngine, PendingSnapshot, WindowMode};
+#[cfg(feature = "net")]
+pub use bundle::BundleRelay;
+#[cfg(feature = "net")]
+pub use bundler::{BundlerRelay, UserOpIngress, USER_OPERATION_METHOD};
+#[cfg(feature = "net")]
+pub use chain::ChainState;
+pub use circuit_breaker::{BreakerPolicy, BreakerState, CircuitBreaker};
+pub use clock::{Clock, MockClock, SystemClock};
+pub use commit_reveal::CommitRevealPipeline;
+#[cfg(feature = "net")]
+pub use compression::Compression;
+#[cfg(feature = "net")]
+pub use config::IngressConfig;
+pub use crypto::{Commitment, CommitmentScheme, HashAlgo, Nonce, ShuffleSecret, MIN_SHUFFLE_SECRET_LEN};
+#[cfg(feature = "net")]
+pub use discovery::{EnvRelayProvider, HttpRelayDiscovery, RelayProvider, StaticRelayProvider};
+pub use encryption::KeyShare;
+pub use entropy::{Entropy, OsEntropy, SeededEntropy};
+pub use envelope::{KnownAccount, PrivacyHints, TransactionConditions, TransactionEnvelope};
+pub use error::IngressError;
+#[cfg(feature = "net")]
+pub use ingress::{CommitHandle, PenumIngress};
+pub use jitter::JitterDistribution;
+pub use merkle::{canonical_commitment, verify_merkle_proof, MerkleProof};
+pub use metrics::{AggregateMetrics, LatencyStats, MetricsCollector, PrivacyReport};
+#[cfg(feature = "net")]
+pub use mev_share::MevShareRelay;
+pub use operator::{verify_operator_signature, OperatorKey};
+pub use ordering::{FeeDescendingPolicy, IdentityPolicy, OrderingPolicy, ShufflePolicy};
+pub use registry::{BatchRegistry, TxStatus};
+#[cfg(feature = "net")]
+pub use replay::{replay_wal, ReplayedBatch, WalReplay};
+#[cfg(feature = "net")]
+pub use relay::{BlockSchedule, HttpRelay, InMemoryRelay, RelayForwarder, RelayResult, RelayTransport, RetryPolicy};
+#[cfg(feature = "net")]
+pub use routing::{PriorityFeeRule, RelayGroup, RelayRouter, RoutingRule};
+pub use service::{Ingress, IngressFuture};
+pub use shuffle::{apply_permutation, shuffle_with_seed};
+#[cfg(feature = "net")]
+pub use simulated_relay::{SimulatedOutcome, SimulatedRelay, SimulatedResponse};
+pub use transaction::{
+    blob_sidecar_len, blob_versioned_hashes, recover_sender, transaction_fees, transaction_hash, transaction_nonce,
+    transaction_to, validate_transaction, Address, FeeBid, TxHash, TxType, BYTES_PER_BLOB,
+};
+pub use user_operation::UserOperation;
+pub use wal::{committed_batches, CommittedBatch, Recovered, WriteAheadLog};
@@ -0,0 +1,185 @@
+use crate::crypto::{Commitment, CommitmentScheme, HashAlgo};
+
+// Domain separation prefixes so a leaf can never be confused with an interior node
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+// One level of a Merkle membership proof
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofStep {
+    pub sibling: Vec<u8>,
+    pub sibling_on_left: bool,
+}
+
+// Proof that a single transaction is committed to by a batch, without revealing the others
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof {
+    pub leaf_salt: Vec<u8>, // Per-leaf salt derived from the batch nonce; the nonce itself stays hidden
+    pub path: Vec<ProofStep>,
+    pub hash_algo: HashAlgo, // Hash function of the batch the proof was built from
+    pub scheme: CommitmentScheme, // Commitment scheme of the batch the proof was built from
+    pub envelope_version: u32,    // Envelope version the transaction was committed with, bound since V2
+}
+
+// Helper function to derive the salt for one leaf: H(batch_nonce || tx_hash)
+fn leaf_salt(nonce: &[u8], tx_hash: &[u8], algo: HashAlgo) -> Vec<u8> {
+    let mut input = nonce.to_vec();
+    input.extend_from_slice(tx_hash);
+    algo.hash(&input)
+}
+
+// Helper function to hash a leaf: H(0x00 || tx_hash || salt)
+fn leaf_hash(tx_hash: &[u8], salt: &[u8], algo: HashAlgo) -> Vec<u8> {
+    let mut input = vec![LEAF_PREFIX];
+    input.extend_from_slice(tx_hash);
+    input.extend_from_slice(salt);
+    algo.hash(&input)
+}
+
+// Helper function to hash an interior node: H(0x01 || left || right)
+fn node_hash(left: &[u8], right: &[u8], algo: HashAlgo) -> Vec<u8> {
+    let mut input = vec![NODE_PREFIX];
+    input.extend_from_slice(left);
+    input.extend_from_slice(right);
+    algo.hash(&input)
+}
+
+// Builds the salted leaves for the sorted transaction hashes
+fn salted_leaves(sorted_tx_hashes: &[Vec<u8>], nonce: &[u8], algo: HashAlgo) -> Vec<Vec<u8>> {
+    sorted_tx_hashes
+        .iter()
+        .map(|tx_hash| leaf_hash(tx_hash, &leaf_salt(nonce, tx_hash, algo), algo))
+        .collect()
+}
+
+// Reduces one tree level; an unpaired last node is promoted unchanged rather than duplicated
+fn next_level(level: &[Vec<u8>], algo: HashAlgo) -> Vec<Vec<u8>> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash(left, right, algo),
+            [single] => single.clone(),
+            _ => unreachable!("chunks(2) yields one or two nodes"),
+        })
+        .collect()
+}
+
+// Helper function to put transaction hashes in canonical leaf order: lexicographic over their bytes,
+// most significant byte first, so the order never depends on how a hash type implements Ord
+fn canonical_order(tx_hashes: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let mut sorted = tx_hashes.to_vec();
+    sorted.sort_by(|a, b| a.as_slice().cmp(b.as_slice()));
+    sorted
+}
+
+// Batch commitment: Merkle root over the salted leaves of the canonically ordered transaction hashes
+//
+// Committing and verifying a reveal both go through here, so the two always agree.
+pub fn canonical_commitment(tx_hashes: &[Vec<u8>], nonce: &[u8], algo: HashAlgo) -> Commitment {
+    let sorted = canonical_order(tx_hashes);
+
+    // An empty batch still commits to its nonce
+    let root = if sorted.is_empty() {
+        algo.hash(nonce)
+    } else {
+        let mut level = salted_leaves(&sorted, nonce, algo);
+        while level.len() > 1 {
+            level = next_level(&level, algo);
+        }
+        level.remove(0)
+    };
+    Commitment::try_from(root.as_slice()).expect("both hash functions produce 32-byte digests")
+}
+
+// Builds a membership proof for tx_hash, or None if it is not part of the set
+pub(crate) fn build_merkle_proof(
+    tx_hashes: &[Vec<u8>],
+    nonce: &[u8],
+    tx_hash: &[u8],
+    algo: HashAlgo,
+    scheme: CommitmentScheme,
+    envelope_version: u32,
+) -> Option<MerkleProof> {
+    let sorted = canonical_order(tx_hashes);
+    let mut index = sorted.iter().position(|hash| hash.as_slice() == tx_hash)?;
+
+    let mut path = Vec::new();
+    let mut level = salted_leaves(&sorted, nonce, algo);
+    while level.len() > 1 {
+        let sibling_index = index ^ 1;
+        if let Some(sibling) = level.get(sibling_index) {
+            path.push(ProofStep {
+                sibling: sibling.clone(),
+                sibling_on_left: sibling_index < index,
+            });
+        }
+        level = next_level(&level, algo);
+        index /= 2;
+    }
+
+    Some(MerkleProof {
+        leaf_salt: leaf_salt(nonce, tx_hash, algo),
+        path,
+        hash_algo: algo,
+        scheme,
+        envelope_version,
+    })
+}
+
+// Verifies that tx_bytes is committed to by the given batch commitment (Merkle root)
+pub fn verify_merkle_proof(commitment: &Commitment, tx_bytes: &[u8], proof: &MerkleProof) -> bool {
+    let algo = proof.hash_algo;
+    let tx_hash = proof.scheme.tx_hash(tx_bytes, proof.envelope_version, algo);
+    let mut node = leaf_hash(&tx_hash, &proof.leaf_salt, algo);
+
+    for step in &proof.path {
+        node = if step.sibling_on_left {
+            node_hash(&step.sibling, &node, algo)
+        } else {
+            node_hash(&node, &step.sibling, algo)
+        };
+    }
+
+    node == commitment.as_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_root_is_order_independent_and_nonce_bound() {
+        let algo = HashAlgo::Sha256;
+        let hashes: Vec<Vec<u8>> = (0..5u8).map(|i| algo.hash(&[i])).collect();
+        let mut reversed = hashes.clone();
+        reversed.reverse();
+
+        assert_eq!(canonical_commitment(&hashes, &[1; 32], algo), canonical_commitment(&reversed, &[1; 32], algo));
+        assert_ne!(canonical_commitment(&hashes, &[1; 32], algo), canonical_commitment(&hashes, &[2; 32], algo));
+    }
+
+    #[test]
+    fn test_leaves_ordered_by_leading_byte_first() {
+        let hashes = vec![vec![0x02, 0x00], vec![0x01, 0xff], vec![0x01, 0x00, 0x00]];
+
+        assert_eq!(canonical_order(&hashes), vec![vec![0x01, 0x00, 0x00], vec![0x01, 0xff], vec![0x02, 0x00]]);
+    }
+
+    #[test]
+    fn test_proofs_verify_for_every_leaf() {
+        // Odd sizes exercise the promoted unpaired node
+        for algo in [HashAlgo::Sha256, HashAlgo::Keccak256] {
+            for size in 1..=7u8 {
+                let txs: Vec<Vec<u8>> = (0..size).map(|i| vec![0x02, i]).collect();
+                let hashes: Vec<Vec<u8>> = txs.iter().map(|tx| algo.hash(tx)).collect();
+                let root = canonical_commitment(&hashes, &[9; 32], algo);
+
+                for tx in &txs {
+                    let proof =
+                        build_merkle_proof(&hashes, &[9; 32], &algo.hash(tx), algo, CommitmentScheme::V1, 1).unwrap();
+                    assert!(verify_merkle_proof(&root, tx, &proof), "{:?} size {} proof failed", algo, size);
+                }
+            }
+        }
+    }
+}
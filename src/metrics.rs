@@ -0,0 +1,792 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, SystemTime};
+
+use crate::analysis::{estimate_correlation_success, measure_timing_correlation_reduction};
+use crate::batch::BatchTrigger;
+use crate::circuit_breaker::BreakerState;
+use crate::error::IngressError;
+
+// Histogram bucket upper bounds for the Prometheus exposition
+const BATCH_SIZE_BUCKETS: [f64; 8] = [1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 500.0];
+const LATENCY_MS_BUCKETS: [f64; 10] = [5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0];
+
+// Most recent samples kept per series; older ones are evicted so memory stays bounded
+pub const DEFAULT_SAMPLE_WINDOW: usize = 10_000;
+
+// Averages over every forwarded batch, plus the spread of their anonymity sets
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AggregateMetrics {
+    pub avg_batch_size: f64,
+    pub avg_latency_ms: f64,
+    pub min_anonymity_set: usize, // Fewest distinct senders in any batch, 0 before the first batch
+    pub avg_anonymity_set: f64,
+    pub max_anonymity_set: usize,
+    pub min_k_anonymity: usize, // Worst k-anonymity of any recent batch, 0 before the first batch
+}
+
+// Forwarding latency tail, by the nearest-rank method; all zero before the first batch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+// How well recent batches hid when each transaction was submitted; all zero before the first release
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PrivacyReport {
+    pub timing_correlation_reduction: f64, // Variance of release times over that of submission times
+    pub avg_anonymity_set: f64,
+    pub estimated_attack_success: f64, // Chance an observer links a submission to its transaction in the release
+}
+
+// Privacy-safe observability metrics; clones share the same underlying data
+#[derive(Clone)]
+pub struct MetricsCollector {
+    pub(crate) batch_sizes: Arc<Mutex<VecDeque<usize>>>,
+    pub(crate) forwarding_latencies: Arc<Mutex<VecDeque<Duration>>>,
+    anonymity_sets: Arc<Mutex<VecDeque<usize>>>, // Distinct senders per forwarded batch
+    k_anonymities: Arc<Mutex<VecDeque<usize>>>,  // Smallest quasi-identifier group per forwarded batch
+    release_timings: Arc<Mutex<VecDeque<(SystemTime, SystemTime)>>>, // (received, released) per forwarded transaction
+    sample_window: usize,                        // Capacity of each sample series above
+    max_release_jitter: Duration, // Releases this close together look alike to the privacy report's observer
+    // Exported histograms count every batch ever recorded, so scrapes only ever see them grow
+    batch_size_histogram: Arc<Mutex<Histogram>>,
+    anonymity_set_histogram: Arc<Mutex<Histogram>>,
+    latency_ms_histogram: Arc<Mutex<Histogram>>,
+    relay_acceptance_rates: Arc<Mutex<HashMap<String, (usize, usize)>>>, // (accepted, total)
+    relay_retries: Arc<Mutex<HashMap<String, usize>>>,
+    relay_skips: Arc<Mutex<HashMap<String, usize>>>, // Batches not sent because the relay's deadline had passed
+    relay_timeouts: Arc<Mutex<HashMap<String, usize>>>, // Batches the relay did not take within the request timeout
+    transaction_counts: Arc<Mutex<(usize, usize)>>, // (real, decoy) transactions in forwarded batches
+    latency_breaches: Arc<Mutex<usize>>, // Batches forwarded slower than the ingress's max acceptable latency
+    compression_bytes: Arc<Mutex<(usize, usize)>>, // (uncompressed, sent) bytes of compressed relay bodies
+    rejections: Arc<Mutex<HashMap<&'static str, u64>>>, // Refused submissions by IngressError variant
+    breaker_states: Arc<Mutex<HashMap<String, BreakerState>>>, // Last reported circuit breaker state per relay
+    batch_triggers: Arc<Mutex<HashMap<BatchTrigger, u64>>>, // Batches cut by the engine, by what cut them
+}
+
+impl Default for MetricsCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsCollector {
+    pub fn new() -> Self {
+        Self::with_sample_window(DEFAULT_SAMPLE_WINDOW)
+    }
+
+    // Keeps only the latest sample_window batch sizes, latencies and anonymity sets, so aggregates
+    // and percentiles describe recent behaviour
+    pub fn with_sample_window(sample_window: usize) -> Self {
+        Self {
+            batch_sizes: Arc::new(Mutex::new(VecDeque::new())),
+            forwarding_latencies: Arc::new(Mutex::new(VecDeque::new())),
+            anonymity_sets: Arc::new(Mutex::new(VecDeque::new())),
+            k_anonymities: Arc::new(Mutex::new(VecDeque::new())),
+            release_timings: Arc::new(Mutex::new(VecDeque::new())),
+            sample_window,
+            max_release_jitter: Duration::ZERO,
+            batch_size_histogram: Arc::new(Mutex::new(Histogram::new(&BATCH_SIZE_BUCKETS))),
+            anonymity_set_histogram: Arc::new(Mutex::new(Histogram::new(&BATCH_SIZE_BUCKETS))),
+            latency_ms_histogram: Arc::new(Mutex::new(Histogram::new(&LATENCY_MS_BUCKETS))),
+            relay_acceptance_rates: Arc::new(Mutex::new(HashMap::new())),
+            relay_retries: Arc::new(Mutex::new(HashMap::new())),
+            relay_skips: Arc::new(Mutex::new(HashMap::new())),
+            relay_timeouts: Arc::new(Mutex::new(HashMap::new())),
+            transaction_counts: Arc::new(Mutex::new((0, 0))),
+            latency_breaches: Arc::new(Mutex::new(0)),
+            compression_bytes: Arc::new(Mutex::new((0, 0))),
+            rejections: Arc::new(Mutex::new(HashMap::new())),
+            breaker_states: Arc::new(Mutex::new(HashMap::new())),
+            batch_triggers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Tells the privacy report how far apart releases may be jittered, as configured on the ingress
+    pub fn with_max_release_jitter(mut self, max_release_jitter: Duration) -> Self {
+        self.max_release_jitter = max_release_jitter;
+        self
+    }
+
+    pub fn record_batch_size(&self, size: usize) {
+        let mut sizes = recover(&self.batch_sizes);
+        push_bounded(&mut sizes, size, self.sample_window);
+        recover(&self.batch_size_histogram).observe(size as f64);
+    }
+
+    pub fn record_forwarding_latency(&self, latency: Duration) {
+        let mut latencies = recover(&self.forwarding_latencies);
+        push_bounded(&mut latencies, latency, self.sample_window);
+        recover(&self.latency_ms_histogram).observe(latency.as_secs_f64() * 1000.0);
+    }
+
+    // Records how many distinct senders a batch hid its transactions among
+    pub fn record_anonymity_set(&self, distinct_senders: usize) {
+        let mut sets = recover(&self.anonymity_sets);
+        push_bounded(&mut sets, distinct_senders, self.sample_window);
+        recover(&self.anonymity_set_histogram).observe(distinct_senders as f64);
+    }
+
+    // Records a batch's k-anonymity, the size of its smallest group of transactions sharing a quasi-identifier
+    pub fn record_k_anonymity(&self, k: usize) {
+        let mut ks = recover(&self.k_anonymities);
+        push_bounded(&mut ks, k, self.sample_window);
+    }
+
+    // Records when each real transaction of a batch was received and that the batch was released at released
+    //
+    // Only the times are kept, never which transaction they belong to.
+    pub fn record_release(&self, received: impl IntoIterator<Item = SystemTime>, released: SystemTime) {
+        let mut timings = recover(&self.release_timings);
+        for received in received {
+            push_bounded(&mut timings, (received, released), self.sample_window);
+        }
+    }
+
+    // Counts one forwarding attempt to a relay, and whether the relay accepted it
+    pub fn record_relay_outcome(&self, relay_url: &str, accepted: bool) {
+        let mut rates = recover(&self.relay_acceptance_rates);
+        let (accepted_count, total) = rates.entry(relay_url.to_string()).or_insert((0, 0));
+        if accepted {
+            *accepted_count += 1;
+        }
+        *total += 1;
+    }
+
+    // Counts the real and decoy transactions of a forwarded batch
+    pub fn record_batch_composition(&self, real: usize, decoy: usize) {
+        let mut counts = recover(&self.transaction_counts);
+        counts.0 += real;
+        counts.1 += decoy;
+    }
+
+    // (real, decoy) transactions seen in forwarded batches so far
+    pub fn transaction_counts(&self) -> (usize, usize) {
+        *recover(&self.transaction_counts)
+    }
+
+    // Counts requests re-sent to a relay after transient failures
+    pub fn record_relay_retries(&self, relay_url: &str, retries: u32) {
+        let mut counts = recover(&self.relay_retries);
+        *counts.entry(relay_url.to_string()).or_insert(0) += retries as usize;
+    }
+
+    // Counts a batch that skipped the relay because its submission deadline had passed
+    pub fn record_relay_skipped(&self, relay_url: &str) {
+        let mut counts = recover(&self.relay_skips);
+        *counts.entry(relay_url.to_string()).or_insert(0) += 1;
+    }
+
+    // Batches that skipped the relay so far
+    pub fn skipped_count(&self, relay_url: &str) -> usize {
+        recover(&self.relay_skips).get(relay_url).copied().unwrap_or(0)
+    }
+
+    // Counts a batch the relay did not take within the request timeout
+    pub fn record_relay_timeout(&self, relay_url: &str) {
+        let mut counts = recover(&self.relay_timeouts);
+        *counts.entry(relay_url.to_string()).or_insert(0) += 1;
+    }
+
+    // Batches the relay timed out on so far
+    pub fn timeout_count(&self, relay_url: &str) -> usize {
+        recover(&self.relay_timeouts).get(relay_url).copied().unwrap_or(0)
+    }
+
+    // Counts a batch whose forwarding took longer than the acceptable latency
+    pub fn record_latency_breach(&self) {
+        *recover(&self.latency_breaches) += 1;
+    }
+
+    // Batches forwarded too slowly so far
+    pub fn latency_breach_count(&self) -> usize {
+        *recover(&self.latency_breaches)
+    }
+
+    // Counts a body sent compressed to a relay, before and after compression
+    pub fn record_compression(&self, uncompressed: usize, sent: usize) {
+        let mut bytes = recover(&self.compression_bytes);
+        bytes.0 += uncompressed;
+        bytes.1 += sent;
+    }
+
+    // Uncompressed over sent bytes across every compressed body, or None before the first
+    pub fn compression_ratio(&self) -> Option<f64> {
+        let (uncompressed, sent) = *recover(&self.compression_bytes);
+        (sent > 0).then(|| uncompressed as f64 / sent as f64)
+    }
+
+    // Counts a submission the ingress refused, under the error's variant name
+    pub fn record_rejection(&self, error: &IngressError) {
+        *recover(&self.rejections).entry(error.kind()).or_insert(0) += 1;
+    }
+
+    // Submissions refused so far, by IngressError variant name, e.g. "WrongChainId"
+    pub fn rejection_counts(&self) -> HashMap<&'static str, u64> {
+        recover(&self.rejections).clone()
+    }
+
+    // Records where the relay's circuit breaker stands after a batch
+    pub fn record_breaker_state(&self, relay_url: &str, state: BreakerState) {
+        recover(&self.breaker_states).insert(relay_url.to_string(), state);
+    }
+
+    // Last reported state of the relay's circuit breaker, or None if it has none or was never used
+    pub fn breaker_state(&self, relay_url: &str) -> Option<BreakerState> {
+        recover(&self.breaker_states).get(relay_url).copied()
+    }
+
+    // Counts a batch the engine cut, under the trigger that cut it
+    pub fn record_batch_trigger(&self, trigger: BatchTrigger) {
+        *recover(&self.batch_triggers).entry(trigger).or_insert(0) += 1;
+    }
+
+    // Batches cut so far, by trigger; a trigger that never fired is absent
+    pub fn batch_trigger_counts(&self) -> HashMap<BatchTrigger, u64> {
+        recover(&self.batch_triggers).clone()
+    }
+
+    // Fraction of forwarding attempts the relay accepted, or None if it was never used
+    pub fn acceptance_rate(&self, relay_url: &str) -> Option<f64> {
+        let rates = recover(&self.relay_acceptance_rates);
+        rates
+            .get(relay_url)
+            .filter(|(_, total)| *total > 0)
+            .map(|(accepted, total)| *accepted as f64 / *total as f64)
+    }
+
+    pub fn get_aggregate_metrics(&self) -> AggregateMetrics {
+        let sizes = recover(&self.batch_sizes);
+        let latencies = recover(&self.forwarding_latencies);
+        let anonymity_sets = recover(&self.anonymity_sets);
+        let k_anonymities = recover(&self.k_anonymities);
+
+        let avg_size = if sizes.is_empty() {
+            0.0
+        } else {
+            sizes.iter().sum::<usize>() as f64 / sizes.len() as f64
+        };
+
+        let avg_latency = if latencies.is_empty() {
+            0.0
+        } else {
+            latencies.iter().map(|d| d.as_millis() as f64).sum::<f64>() / latencies.len() as f64
+        };
+
+        let avg_anonymity_set = if anonymity_sets.is_empty() {
+            0.0
+        } else {
+            anonymity_sets.iter().sum::<usize>() as f64 / anonymity_sets.len() as f64
+        };
+
+        AggregateMetrics {
+            avg_batch_size: avg_size,
+            avg_latency_ms: avg_latency,
+            min_anonymity_set: anonymity_sets.iter().copied().min().unwrap_or(0),
+            avg_anonymity_set,
+            max_anonymity_set: anonymity_sets.iter().copied().max().unwrap_or(0),
+            min_k_anonymity: k_anonymities.iter().copied().min().unwrap_or(0),
+        }
+    }
+
+    // Percentiles of the forwarding latency, which the mean hides when a few batches miss the block
+    pub fn latency_percentiles(&self) -> LatencyStats {
+        let mut latencies: Vec<Duration> = recover(&self.forwarding_latencies).iter().copied().collect();
+        if latencies.is_empty() {
+            return LatencyStats::default();
+        }
+        latencies.sort();
+
+        // Smallest latency at or above the given fraction of all samples
+        let percentile = |fraction: f64| {
+            let rank = (fraction * latencies.len() as f64).ceil() as usize;
+            latencies[rank.clamp(1, latencies.len()) - 1]
+        };
+        LatencyStats {
+            p50: percentile(0.50),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: latencies[latencies.len() - 1],
+        }
+    }
+
+    // Timing correlation, anonymity set and correlation attack estimate over the recent releases
+    //
+    // The modelled observer sees every submission and every release time exactly, and guesses uniformly
+    // among the transactions released within the max release jitter of the first release after a
+    // submission, which always includes the whole group released with it.
+    pub fn privacy_report(&self) -> PrivacyReport {
+        let timings = recover(&self.release_timings);
+        if timings.is_empty() {
+            return PrivacyReport::default();
+        }
+        let submissions: Vec<(usize, SystemTime)> = timings.iter().enumerate().map(|(i, (received, _))| (i, *received)).collect();
+        let releases: Vec<(usize, SystemTime)> = timings.iter().enumerate().map(|(i, (_, released))| (i, *released)).collect();
+        drop(timings);
+
+        // Transactions released at the same moment went out in one batch
+        let mut release_groups: HashMap<SystemTime, usize> = HashMap::new();
+        for (_, released) in &releases {
+            *release_groups.entry(*released).or_default() += 1;
+        }
+        let smallest_group = release_groups.values().copied().min().unwrap_or(1);
+
+        PrivacyReport {
+            timing_correlation_reduction: measure_timing_correlation_reduction(&submissions, &releases),
+            avg_anonymity_set: self.get_aggregate_metrics().avg_anonymity_set,
+            estimated_attack_success: estimate_correlation_success(
+                &submissions,
+                &releases,
+                smallest_group,
+                self.max_release_jitter,
+            ),
+        }
+    }
+
+    // Renders all metrics in the Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        let sizes = recover(&self.batch_size_histogram).clone();
+        let anonymity_sets = recover(&self.anonymity_set_histogram).clone();
+        let latencies = recover(&self.latency_ms_histogram).clone();
+        // Sorted so the output is stable between scrapes
+        let rates: BTreeMap<String, (usize, usize)> = recover(&self.relay_acceptance_rates)
+            .iter()
+            .map(|(url, counts)| (url.clone(), *counts))
+            .collect();
+        let retries: BTreeMap<String, usize> = recover(&self.relay_retries)
+            .iter()
+            .map(|(url, count)| (url.clone(), *count))
+            .collect();
+        let skips: BTreeMap<String, usize> = recover(&self.relay_skips)
+            .iter()
+            .map(|(url, count)| (url.clone(), *count))
+            .collect();
+        let timeouts: BTreeMap<String, usize> = recover(&self.relay_timeouts)
+            .iter()
+            .map(|(url, count)| (url.clone(), *count))
+            .collect();
+        let breaker_states: BTreeMap<String, BreakerState> = recover(&self.breaker_states)
+            .iter()
+            .map(|(url, state)| (url.clone(), *state))
+            .collect();
+        let rejections: BTreeMap<&str, u64> = recover(&self.rejections).iter().map(|(&kind, &count)| (kind, count)).collect();
+
+        let batch_triggers = self.batch_trigger_counts();
+        let (real, decoy) = self.transaction_counts();
+        let latency_breaches = self.latency_breach_count();
+        let min_k_anonymity = self.get_aggregate_metrics().min_k_anonymity;
+        let compression_ratio = self.compression_ratio().unwrap_or(0.0);
+
+        let mut out = String::new();
+        write_histogram(&mut out, "penum_batch_size", "Number of transactions per batch", &sizes);
+        write_histogram(&mut out, "penum_anonymity_set", "Distinct senders per batch", &anonymity_sets);
+        write_histogram(
+            &mut out,
+            "penum_forward_latency_ms",
+            "Time taken to forward a batch to all relays, in milliseconds",
+            &latencies,
+        );
+
+        writeln!(out, "# HELP penum_batches_total Batches cut by the batching engine, by what triggered them").unwrap();
+        writeln!(out, "# TYPE penum_batches_total counter").unwrap();
+        for trigger in BatchTrigger::ALL {
+            let count = batch_triggers.get(&trigger).copied().unwrap_or(0);
+            writeln!(out, "penum_batches_total{{trigger=\"{}\"}} {}", trigger.as_str(), count).unwrap();
+        }
+
+        writeln!(out, "# HELP penum_transactions_total Transactions in forwarded batches, real or decoy padding").unwrap();
+        writeln!(out, "# TYPE penum_transactions_total counter").unwrap();
+        writeln!(out, "penum_transactions_total{{kind=\"real\"}} {}", real).unwrap();
+        writeln!(out, "penum_transactions_total{{kind=\"decoy\"}} {}", decoy).unwrap();
+
+        writeln!(out, "# HELP penum_k_anonymity_min Smallest quasi-identifier group in any recent batch").unwrap();
+        writeln!(out, "# TYPE penum_k_anonymity_min gauge").unwrap();
+        writeln!(out, "penum_k_anonymity_min {}", min_k_anonymity).unwrap();
+
+        writeln!(out, "# HELP penum_latency_breach_total Batches forwarded slower than the maximum acceptable latency").unwrap();
+        writeln!(out, "# TYPE penum_latency_breach_total counter").unwrap();
+        writeln!(out, "penum_latency_breach_total {}", latency_breaches).unwrap();
+
+        writeln!(out, "# HELP penum_compression_ratio Uncompressed over sent bytes of compressed relay bodies").unwrap();
+        writeln!(out, "# TYPE penum_compression_ratio gauge").unwrap();
+        writeln!(out, "penum_compression_ratio {}", compression_ratio).unwrap();
+
+        writeln!(out, "# HELP penum_relay_accepted_total Batches accepted by each relay").unwrap();
+        writeln!(out, "# TYPE penum_relay_accepted_total counter").unwrap();
+        for (url, (accepted, _)) in &rates {
+            writeln!(out, "penum_relay_accepted_total{{relay=\"{}\"}} {}", escape_label(url), accepted).unwrap();
+        }
+
+        writeln!(out, "# HELP penum_relay_total Batches forwarded to each relay").unwrap();
+        writeln!(out, "# TYPE penum_relay_total counter").unwrap();
+        for (url, (_, total)) in &rates {
+            writeln!(out, "penum_relay_total{{relay=\"{}\"}} {}", escape_label(url), total).unwrap();
+        }
+
+        writeln!(out, "# HELP penum_relay_retries_total Requests retried after a transient relay failure").unwrap();
+        writeln!(out, "# TYPE penum_relay_retries_total counter").unwrap();
+        for (url, count) in &retries {
+            writeln!(out, "penum_relay_retries_total{{relay=\"{}\"}} {}", escape_label(url), count).unwrap();
+        }
+
+        writeln!(out, "# HELP penum_relay_skipped_total Batches not sent to a relay past its submission deadline").unwrap();
+        writeln!(out, "# TYPE penum_relay_skipped_total counter").unwrap();
+        for (url, count) in &skips {
+            writeln!(out, "penum_relay_skipped_total{{relay=\"{}\"}} {}", escape_label(url), count).unwrap();
+        }
+
+        writeln!(out, "# HELP penum_relay_timeouts_total Batches a relay did not take within the request timeout").unwrap();
+        writeln!(out, "# TYPE penum_relay_timeouts_total counter").unwrap();
+        for (url, count) in &timeouts {
+            writeln!(out, "penum_relay_timeouts_total{{relay=\"{}\"}} {}", escape_label(url), count).unwrap();
+        }
+
+        writeln!(out, "# HELP penum_relay_circuit_state Relay circuit breakers: 0 closed, 1 half-open, 2 open").unwrap();
+        writeln!(out, "# TYPE penum_relay_circuit_state gauge").unwrap();
+        for (url, state) in &breaker_states {
+            let value = match state {
+                BreakerState::Closed => 0,
+                BreakerState::HalfOpen => 1,
+                BreakerState::Open => 2,
+            };
+            writeln!(out, "penum_relay_circuit_state{{relay=\"{}\"}} {}", escape_label(url), value).unwrap();
+        }
+
+        writeln!(out, "# HELP penum_rejections_total Submissions refused by the ingress, by reason").unwrap();
+        writeln!(out, "# TYPE penum_rejections_total counter").unwrap();
+        for (reason, count) in &rejections {
+            writeln!(out, "penum_rejections_total{{reason=\"{}\"}} {}", reason, count).unwrap();
+        }
+
+        out
+    }
+}
+
+// Helper function to lock a series, recovering it from a thread that panicked while holding it
+//
+// Every update is a single push or increment, so a recovered series is at worst missing the sample
+// that was being recorded; metrics must never take batching down with them.
+fn recover<T>(series: &Mutex<T>) -> MutexGuard<'_, T> {
+    series.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Helper function to append a sample, evicting the oldest ones beyond capacity
+fn push_bounded<T>(samples: &mut VecDeque<T>, sample: T, capacity: usize) {
+    samples.push_back(sample);
+    while samples.len() > capacity {
+        samples.pop_front();
+    }
+}
+
+// Cumulative Prometheus histogram over fixed bucket bounds; observations are counted, never stored
+#[derive(Clone)]
+struct Histogram {
+    bounds: &'static [f64],
+    bucket_counts: Vec<u64>, // Observations at or below each bound
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            bucket_counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, bucket_count) in self.bounds.iter().zip(&mut self.bucket_counts) {
+            if value <= *bound {
+                *bucket_count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+// Helper function to write one cumulative histogram with its _bucket, _sum and _count series
+fn write_histogram(out: &mut String, name: &str, help: &str, histogram: &Histogram) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} histogram", name).unwrap();
+    for (bound, count) in histogram.bounds.iter().zip(&histogram.bucket_counts) {
+        writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count).unwrap();
+    }
+    writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, histogram.count).unwrap();
+    writeln!(out, "{}_sum {}", name, histogram.sum).unwrap();
+    writeln!(out, "{}_count {}", name, histogram.count).unwrap();
+}
+
+// Escapes a label value as required by the exposition format
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (metric name, labels, value) of one exposition sample line
+    type Sample = (String, Vec<(String, String)>, f64);
+
+    // Minimal exposition parser, panics on any line that is not a valid sample
+    fn parse_samples(text: &str) -> Vec<Sample> {
+        text.lines()
+            .filter(|line| !line.starts_with('#') && !line.is_empty())
+            .map(|line| {
+                let (series, value) = line.rsplit_once(' ').expect("sample needs a value");
+                let value: f64 = value.parse().expect("sample value must be numeric");
+                let (name, labels) = match series.split_once('{') {
+                    Some((name, rest)) => {
+                        let labels = rest
+                            .strip_suffix('}')
+                            .expect("unterminated label set")
+                            .split(',')
+                            .map(|pair| {
+                                let (key, value) = pair.split_once('=').expect("label needs a value");
+                                (key.to_string(), value.trim_matches('"').to_string())
+                            })
+                            .collect();
+                        (name, labels)
+                    }
+                    None => (series, Vec::new()),
+                };
+                (name.to_string(), labels, value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_acceptance_rate_tracks_mixed_outcomes() {
+        let metrics = MetricsCollector::new();
+        assert_eq!(metrics.acceptance_rate("https://relay.a"), None);
+
+        metrics.record_relay_outcome("https://relay.a", true);
+        metrics.record_relay_outcome("https://relay.a", false);
+        metrics.record_relay_outcome("https://relay.a", true);
+        metrics.record_relay_outcome("https://relay.a", false);
+        metrics.record_relay_outcome("https://relay.b", false);
+
+        assert_eq!(metrics.acceptance_rate("https://relay.a"), Some(0.5));
+        assert_eq!(metrics.acceptance_rate("https://relay.b"), Some(0.0));
+        assert_eq!(metrics.acceptance_rate("https://relay.c"), None);
+    }
+
+    #[test]
+    fn test_anonymity_set_spread() {
+        let metrics = MetricsCollector::new();
+        let empty = metrics.get_aggregate_metrics();
+        assert_eq!((empty.min_anonymity_set, empty.avg_anonymity_set, empty.max_anonymity_set), (0, 0.0, 0));
+
+        for distinct_senders in [4, 1, 7] {
+            metrics.record_anonymity_set(distinct_senders);
+        }
+
+        let aggregate = metrics.get_aggregate_metrics();
+        assert_eq!(aggregate.min_anonymity_set, 1);
+        assert_eq!(aggregate.avg_anonymity_set, 4.0);
+        assert_eq!(aggregate.max_anonymity_set, 7);
+    }
+
+    #[test]
+    fn test_latency_percentiles_expose_the_tail() {
+        let metrics = MetricsCollector::new();
+        assert_eq!(metrics.latency_percentiles(), LatencyStats::default());
+
+        // 90 fast batches, 8 slow ones and 2 that miss the block entirely
+        for _ in 0..90 {
+            metrics.record_forwarding_latency(Duration::from_millis(10));
+        }
+        for _ in 0..8 {
+            metrics.record_forwarding_latency(Duration::from_millis(200));
+        }
+        metrics.record_forwarding_latency(Duration::from_millis(4000));
+        metrics.record_forwarding_latency(Duration::from_millis(9000));
+
+        let stats = metrics.latency_percentiles();
+        assert_eq!(stats.p50, Duration::from_millis(10));
+        assert_eq!(stats.p95, Duration::from_millis(200));
+        assert_eq!(stats.p99, Duration::from_millis(4000));
+        assert_eq!(stats.max, Duration::from_millis(9000));
+
+        // The mean alone stays well under a block time
+        assert!(metrics.get_aggregate_metrics().avg_latency_ms < 200.0);
+    }
+
+    #[test]
+    fn test_latency_percentiles_of_one_sample() {
+        let metrics = MetricsCollector::new();
+        metrics.record_forwarding_latency(Duration::from_millis(42));
+
+        let stats = metrics.latency_percentiles();
+        let only = Duration::from_millis(42);
+        assert_eq!((stats.p50, stats.p95, stats.p99, stats.max), (only, only, only, only));
+    }
+
+    #[test]
+    fn test_sample_window_evicts_oldest() {
+        let metrics = MetricsCollector::with_sample_window(3);
+        for size in 1..=5 {
+            metrics.record_batch_size(size);
+            metrics.record_forwarding_latency(Duration::from_millis(size as u64 * 100));
+            metrics.record_anonymity_set(size);
+        }
+
+        assert_eq!(metrics.batch_sizes.lock().unwrap().len(), 3);
+        assert_eq!(metrics.forwarding_latencies.lock().unwrap().len(), 3);
+
+        // Only sizes 3, 4 and 5 remain
+        let aggregate = metrics.get_aggregate_metrics();
+        assert_eq!(aggregate.avg_batch_size, 4.0);
+        assert_eq!(aggregate.avg_latency_ms, 400.0);
+        assert_eq!((aggregate.min_anonymity_set, aggregate.max_anonymity_set), (3, 5));
+        assert_eq!(metrics.latency_percentiles().p50, Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_exported_histograms_outlive_sample_window() {
+        let metrics = MetricsCollector::with_sample_window(2);
+        let count_of = |metrics: &MetricsCollector, name: &str| {
+            parse_samples(&metrics.render_prometheus())
+                .into_iter()
+                .find(|(n, labels, _)| n == name && labels.is_empty())
+                .map(|(_, _, value)| value)
+        };
+
+        let mut last = 0.0;
+        for size in 1..=5 {
+            metrics.record_batch_size(size);
+            metrics.record_anonymity_set(size);
+            metrics.record_forwarding_latency(Duration::from_millis(10));
+            let count = count_of(&metrics, "penum_batch_size_count").unwrap();
+            assert!(count > last, "histogram count went from {} to {}", last, count);
+            last = count;
+        }
+
+        // Evicted samples still count, while the aggregates only see the window
+        assert_eq!(count_of(&metrics, "penum_batch_size_sum"), Some(15.0));
+        assert_eq!(count_of(&metrics, "penum_anonymity_set_count"), Some(5.0));
+        assert_eq!(count_of(&metrics, "penum_forward_latency_ms_count"), Some(5.0));
+        assert_eq!(metrics.get_aggregate_metrics().avg_batch_size, 4.5);
+    }
+
+    #[test]
+    fn test_privacy_report_from_recorded_releases() {
+        let metrics = MetricsCollector::new();
+        assert_eq!(metrics.privacy_report(), PrivacyReport::default());
+
+        let at = |ms: u64| SystemTime::UNIX_EPOCH + Duration::from_millis(ms);
+        metrics.record_release([at(0), at(100), at(200)], at(500));
+        metrics.record_anonymity_set(3);
+        metrics.record_release([at(600), at(700)], at(1000));
+        metrics.record_anonymity_set(2);
+
+        let report = metrics.privacy_report();
+        // Release variance 60000 ms² over submission variance 77600 ms²
+        assert!((report.timing_correlation_reduction - 60_000.0 / 77_600.0).abs() < 1e-9);
+        assert_eq!(report.avg_anonymity_set, 2.5);
+        // Each submission is guessed among the transactions released with it: (3 · 1/3 + 2 · 1/2) / 5
+        assert!((report.estimated_attack_success - 0.4).abs() < 1e-9);
+
+        // With jitter covering both releases the observer can no longer tell them apart
+        let jittered = metrics.with_max_release_jitter(Duration::from_millis(500));
+        assert!((jittered.privacy_report().estimated_attack_success - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_poisoned_series_keeps_recording() {
+        let metrics = MetricsCollector::new();
+        metrics.record_batch_size(4);
+        std::thread::scope(|scope| {
+            let _ = scope
+                .spawn(|| {
+                    let _sizes = metrics.batch_sizes.lock().unwrap();
+                    panic!("poisoning the batch size series");
+                })
+                .join();
+        });
+
+        metrics.record_batch_size(6);
+        assert_eq!(metrics.get_aggregate_metrics().avg_batch_size, 5.0);
+        assert!(metrics.render_prometheus().contains("penum_batch_size_count 2"));
+    }
+
+    #[test]
+    fn test_render_prometheus_exposition() {
+        let metrics = MetricsCollector::new();
+        metrics.record_batch_size(3);
+        metrics.record_batch_size(12);
+        metrics.record_forwarding_latency(Duration::from_millis(40));
+        metrics.record_relay_outcome("https://relay.a", true);
+        metrics.record_relay_outcome("https://relay.a", false);
+        metrics.record_relay_outcome("https://relay.b", true);
+        metrics.record_relay_retries("https://relay.b", 2);
+        metrics.record_relay_skipped("https://relay.a");
+        metrics.record_relay_timeout("https://relay.b");
+        metrics.record_batch_composition(3, 5);
+        metrics.record_anonymity_set(2);
+        metrics.record_latency_breach();
+        metrics.record_k_anonymity(3);
+        metrics.record_k_anonymity(1);
+        metrics.record_compression(900, 200);
+        metrics.record_compression(300, 100);
+        metrics.record_rejection(&IngressError::Duplicate);
+        metrics.record_breaker_state("https://relay.a", BreakerState::Closed);
+        metrics.record_breaker_state("https://relay.a", BreakerState::Open);
+        metrics.record_breaker_state("https://relay.b", BreakerState::HalfOpen);
+        metrics.record_batch_trigger(BatchTrigger::SizeThreshold);
+        metrics.record_batch_trigger(BatchTrigger::SizeThreshold);
+        metrics.record_batch_trigger(BatchTrigger::TimeWindow);
+
+        let text = metrics.render_prometheus();
+        let samples = parse_samples(&text);
+        let sample = |name: &str, labels: &[(&str, &str)]| {
+            samples
+                .iter()
+                .find(|(n, l, _)| {
+                    n == name && l.len() == labels.len() && labels.iter().all(|(k, v)| l.contains(&(k.to_string(), v.to_string())))
+                })
+                .map(|(_, _, value)| *value)
+        };
+
+        assert!(text.contains("# TYPE penum_batch_size histogram"));
+        assert!(text.contains("# TYPE penum_forward_latency_ms histogram"));
+        assert!(text.contains("# TYPE penum_relay_accepted_total counter"));
+        assert!(text.contains("# TYPE penum_relay_total counter"));
+        assert!(text.contains("# TYPE penum_relay_retries_total counter"));
+
+        assert_eq!(sample("penum_batch_size_bucket", &[("le", "5")]), Some(1.0));
+        assert_eq!(sample("penum_batch_size_bucket", &[("le", "+Inf")]), Some(2.0));
+        assert_eq!(sample("penum_batch_size_sum", &[]), Some(15.0));
+        assert_eq!(sample("penum_anonymity_set_bucket", &[("le", "1")]), Some(0.0));
+        assert_eq!(sample("penum_anonymity_set_bucket", &[("le", "2")]), Some(1.0));
+        assert_eq!(sample("penum_forward_latency_ms_bucket", &[("le", "25")]), Some(0.0));
+        assert_eq!(sample("penum_forward_latency_ms_bucket", &[("le", "50")]), Some(1.0));
+        assert_eq!(sample("penum_forward_latency_ms_count", &[]), Some(1.0));
+        assert_eq!(sample("penum_relay_accepted_total", &[("relay", "https://relay.a")]), Some(1.0));
+        assert_eq!(sample("penum_relay_total", &[("relay", "https://relay.a")]), Some(2.0));
+        assert_eq!(sample("penum_relay_total", &[("relay", "https://relay.b")]), Some(1.0));
+        assert_eq!(sample("penum_relay_retries_total", &[("relay", "https://relay.b")]), Some(2.0));
+        assert_eq!(sample("penum_relay_skipped_total", &[("relay", "https://relay.a")]), Some(1.0));
+        assert_eq!(sample("penum_relay_timeouts_total", &[("relay", "https://relay.b")]), Some(1.0));
+        assert_eq!(sample("penum_transactions_total", &[("kind", "real")]), Some(3.0));
+        assert_eq!(sample("penum_transactions_total", &[("kind", "decoy")]), Some(5.0));
+        assert_eq!(sample("penum_latency_breach_total", &[]), Some(1.0));
+        assert_eq!(sample("penum_k_anonymity_min", &[]), Some(1.0));
+        assert_eq!(sample("penum_compression_ratio", &[]), Some(4.0));
+        assert_eq!(sample("penum_batches_total", &[("trigger", "size_threshold")]), Some(2.0));
+        assert_eq!(sample("penum_batches_total", &[("trigger", "time_window")]), Some(1.0));
+        assert_eq!(sample("penum_batches_total", &[("trigger", "forced_flush")]), Some(0.0));
+        assert_eq!(sample("penum_rejections_total", &[("reason", "Duplicate")]), Some(1.0));
+        assert_eq!(sample("penum_relay_circuit_state", &[("relay", "https://relay.a")]), Some(2.0));
+        assert_eq!(sample("penum_relay_circuit_state", &[("relay", "https://relay.b")]), Some(1.0));
+    }
+}
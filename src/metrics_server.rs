@@ -0,0 +1,50 @@
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use tokio::net::TcpListener;
+
+use crate::metrics::MetricsCollector;
+
+// Content type of the Prometheus text exposition format
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+// Router exposing the collector on GET /metrics
+pub fn metrics_router(metrics: MetricsCollector) -> Router {
+    Router::new().route("/metrics", get(render)).with_state(metrics)
+}
+
+// Serves /metrics on the given listener until the task is dropped
+pub async fn serve_metrics(listener: TcpListener, metrics: MetricsCollector) -> std::io::Result<()> {
+    axum::serve(listener, metrics_router(metrics)).await
+}
+
+async fn render(State(metrics): State<MetricsCollector>) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], metrics.render_prometheus())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_metrics_endpoint_serves_exposition() {
+        let metrics = MetricsCollector::new();
+        metrics.record_batch_size(4);
+        metrics.record_forwarding_latency(Duration::from_millis(12));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_metrics(listener, metrics));
+
+        let response = reqwest::get(format!("http://{}/metrics", addr)).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()[header::CONTENT_TYPE.as_str()], PROMETHEUS_CONTENT_TYPE);
+
+        let body = response.text().await.unwrap();
+        assert!(body.contains("penum_batch_size_count 1"));
+        assert!(body.contains("penum_forward_latency_ms_count 1"));
+    }
+}
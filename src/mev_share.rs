@@ -0,0 +1,222 @@
+use crate::batch::TransactionBatch;
+use crate::bundle::{head_block, target_block, DEFAULT_BLOCK_OFFSET};
+use crate::envelope::PrivacyHints;
+use crate::relay::{post_json_rpc, RelayFuture, RelayResult, RelayTransport, RetryPolicy};
+
+// Version of the mev_sendBundle request format
+const MEV_SHARE_BUNDLE_VERSION: &str = "v0.1";
+
+// Relay speaking the MEV-Share mev_sendBundle API, sending each batch as one bundle whose privacy
+// section tells the matchmaker what searchers may see
+//
+// MEV-Share takes hints per bundle, so a batch shares only the hints every one of its transactions
+// allows; a single transaction with the default hints keeps the whole batch private.
+#[derive(Clone)]
+pub struct MevShareRelay {
+    relay_url: String,
+    node_url: String,
+    block_offset: u64,
+    client: reqwest::Client,
+    retry_policy: RetryPolicy,
+}
+
+impl MevShareRelay {
+    pub fn new(relay_url: String, node_url: String) -> Self {
+        Self {
+            relay_url,
+            node_url,
+            block_offset: DEFAULT_BLOCK_OFFSET,
+            client: reqwest::Client::new(),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    // Targets the block this many blocks after the current head
+    pub fn with_block_offset(mut self, block_offset: u64) -> Self {
+        self.block_offset = block_offset;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    // mev_sendBundle request for the batch, targeting target_block and, if the batch has an inclusion
+    // deadline, every block up to it
+    pub fn bundle_request(&self, batch: &TransactionBatch, target_block: u64) -> serde_json::Value {
+        let body: Vec<serde_json::Value> = batch
+            .transactions
+            .iter()
+            .map(|tx| serde_json::json!({"tx": format!("0x{}", hex::encode(&tx.tx_bytes)), "canRevert": false}))
+            .collect();
+        let shared = batch
+            .transactions
+            .iter()
+            .map(|tx| tx.privacy_hints)
+            .reduce(PrivacyHints::intersect)
+            .unwrap_or_default();
+
+        let mut inclusion = serde_json::json!({"block": format!("0x{:x}", target_block)});
+        if let Some(valid_until_block) = batch.valid_until_block() {
+            inclusion["maxBlock"] = serde_json::json!(format!("0x{:x}", valid_until_block.max(target_block)));
+        }
+
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "mev_sendBundle",
+            "params": [{
+                "version": MEV_SHARE_BUNDLE_VERSION,
+                "inclusion": inclusion,
+                "body": body,
+                "privacy": {"hints": hint_names(shared)},
+            }],
+        })
+    }
+
+    async fn send_bundle(&self, batch: &TransactionBatch) -> RelayResult {
+        let mut result = RelayResult::new(self.relay_url.as_str());
+        let target = head_block(&self.client, &self.node_url)
+            .await
+            .and_then(|head| target_block(head, self.block_offset, batch));
+        let target_block = match target {
+            Ok(target_block) => target_block,
+            Err(error) => {
+                result.error = Some(error);
+                return result;
+            }
+        };
+
+        let request = self.bundle_request(batch, target_block);
+        post_json_rpc(&self.client, &self.relay_url, &request, batch, &self.retry_policy, &mut result).await;
+        result
+    }
+}
+
+impl RelayTransport for MevShareRelay {
+    fn name(&self) -> &str {
+        &self.relay_url
+    }
+
+    fn submit<'a>(&'a self, batch: &'a TransactionBatch) -> RelayFuture<'a> {
+        Box::pin(self.send_bundle(batch))
+    }
+}
+
+// Helper function to name the shared fields as MEV-Share hints; sending none shares nothing
+//
+// The list is always sent, even empty, since a bundle without a privacy section gets the matchmaker's
+// default hints instead.
+fn hint_names(hints: PrivacyHints) -> Vec<&'static str> {
+    [
+        (hints.calldata, "calldata"),
+        (hints.contract_address, "contract_address"),
+        (hints.function_selector, "function_selector"),
+        (hints.logs, "logs"),
+    ]
+    .into_iter()
+    .filter_map(|(shared, name)| shared.then_some(name))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::TransactionEnvelope;
+    use crate::relay::RelayForwarder;
+    use crate::test_utils::{node_at_block, signed_dynamic_fee_tx};
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn batch_with_hints(hints: &[PrivacyHints]) -> TransactionBatch {
+        let transactions = hints
+            .iter()
+            .zip(1u8..)
+            .map(|(&hints, key)| {
+                let mut tx = TransactionEnvelope::new(signed_dynamic_fee_tx(key, 0), String::new());
+                tx.privacy_hints = hints;
+                tx
+            })
+            .collect();
+        TransactionBatch::new(transactions).unwrap()
+    }
+
+    #[test]
+    fn test_privacy_hints_map_to_mev_share_fields() {
+        let relay = MevShareRelay::new(String::new(), String::new());
+        let hints = |request: serde_json::Value| request["params"][0]["privacy"]["hints"].clone();
+
+        // Nothing is shared unless asked for, and the empty list is still sent
+        let private = relay.bundle_request(&batch_with_hints(&[PrivacyHints::default()]), 1);
+        assert_eq!(hints(private), serde_json::json!([]));
+
+        let everything = PrivacyHints {
+            calldata: true,
+            contract_address: true,
+            function_selector: true,
+            logs: true,
+        };
+        let request = relay.bundle_request(&batch_with_hints(&[everything]), 1);
+        assert_eq!(hints(request), serde_json::json!(["calldata", "contract_address", "function_selector", "logs"]));
+
+        // A batch shares only what every transaction allows
+        let selector_and_logs = PrivacyHints {
+            function_selector: true,
+            logs: true,
+            ..PrivacyHints::default()
+        };
+        let logs_and_calldata = PrivacyHints {
+            calldata: true,
+            logs: true,
+            ..PrivacyHints::default()
+        };
+        let request = relay.bundle_request(&batch_with_hints(&[selector_and_logs, logs_and_calldata]), 0x20);
+        assert_eq!(hints(request.clone()), serde_json::json!(["logs"]));
+        assert_eq!(request["method"], "mev_sendBundle");
+        assert_eq!(request["params"][0]["version"], "v0.1");
+        assert_eq!(request["params"][0]["inclusion"]["block"], "0x20");
+        assert_eq!(request["params"][0]["body"][1]["canRevert"], false);
+    }
+
+    #[test]
+    fn test_inclusion_deadline_sent_as_max_block() {
+        let relay = MevShareRelay::new(String::new(), String::new());
+        let mut batch = batch_with_hints(&[PrivacyHints::default(); 2]);
+        let inclusion = |batch: &TransactionBatch| relay.bundle_request(batch, 0x20)["params"][0]["inclusion"].clone();
+        assert_eq!(inclusion(&batch), serde_json::json!({"block": "0x20"}));
+
+        batch.transactions[0].valid_until_block = Some(0x30);
+        batch.transactions[1].valid_until_block = Some(0x24);
+        assert_eq!(inclusion(&batch), serde_json::json!({"block": "0x20", "maxBlock": "0x24"}));
+    }
+
+    #[tokio::test]
+    async fn test_batch_sent_as_one_mev_share_bundle() {
+        let node = node_at_block(0x1000).await;
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({"method": "mev_sendBundle"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": {"bundleHash": format!("0x{}", "ab".repeat(32))}}),
+            ))
+            .expect(1)
+            .mount(&relay)
+            .await;
+        let forwarder = RelayForwarder::from_transports(vec![Box::new(MevShareRelay::new(relay.uri(), node.uri()))]);
+        let batch = batch_with_hints(&[PrivacyHints::default(); 3]);
+
+        let results = forwarder.forward_batch(&batch).await;
+        assert!(results[0].is_success());
+
+        let requests = relay.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["params"][0]["inclusion"]["block"], "0x1001");
+        let expected: Vec<serde_json::Value> = batch
+            .transactions
+            .iter()
+            .map(|tx| serde_json::json!({"tx": format!("0x{}", hex::encode(&tx.tx_bytes)), "canRevert": false}))
+            .collect();
+        assert_eq!(body["params"][0]["body"], serde_json::json!(expected));
+    }
+}
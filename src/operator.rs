@@ -0,0 +1,105 @@
+use k256::ecdsa::signature::hazmat::{PrehashSigner, PrehashVerifier};
+use k256::ecdsa::{Signature, SigningKey, VerifyingKey};
+
+use crate::batch::TransactionBatch;
+use crate::crypto::Commitment;
+use crate::error::IngressError;
+use crate::transaction::keccak256;
+
+// Length of an operator signature: r || s, without a recovery id
+pub const OPERATOR_SIGNATURE_LEN: usize = 64;
+
+// Prefix keeping operator signatures from ever doubling as transaction or message signatures
+const SIGNING_DOMAIN: &[u8] = b"penum-ingress batch commitment";
+
+// The ingress operator's secp256k1 key, which signs every batch commitment it forwards
+#[derive(Clone)]
+pub struct OperatorKey {
+    signing_key: SigningKey,
+}
+
+impl OperatorKey {
+    // Key from a 32-byte secret scalar
+    pub fn from_bytes(secret: &[u8]) -> Result<Self, IngressError> {
+        let signing_key = SigningKey::from_slice(secret)
+            .map_err(|e| IngressError::Config(format!("operator key: {}", e)))?;
+        Ok(Self { signing_key })
+    }
+
+    // Compressed SEC1 public key that relays verify batches against
+    pub fn public_key(&self) -> Vec<u8> {
+        self.signing_key.verifying_key().to_encoded_point(true).as_bytes().to_vec()
+    }
+
+    pub fn sign_commitment(&self, commitment: &Commitment) -> Result<[u8; OPERATOR_SIGNATURE_LEN], IngressError> {
+        let signature: Signature = self
+            .signing_key
+            .sign_prehash(&signing_digest(commitment))
+            .map_err(|_| IngressError::InvalidOperatorSignature)?;
+        Ok(signature.to_bytes().into())
+    }
+}
+
+// Checks that the batch carries a signature over its commitment by the operator holding pubkey,
+// given as a compressed or uncompressed SEC1 public key
+pub fn verify_operator_signature(batch: &TransactionBatch, pubkey: &[u8]) -> Result<(), IngressError> {
+    let signature = batch.operator_signature.ok_or(IngressError::InvalidOperatorSignature)?;
+    let verifying_key = VerifyingKey::from_sec1_bytes(pubkey).map_err(|_| IngressError::InvalidOperatorSignature)?;
+    let signature = Signature::from_slice(&signature).map_err(|_| IngressError::InvalidOperatorSignature)?;
+
+    verifying_key
+        .verify_prehash(&signing_digest(&batch.commitment), &signature)
+        .map_err(|_| IngressError::InvalidOperatorSignature)
+}
+
+// Helper function to hash the commitment under the operator signing domain
+fn signing_digest(commitment: &Commitment) -> [u8; 32] {
+    let mut message = SIGNING_DOMAIN.to_vec();
+    message.extend_from_slice(commitment.as_bytes());
+    keccak256(&message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::TransactionEnvelope;
+
+    fn signed_batch(key: &OperatorKey) -> TransactionBatch {
+        let mut batch = TransactionBatch::new(vec![
+            TransactionEnvelope::new(vec![0x02, 0x01], "a".to_string()),
+            TransactionEnvelope::new(vec![0x02, 0x02], "b".to_string()),
+        ])
+        .unwrap();
+        batch.operator_signature = Some(key.sign_commitment(&batch.commitment).unwrap());
+        batch
+    }
+
+    #[test]
+    fn test_valid_signature_verifies() {
+        let key = OperatorKey::from_bytes(&[7; 32]).unwrap();
+        let batch = signed_batch(&key);
+
+        assert_eq!(verify_operator_signature(&batch, &key.public_key()), Ok(()));
+    }
+
+    #[test]
+    fn test_tampered_commitment_fails() {
+        let key = OperatorKey::from_bytes(&[7; 32]).unwrap();
+        let mut batch = signed_batch(&key);
+        batch.commitment.0[0] ^= 0x01;
+
+        assert_eq!(verify_operator_signature(&batch, &key.public_key()), Err(IngressError::InvalidOperatorSignature));
+    }
+
+    #[test]
+    fn test_other_operator_or_missing_signature_fails() {
+        let key = OperatorKey::from_bytes(&[7; 32]).unwrap();
+        let impostor = OperatorKey::from_bytes(&[8; 32]).unwrap();
+        let mut batch = signed_batch(&impostor);
+
+        assert_eq!(verify_operator_signature(&batch, &key.public_key()), Err(IngressError::InvalidOperatorSignature));
+        batch.operator_signature = None;
+        assert_eq!(verify_operator_signature(&batch, &key.public_key()), Err(IngressError::InvalidOperatorSignature));
+        assert!(matches!(OperatorKey::from_bytes(&[0; 32]), Err(IngressError::Config(_))));
+    }
+}
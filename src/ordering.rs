@@ -0,0 +1,99 @@
+use std::cmp::Reverse;
+
+use crate::envelope::TransactionEnvelope;
+use crate::shuffle::shuffle_in_place;
+use crate::transaction::transaction_fees;
+
+// Orders a batch's transactions before they are forwarded
+//
+// The seed is derived from the batch ID unless the engine pins one, so a policy that only depends on
+// the seed and the transactions can be replayed by anyone holding the ID. A policy must only reorder:
+// the batch is already committed to its transaction set. Each sender's nonces are put back in
+// ascending order afterwards, whatever the policy did.
+pub trait OrderingPolicy: Send + Sync {
+    fn order(&self, txs: &mut Vec<TransactionEnvelope>, seed: [u8; 32]);
+}
+
+// Deterministic privacy shuffle of the arrival order
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShufflePolicy;
+
+impl OrderingPolicy for ShufflePolicy {
+    fn order(&self, txs: &mut Vec<TransactionEnvelope>, seed: [u8; 32]) {
+        shuffle_in_place(txs, seed);
+    }
+}
+
+// Shuffle, then highest fee bids first; trades ordering privacy for inclusion
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FeeDescendingPolicy;
+
+impl OrderingPolicy for FeeDescendingPolicy {
+    fn order(&self, txs: &mut Vec<TransactionEnvelope>, seed: [u8; 32]) {
+        shuffle_in_place(txs, seed);
+        // Stable, so equal bids keep their shuffled order
+        txs.sort_by_cached_key(|tx| Reverse(transaction_fees(&tx.tx_bytes).unwrap_or_default()));
+    }
+}
+
+// Arrival order, or content order for content-addressed batches; reveals arrival order to every relay
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IdentityPolicy;
+
+impl OrderingPolicy for IdentityPolicy {
+    fn order(&self, _txs: &mut Vec<TransactionEnvelope>, _seed: [u8; 32]) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffle::shuffle_with_seed;
+    use crate::test_utils::fee_bidding_tx;
+
+    // Five transactions from distinct senders in ascending fee order, two of them bidding the same
+    fn fixed_input() -> Vec<TransactionEnvelope> {
+        [(1, 100), (2, 200), (3, 300), (4, 300), (5, 500)]
+            .into_iter()
+            .map(|(key, fee)| TransactionEnvelope::new(fee_bidding_tx(key, fee, fee * 10), String::new()))
+            .collect()
+    }
+
+    fn bytes(txs: &[TransactionEnvelope]) -> Vec<Vec<u8>> {
+        txs.iter().map(|tx| tx.tx_bytes.clone()).collect()
+    }
+
+    #[test]
+    fn test_shuffle_policy_applies_seeded_permutation() {
+        let mut txs = fixed_input();
+        ShufflePolicy.order(&mut txs, [3; 32]);
+
+        let permutation = shuffle_with_seed(&fixed_input(), [3; 32]);
+        let expected: Vec<Vec<u8>> = permutation.iter().map(|&index| fixed_input()[index].tx_bytes.clone()).collect();
+        assert_eq!(bytes(&txs), expected);
+        assert_ne!(bytes(&txs), bytes(&fixed_input()));
+    }
+
+    #[test]
+    fn test_fee_descending_policy_sorts_by_bid() {
+        let mut txs = fixed_input();
+        FeeDescendingPolicy.order(&mut txs, [3; 32]);
+
+        let fees: Vec<u128> = txs.iter().map(|tx| transaction_fees(&tx.tx_bytes).unwrap().max_fee).collect();
+        assert_eq!(fees, vec![5_000, 3_000, 3_000, 2_000, 1_000]);
+
+        // The tied bids keep the order the shuffle gave them
+        let mut shuffled = fixed_input();
+        ShufflePolicy.order(&mut shuffled, [3; 32]);
+        let tied = |txs: &[TransactionEnvelope]| {
+            bytes(txs).into_iter().filter(|tx| [2, 3].map(|i| fixed_input()[i].tx_bytes.clone()).contains(tx)).collect::<Vec<_>>()
+        };
+        assert_eq!(tied(&txs), tied(&shuffled));
+    }
+
+    #[test]
+    fn test_identity_policy_keeps_arrival_order() {
+        let mut txs = fixed_input();
+        IdentityPolicy.order(&mut txs, [3; 32]);
+        assert_eq!(bytes(&txs), bytes(&fixed_input()));
+    }
+}
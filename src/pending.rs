@@ -0,0 +1,170 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::SystemTime;
+
+use crate::envelope::TransactionEnvelope;
+use crate::error::{lock, IngressError};
+
+// Independently locked shards per pending pool, so concurrent submissions rarely wait on each other
+pub const PENDING_SHARDS: usize = 16;
+
+// A shard's transactions, each tagged with its arrival sequence number
+type Shard = Vec<(u64, TransactionEnvelope)>;
+
+// A lane's pending transactions, sharded by sender
+//
+// Every transaction of a sender lands in the same shard, so fee-bump replacement only ever looks
+// at one shard. Draining locks every shard at once and returns transactions in arrival order, so
+// a batch sees exactly what a single locked Vec would have held.
+pub(crate) struct PendingPool {
+    shards: Vec<Mutex<Shard>>,
+    len: AtomicUsize,    // Transactions across all shards, read without locking
+    next_seq: AtomicU64, // Arrival order across shards
+    cutover: Mutex<()>,  // Held while draining, so two threads reaching the threshold cut one batch
+    oldest: Mutex<Option<SystemTime>>, // Arrival of the longest-waiting transaction, None while empty
+}
+
+impl PendingPool {
+    pub(crate) fn new() -> Self {
+        Self {
+            shards: (0..PENDING_SHARDS).map(|_| Mutex::new(Vec::new())).collect(),
+            len: AtomicUsize::new(0),
+            next_seq: AtomicU64::new(0),
+            cutover: Mutex::new(()),
+            oldest: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len.load(Ordering::SeqCst)
+    }
+
+    // When the longest-waiting transaction arrived, if any are pending
+    pub(crate) fn oldest(&self) -> Option<SystemTime> {
+        *self.oldest_arrival()
+    }
+
+    // Locks the shard the transaction belongs to, returning it with the transaction's sequence number
+    pub(crate) fn shard_for(&self, tx: &TransactionEnvelope) -> Result<(MutexGuard<'_, Shard>, u64), IngressError> {
+        let seq = self.next_seq.fetch_add(1, Ordering::SeqCst);
+        Ok((lock(&self.shards[shard_index(tx, seq)])?, seq))
+    }
+
+    // Adds a transaction that arrived at now to a shard the caller holds locked through shard_for
+    pub(crate) fn push(&self, shard: &mut Shard, seq: u64, tx: TransactionEnvelope, now: SystemTime) {
+        shard.push((seq, tx));
+        self.len.fetch_add(1, Ordering::SeqCst);
+        self.oldest_arrival().get_or_insert(now);
+    }
+
+    // Adds transactions back to the pool, e.g. ones recovered from the log or drained for a batch that failed,
+    // counting them as pending since arrived
+    //
+    // A poisoned shard still holds a valid Vec, so nothing handed back is ever lost.
+    pub(crate) fn extend(&self, transactions: impl IntoIterator<Item = TransactionEnvelope>, arrived: SystemTime) {
+        for tx in transactions {
+            let seq = self.next_seq.fetch_add(1, Ordering::SeqCst);
+            let mut shard = self.shards[shard_index(&tx, seq)].lock().unwrap_or_else(PoisonError::into_inner);
+            shard.push((seq, tx));
+            self.len.fetch_add(1, Ordering::SeqCst);
+            // Updated under the shard lock, so a concurrent drain never leaves a transaction without an arrival
+            let mut oldest = self.oldest_arrival();
+            *oldest = Some(oldest.map_or(arrived, |oldest| oldest.min(arrived)));
+        }
+    }
+
+    // Serialises batch cutover; hold the guard across len checks and drain
+    pub(crate) fn cutover(&self) -> Result<MutexGuard<'_, ()>, IngressError> {
+        lock(&self.cutover)
+    }
+
+    // Removes every pending transaction, oldest first
+    //
+    // All shards are locked before any is emptied, so a poisoned shard leaves the pool untouched.
+    pub(crate) fn drain(&self) -> Result<Vec<TransactionEnvelope>, IngressError> {
+        let mut shards = self.shards.iter().map(lock).collect::<Result<Vec<_>, _>>()?;
+        let mut drained: Shard = shards.iter_mut().flat_map(|shard| std::mem::take(&mut **shard)).collect();
+        self.len.fetch_sub(drained.len(), Ordering::SeqCst);
+        *self.oldest_arrival() = None;
+        drop(shards);
+
+        drained.sort_by_key(|(seq, _)| *seq);
+        Ok(drained.into_iter().map(|(_, tx)| tx).collect())
+    }
+
+    // Removes every pending transaction matching remove, oldest first, leaving the rest in place
+    //
+    // The oldest arrival only resets once the pool is empty, so an age-triggered cut may come a
+    // little early after a removal.
+    pub(crate) fn remove_where(
+        &self,
+        mut remove: impl FnMut(&TransactionEnvelope) -> bool,
+    ) -> Result<Vec<TransactionEnvelope>, IngressError> {
+        let mut shards = self.shards.iter().map(lock).collect::<Result<Vec<_>, _>>()?;
+        let mut removed: Shard = Vec::new();
+        for shard in shards.iter_mut() {
+            let (taken, kept): (Shard, Shard) = std::mem::take(&mut **shard).into_iter().partition(|(_, tx)| remove(tx));
+            **shard = kept;
+            removed.extend(taken);
+        }
+        let remaining = self.len.fetch_sub(removed.len(), Ordering::SeqCst) - removed.len();
+        if remaining == 0 {
+            *self.oldest_arrival() = None;
+        }
+        drop(shards);
+
+        removed.sort_by_key(|(seq, _)| *seq);
+        Ok(removed.into_iter().map(|(_, tx)| tx).collect())
+    }
+
+    // Visits every pending transaction without removing or reordering any, returning the oldest arrival
+    //
+    // All shards are locked for the whole visit, so it sees the pool as of one instant.
+    pub(crate) fn inspect(&self, mut visit: impl FnMut(&TransactionEnvelope)) -> Result<Option<SystemTime>, IngressError> {
+        let shards = self.shards.iter().map(lock).collect::<Result<Vec<_>, _>>()?;
+        shards.iter().flat_map(|shard| shard.iter()).for_each(|(_, tx)| visit(tx));
+        Ok(*self.oldest_arrival())
+    }
+
+    // A poisoned lock still holds a valid time
+    fn oldest_arrival(&self) -> MutexGuard<'_, Option<SystemTime>> {
+        self.oldest.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Copy of every pending transaction, oldest first
+    #[cfg(test)]
+    pub(crate) fn snapshot(&self) -> Result<Vec<TransactionEnvelope>, IngressError> {
+        let mut pending: Shard = Vec::new();
+        for shard in &self.shards {
+            pending.extend(lock(shard)?.iter().cloned());
+        }
+        pending.sort_by_key(|(seq, _)| *seq);
+        Ok(pending.into_iter().map(|(_, tx)| tx).collect())
+    }
+
+    // Poisons every shard lock, for tests of the poisoned-lock paths
+    #[cfg(test)]
+    pub(crate) fn poison(&self) {
+        std::thread::scope(|scope| {
+            for shard in &self.shards {
+                let _ = scope
+                    .spawn(move || {
+                        let _guard = shard.lock().unwrap();
+                        panic!("poisoning a pending shard");
+                    })
+                    .join();
+            }
+        });
+    }
+}
+
+// Helper function to pick a transaction's shard
+//
+// Addresses are hash outputs, so their last byte spreads senders evenly; transactions without a
+// recorded sender are never replaced and are spread by arrival instead.
+fn shard_index(tx: &TransactionEnvelope, seq: u64) -> usize {
+    match tx.sender {
+        Some(sender) => sender[sender.len() - 1] as usize % PENDING_SHARDS,
+        None => (seq % PENDING_SHARDS as u64) as usize,
+    }
+}
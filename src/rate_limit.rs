@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::transaction::Address;
+
+// Buckets tracked before full ones are dropped; a full bucket behaves exactly like a missing one
+const PRUNE_THRESHOLD: usize = 4096;
+
+// Who a submission is charged to: the caller-supplied client ID, or else the recovered sender
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum RateLimitKey {
+    Client(String),
+    Sender(Address),
+}
+
+// Token bucket per key: each holds up to burst tokens and refills at rate tokens per second
+pub(crate) struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: HashMap<RateLimitKey, (f64, Instant)>, // (tokens left, last refill)
+}
+
+impl RateLimiter {
+    pub(crate) fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate,
+            burst: f64::from(burst),
+            buckets: HashMap::new(),
+        }
+    }
+
+    // Takes one token from the key's bucket, returning false if it is empty
+    pub(crate) fn try_acquire(&mut self, key: RateLimitKey, now: Instant) -> bool {
+        if self.buckets.len() >= PRUNE_THRESHOLD {
+            self.prune(now);
+        }
+
+        let (rate, burst) = (self.rate, self.burst);
+        let (tokens, last_refill) = self.buckets.entry(key).or_insert((burst, now));
+        *tokens = refilled(*tokens, *last_refill, now, rate, burst);
+        *last_refill = now;
+        if *tokens < 1.0 {
+            return false;
+        }
+        *tokens -= 1.0;
+        true
+    }
+
+    // Drops every bucket that has refilled completely so memory stays bounded by active clients
+    fn prune(&mut self, now: Instant) {
+        let (rate, burst) = (self.rate, self.burst);
+        self.buckets.retain(|_, (tokens, last_refill)| refilled(*tokens, *last_refill, now, rate, burst) < burst);
+    }
+}
+
+// Helper function to add the tokens earned since the last refill, capped at the burst size
+fn refilled(tokens: f64, last_refill: Instant, now: Instant, rate: f64, burst: f64) -> f64 {
+    let elapsed = now.saturating_duration_since(last_refill).as_secs_f64();
+    (tokens + elapsed * rate).min(burst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_burst_then_refill() {
+        let mut limiter = RateLimiter::new(2.0, 3);
+        let client = || RateLimitKey::Client("wallet".to_string());
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.try_acquire(client(), start));
+        }
+        assert!(!limiter.try_acquire(client(), start));
+        assert!(!limiter.try_acquire(client(), start + Duration::from_millis(400)));
+
+        // Half a second at two tokens per second buys exactly one more submission
+        assert!(limiter.try_acquire(client(), start + Duration::from_millis(500)));
+        assert!(!limiter.try_acquire(client(), start + Duration::from_millis(500)));
+
+        // Refill is capped at the burst size
+        let later = start + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert!(limiter.try_acquire(client(), later));
+        }
+        assert!(!limiter.try_acquire(client(), later));
+    }
+
+    #[test]
+    fn test_keys_have_separate_buckets() {
+        let mut limiter = RateLimiter::new(1.0, 1);
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire(RateLimitKey::Sender([1; 20]), now));
+        assert!(!limiter.try_acquire(RateLimitKey::Sender([1; 20]), now));
+        assert!(limiter.try_acquire(RateLimitKey::Sender([2; 20]), now));
+        assert!(limiter.try_acquire(RateLimitKey::Client("wallet".to_string()), now));
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use crate::batch::TransactionBatch;
+use crate::clock::{Clock, SystemClock};
+use crate::error::{lock, IngressError};
+use crate::transaction::transaction_hash;
+
+// How long a transaction is remembered once its status is final
+pub const DEFAULT_STATUS_RETENTION: Duration = Duration::from_secs(600);
+
+// Lifecycle of a submitted transaction, keyed by its Keccak-256 hash
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TxStatus {
+    Pending,         // Accepted, waiting for a batch
+    Batched(String), // Placed in the batch with this id
+    Committed,       // Batch commitment recorded, waiting to be revealed
+    Forwarded,       // Revealed and accepted by the relay quorum
+    Failed,          // Batch failed verification or missed its relay quorum
+    Expired,         // Its inclusion deadline passed before it was forwarded
+    Mined,           // Already on chain when its batch was released, so it was never forwarded
+}
+
+impl TxStatus {
+    // Whether the transaction has left the pipeline, so its status can no longer change
+    pub fn is_final(&self) -> bool {
+        matches!(self, TxStatus::Forwarded | TxStatus::Failed | TxStatus::Expired | TxStatus::Mined)
+    }
+}
+
+// Statuses by transaction hash, with final ones queued for forgetting in the order they became final
+#[derive(Default)]
+struct StatusLog {
+    statuses: HashMap<[u8; 32], (TxStatus, SystemTime)>, // Status and when it was set
+    finished_order: VecDeque<(SystemTime, [u8; 32])>,    // (finished at, tx hash) of final statuses, oldest first
+}
+
+impl StatusLog {
+    fn get(&self, tx_hash: &[u8; 32]) -> Option<&TxStatus> {
+        self.statuses.get(tx_hash).map(|(status, _)| status)
+    }
+
+    fn set(&mut self, tx_hash: [u8; 32], status: TxStatus, now: SystemTime) {
+        if status.is_final() {
+            self.finished_order.push_back((now, tx_hash));
+        }
+        self.statuses.insert(tx_hash, (status, now));
+    }
+
+    // Forgets transactions whose status has been final for retention; a transaction resubmitted
+    // since is kept, since its entry in the queue no longer matches when its status was set
+    fn prune(&mut self, now: SystemTime, retention: Duration) {
+        while let Some((finished_at, _)) = self.finished_order.front() {
+            if now.duration_since(*finished_at).unwrap_or_default() < retention {
+                break;
+            }
+            let Some((finished_at, tx_hash)) = self.finished_order.pop_front() else {
+                break;
+            };
+            if self.statuses.get(&tx_hash).is_some_and(|(status, set_at)| status.is_final() && *set_at == finished_at) {
+                self.statuses.remove(&tx_hash);
+            }
+        }
+    }
+}
+
+// Tracks where each submitted transaction is in the pipeline; clones share the statuses
+//
+// Only transaction hashes are stored, never senders, so lookups reveal nothing a relay doesn't see.
+// Transactions are forgotten once their status has been final for the retention period.
+#[derive(Clone)]
+pub struct BatchRegistry {
+    statuses: Arc<Mutex<StatusLog>>,
+    batch_members: Arc<Mutex<HashMap<String, Vec<[u8; 32]>>>>, // Real transaction hashes of batches in flight
+    retention: Duration,                                       // How long a final status is kept
+    clock: Arc<dyn Clock>,                                     // Times when statuses become final
+}
+
+impl Default for BatchRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BatchRegistry {
+    pub fn new() -> Self {
+        Self {
+            statuses: Arc::new(Mutex::new(StatusLog::default())),
+            batch_members: Arc::new(Mutex::new(HashMap::new())),
+            retention: DEFAULT_STATUS_RETENTION,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    // Reads time from the given clock instead of the system clock, e.g. the batching engine's
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    // Keeps a final status for retention instead of ten minutes
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn status_of(&self, tx_hash: &[u8]) -> Result<Option<TxStatus>, IngressError> {
+        let Ok(key) = <[u8; 32]>::try_from(tx_hash) else {
+            return Ok(None);
+        };
+        Ok(self.statuses()?.get(&key).cloned())
+    }
+
+    // Marks a transaction pending, unless a batch already picked it up in the meantime
+    pub fn record_pending(&self, tx_hash: [u8; 32]) -> Result<(), IngressError> {
+        let mut statuses = self.statuses()?;
+        if !matches!(statuses.get(&tx_hash), Some(TxStatus::Batched(_) | TxStatus::Committed)) {
+            statuses.set(tx_hash, TxStatus::Pending, self.clock.now());
+        }
+        Ok(())
+    }
+
+    // Moves every real transaction of a freshly created batch to Batched; decoys are not tracked
+    pub fn record_batched(&self, batch: &TransactionBatch) -> Result<(), IngressError> {
+        let members: Vec<[u8; 32]> = batch
+            .transactions
+            .iter()
+            .filter(|tx| !tx.is_decoy())
+            .map(|tx| transaction_hash(&tx.tx_bytes))
+            .collect();
+        self.record_members(&batch.id, members)
+    }
+
+    // Moves the given transactions to Batched as members of batch_id, for batches already encrypted
+    pub fn record_members(&self, batch_id: &str, members: Vec<[u8; 32]>) -> Result<(), IngressError> {
+        let mut statuses = self.statuses()?;
+        let now = self.clock.now();
+        for tx_hash in &members {
+            statuses.set(*tx_hash, TxStatus::Batched(batch_id.to_string()), now);
+        }
+        lock(&self.batch_members)?.insert(batch_id.to_string(), members);
+        Ok(())
+    }
+
+    // Marks a transaction dropped for missing its inclusion deadline; nothing overwrites this
+    pub fn record_expired(&self, tx_hash: [u8; 32]) -> Result<(), IngressError> {
+        self.statuses()?.set(tx_hash, TxStatus::Expired, self.clock.now());
+        Ok(())
+    }
+
+    // Marks a transaction dropped for already being on chain; like Expired, nothing overwrites this
+    pub fn record_mined(&self, tx_hash: [u8; 32]) -> Result<(), IngressError> {
+        self.statuses()?.set(tx_hash, TxStatus::Mined, self.clock.now());
+        Ok(())
+    }
+
+    // Applies a status to every member of a batch; Forwarded and Failed are final, and members that
+    // expired or were mined while the batch was held keep Expired or Mined
+    pub fn update_batch(&self, batch_id: &str, status: TxStatus) -> Result<(), IngressError> {
+        let mut batch_members = lock(&self.batch_members)?;
+        let members = match status {
+            TxStatus::Forwarded | TxStatus::Failed => batch_members.remove(batch_id).unwrap_or_default(),
+            _ => batch_members.get(batch_id).cloned().unwrap_or_default(),
+        };
+
+        let mut statuses = self.statuses()?;
+        let now = self.clock.now();
+        for tx_hash in members {
+            if !matches!(statuses.get(&tx_hash), Some(TxStatus::Expired | TxStatus::Mined)) {
+                statuses.set(tx_hash, status.clone(), now);
+            }
+        }
+        Ok(())
+    }
+
+    // Locks the statuses, first forgetting the ones past their retention
+    fn statuses(&self) -> Result<MutexGuard<'_, StatusLog>, IngressError> {
+        let mut statuses = lock(&self.statuses)?;
+        statuses.prune(self.clock.now(), self.retention);
+        Ok(statuses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::envelope::TransactionEnvelope;
+    use crate::test_utils::dynamic_fee_tx;
+
+    fn batch_of(nonces: &[u64]) -> TransactionBatch {
+        let transactions = nonces
+            .iter()
+            .map(|&nonce| TransactionEnvelope::new(dynamic_fee_tx(nonce), String::new()))
+            .collect();
+        TransactionBatch::new(transactions).unwrap()
+    }
+
+    #[test]
+    fn test_status_walks_through_lifecycle() {
+        let registry = BatchRegistry::new();
+        let tx_hash = transaction_hash(&dynamic_fee_tx(0));
+        assert_eq!(registry.status_of(&tx_hash), Ok(None));
+
+        registry.record_pending(tx_hash).unwrap();
+        assert_eq!(registry.status_of(&tx_hash), Ok(Some(TxStatus::Pending)));
+
+        let batch = batch_of(&[0, 1]);
+        registry.record_batched(&batch).unwrap();
+        assert_eq!(registry.status_of(&tx_hash), Ok(Some(TxStatus::Batched(batch.id.clone()))));
+
+        registry.update_batch(&batch.id, TxStatus::Committed).unwrap();
+        assert_eq!(registry.status_of(&tx_hash), Ok(Some(TxStatus::Committed)));
+
+        registry.update_batch(&batch.id, TxStatus::Forwarded).unwrap();
+        assert_eq!(registry.status_of(&tx_hash), Ok(Some(TxStatus::Forwarded)));
+
+        // The batch is finished, so later updates for it touch nothing
+        registry.update_batch(&batch.id, TxStatus::Failed).unwrap();
+        assert_eq!(registry.status_of(&tx_hash), Ok(Some(TxStatus::Forwarded)));
+    }
+
+    #[test]
+    fn test_failed_batch_marks_every_member() {
+        let registry = BatchRegistry::new();
+        let batch = batch_of(&[0, 1, 2]);
+        let other = batch_of(&[3]);
+        registry.record_batched(&batch).unwrap();
+        registry.record_batched(&other).unwrap();
+
+        registry.update_batch(&batch.id, TxStatus::Failed).unwrap();
+
+        for nonce in 0..3 {
+            assert_eq!(registry.status_of(&transaction_hash(&dynamic_fee_tx(nonce))), Ok(Some(TxStatus::Failed)));
+        }
+        let untouched = registry.status_of(&transaction_hash(&dynamic_fee_tx(3)));
+        assert_eq!(untouched, Ok(Some(TxStatus::Batched(other.id))));
+    }
+
+    #[test]
+    fn test_final_status_forgotten_after_retention() {
+        let clock = MockClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let registry = BatchRegistry::new()
+            .with_clock(Arc::new(clock.clone()))
+            .with_retention(Duration::from_secs(60));
+        let (forwarded, resubmitted) = (batch_of(&[0]), batch_of(&[1]));
+        let forwarded_hash = transaction_hash(&dynamic_fee_tx(0));
+        let resubmitted_hash = transaction_hash(&dynamic_fee_tx(1));
+        registry.record_batched(&forwarded).unwrap();
+        registry.record_batched(&resubmitted).unwrap();
+        registry.update_batch(&forwarded.id, TxStatus::Forwarded).unwrap();
+        registry.update_batch(&resubmitted.id, TxStatus::Failed).unwrap();
+
+        // In-flight statuses are kept however old they are, and a failed transaction submitted again is pending
+        clock.advance(Duration::from_secs(59));
+        registry.record_pending(resubmitted_hash).unwrap();
+        assert_eq!(registry.status_of(&forwarded_hash), Ok(Some(TxStatus::Forwarded)));
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(registry.status_of(&forwarded_hash), Ok(None));
+        assert_eq!(registry.status_of(&resubmitted_hash), Ok(Some(TxStatus::Pending)));
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(registry.status_of(&resubmitted_hash), Ok(Some(TxStatus::Pending)));
+    }
+
+    #[test]
+    fn test_pending_does_not_overwrite_in_flight_status() {
+        let registry = BatchRegistry::new();
+        let batch = batch_of(&[0]);
+        let tx_hash = transaction_hash(&dynamic_fee_tx(0));
+
+        registry.record_batched(&batch).unwrap();
+        registry.record_pending(tx_hash).unwrap();
+
+        assert_eq!(registry.status_of(&tx_hash), Ok(Some(TxStatus::Batched(batch.id))));
+        assert_eq!(registry.status_of(&[0u8; 4]), Ok(None));
+    }
+}
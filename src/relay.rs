@@ -0,0 +1,1497 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, SystemTime};
+
+use rand::Rng;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::batch::TransactionBatch;
+use crate::circuit_breaker::{BreakerPolicy, BreakerState, CircuitBreaker};
+use crate::compression::Compression;
+use crate::discovery::RelayProvider;
+use crate::envelope::TransactionEnvelope;
+use crate::error::{lock, IngressError};
+use crate::transaction::transaction_hash;
+
+// Request header carrying a batch's operator signature, as 0x-prefixed hex
+pub const OPERATOR_SIGNATURE_HEADER: &str = "X-Penum-Operator-Signature";
+// Request header carrying a batch's sequence number, so relays and auditors can spot missing batches
+pub const BATCH_SEQUENCE_HEADER: &str = "X-Penum-Batch-Sequence";
+
+// JSON-RPC method for transactions carrying TransactionConditions, and the error code of a relay without it
+const CONDITIONAL_METHOD: &str = "eth_sendRawTransactionConditional";
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// Retry schedule for transient relay failures (transport errors, HTTP 429 and 5xx)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32, // Total attempts per request, including the first
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Exponential backoff capped at max_delay, with jitter over its upper half so relays aren't hit in lockstep
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self.base_delay.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let delay = exponential.min(self.max_delay);
+        delay / 2 + delay.mul_f64(rand::thread_rng().gen_range(0.0..0.5))
+    }
+}
+
+// Health multiplier applied for every failed attempt, including retried ones
+const HEALTH_DECAY: f64 = 0.5;
+// Health regained by a relay that accepted a batch
+const HEALTH_RECOVERY: f64 = 0.25;
+// Health regained per batch by a relay that was not selected, so it can eventually rejoin
+const HEALTH_IDLE_RECOVERY: f64 = 0.05;
+
+// How long a relay gets to take a batch, retries included, before it is reported as timed out:
+// a third of a mainnet slot, so one hung relay cannot hold the batch past the block
+pub const DEFAULT_RELAY_REQUEST_TIMEOUT: Duration = Duration::from_secs(4);
+
+// Mainnet slot time, for schedules that don't set their own
+pub const DEFAULT_BLOCK_TIME: Duration = Duration::from_secs(12);
+
+// When blocks are expected: one every block_time, counted from the timestamp of a known block
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockSchedule {
+    pub reference: SystemTime, // Timestamp of any past block
+    pub block_time: Duration,
+}
+
+impl BlockSchedule {
+    pub fn new(reference: SystemTime, block_time: Duration) -> Self {
+        Self { reference, block_time }
+    }
+
+    // Time of the first block strictly after now
+    pub fn next_block_at(&self, now: SystemTime) -> SystemTime {
+        let Ok(elapsed) = now.duration_since(self.reference) else {
+            return self.reference;
+        };
+        let block_nanos = self.block_time.as_nanos().max(1);
+        let blocks = elapsed.as_nanos() / block_nanos + 1;
+        self.reference + Duration::from_nanos((blocks * block_nanos).min(u64::MAX as u128) as u64)
+    }
+}
+
+// Outcome of forwarding a batch to a single relay
+#[derive(Clone, Debug)]
+pub struct RelayResult {
+    pub relay_url: String,           // Name of the relay's transport; the URL for HTTP relays
+    pub status: Option<u16>,         // HTTP status of the last response, None if unreachable or not HTTP
+    pub error: Option<IngressError>, // First JSON-RPC or transport error reported by the relay
+    pub retries: u32,                // Requests re-sent after a transient failure
+    pub latency: Duration,           // Time spent forwarding the whole batch to this relay
+    pub skipped: bool,               // The relay's submission deadline had passed, so nothing was sent
+    pub body_bytes: Option<(usize, usize)>, // (uncompressed, sent) body size when the batch went out compressed
+    pub group: Option<String>,       // Relay group the result belongs to, when batches are split between groups
+    pub circuit_open: bool,          // The relay's circuit breaker was open, so nothing was sent
+    pub breaker: Option<BreakerState>, // State of the relay's circuit breaker after this batch, if it has one
+    pub accepted_txs: Vec<[u8; 32]>,   // Keccak-256 hashes of the transactions the relay took, in batch order
+    pub rejected: Vec<([u8; 32], String)>, // Hash and reason of every transaction the relay refused
+}
+
+impl RelayResult {
+    // Outcome that reports no status, error or retries yet
+    pub fn new(relay_url: impl Into<String>) -> Self {
+        Self {
+            relay_url: relay_url.into(),
+            status: None,
+            error: None,
+            retries: 0,
+            latency: Duration::ZERO,
+            skipped: false,
+            body_bytes: None,
+            group: None,
+            circuit_open: false,
+            breaker: None,
+            accepted_txs: Vec::new(),
+            rejected: Vec::new(),
+        }
+    }
+
+    // Records every transaction of the batch as taken or refused together, for transports that send the
+    // batch as one unit, e.g. a bundle, and so cannot tell its transactions apart
+    pub fn record_whole_batch(&mut self, batch: &TransactionBatch) {
+        let hashes = batch.transactions.iter().map(|tx| transaction_hash(&tx.tx_bytes));
+        if self.is_success() {
+            self.accepted_txs = hashes.collect();
+        } else {
+            let reason = self.error.as_ref().map_or_else(|| "not accepted".to_string(), ToString::to_string);
+            self.rejected = hashes.map(|tx_hash| (tx_hash, reason.clone())).collect();
+        }
+    }
+
+    // Folds in the outcome of sending one part of a split batch, keeping the first failure's status and error
+    fn absorb(&mut self, part: RelayResult) {
+        if self.is_success() {
+            self.status = part.status.or(self.status);
+        }
+        self.error = self.error.take().or(part.error);
+        self.retries += part.retries;
+        self.body_bytes = match (self.body_bytes, part.body_bytes) {
+            (Some((uncompressed, sent)), Some((part_uncompressed, part_sent))) => {
+                Some((uncompressed + part_uncompressed, sent + part_sent))
+            }
+            (body_bytes, part_body_bytes) => body_bytes.or(part_body_bytes),
+        };
+        self.accepted_txs.extend(part.accepted_txs);
+        self.rejected.extend(part.rejected);
+    }
+
+    pub fn is_success(&self) -> bool {
+        !self.skipped
+            && !self.circuit_open
+            && self.error.is_none()
+            && self.status.is_none_or(|status| (200..300).contains(&status))
+    }
+}
+
+// Future returned by a relay transport, resolving to the outcome of forwarding one batch
+pub type RelayFuture<'a> = Pin<Box<dyn Future<Output = RelayResult> + Send + 'a>>;
+
+// How batches reach one relay; implement it to plug in gRPC, MEV-Share or custom relays
+//
+// Failures are reported in the returned RelayResult, never panicked or dropped, so health and
+// quorum accounting see every attempt.
+pub trait RelayTransport: Send + Sync {
+    // Identifies the relay in results, metrics and logs
+    fn name(&self) -> &str;
+
+    fn submit<'a>(&'a self, batch: &'a TransactionBatch) -> RelayFuture<'a>;
+}
+
+// JSON-RPC relay over HTTP, sent one eth_sendRawTransaction call per transaction
+//
+// With compression set, the batch instead goes out as a single compressed JSON-RPC batch request.
+// A relay answering that with 415 Unsupported Media Type gets the uncompressed calls right away and
+// on every later batch.
+//
+// Transactions with conditions go out as eth_sendRawTransactionConditional if the relay supports it,
+// and as plain eth_sendRawTransaction calls, without their conditions, if it does not.
+#[derive(Clone)]
+pub struct HttpRelay {
+    url: String,
+    client: reqwest::Client,
+    retry_policy: RetryPolicy,
+    compression: Option<Compression>,
+    encoding_rejected: Arc<AtomicBool>, // Set once the relay refused a compressed body
+    conditional_support: Arc<OnceLock<bool>>, // Whether the relay takes conditional transactions, once known
+}
+
+impl HttpRelay {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            client: reqwest::Client::new(),
+            retry_policy: RetryPolicy::default(),
+            compression: None,
+            encoding_rejected: Arc::new(AtomicBool::new(false)),
+            conditional_support: Arc::new(OnceLock::new()),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    // Declares whether the relay takes eth_sendRawTransactionConditional, instead of asking it
+    pub fn with_conditional_support(mut self, supported: bool) -> Self {
+        self.conditional_support = Arc::new(OnceLock::from(supported));
+        self
+    }
+
+    // Whether conditional transactions may go out as such, asking the relay with an empty call the
+    // first time a batch carries one
+    //
+    // Method not found means the relay lacks it; any other JSON-RPC answer, invalid params included,
+    // means it has it. A relay that gives no JSON-RPC answer is asked again with the next batch, and
+    // this one goes out unconditionally.
+    async fn supports_conditional(&self) -> bool {
+        if let Some(&supported) = self.conditional_support.get() {
+            return supported;
+        }
+        let probe = serde_json::json!({"jsonrpc": "2.0", "id": 0, "method": CONDITIONAL_METHOD, "params": []});
+        let body = match self.client.post(&self.url).json(&probe).send().await {
+            Ok(response) => response.json::<serde_json::Value>().await.ok(),
+            Err(_) => None,
+        };
+        let Some(body) = body.filter(|body| body.get("result").is_some() || body.get("error").is_some()) else {
+            return false;
+        };
+
+        let supported = body.pointer("/error/code").and_then(serde_json::Value::as_i64) != Some(METHOD_NOT_FOUND);
+        if !supported {
+            warn!(relay = %self.url, "relay does not support conditional transactions, sending them without conditions");
+        }
+        *self.conditional_support.get_or_init(|| supported)
+    }
+
+    async fn send_batch(&self, batch: &TransactionBatch) -> RelayResult {
+        let conditional =
+            batch.transactions.iter().any(|tx| tx.conditions.is_some()) && self.supports_conditional().await;
+        if let Some(compression) = self.compression
+            && !self.encoding_rejected.load(Ordering::SeqCst)
+            && let Some(result) = self.send_compressed(batch, compression, conditional).await
+        {
+            return result;
+        }
+
+        let mut result = RelayResult::new(self.url.as_str());
+
+        // Each transaction is sent as its own call, in batch order, so the relay answers for each one separately
+        for (index, tx) in batch.transactions.iter().enumerate() {
+            let body = send_request(index, tx, conditional).to_string().into_bytes();
+            let tx_hash = transaction_hash(&tx.tx_bytes);
+            match post_body(&self.client, &self.url, body, None, batch, &self.retry_policy, &mut result).await {
+                Ok(_) => result.accepted_txs.push(tx_hash),
+                Err(error) => result.rejected.push((tx_hash, error.to_string())),
+            }
+        }
+
+        result
+    }
+
+    // Sends the whole batch as one compressed JSON-RPC batch request, or returns None if it has to be
+    // resent uncompressed
+    async fn send_compressed(
+        &self,
+        batch: &TransactionBatch,
+        compression: Compression,
+        conditional: bool,
+    ) -> Option<RelayResult> {
+        let requests: Vec<serde_json::Value> = batch
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| send_request(index, tx, conditional))
+            .collect();
+        let body = serde_json::Value::Array(requests).to_string().into_bytes();
+        let compressed = match compression.compress(&body) {
+            Ok(compressed) => compressed,
+            Err(error) => {
+                warn!(relay = %self.url, %error, "compressing batch failed, sending uncompressed");
+                return None;
+            }
+        };
+
+        let mut result = RelayResult::new(self.url.as_str());
+        result.body_bytes = Some((body.len(), compressed.len()));
+        let encoding = Some(compression.content_encoding());
+        let response =
+            post_body(&self.client, &self.url, compressed, encoding, batch, &self.retry_policy, &mut result).await;
+
+        if result.status == Some(reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE.as_u16()) {
+            warn!(relay = %self.url, encoding = compression.content_encoding(), "relay rejected compressed batch");
+            self.encoding_rejected.store(true, Ordering::SeqCst);
+            return None;
+        }
+
+        // Batch responses may come back in any order, so each is matched to its call by ID; only a call
+        // answered with an error object counts as refused, matching how the batch as a whole is judged
+        for (index, tx) in batch.transactions.iter().enumerate() {
+            let tx_hash = transaction_hash(&tx.tx_bytes);
+            let outcome = match &response {
+                Ok(serde_json::Value::Array(responses)) => responses
+                    .iter()
+                    .find(|response| response.get("id") == Some(&serde_json::json!(index)))
+                    .and_then(|response| response.get("error"))
+                    .map(|rpc_error| IngressError::RelayRejected(rpc_error.to_string()).to_string()),
+                Ok(_) => None,
+                Err(error) => Some(error.to_string()),
+            };
+            match outcome {
+                None => result.accepted_txs.push(tx_hash),
+                Some(reason) => result.rejected.push((tx_hash, reason)),
+            }
+        }
+        Some(result)
+    }
+}
+
+impl RelayTransport for HttpRelay {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    fn submit<'a>(&'a self, batch: &'a TransactionBatch) -> RelayFuture<'a> {
+        Box::pin(self.send_batch(batch))
+    }
+}
+
+// Relay that keeps every batch it is given instead of sending it, for tests and dry runs;
+// clones share the captured batches
+#[derive(Clone)]
+pub struct InMemoryRelay {
+    name: String,
+    batches: Arc<Mutex<Vec<TransactionBatch>>>,
+}
+
+impl InMemoryRelay {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            batches: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    // Batches accepted so far, in forwarding order
+    pub fn forwarded(&self) -> Result<Vec<TransactionBatch>, IngressError> {
+        Ok(lock(&self.batches)?.clone())
+    }
+}
+
+impl RelayTransport for InMemoryRelay {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn submit<'a>(&'a self, batch: &'a TransactionBatch) -> RelayFuture<'a> {
+        let mut result = RelayResult::new(self.name.as_str());
+        match lock(&self.batches) {
+            Ok(mut batches) => batches.push(batch.clone()),
+            Err(error) => result.error = Some(error),
+        }
+        Box::pin(std::future::ready(result))
+    }
+}
+
+// One configured relay: its transport, selection weight, and URL if it was built from one
+#[derive(Clone)]
+struct Relay {
+    transport: Arc<dyn RelayTransport>,
+    weight: u32,
+    http_url: Option<String>, // Lets with_retry_policy rebuild relays given as URLs
+    submission_cutoff: Option<Duration>, // How long before the next block the relay stops taking submissions
+}
+
+// Relay Forwarding Layer; clones share relay health and circuit breakers
+#[derive(Clone)]
+pub struct RelayForwarder {
+    relays: Vec<Relay>,
+    health: Arc<Mutex<Vec<f64>>>, // Per-relay health in [0, 1], indexed like relays
+    breakers: Option<Arc<Mutex<Vec<CircuitBreaker>>>>, // Per-relay circuit breakers, indexed like relays
+    top_n: Option<usize>,         // Forward only to the N best relays instead of all of them
+    block_schedule: Option<BlockSchedule>, // Needed for submission cutoffs to take effect
+    dry_run: bool,                          // Select relays as usual but never send to them
+    retry_policy: RetryPolicy,              // Applied to relays given as URLs
+    compression: Option<Compression>,       // Applied to relays given as URLs
+    request_timeout: Duration,              // Bounds each relay's submission, whatever its transport
+    max_payload_bytes: Option<usize>,       // Larger batches reach each relay in parts under this size
+    breaker_policy: Option<BreakerPolicy>,  // Given to relays a provider adds later
+    provider: Option<Arc<dyn RelayProvider>>, // Replaces relays with the provider's set for every batch
+    provided: Arc<Mutex<Option<Arc<RelayForwarder>>>>, // Forwarder over the provider's last relay set
+}
+
+impl RelayForwarder {
+    pub fn new(relay_urls: Vec<String>) -> Self {
+        Self::with_weights(relay_urls.into_iter().map(|url| (url, 1)).collect())
+    }
+
+    // HTTP relays with selection weights; weights only matter once with_top_n is set
+    pub fn with_weights(weighted_relays: Vec<(String, u32)>) -> Self {
+        let relays = weighted_relays
+            .into_iter()
+            .map(|(url, weight)| Relay {
+                transport: Arc::new(HttpRelay::new(url.clone())),
+                weight,
+                http_url: Some(url),
+                submission_cutoff: None,
+            })
+            .collect();
+        Self::from_relays(relays)
+    }
+
+    // Relays reached through arbitrary transports, all weighted equally
+    pub fn from_transports(transports: Vec<Box<dyn RelayTransport>>) -> Self {
+        Self::with_weighted_transports(transports.into_iter().map(|transport| (transport, 1)).collect())
+    }
+
+    pub fn with_weighted_transports(weighted_transports: Vec<(Box<dyn RelayTransport>, u32)>) -> Self {
+        let relays = weighted_transports
+            .into_iter()
+            .map(|(transport, weight)| Relay {
+                transport: Arc::from(transport),
+                weight,
+                http_url: None,
+                submission_cutoff: None,
+            })
+            .collect();
+        Self::from_relays(relays)
+    }
+
+    fn from_relays(relays: Vec<Relay>) -> Self {
+        Self {
+            health: Arc::new(Mutex::new(vec![1.0; relays.len()])),
+            breakers: None,
+            relays,
+            top_n: None,
+            block_schedule: None,
+            dry_run: false,
+            retry_policy: RetryPolicy::default(),
+            compression: None,
+            request_timeout: DEFAULT_RELAY_REQUEST_TIMEOUT,
+            max_payload_bytes: None,
+            breaker_policy: None,
+            provider: None,
+            provided: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // Sends batches whose transactions encode to more than max_payload_bytes in parts, one after
+    // another, so relays capping their request size still take them; see TransactionBatch::split
+    //
+    // A relay's result covers every part: it fails if any part failed, and request_timeout bounds all
+    // of them together.
+    pub fn with_max_payload_bytes(mut self, max_payload_bytes: usize) -> Self {
+        self.max_payload_bytes = Some(max_payload_bytes);
+        self
+    }
+
+    // Gives up on a relay that has not taken the batch within request_timeout; other relays are unaffected
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    // Gives every relay a circuit breaker, so one failing policy.failure_threshold batches in a row is
+    // skipped for policy.cool_off instead of being retried on every batch
+    pub fn with_circuit_breaker(mut self, policy: BreakerPolicy) -> Self {
+        let breakers = vec![CircuitBreaker::new(policy); self.relays.len()];
+        self.breakers = Some(Arc::new(Mutex::new(breakers)));
+        self.breaker_policy = Some(policy);
+        self
+    }
+
+    // Takes the relays for every batch from provider instead of those given at construction
+    //
+    // Provided relays are HTTP relays of equal weight, built with this forwarder's retry policy,
+    // compression, timeout and breaker policy. A relay the provider keeps listing keeps its health
+    // and circuit breaker when the set changes; one it adds starts healthy.
+    pub fn with_provider(mut self, provider: Arc<dyn RelayProvider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    // Forwards each batch to the n relays with the highest weight * health rather than to all of them
+    pub fn with_top_n(mut self, n: usize) -> Self {
+        self.top_n = Some(n);
+        self
+    }
+
+    // Applies to relays given as URLs; custom transports handle their own retries
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self.rebuild_http_relays();
+        self
+    }
+
+    // Compresses batch bodies sent to relays given as URLs, falling back per relay if it refuses the encoding
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self.rebuild_http_relays();
+        self
+    }
+
+    fn rebuild_http_relays(&mut self) {
+        for index in 0..self.relays.len() {
+            if let Some(url) = &self.relays[index].http_url {
+                self.relays[index].transport = self.http_transport(url);
+            }
+        }
+    }
+
+    fn http_transport(&self, url: &str) -> Arc<dyn RelayTransport> {
+        let mut transport = HttpRelay::new(url.to_string()).with_retry_policy(self.retry_policy);
+        if let Some(compression) = self.compression {
+            transport = transport.with_compression(compression);
+        }
+        Arc::new(transport)
+    }
+
+    // Sets when blocks are expected, which submission cutoffs are measured against
+    pub fn with_block_schedule(mut self, block_schedule: BlockSchedule) -> Self {
+        self.block_schedule = Some(block_schedule);
+        self
+    }
+
+    // Runs selection as usual but sends nothing, reporting every selected relay as accepting;
+    // for shadowing real traffic to tune batching and privacy parameters
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    // Stops forwarding to the named relay once the next block is less than cutoff away
+    pub fn with_submission_cutoff(mut self, relay_name: &str, cutoff: Duration) -> Self {
+        for relay in &mut self.relays {
+            if relay.transport.name() == relay_name {
+                relay.submission_cutoff = Some(cutoff);
+            }
+        }
+        self
+    }
+
+    // Results of relays skipped for an open circuit breaker, then of those past their deadline, follow
+    // those of the relays the batch was sent to
+    pub async fn forward_batch(&self, batch: &TransactionBatch) -> Vec<RelayResult> {
+        match &self.provider {
+            Some(provider) => self.provided(provider.relay_urls()).forward_to_relays(batch).await,
+            None => self.forward_to_relays(batch).await,
+        }
+    }
+
+    // Forwarder over relay_urls, rebuilt only when the provider's set has changed since the last batch
+    fn provided(&self, relay_urls: Vec<String>) -> Arc<RelayForwarder> {
+        let mut provided = self.provided.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(current) = provided.as_ref()
+            && current.relay_urls().eq(relay_urls.iter().map(String::as_str))
+        {
+            return current.clone();
+        }
+
+        let next = Arc::new(self.with_relay_set(relay_urls, provided.as_deref()));
+        debug!(relays = next.relays.len(), "relay set changed");
+        *provided = Some(next.clone());
+        next
+    }
+
+    fn relay_urls(&self) -> impl Iterator<Item = &str> {
+        self.relays.iter().map(|relay| relay.http_url.as_deref().unwrap_or_else(|| relay.transport.name()))
+    }
+
+    // This forwarder's settings over HTTP relays at relay_urls; relays already in previous keep their
+    // transport, health and circuit breaker
+    fn with_relay_set(&self, relay_urls: Vec<String>, previous: Option<&RelayForwarder>) -> RelayForwarder {
+        let previous_health = previous.map(|previous| previous.health().clone()).unwrap_or_default();
+        let previous_breakers = previous
+            .and_then(|previous| previous.breakers.as_ref())
+            .map(|breakers| breakers.lock().unwrap_or_else(PoisonError::into_inner).clone())
+            .unwrap_or_default();
+
+        let (mut relays, mut health, mut breakers) = (Vec::new(), Vec::new(), Vec::new());
+        for url in relay_urls {
+            let kept = previous.and_then(|previous| {
+                previous.relays.iter().position(|relay| relay.http_url.as_deref() == Some(url.as_str()))
+            });
+            relays.push(match (previous, kept) {
+                (Some(previous), Some(index)) => previous.relays[index].clone(),
+                _ => Relay {
+                    transport: self.http_transport(&url),
+                    weight: 1,
+                    http_url: Some(url),
+                    submission_cutoff: None,
+                },
+            });
+            health.push(kept.and_then(|index| previous_health.get(index).copied()).unwrap_or(1.0));
+            let breaker = kept.and_then(|index| previous_breakers.get(index).cloned());
+            breakers.extend(breaker.or_else(|| self.breaker_policy.map(CircuitBreaker::new)));
+        }
+
+        let mut next = self.clone();
+        next.relays = relays;
+        next.health = Arc::new(Mutex::new(health));
+        next.breakers = self.breaker_policy.map(|_| Arc::new(Mutex::new(breakers)));
+        next.provider = None;
+        next
+    }
+
+    async fn forward_to_relays(&self, batch: &TransactionBatch) -> Vec<RelayResult> {
+        // Forward to the selected relays concurrently
+        let now = SystemTime::now();
+        let (selected, circuit_open) = if self.dry_run {
+            (self.select_relays(now), Vec::new())
+        } else {
+            self.admit(self.select_relays(now), now)
+        };
+        let mut results: Vec<RelayResult> = if self.dry_run {
+            // Nothing was sent, so relay health and circuit breakers learn nothing either
+            selected.iter().map(|&index| RelayResult::new(self.relays[index].transport.name())).collect()
+        } else {
+            // Split once for every relay
+            let parts = self.max_payload_bytes.map(|max| batch.split(max)).filter(|parts| parts.len() > 1);
+            if let Some(parts) = &parts {
+                debug!(parts = parts.len(), payload_bytes = batch.payload_bytes(), "splitting oversized batch");
+            }
+            let submissions = selected
+                .iter()
+                .map(|&index| self.forward_to_relay(self.relays[index].transport.as_ref(), batch, parts.as_deref()));
+            let results = futures::future::join_all(submissions).await;
+            self.update_health(&selected, &results);
+            self.update_breakers(&selected, &results);
+            results
+        };
+
+        for &index in &circuit_open {
+            debug!(relay = self.relays[index].transport.name(), "circuit breaker open");
+            let mut result = RelayResult::new(self.relays[index].transport.name());
+            result.circuit_open = true;
+            results.push(result);
+        }
+        let past_deadline: Vec<usize> =
+            (0..self.relays.len()).filter(|&index| self.deadline_passed(&self.relays[index], now)).collect();
+        for &index in &past_deadline {
+            debug!(relay = self.relays[index].transport.name(), "submission deadline passed");
+            let mut result = RelayResult::new(self.relays[index].transport.name());
+            result.skipped = true;
+            results.push(result);
+        }
+
+        if let Some(breakers) = &self.breakers {
+            let breakers = breakers.lock().unwrap_or_else(PoisonError::into_inner);
+            let order = selected.iter().chain(&circuit_open).chain(&past_deadline);
+            for (result, &index) in results.iter_mut().zip(order) {
+                result.breaker = Some(breakers[index].state());
+            }
+        }
+        results
+    }
+
+    // Splits the selected relays into those whose breaker lets the batch through, claiming the probe of
+    // any half-opening breaker, and the open ones, selected or not, the batch must skip
+    fn admit(&self, selected: Vec<usize>, now: SystemTime) -> (Vec<usize>, Vec<usize>) {
+        let Some(breakers) = &self.breakers else {
+            return (selected, Vec::new());
+        };
+        let mut breakers = breakers.lock().unwrap_or_else(PoisonError::into_inner);
+        let instant = std::time::Instant::now();
+
+        let (admitted, mut refused): (Vec<usize>, Vec<usize>) =
+            selected.iter().partition(|&&index| breakers[index].allow(instant));
+        refused.extend((0..self.relays.len()).filter(|&index| {
+            !selected.contains(&index)
+                && !self.deadline_passed(&self.relays[index], now)
+                && breakers[index].blocks(instant)
+        }));
+        refused.sort_unstable();
+        (admitted, refused)
+    }
+
+    // Feeds every sent relay's outcome to its breaker
+    fn update_breakers(&self, selected: &[usize], results: &[RelayResult]) {
+        let Some(breakers) = &self.breakers else {
+            return;
+        };
+        let mut breakers = breakers.lock().unwrap_or_else(PoisonError::into_inner);
+        let instant = std::time::Instant::now();
+        for (&index, result) in selected.iter().zip(results) {
+            breakers[index].record(result.is_success(), instant);
+        }
+    }
+
+    // Whether the relay's cutoff before the next block has already been reached at now
+    fn deadline_passed(&self, relay: &Relay, now: SystemTime) -> bool {
+        match (self.block_schedule, relay.submission_cutoff) {
+            (Some(schedule), Some(cutoff)) => now + cutoff > schedule.next_block_at(now),
+            _ => false,
+        }
+    }
+
+    // Indices of the relays to forward to, best score first when top-N selection is enabled; relays
+    // past their submission deadline or behind an open circuit breaker are never selected
+    fn select_relays(&self, now: SystemTime) -> Vec<usize> {
+        let blocked: Vec<bool> = match &self.breakers {
+            Some(breakers) => {
+                let instant = std::time::Instant::now();
+                let breakers = breakers.lock().unwrap_or_else(PoisonError::into_inner);
+                breakers.iter().map(|breaker| breaker.blocks(instant)).collect()
+            }
+            None => vec![false; self.relays.len()],
+        };
+        let open = (0..self.relays.len())
+            .filter(|&index| !self.deadline_passed(&self.relays[index], now) && !blocked[index]);
+        let Some(n) = self.top_n else {
+            return open.collect();
+        };
+
+        let health = self.health();
+        let mut ranked: Vec<usize> = open.collect();
+        // Stable sort keeps configuration order between equally scored relays
+        ranked.sort_by(|&a, &b| {
+            let score = |index: usize| self.relays[index].weight as f64 * health[index];
+            score(b).total_cmp(&score(a))
+        });
+        ranked.truncate(n);
+        ranked
+    }
+
+    // Decays health once per failed attempt and recovers it on acceptance
+    fn update_health(&self, selected: &[usize], results: &[RelayResult]) {
+        let mut health = self.health();
+        for index in 0..health.len() {
+            if !selected.contains(&index) {
+                health[index] = (health[index] + HEALTH_IDLE_RECOVERY).min(1.0);
+            }
+        }
+        for (&index, result) in selected.iter().zip(results) {
+            let failed_attempts = result.retries + u32::from(!result.is_success());
+            health[index] *= HEALTH_DECAY.powi(failed_attempts as i32);
+            if result.is_success() {
+                health[index] = (health[index] + HEALTH_RECOVERY).min(1.0);
+            }
+        }
+    }
+
+    // Health is advisory, so a poisoned lock keeps whatever scores it holds
+    fn health(&self) -> std::sync::MutexGuard<'_, Vec<f64>> {
+        self.health.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Latency and the timeout are measured here, so every transport gets them the same way; on the
+    // tokio clock, so paused-time tests see simulated delays
+    async fn forward_to_relay(
+        &self,
+        transport: &dyn RelayTransport,
+        batch: &TransactionBatch,
+        parts: Option<&[TransactionBatch]>,
+    ) -> RelayResult {
+        debug!(relay = transport.name(), "forwarding batch");
+        let start_time = Instant::now();
+
+        let submission = async {
+            let Some(parts) = parts else {
+                return transport.submit(batch).await;
+            };
+            // Later parts still go out after one fails, so as much of the batch as possible lands
+            let mut result = RelayResult::new(transport.name());
+            for part in parts {
+                let mut part_result = transport.submit(part).await;
+                if part_result.accepted_txs.is_empty() && part_result.rejected.is_empty() {
+                    part_result.record_whole_batch(part);
+                }
+                result.absorb(part_result);
+            }
+            result
+        };
+        let mut result = match tokio::time::timeout(self.request_timeout, submission).await {
+            Ok(result) => result,
+            Err(_) => {
+                let mut result = RelayResult::new(transport.name());
+                result.error = Some(IngressError::RelayTimeout(self.request_timeout));
+                result
+            }
+        };
+        result.latency = start_time.elapsed();
+        if result.accepted_txs.is_empty() && result.rejected.is_empty() {
+            result.record_whole_batch(batch);
+        }
+        result
+    }
+}
+
+// Posts one JSON-RPC request for the batch, retrying transient failures, and folds the outcome into result
+//
+// The batch's operator signature, if any, travels in a header. The first error is kept; the status is
+// that of the last response.
+pub(crate) async fn post_json_rpc(
+    client: &reqwest::Client,
+    url: &str,
+    request: &serde_json::Value,
+    batch: &TransactionBatch,
+    retry_policy: &RetryPolicy,
+    result: &mut RelayResult,
+) {
+    let _ = post_body(client, url, request.to_string().into_bytes(), None, batch, retry_policy, result).await;
+}
+
+// Posts an already serialised JSON-RPC body, sent with the given Content-Encoding if it was compressed,
+// returning the response body or this request's error
+//
+// Responses to JSON-RPC batch requests are arrays, returned as they are; the first error object in
+// one is reported.
+pub(crate) async fn post_body(
+    client: &reqwest::Client,
+    url: &str,
+    body: Vec<u8>,
+    content_encoding: Option<&str>,
+    batch: &TransactionBatch,
+    retry_policy: &RetryPolicy,
+    result: &mut RelayResult,
+) -> Result<serde_json::Value, IngressError> {
+    // Transient failures are retried; the outcome of the last attempt is what gets reported
+    let mut attempt = 1;
+    let outcome = loop {
+        let mut post = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.clone());
+        if let Some(content_encoding) = content_encoding {
+            post = post.header(reqwest::header::CONTENT_ENCODING, content_encoding);
+        }
+        post = post.header(BATCH_SEQUENCE_HEADER, batch.sequence);
+        if let Some(signature) = &batch.operator_signature {
+            post = post.header(OPERATOR_SIGNATURE_HEADER, format!("0x{}", hex::encode(signature)));
+        }
+        let outcome = post.send().await;
+        let transient = match &outcome {
+            Ok(response) => is_transient(response.status()),
+            Err(_) => true,
+        };
+        if !transient || attempt >= retry_policy.max_attempts {
+            break outcome;
+        }
+
+        tokio::time::sleep(retry_policy.backoff(attempt)).await;
+        result.retries += 1;
+        attempt += 1;
+    };
+
+    let fail = |result: &mut RelayResult, error: IngressError| {
+        result.error.get_or_insert_with(|| error.clone());
+        Err(error)
+    };
+    let response = match outcome {
+        Ok(response) => response,
+        Err(e) => return fail(result, IngressError::RelayUnreachable(e.to_string())),
+    };
+
+    let status = response.status();
+    result.status = Some(status.as_u16());
+    if !status.is_success() {
+        return fail(result, IngressError::RelayRejected(format!("HTTP {}", status)));
+    }
+
+    // A 2xx response can still carry a JSON-RPC error object
+    match response.json::<serde_json::Value>().await {
+        Ok(serde_json::Value::Array(responses)) => {
+            if let Some(rpc_error) = responses.iter().find_map(|response| response.get("error")) {
+                result.error.get_or_insert_with(|| IngressError::RelayRejected(rpc_error.to_string()));
+            }
+            Ok(serde_json::Value::Array(responses))
+        }
+        Ok(body) => match body.get("error") {
+            Some(rpc_error) => fail(result, IngressError::RelayRejected(rpc_error.to_string())),
+            None => Ok(body),
+        },
+        Err(e) => fail(result, IngressError::RelayRejected(format!("Invalid JSON-RPC response: {}", e))),
+    }
+}
+
+// Helper function to build the call submitting one transaction, with its conditions if it has them
+// and the relay takes them
+fn send_request(id: usize, tx: &TransactionEnvelope, conditional: bool) -> serde_json::Value {
+    let raw = format!("0x{}", hex::encode(&tx.tx_bytes));
+    let (method, params) = match &tx.conditions {
+        Some(conditions) if conditional => (CONDITIONAL_METHOD, serde_json::json!([raw, conditions])),
+        _ => ("eth_sendRawTransaction", serde_json::json!([raw])),
+    };
+    serde_json::json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+}
+
+// Helper function to classify responses worth retrying: rate limiting and server errors
+fn is_transient(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::{KnownAccount, TransactionConditions};
+    use crate::simulated_relay::{SimulatedRelay, SimulatedResponse};
+    use wiremock::matchers::{body_partial_json, header, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_forward_batch_sends_one_request_per_transaction() {
+        let mut relays = Vec::new();
+        for _ in 0..2 {
+            let relay = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(body_partial_json(serde_json::json!({"jsonrpc": "2.0", "method": "eth_sendRawTransaction"})))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+                .expect(3)
+                .mount(&relay)
+                .await;
+            relays.push(relay);
+        }
+
+        let forwarder = RelayForwarder::new(relays.iter().map(|relay| relay.uri()).collect());
+        let batch = TransactionBatch::new(vec![
+            TransactionEnvelope::new(vec![0x02, 0xaa], "a".to_string()),
+            TransactionEnvelope::new(vec![0x02, 0xbb], "b".to_string()),
+            TransactionEnvelope::new(vec![0x02, 0xcc], "c".to_string()),
+        ])
+        .unwrap();
+
+        let results = forwarder.forward_batch(&batch).await;
+
+        assert_eq!(results.len(), 2);
+        for (result, relay) in results.iter().zip(&relays) {
+            assert_eq!(result.relay_url, relay.uri());
+            assert_eq!(result.status, Some(200));
+            assert!(result.is_success());
+        }
+
+        // Each relay saw the hex-encoded payloads of exactly this batch
+        for relay in &relays {
+            let mut params: Vec<String> = relay
+                .received_requests()
+                .await
+                .unwrap()
+                .iter()
+                .map(|request| {
+                    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                    body["params"][0].as_str().unwrap().to_string()
+                })
+                .collect();
+            params.sort();
+            assert_eq!(params, vec!["0x02aa", "0x02bb", "0x02cc"]);
+        }
+    }
+
+    fn fast_retries(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+        }
+    }
+
+    fn three_tx_batch() -> TransactionBatch {
+        TransactionBatch::new(vec![
+            TransactionEnvelope::new(vec![0x02, 0xaa], "a".to_string()),
+            TransactionEnvelope::new(vec![0x02, 0xbb], "b".to_string()),
+            TransactionEnvelope::new(vec![0x02, 0xcc], "c".to_string()),
+        ])
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_compressed_batch_decompresses_to_json_rpc_batch() {
+        use std::io::Read;
+
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("content-encoding", "gzip"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([{"jsonrpc": "2.0", "id": 0, "result": "0x"}])))
+            .expect(1)
+            .mount(&relay)
+            .await;
+        let forwarder = RelayForwarder::new(vec![relay.uri()]).with_compression(Compression::Gzip);
+        let batch = three_tx_batch();
+
+        let results = forwarder.forward_batch(&batch).await;
+        assert!(results[0].is_success());
+
+        let requests = relay.received_requests().await.unwrap();
+        let mut body = Vec::new();
+        flate2::read::GzDecoder::new(requests[0].body.as_slice()).read_to_end(&mut body).unwrap();
+        let expected: Vec<serde_json::Value> = ["0x02aa", "0x02bb", "0x02cc"]
+            .iter()
+            .enumerate()
+            .map(|(id, raw)| serde_json::json!({"jsonrpc": "2.0", "id": id, "method": "eth_sendRawTransaction", "params": [raw]}))
+            .collect();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap(), serde_json::json!(expected));
+        assert_eq!(results[0].body_bytes, Some((body.len(), requests[0].body.len())));
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_encoding_falls_back_to_uncompressed() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("content-encoding", "zstd"))
+            .respond_with(ResponseTemplate::new(415))
+            .expect(1)
+            .mount(&relay)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .mount(&relay)
+            .await;
+        let forwarder = RelayForwarder::new(vec![relay.uri()]).with_compression(Compression::Zstd);
+
+        // The refused compressed body is followed by one plain call per transaction
+        let first = forwarder.forward_batch(&three_tx_batch()).await;
+        assert!(first[0].is_success());
+        assert_eq!(first[0].body_bytes, None);
+        assert_eq!(relay.received_requests().await.unwrap().len(), 4);
+
+        // Later batches skip straight to the uncompressed calls
+        forwarder.forward_batch(&three_tx_batch()).await;
+        let requests = relay.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 7);
+        assert!(requests[1..].iter().all(|request| !request.headers.contains_key("content-encoding")));
+    }
+
+    #[tokio::test]
+    async fn test_forward_retries_transient_failures() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&relay)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(1)
+            .mount(&relay)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .mount(&relay)
+            .await;
+
+        let forwarder = RelayForwarder::new(vec![relay.uri()]).with_retry_policy(fast_retries(3));
+        let batch = TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, 0x01], "a".to_string())]).unwrap();
+
+        let results = forwarder.forward_batch(&batch).await;
+
+        assert!(results[0].is_success());
+        assert_eq!(results[0].retries, 2);
+        assert_eq!(relay.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_forward_reports_last_error_after_retries() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .mount(&relay)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(502))
+            .mount(&relay)
+            .await;
+
+        let forwarder = RelayForwarder::new(vec![relay.uri()]).with_retry_policy(fast_retries(2));
+        let batch = TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, 0x01], "a".to_string())]).unwrap();
+
+        let results = forwarder.forward_batch(&batch).await;
+
+        assert_eq!(results[0].status, Some(502));
+        assert_eq!(results[0].error, Some(IngressError::RelayRejected("HTTP 502 Bad Gateway".to_string())));
+        assert_eq!(results[0].retries, 1);
+        assert_eq!(relay.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        };
+
+        for _ in 0..20 {
+            let first = policy.backoff(1);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let third = policy.backoff(3);
+            assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+            assert!(policy.backoff(9) <= Duration::from_millis(1000));
+        }
+    }
+
+    async fn accepting_relay() -> MockServer {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .mount(&relay)
+            .await;
+        relay
+    }
+
+    #[tokio::test]
+    async fn test_top_n_selection_respects_weights() {
+        let relays = [accepting_relay().await, accepting_relay().await, accepting_relay().await];
+        let forwarder = RelayForwarder::with_weights(vec![
+            (relays[0].uri(), 1),
+            (relays[1].uri(), 5),
+            (relays[2].uri(), 3),
+        ])
+        .with_top_n(2);
+        let batch = TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, 0x01], "a".to_string())]).unwrap();
+
+        let results = forwarder.forward_batch(&batch).await;
+
+        let urls: Vec<&str> = results.iter().map(|result| result.relay_url.as_str()).collect();
+        assert_eq!(urls, vec![relays[1].uri(), relays[2].uri()]);
+        assert!(relays[0].received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failing_relay_drops_out_of_selection() {
+        let failing = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "error": {"code": -32000, "message": "rejected"}
+            })))
+            .mount(&failing)
+            .await;
+        let healthy = [accepting_relay().await, accepting_relay().await];
+
+        // The failing relay starts with the highest weight
+        let forwarder = RelayForwarder::with_weights(vec![
+            (failing.uri(), 4),
+            (healthy[0].uri(), 2),
+            (healthy[1].uri(), 1),
+        ])
+        .with_top_n(2);
+        assert_eq!(forwarder.select_relays(SystemTime::now()), vec![0, 1]);
+
+        for i in 0..3u8 {
+            let batch = TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, i], "a".to_string())]).unwrap();
+            forwarder.forward_batch(&batch).await;
+        }
+
+        assert_eq!(forwarder.select_relays(SystemTime::now()), vec![1, 2]);
+
+        // Once dropped, further batches no longer reach it
+        let seen = failing.received_requests().await.unwrap().len();
+        let batch = TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, 0xff], "a".to_string())]).unwrap();
+        let results = forwarder.forward_batch(&batch).await;
+        assert!(results.iter().all(|result| result.is_success()));
+        assert_eq!(failing.received_requests().await.unwrap().len(), seen);
+    }
+
+    #[tokio::test]
+    async fn test_forward_batch_reports_json_rpc_error() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "error": {"code": -32000, "message": "nonce too low"}
+            })))
+            .mount(&relay)
+            .await;
+
+        let forwarder = RelayForwarder::new(vec![relay.uri()]);
+        let batch = TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, 0x01], "a".to_string())]).unwrap();
+
+        let results = forwarder.forward_batch(&batch).await;
+
+        assert_eq!(results[0].status, Some(200));
+        match &results[0].error {
+            Some(IngressError::RelayRejected(message)) => assert!(message.contains("nonce too low")),
+            other => panic!("expected a relay rejection, got {:?}", other),
+        }
+        assert!(!results[0].is_success());
+    }
+
+    #[tokio::test]
+    async fn test_relay_results_list_each_refused_transaction() {
+        let nonce_too_low = serde_json::json!({"code": -32000, "message": "nonce too low"});
+        let plain = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({"params": ["0x02bb"]})))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 1, "error": nonce_too_low})),
+            )
+            .mount(&plain)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .mount(&plain)
+            .await;
+        let compressed = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                {"jsonrpc": "2.0", "id": 2, "result": "0x"},
+                {"jsonrpc": "2.0", "id": 1, "error": nonce_too_low},
+                {"jsonrpc": "2.0", "id": 0, "result": "0x"},
+            ])))
+            .mount(&compressed)
+            .await;
+        let forwarder = RelayForwarder::from_transports(vec![
+            Box::new(HttpRelay::new(plain.uri())),
+            Box::new(HttpRelay::new(compressed.uri()).with_compression(Compression::Gzip)),
+        ]);
+
+        let results = forwarder.forward_batch(&three_tx_batch()).await;
+
+        // Both ways of sending single out the one refused transaction and still take the others
+        let [aa, bb, cc] = [[0x02, 0xaa], [0x02, 0xbb], [0x02, 0xcc]].map(|tx_bytes| transaction_hash(&tx_bytes));
+        for result in &results {
+            assert_eq!(result.accepted_txs, vec![aa, cc]);
+            assert_eq!(result.rejected.len(), 1);
+            assert_eq!(result.rejected[0].0, bb);
+            assert!(result.rejected[0].1.contains("nonce too low"));
+            assert!(!result.is_success());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conditional_transactions_fall_back_on_relays_without_the_method() {
+        let accepted = serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"});
+        let supporting = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(accepted.clone()))
+            .mount(&supporting)
+            .await;
+        let unsupporting = MockServer::start().await;
+        let method_not_found = serde_json::json!({"code": METHOD_NOT_FOUND, "message": "the method does not exist"});
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({"method": CONDITIONAL_METHOD})))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "error": method_not_found})),
+            )
+            .mount(&unsupporting)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(accepted))
+            .mount(&unsupporting)
+            .await;
+        let forwarder = RelayForwarder::new(vec![supporting.uri(), unsupporting.uri()]);
+
+        let mut conditional = TransactionEnvelope::new(vec![0x02, 0xaa], "a".to_string());
+        conditional.conditions = Some(TransactionConditions {
+            known_accounts: [([0x11; 20], KnownAccount::StorageRoot([0x22; 32]))].into(),
+            block_number_min: None,
+            block_number_max: Some(100),
+        });
+        let plain = TransactionEnvelope::new(vec![0x02, 0xbb], "b".to_string());
+        let batch = TransactionBatch::new(vec![conditional, plain]).unwrap();
+        for _ in 0..2 {
+            assert!(forwarder.forward_batch(&batch).await.iter().all(RelayResult::is_success));
+        }
+
+        // Each relay is asked once; only the one that has the method gets the conditions
+        let calls = |requests: Vec<wiremock::Request>| -> Vec<(String, serde_json::Value)> {
+            let bodies = requests.iter().map(|request| request.body_json::<serde_json::Value>().unwrap());
+            bodies.map(|body| (body["method"].as_str().unwrap().to_string(), body["params"].clone())).collect()
+        };
+        let known_accounts = serde_json::json!({format!("0x{}", "11".repeat(20)): format!("0x{}", "22".repeat(32))});
+        let options = serde_json::json!({"knownAccounts": known_accounts, "blockNumberMax": "0x64"});
+        let conditional_call = (CONDITIONAL_METHOD.to_string(), serde_json::json!(["0x02aa", options]));
+        let plain_call = |raw: &str| ("eth_sendRawTransaction".to_string(), serde_json::json!([raw]));
+        let probe = (CONDITIONAL_METHOD.to_string(), serde_json::json!([]));
+        assert_eq!(
+            calls(supporting.received_requests().await.unwrap()),
+            vec![probe.clone(), conditional_call.clone(), plain_call("0x02bb"), conditional_call, plain_call("0x02bb")]
+        );
+        assert_eq!(
+            calls(unsupporting.received_requests().await.unwrap()),
+            vec![probe, plain_call("0x02aa"), plain_call("0x02bb"), plain_call("0x02aa"), plain_call("0x02bb")]
+        );
+
+        // Declared support skips the question
+        let declared = HttpRelay::new(unsupporting.uri()).with_conditional_support(false);
+        unsupporting.reset().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .mount(&unsupporting)
+            .await;
+        assert!(declared.submit(&batch).await.is_success());
+        assert_eq!(calls(unsupporting.received_requests().await.unwrap())[0], plain_call("0x02aa"));
+    }
+
+    #[tokio::test]
+    async fn test_operator_signature_and_sequence_sent_as_headers() {
+        let relay = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header(OPERATOR_SIGNATURE_HEADER, format!("0x{}", "ab".repeat(64)).as_str()))
+            .and(header(BATCH_SEQUENCE_HEADER, "7"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": "0x"})))
+            .expect(1)
+            .mount(&relay)
+            .await;
+        let mut batch = TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, 0x01], "a".to_string())]).unwrap();
+        batch.operator_signature = Some([0xab; 64]);
+        batch.sequence = 7;
+
+        let results = RelayForwarder::new(vec![relay.uri()]).forward_batch(&batch).await;
+
+        assert!(results[0].is_success());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_transport_captures_forwarded_batches() {
+        let captured = InMemoryRelay::new("memory");
+        let forwarder = RelayForwarder::from_transports(vec![Box::new(captured.clone())]);
+        let batch = TransactionBatch::new(vec![
+            TransactionEnvelope::new(vec![0x02, 0xaa], "a".to_string()),
+            TransactionEnvelope::new(vec![0x02, 0xbb], "b".to_string()),
+        ])
+        .unwrap();
+
+        let results = forwarder.forward_batch(&batch).await;
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].relay_url, "memory");
+        assert!(results[0].is_success());
+        let forwarded = captured.forwarded().unwrap();
+        assert_eq!(forwarded.len(), 1);
+        assert_eq!(forwarded[0].id, batch.id);
+        let payloads: Vec<&[u8]> = forwarded[0].transactions.iter().map(|tx| tx.tx_bytes.as_slice()).collect();
+        assert_eq!(payloads, vec![&[0x02, 0xaa][..], &[0x02, 0xbb][..]]);
+
+        // A transport taking the batch whole accepts each of its transactions
+        let hashes = [transaction_hash(&[0x02, 0xaa]), transaction_hash(&[0x02, 0xbb])];
+        assert_eq!(results[0].accepted_txs, hashes);
+        assert!(results[0].rejected.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_batch_forwarded_in_parts_under_cap() {
+        // Each transaction takes 202 bytes as hex, so three fit under the cap and ten make four parts
+        let transactions: Vec<TransactionEnvelope> =
+            (0..10u8).map(|i| TransactionEnvelope::new(vec![i; 100], i.to_string())).collect();
+        let tx_bytes: Vec<Vec<u8>> = transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        let batch = TransactionBatch::new(transactions).unwrap();
+        assert_eq!(batch.payload_bytes(), 2020);
+
+        let captured = InMemoryRelay::new("memory");
+        let forwarder = RelayForwarder::from_transports(vec![Box::new(captured.clone())]).with_max_payload_bytes(700);
+        let results = forwarder.forward_batch(&batch).await;
+
+        let parts = captured.forwarded().unwrap();
+        let sizes: Vec<usize> = parts.iter().map(|part| part.transactions.len()).collect();
+        assert_eq!(sizes, vec![3, 3, 3, 1]);
+        assert!(parts.iter().all(|part| part.payload_bytes() <= 700));
+        assert!(parts.iter().all(|part| part.id == batch.id && part.commitment == batch.commitment));
+        // Parts follow forwarding order, and the same batch always splits the same way
+        let sent: Vec<Vec<u8>> =
+            parts.iter().flat_map(|part| part.transactions.iter().map(|tx| tx.tx_bytes.clone())).collect();
+        assert_eq!(sent, tx_bytes);
+        let resplit: Vec<usize> = batch.split(700).iter().map(|part| part.transactions.len()).collect();
+        assert_eq!(resplit, sizes);
+
+        // One result per relay covers every part
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_success());
+        let hashes: Vec<[u8; 32]> = tx_bytes.iter().map(|tx| transaction_hash(tx)).collect();
+        assert_eq!(results[0].accepted_txs, hashes);
+
+        // A batch under the cap goes out whole
+        let small = TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, 0xaa], "a".to_string())]).unwrap();
+        forwarder.forward_batch(&small).await;
+        assert_eq!(captured.forwarded().unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_skips_failing_relay_until_probe_succeeds() {
+        let flaky = SimulatedRelay::new("flaky").with_script([SimulatedResponse::reject(503); 3]);
+        let steady = SimulatedRelay::new("steady");
+        let forwarder = RelayForwarder::from_transports(vec![Box::new(flaky.clone()), Box::new(steady.clone())])
+            .with_circuit_breaker(BreakerPolicy { failure_threshold: 2, cool_off: Duration::from_millis(50) });
+        let batch = TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, 0x01], "a".to_string())]).unwrap();
+        let flaky_outcome = |results: Vec<RelayResult>| {
+            let result = results.into_iter().find(|result| result.relay_url == "flaky").unwrap();
+            (result.is_success(), result.circuit_open, result.breaker.unwrap())
+        };
+
+        // Two failures in a row open the breaker, and the next batch skips the relay without sending
+        assert_eq!(flaky_outcome(forwarder.forward_batch(&batch).await), (false, false, BreakerState::Closed));
+        assert_eq!(flaky_outcome(forwarder.forward_batch(&batch).await), (false, false, BreakerState::Open));
+        let results = forwarder.forward_batch(&batch).await;
+        assert_eq!(results.iter().filter(|result| result.is_success()).count(), 1);
+        assert_eq!(flaky_outcome(results), (false, true, BreakerState::Open));
+        assert_eq!(flaky.received_batches().unwrap().len(), 2);
+
+        // The first probe after the cool-off fails and reopens it
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(flaky_outcome(forwarder.forward_batch(&batch).await), (false, false, BreakerState::Open));
+        assert_eq!(flaky_outcome(forwarder.forward_batch(&batch).await), (false, true, BreakerState::Open));
+
+        // The next one succeeds, closing the breaker so batches flow again
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(flaky_outcome(forwarder.forward_batch(&batch).await), (true, false, BreakerState::Closed));
+        assert_eq!(flaky_outcome(forwarder.forward_batch(&batch).await), (true, false, BreakerState::Closed));
+        assert_eq!(flaky.received_batches().unwrap().len(), 5);
+        assert_eq!(steady.received_batches().unwrap().len(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_top_n_selection_over_custom_transports() {
+        let relays = [InMemoryRelay::new("a"), InMemoryRelay::new("b"), InMemoryRelay::new("c")];
+        let forwarder = RelayForwarder::with_weighted_transports(vec![
+            (Box::new(relays[0].clone()), 1),
+            (Box::new(relays[1].clone()), 5),
+            (Box::new(relays[2].clone()), 3),
+        ])
+        .with_top_n(1)
+        .with_retry_policy(fast_retries(1));
+        let batch = TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, 0x01], "a".to_string())]).unwrap();
+
+        forwarder.forward_batch(&batch).await;
+
+        let counts: Vec<usize> = relays.iter().map(|relay| relay.forwarded().unwrap().len()).collect();
+        assert_eq!(counts, vec![0, 1, 0]);
+    }
+
+    // Schedule whose next block is due in about one second
+    fn block_due_soon() -> BlockSchedule {
+        BlockSchedule::new(SystemTime::now() - Duration::from_secs(11), DEFAULT_BLOCK_TIME)
+    }
+
+    #[test]
+    fn test_next_block_follows_the_schedule() {
+        let reference = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let schedule = BlockSchedule::new(reference, Duration::from_secs(12));
+
+        assert_eq!(schedule.next_block_at(reference), reference + Duration::from_secs(12));
+        assert_eq!(schedule.next_block_at(reference + Duration::from_secs(30)), reference + Duration::from_secs(36));
+        assert_eq!(schedule.next_block_at(reference - Duration::from_secs(5)), reference);
+    }
+
+    #[tokio::test]
+    async fn test_relay_past_deadline_is_skipped() {
+        let relays = [InMemoryRelay::new("early"), InMemoryRelay::new("late"), InMemoryRelay::new("open")];
+        let forwarder = RelayForwarder::from_transports(relays.iter().map(|relay| Box::new(relay.clone()) as _).collect())
+            .with_block_schedule(block_due_soon())
+            .with_submission_cutoff("early", Duration::from_secs(4))
+            .with_submission_cutoff("late", Duration::from_millis(100));
+        let batch = TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, 0x01], "a".to_string())]).unwrap();
+
+        let results = forwarder.forward_batch(&batch).await;
+
+        let outcomes: Vec<(&str, bool, bool)> = results
+            .iter()
+            .map(|result| (result.relay_url.as_str(), result.is_success(), result.skipped))
+            .collect();
+        assert_eq!(outcomes, vec![("late", true, false), ("open", true, false), ("early", false, true)]);
+        let counts: Vec<usize> = relays.iter().map(|relay| relay.forwarded().unwrap().len()).collect();
+        assert_eq!(counts, vec![0, 1, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_top_n_fills_in_for_relay_past_deadline() {
+        let relays = [InMemoryRelay::new("a"), InMemoryRelay::new("b")];
+        let forwarder = RelayForwarder::with_weighted_transports(vec![
+            (Box::new(relays[0].clone()), 5),
+            (Box::new(relays[1].clone()), 1),
+        ])
+        .with_top_n(1)
+        .with_block_schedule(block_due_soon())
+        .with_submission_cutoff("a", Duration::from_secs(4));
+        let batch = TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, 0x01], "a".to_string())]).unwrap();
+
+        forwarder.forward_batch(&batch).await;
+
+        let counts: Vec<usize> = relays.iter().map(|relay| relay.forwarded().unwrap().len()).collect();
+        assert_eq!(counts, vec![0, 1]);
+
+        // A skipped relay is not penalised for the missed batch
+        assert_eq!(forwarder.health()[0], 1.0);
+    }
+}
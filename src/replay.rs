@@ -0,0 +1,220 @@
+use std::ops::{Range, RangeInclusive};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use tracing::info;
+
+use crate::batch::TransactionBatch;
+use crate::chain::ChainState;
+use crate::error::IngressError;
+use crate::relay::{RelayForwarder, RelayResult};
+use crate::wal::{committed_batches, CommittedBatch};
+
+// What replaying one logged batch did
+#[derive(Clone, Debug)]
+pub struct ReplayedBatch {
+    pub batch_id: String,
+    pub sequence: Option<u64>,
+    pub mined: usize,   // Transactions left out because the chain already includes their sender's nonce
+    pub expired: usize, // Transactions left out because their inclusion deadline had passed
+    pub relay_results: Vec<RelayResult>, // Empty when nothing was left to send
+}
+
+// Re-forwards batches committed to a write-ahead log, e.g. to relays that were down when they went out
+//
+// Without a chain state every logged transaction is sent again; relays refuse the ones already
+// mined, but they still spend relay capacity.
+pub struct WalReplay {
+    path: PathBuf,
+    sequences: Option<RangeInclusive<u64>>,
+    timestamps: Option<Range<SystemTime>>,
+    chain_state: Option<ChainState>,
+}
+
+impl WalReplay {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            sequences: None,
+            timestamps: None,
+            chain_state: None,
+        }
+    }
+
+    // Replays only batches whose sequence number is in range
+    pub fn with_sequences(mut self, sequences: RangeInclusive<u64>) -> Self {
+        self.sequences = Some(sequences);
+        self
+    }
+
+    // Replays only batches timestamped within range; batches logged without a timestamp are left out
+    pub fn with_timestamps(mut self, timestamps: Range<SystemTime>) -> Self {
+        self.timestamps = Some(timestamps);
+        self
+    }
+
+    // Leaves out transactions the node shows as mined or past their inclusion deadline
+    pub fn with_chain_state(mut self, chain_state: ChainState) -> Self {
+        self.chain_state = Some(chain_state);
+        self
+    }
+
+    // Forwards every selected batch in commit order, one at a time
+    //
+    // A replayed batch keeps its ID, sequence and timestamp but is committed again under a fresh
+    // nonce, since the log does not keep the original; relays only ever see the transactions.
+    pub async fn run(&self, forwarder: &RelayForwarder) -> Result<Vec<ReplayedBatch>, IngressError> {
+        let mut replayed = Vec::new();
+        for logged in committed_batches(&self.path)?.into_iter().filter(|logged| self.selects(logged)) {
+            let CommittedBatch { batch_id, sequence, timestamp, mut transactions } = logged;
+            let (mut mined, mut expired) = (0, 0);
+            if let Some(chain_state) = &self.chain_state {
+                mined = chain_state.drop_mined(&mut transactions).await;
+                if transactions.iter().any(|tx| tx.valid_until_block.is_some())
+                    && let Ok(head) = chain_state.head_block().await
+                {
+                    let live = transactions.len();
+                    transactions.retain(|tx| !tx.is_expired_at(head));
+                    expired = live - transactions.len();
+                }
+            }
+
+            let mut relay_results = Vec::new();
+            if !transactions.is_empty() {
+                let mut batch = TransactionBatch::new(transactions)?;
+                batch.id = batch_id.clone();
+                batch.sequence = sequence.unwrap_or_default();
+                batch.timestamp = timestamp.unwrap_or(batch.timestamp);
+                relay_results = forwarder.forward_batch(&batch).await;
+            }
+            info!(batch_id = %batch_id, ?sequence, mined, expired, relays = relay_results.len(), "replayed batch");
+            replayed.push(ReplayedBatch { batch_id, sequence, mined, expired, relay_results });
+        }
+        Ok(replayed)
+    }
+
+    fn selects(&self, logged: &CommittedBatch) -> bool {
+        let in_sequences = match (&self.sequences, logged.sequence) {
+            (Some(sequences), Some(sequence)) => sequences.contains(&sequence),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let in_timestamps = match (&self.timestamps, logged.timestamp) {
+            (Some(timestamps), Some(timestamp)) => timestamps.contains(&timestamp),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        in_sequences && in_timestamps
+    }
+}
+
+// Re-forwards every batch committed to the log at path; see WalReplay for filtering and skipping
+// mined transactions
+pub async fn replay_wal(
+    path: impl Into<PathBuf>,
+    forwarder: &RelayForwarder,
+) -> Result<Vec<ReplayedBatch>, IngressError> {
+    WalReplay::new(path).run(forwarder).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, ResponseTemplate};
+
+    use super::*;
+    use crate::envelope::TransactionEnvelope;
+    use crate::simulated_relay::{SimulatedRelay, SimulatedResponse};
+    use crate::test_utils::{node_at_block, signed_dynamic_fee_tx, test_address};
+    use crate::wal::WriteAheadLog;
+
+    fn envelope(key: u8, nonce: u64) -> TransactionEnvelope {
+        TransactionEnvelope::new(signed_dynamic_fee_tx(key, nonce), format!("tx-{}-{}", key, nonce))
+    }
+
+    // Logs each group as one committed batch, numbered from 0 and a minute apart
+    fn write_wal(path: &std::path::Path, groups: Vec<Vec<TransactionEnvelope>>) -> Vec<String> {
+        let wal = WriteAheadLog::open(path).unwrap();
+        let mut batch_ids = Vec::new();
+        for (sequence, transactions) in groups.into_iter().enumerate() {
+            for tx in &transactions {
+                wal.record_accepted(tx).unwrap();
+            }
+            let mut batch = TransactionBatch::new(transactions).unwrap();
+            batch.sequence = sequence as u64;
+            batch.timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(60 * sequence as u64);
+            wal.record_committed(&batch).unwrap();
+            batch_ids.push(batch.id);
+        }
+        batch_ids
+    }
+
+    fn sent(relay: &SimulatedRelay) -> Vec<Vec<Vec<u8>>> {
+        let batches = relay.received_batches().unwrap();
+        batches.iter().map(|batch| batch.transactions.iter().map(|tx| tx.tx_bytes.clone()).collect()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_replay_reforwards_logged_batches_skipping_mined() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ingress.wal");
+        let mut expiring = envelope(3, 0);
+        expiring.valid_until_block = Some(90);
+        let batch_ids = write_wal(
+            &path,
+            vec![vec![envelope(1, 0), envelope(2, 0)], vec![envelope(1, 1), expiring], vec![envelope(2, 1)]],
+        );
+
+        // Everything comes back out in commit order, with the original IDs and sequence numbers
+        let relay = SimulatedRelay::new("recovered").with_script([SimulatedResponse::unreachable()]);
+        let forwarder = RelayForwarder::from_transports(vec![Box::new(relay.clone())]);
+        let replayed = replay_wal(&path, &forwarder).await.unwrap();
+        let summary: Vec<(String, Option<u64>, bool)> = replayed
+            .iter()
+            .map(|batch| (batch.batch_id.clone(), batch.sequence, batch.relay_results[0].is_success()))
+            .collect();
+        let expected: Vec<(String, Option<u64>, bool)> =
+            batch_ids.iter().zip(0..).map(|(id, sequence)| (id.clone(), Some(sequence), sequence > 0)).collect();
+        assert_eq!(summary, expected);
+        assert_eq!(sent(&relay).len(), 2);
+        assert_eq!(relay.received_batches().unwrap()[0].id, batch_ids[1]);
+
+        // Sender 1 has since mined both its transactions and block 100 is past the deadline; sender 2's
+        // are still open
+        let node = node_at_block(100).await;
+        for (key, nonce) in [(1, 2), (2, 0), (3, 0)] {
+            let address = format!("0x{}", hex::encode(test_address(key)));
+            let response = serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": format!("0x{:x}", nonce)});
+            Mock::given(method("POST"))
+                .and(body_partial_json(serde_json::json!({"params": [address]})))
+                .respond_with(ResponseTemplate::new(200).set_body_json(response))
+                .mount(&node)
+                .await;
+        }
+        let relay = SimulatedRelay::new("recovered");
+        let forwarder = RelayForwarder::from_transports(vec![Box::new(relay.clone())]);
+        let replay = WalReplay::new(&path).with_chain_state(ChainState::new(node.uri()));
+        let replayed = replay.run(&forwarder).await.unwrap();
+        let skipped: Vec<(usize, usize)> = replayed.iter().map(|batch| (batch.mined, batch.expired)).collect();
+        assert_eq!(skipped, vec![(1, 0), (1, 1), (0, 0)]);
+        // The second batch had nothing left to send
+        assert!(replayed[1].relay_results.is_empty());
+        assert_eq!(sent(&relay), vec![vec![signed_dynamic_fee_tx(2, 0)], vec![signed_dynamic_fee_tx(2, 1)]]);
+
+        // Sequence and time ranges narrow the replay to the batches in both
+        let relay = SimulatedRelay::new("recovered");
+        let forwarder = RelayForwarder::from_transports(vec![Box::new(relay.clone())]);
+        let minute = |minutes: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(60 * minutes);
+        let replayed = WalReplay::new(&path)
+            .with_sequences(1..=2)
+            .with_timestamps(minute(0)..minute(2))
+            .run(&forwarder)
+            .await
+            .unwrap();
+        assert_eq!(replayed.len(), 1);
+        assert_eq!(replayed[0].batch_id, batch_ids[1]);
+        assert_eq!(sent(&relay), vec![vec![signed_dynamic_fee_tx(1, 1), signed_dynamic_fee_tx(3, 0)]]);
+    }
+}
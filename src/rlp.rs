@@ -0,0 +1,188 @@
+// Minimal RLP encoding and decoding for raw Ethereum transactions
+
+// A decoded RLP item borrowing from the input buffer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum RlpItem<'a> {
+    Bytes(&'a [u8]),
+    List(Vec<RlpItem<'a>>),
+}
+
+impl<'a> RlpItem<'a> {
+    #[cfg(test)]
+    pub(crate) fn as_list(&self) -> Option<&[RlpItem<'a>]> {
+        match self {
+            RlpItem::List(items) => Some(items),
+            RlpItem::Bytes(_) => None,
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            RlpItem::Bytes(bytes) => Some(bytes),
+            RlpItem::List(_) => None,
+        }
+    }
+
+    // Re-encodes the item; decoding is canonical so this reproduces the original bytes
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        match self {
+            RlpItem::Bytes(bytes) => encode_bytes(bytes),
+            RlpItem::List(items) => encode_list(&items.iter().map(RlpItem::encode).collect::<Vec<_>>()),
+        }
+    }
+}
+
+// Decodes a buffer that must contain exactly one RLP item
+pub(crate) fn decode(data: &[u8]) -> Result<RlpItem<'_>, String> {
+    let (item, consumed) = decode_item(data)?;
+    if consumed != data.len() {
+        return Err(format!("{} trailing bytes after RLP item", data.len() - consumed));
+    }
+    Ok(item)
+}
+
+// Decodes one item from the start of the buffer, returning it and the number of bytes consumed
+fn decode_item(data: &[u8]) -> Result<(RlpItem<'_>, usize), String> {
+    let prefix = *data.first().ok_or("unexpected end of RLP input")?;
+
+    match prefix {
+        // Single byte encoding itself
+        0x00..=0x7f => Ok((RlpItem::Bytes(&data[..1]), 1)),
+        // Short string (0-55 bytes)
+        0x80..=0xb7 => {
+            let len = (prefix - 0x80) as usize;
+            let payload = payload(data, 1, len)?;
+            if len == 1 && payload[0] < 0x80 {
+                return Err("non-canonical single byte string".to_string());
+            }
+            Ok((RlpItem::Bytes(payload), 1 + len))
+        }
+        // Long string
+        0xb8..=0xbf => {
+            let len_of_len = (prefix - 0xb7) as usize;
+            let len = long_length(data, len_of_len)?;
+            let payload = payload(data, 1 + len_of_len, len)?;
+            Ok((RlpItem::Bytes(payload), 1 + len_of_len + len))
+        }
+        // Short list (0-55 bytes of payload)
+        0xc0..=0xf7 => {
+            let len = (prefix - 0xc0) as usize;
+            let payload = payload(data, 1, len)?;
+            Ok((RlpItem::List(decode_list_payload(payload)?), 1 + len))
+        }
+        // Long list
+        0xf8..=0xff => {
+            let len_of_len = (prefix - 0xf7) as usize;
+            let len = long_length(data, len_of_len)?;
+            let payload = payload(data, 1 + len_of_len, len)?;
+            Ok((RlpItem::List(decode_list_payload(payload)?), 1 + len_of_len + len))
+        }
+    }
+}
+
+// Helper function to read the big-endian length of a long string or list
+fn long_length(data: &[u8], len_of_len: usize) -> Result<usize, String> {
+    let bytes = payload(data, 1, len_of_len)?;
+    if bytes[0] == 0 {
+        return Err("RLP length has leading zeros".to_string());
+    }
+    if len_of_len > std::mem::size_of::<usize>() {
+        return Err("RLP length overflows".to_string());
+    }
+
+    let len = bytes.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+    if len <= 55 {
+        return Err("non-canonical long RLP length".to_string());
+    }
+    Ok(len)
+}
+
+// Helper function to slice a payload, failing if the input is truncated
+fn payload(data: &[u8], offset: usize, len: usize) -> Result<&[u8], String> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or_else(|| "RLP item is truncated".to_string())
+}
+
+fn decode_list_payload(mut payload: &[u8]) -> Result<Vec<RlpItem<'_>>, String> {
+    let mut items = Vec::new();
+    while !payload.is_empty() {
+        let (item, consumed) = decode_item(payload)?;
+        items.push(item);
+        payload = &payload[consumed..];
+    }
+    Ok(items)
+}
+
+// Helper function to RLP-encode a byte string
+pub(crate) fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut out = encode_length(bytes.len(), 0x80);
+    out.extend_from_slice(bytes);
+    out
+}
+
+// Helper function to RLP-encode an unsigned integer (big-endian, no leading zeros)
+pub(crate) fn encode_u64(value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let first = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    encode_bytes(&bytes[first..])
+}
+
+// Helper function to wrap already-encoded items in an RLP list
+pub(crate) fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload: Vec<u8> = items.concat();
+    let mut out = encode_length(payload.len(), 0xc0);
+    out.extend_from_slice(&payload);
+    out
+}
+
+fn encode_length(len: usize, offset: u8) -> Vec<u8> {
+    if len <= 55 {
+        return vec![offset + len as u8];
+    }
+    let bytes = len.to_be_bytes();
+    let first = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    let mut out = vec![offset + 55 + (bytes.len() - first) as u8];
+    out.extend_from_slice(&bytes[first..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_nested_list() {
+        let long = vec![0xab; 60];
+        let encoded = encode_list(&[encode_u64(0), encode_u64(1024), encode_bytes(&long), encode_list(&[])]);
+
+        let decoded = decode(&encoded).unwrap();
+        let items = decoded.as_list().unwrap();
+
+        assert_eq!(items.len(), 4);
+        assert_eq!(items[0].as_bytes(), Some(&[][..]));
+        assert_eq!(items[1].as_bytes(), Some(&[0x04, 0x00][..]));
+        assert_eq!(items[2].as_bytes(), Some(&long[..]));
+        assert_eq!(items[3].as_list().map(|l| l.len()), Some(0));
+        assert_eq!(decoded.encode(), encoded);
+    }
+
+    #[test]
+    fn test_rejects_malformed_input() {
+        assert!(decode(&[]).is_err());
+        // Truncated string
+        assert!(decode(&[0x83, 0x01, 0x02]).is_err());
+        // Trailing bytes
+        assert!(decode(&[0x01, 0x02]).is_err());
+        // Non-canonical single byte
+        assert!(decode(&[0x81, 0x05]).is_err());
+        // Long form used for a short payload
+        assert!(decode(&[0xb8, 0x02, 0x01, 0x02]).is_err());
+        // List whose declared length runs past the input
+        assert!(decode(&[0xc5, 0x01]).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use std::sync::Arc;
+
+use crate::batch::TransactionBatch;
+use crate::envelope::TransactionEnvelope;
+use crate::error::IngressError;
+use crate::relay::{RelayForwarder, RelayResult};
+use crate::transaction::transaction_fees;
+
+// Picks the relay group each transaction of a batch is forwarded to
+pub trait RoutingRule: Send + Sync {
+    // Name of the transaction's group; a name no group has falls back to the first group
+    fn route(&self, tx: &TransactionEnvelope) -> &str;
+}
+
+// Sends transactions bidding at least min_priority_fee to one group, e.g. private order flow relays,
+// and everything else to another
+#[derive(Clone, Debug)]
+pub struct PriorityFeeRule {
+    min_priority_fee: u128,
+    high_value_group: String,
+    default_group: String,
+}
+
+impl PriorityFeeRule {
+    pub fn new(min_priority_fee: u128, high_value_group: impl Into<String>, default_group: impl Into<String>) -> Self {
+        Self {
+            min_priority_fee,
+            high_value_group: high_value_group.into(),
+            default_group: default_group.into(),
+        }
+    }
+}
+
+impl RoutingRule for PriorityFeeRule {
+    fn route(&self, tx: &TransactionEnvelope) -> &str {
+        let bid = transaction_fees(&tx.tx_bytes).map(|fees| fees.max_priority_fee).unwrap_or_default();
+        if bid >= self.min_priority_fee {
+            &self.high_value_group
+        } else {
+            &self.default_group
+        }
+    }
+}
+
+// A named set of relays with its own transports, timeout and quorum
+#[derive(Clone)]
+pub struct RelayGroup {
+    name: String,
+    forwarder: RelayForwarder, // Carries the group's transports, selection, retries and request timeout
+    min_quorum: usize,
+}
+
+impl RelayGroup {
+    pub fn new(name: impl Into<String>, forwarder: RelayForwarder) -> Self {
+        Self {
+            name: name.into(),
+            forwarder,
+            min_quorum: 0,
+        }
+    }
+
+    // Counts the group's share of a batch as failed unless at least min_quorum of its relays accept it
+    pub fn with_min_quorum(mut self, min_quorum: usize) -> Self {
+        self.min_quorum = min_quorum;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+// Splits each batch between relay groups by a routing rule and forwards every part to its group
+//
+// Every part keeps the batch's ID, commitment and signature, so relays can still match it to the
+// commitment and check Merkle proofs for its transactions. Groups no transaction routes to are not
+// contacted.
+#[derive(Clone)]
+pub struct RelayRouter {
+    groups: Vec<RelayGroup>,
+    rule: Arc<dyn RoutingRule>,
+}
+
+impl RelayRouter {
+    // The first group takes every transaction routed to a name no group has; at least one group is required
+    pub fn new(groups: Vec<RelayGroup>, rule: Arc<dyn RoutingRule>) -> Result<Self, IngressError> {
+        if groups.is_empty() {
+            return Err(IngressError::Config("relay routing needs at least one group".to_string()));
+        }
+        Ok(Self { groups, rule })
+    }
+
+    // Applies dry-run mode to every group's forwarder
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        for group in &mut self.groups {
+            group.forwarder = group.forwarder.clone().with_dry_run(dry_run);
+        }
+        self
+    }
+
+    // Whether no group sends anything
+    pub fn is_dry_run(&self) -> bool {
+        self.groups.iter().all(|group| group.forwarder.is_dry_run())
+    }
+
+    // Each group's part of the batch, in group order, leaving out groups that get no transactions
+    pub fn split(&self, batch: &TransactionBatch) -> Vec<(usize, TransactionBatch)> {
+        let mut parts: Vec<Vec<TransactionEnvelope>> = vec![Vec::new(); self.groups.len()];
+        for tx in &batch.transactions {
+            let name = self.rule.route(tx);
+            let index = self.groups.iter().position(|group| group.name == name).unwrap_or(0);
+            parts[index].push(tx.clone());
+        }
+
+        parts
+            .into_iter()
+            .enumerate()
+            .filter(|(_, transactions)| !transactions.is_empty())
+            .map(|(index, transactions)| {
+                let mut part = batch.clone();
+                part.transactions = transactions;
+                // The recorded permutation describes the whole batch, not this part
+                part.permutation = None;
+                (index, part)
+            })
+            .collect()
+    }
+
+    // Forwards every part concurrently; each result is tagged with the group it came from
+    pub async fn forward_batch(&self, batch: &TransactionBatch) -> Vec<RelayResult> {
+        let parts = self.split(batch);
+        let submissions = parts.iter().map(|(index, part)| async move {
+            let group = &self.groups[*index];
+            let mut results = group.forwarder.forward_batch(part).await;
+            for result in &mut results {
+                result.group = Some(group.name.clone());
+            }
+            results
+        });
+        futures::future::join_all(submissions).await.into_iter().flatten().collect()
+    }
+
+    // Fails with the first group that was sent part of the batch but fell short of its own quorum
+    //
+    // Groups are judged on their own results only, so one group missing its quorum does not fail another.
+    pub fn check_quorums(&self, results: &[RelayResult]) -> Result<(), IngressError> {
+        for group in &self.groups {
+            let group_results: Vec<&RelayResult> =
+                results.iter().filter(|result| result.group.as_deref() == Some(group.name.as_str())).collect();
+            if group_results.is_empty() {
+                continue;
+            }
+            let accepted = group_results.iter().filter(|result| result.is_success()).count();
+            if accepted < group.min_quorum {
+                return Err(IngressError::GroupQuorumNotMet {
+                    group: group.name.clone(),
+                    accepted,
+                    required: group.min_quorum,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulated_relay::{SimulatedRelay, SimulatedResponse};
+    use crate::test_utils::fee_bidding_tx;
+
+    // A private group of two relays needing both, and a public group of two needing one
+    fn router(private: &[SimulatedRelay], public: &[SimulatedRelay]) -> RelayRouter {
+        let forwarder = |relays: &[SimulatedRelay]| {
+            RelayForwarder::from_transports(relays.iter().map(|relay| Box::new(relay.clone()) as _).collect())
+        };
+        let groups = vec![
+            RelayGroup::new("public", forwarder(public)).with_min_quorum(1),
+            RelayGroup::new("private", forwarder(private)).with_min_quorum(2),
+        ];
+        RelayRouter::new(groups, Arc::new(PriorityFeeRule::new(50, "private", "public"))).unwrap()
+    }
+
+    // Transactions from distinct senders bidding the given priority fees
+    fn batch(priority_fees: &[u64]) -> TransactionBatch {
+        let transactions = priority_fees
+            .iter()
+            .zip(1u8..)
+            .map(|(&fee, key)| TransactionEnvelope::new(fee_bidding_tx(key, fee, fee * 10), String::new()))
+            .collect();
+        TransactionBatch::new(transactions).unwrap()
+    }
+
+    fn received_bids(relay: &SimulatedRelay) -> Vec<Vec<u128>> {
+        relay
+            .received_batches()
+            .unwrap()
+            .iter()
+            .map(|batch| {
+                batch.transactions.iter().map(|tx| transaction_fees(&tx.tx_bytes).unwrap().max_priority_fee).collect()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_transactions_route_to_their_group() {
+        let private = [SimulatedRelay::new("flashbots-protect"), SimulatedRelay::new("mev-blocker")];
+        let public = [SimulatedRelay::new("public-1"), SimulatedRelay::new("public-2")];
+        let router = router(&private, &public);
+        let batch = batch(&[10, 80, 49, 50]);
+
+        let results = router.forward_batch(&batch).await;
+        assert_eq!(results.len(), 4);
+        assert_eq!(router.check_quorums(&results), Ok(()));
+
+        for relay in &private {
+            assert_eq!(received_bids(relay), vec![vec![80, 50]]);
+            assert_eq!(relay.received_batches().unwrap()[0].commitment, batch.commitment);
+        }
+        for relay in &public {
+            assert_eq!(received_bids(relay), vec![vec![10, 49]]);
+        }
+        let private_results = results.iter().filter(|result| result.group.as_deref() == Some("private")).count();
+        assert_eq!(private_results, 2);
+
+        // A batch of only low bids never contacts the private group
+        router.forward_batch(&self::batch(&[1, 2])).await;
+        assert_eq!(private[0].received_batches().unwrap().len(), 1);
+        assert_eq!(public[0].received_batches().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_each_group_quorum_judged_on_its_own_relays() {
+        // One private relay refuses, which breaks the private quorum of two but not the public one
+        let private = [
+            SimulatedRelay::new("flashbots-protect"),
+            SimulatedRelay::new("mev-blocker").with_script([SimulatedResponse::reject(503)]),
+        ];
+        let public = || {
+            let unreachable = SimulatedRelay::new("public-1").with_script([SimulatedResponse::unreachable()]);
+            [unreachable, SimulatedRelay::new("public-2")]
+        };
+        let router = router(&private, &public());
+
+        let results = router.forward_batch(&batch(&[10, 80])).await;
+        assert_eq!(
+            router.check_quorums(&results),
+            Err(IngressError::GroupQuorumNotMet { group: "private".to_string(), accepted: 1, required: 2 })
+        );
+
+        // With both private relays accepting, the public group's single acceptance is enough
+        let router = self::router(&[SimulatedRelay::new("a"), SimulatedRelay::new("b")], &public());
+        let results = router.forward_batch(&batch(&[10, 80])).await;
+        assert_eq!(results.iter().filter(|result| result.is_success()).count(), 3);
+        assert_eq!(router.check_quorums(&results), Ok(()));
+
+        // Only low bids, so the private group is not judged even though it would have missed quorum
+        let refusing = SimulatedRelay::new("a").with_default_response(SimulatedResponse::reject(503));
+        let router = self::router(&[refusing], &public());
+        let results = router.forward_batch(&batch(&[10])).await;
+        assert_eq!(router.check_quorums(&results), Ok(()));
+    }
+}
@@ -0,0 +1,206 @@
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::post;
+use axum::Router;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+use crate::envelope::TransactionConditions;
+use crate::error::IngressError;
+use crate::ingress::PenumIngress;
+
+// JSON-RPC 2.0 error codes, plus the generic server error go-ethereum returns for rejected transactions
+// and the EIP-1474 code for rate limiting
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+const TRANSACTION_REJECTED: i64 = -32000;
+const LIMIT_EXCEEDED: i64 = -32005;
+
+// Router accepting JSON-RPC requests on POST /, so wallets can use the ingress as their RPC URL
+pub fn rpc_router(ingress: PenumIngress) -> Router {
+    Router::new().route("/", post(handle)).with_state(ingress)
+}
+
+// Serves the JSON-RPC endpoint on the given listener until the task is dropped
+pub async fn serve_rpc(listener: TcpListener, ingress: PenumIngress) -> std::io::Result<()> {
+    axum::serve(listener, rpc_router(ingress)).await
+}
+
+async fn handle(State(ingress): State<PenumIngress>, body: Bytes) -> impl IntoResponse {
+    let response = match serde_json::from_slice::<Value>(&body) {
+        Ok(Value::Array(requests)) if !requests.is_empty() => {
+            let mut responses = Vec::with_capacity(requests.len());
+            for request in &requests {
+                responses.push(dispatch(&ingress, request).await);
+            }
+            Value::Array(responses)
+        }
+        Ok(request @ Value::Object(_)) => dispatch(&ingress, &request).await,
+        Ok(_) => error_response(Value::Null, INVALID_REQUEST, "invalid request"),
+        Err(error) => error_response(Value::Null, PARSE_ERROR, &error.to_string()),
+    };
+
+    ([(header::CONTENT_TYPE, "application/json")], response.to_string())
+}
+
+async fn dispatch(ingress: &PenumIngress, request: &Value) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return error_response(id, INVALID_REQUEST, "invalid request");
+    };
+
+    match method {
+        "eth_sendRawTransaction" | "eth_sendRawTransactionConditional" => {
+            let conditional = method == "eth_sendRawTransactionConditional";
+            match send_raw_transaction(ingress, request.get("params"), conditional).await {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, message)) => error_response(id, code, &message),
+            }
+        }
+        _ => error_response(id, METHOD_NOT_FOUND, &format!("the method {} does not exist/is not available", method)),
+    }
+}
+
+// Submits the hex-encoded transaction and returns its Keccak-256 hash, as go-ethereum does; conditional
+// calls carry their options object through to relays that take them
+async fn send_raw_transaction(
+    ingress: &PenumIngress,
+    params: Option<&Value>,
+    conditional: bool,
+) -> Result<String, (i64, String)> {
+    let params = params.and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+    let raw = params
+        .first()
+        .and_then(Value::as_str)
+        .ok_or((INVALID_PARAMS, "missing value for required argument 0".to_string()))?;
+    let tx_bytes = raw
+        .strip_prefix("0x")
+        .and_then(|hex_payload| hex::decode(hex_payload).ok())
+        .ok_or((INVALID_PARAMS, "invalid argument 0: hex string without 0x prefix or invalid hex".to_string()))?;
+
+    let submitted = if conditional {
+        let options = params.get(1).ok_or((INVALID_PARAMS, "missing value for required argument 1".to_string()))?;
+        let conditions = TransactionConditions::deserialize(options)
+            .map_err(|error| (INVALID_PARAMS, format!("invalid argument 1: {}", error)))?;
+        ingress.submit_transaction_with_conditions(tx_bytes, conditions).await
+    } else {
+        ingress.submit_transaction(tx_bytes).await
+    };
+    let tx_hash = submitted.map_err(|error| (error_code(&error), error.to_string()))?;
+
+    Ok(format!("0x{}", hex::encode(tx_hash)))
+}
+
+fn error_code(error: &IngressError) -> i64 {
+    match error {
+        IngressError::EmptyTransaction
+        | IngressError::InvalidTransaction(_)
+        | IngressError::InvalidSignature
+        | IngressError::Duplicate
+        | IngressError::ReplacementUnderpriced
+        | IngressError::WrongChainId { .. }
+        | IngressError::TxTooLarge { .. }
+        | IngressError::BlobTransactionsDisabled
+        | IngressError::PendingPoolFull { .. }
+        | IngressError::BlobPoolFull { .. } => TRANSACTION_REJECTED,
+        IngressError::RateLimited => LIMIT_EXCEEDED,
+        _ => INTERNAL_ERROR,
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::HashAlgo;
+    use crate::test_utils::dynamic_fee_tx;
+    use std::time::Duration;
+
+    async fn start_server() -> String {
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), Vec::new());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_rpc(listener, ingress));
+        format!("http://{}/", addr)
+    }
+
+    async fn call(url: &str, request: Value) -> Value {
+        let response = reqwest::Client::new().post(url).json(&request).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        response.json().await.unwrap()
+    }
+
+    fn send_raw(id: u64, tx_bytes: &[u8]) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "eth_sendRawTransaction",
+            "params": [format!("0x{}", hex::encode(tx_bytes))],
+        })
+    }
+
+    fn send_raw_conditional(id: u64, tx_bytes: &[u8], options: Value) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "eth_sendRawTransactionConditional",
+            "params": [format!("0x{}", hex::encode(tx_bytes)), options],
+        })
+    }
+
+    #[tokio::test]
+    async fn test_send_raw_transaction_returns_hash() {
+        let url = start_server().await;
+        let tx = dynamic_fee_tx(0);
+
+        let response = call(&url, send_raw(7, &tx)).await;
+
+        assert_eq!(response["id"], 7);
+        let result = response["result"].as_str().unwrap();
+        assert!(result.starts_with("0x"));
+        assert_eq!(hex::decode(&result[2..]).unwrap().len(), 32);
+        assert_eq!(result, format!("0x{}", hex::encode(HashAlgo::Keccak256.hash(&tx))));
+
+        // Conditional submissions answer the same way
+        let tx = dynamic_fee_tx(1);
+        let response = call(&url, send_raw_conditional(8, &tx, json!({"blockNumberMax": "0x1312d00"}))).await;
+        assert_eq!(response["result"], format!("0x{}", hex::encode(HashAlgo::Keccak256.hash(&tx))));
+    }
+
+    #[tokio::test]
+    async fn test_errors_map_to_json_rpc_codes() {
+        let url = start_server().await;
+
+        // A resubmission is rejected like any other invalid transaction
+        let tx = dynamic_fee_tx(1);
+        call(&url, send_raw(1, &tx)).await;
+        let duplicate = call(&url, send_raw(2, &tx)).await;
+        assert_eq!(duplicate["error"]["code"], TRANSACTION_REJECTED);
+
+        let bad_hex = json!({ "jsonrpc": "2.0", "id": 3, "method": "eth_sendRawTransaction", "params": ["02ff"] });
+        assert_eq!(call(&url, bad_hex).await["error"]["code"], INVALID_PARAMS);
+
+        let unknown = json!({ "jsonrpc": "2.0", "id": 4, "method": "eth_blockNumber", "params": [] });
+        assert_eq!(call(&url, unknown).await["error"]["code"], METHOD_NOT_FOUND);
+
+        // Conditional options must parse and leave some block to land in
+        let malformed = send_raw_conditional(5, &dynamic_fee_tx(2), json!({"knownAccounts": {"0x11": "0x22"}}));
+        assert_eq!(call(&url, malformed).await["error"]["code"], INVALID_PARAMS);
+        let empty_range = json!({"blockNumberMin": "0x2", "blockNumberMax": "0x1"});
+        let empty_range = call(&url, send_raw_conditional(6, &dynamic_fee_tx(2), empty_range)).await;
+        assert_eq!(empty_range["error"]["code"], TRANSACTION_REJECTED);
+
+        let response = reqwest::Client::new().post(&url).body("{not json").send().await.unwrap();
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], PARSE_ERROR);
+    }
+}
@@ -0,0 +1,125 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::error::IngressError;
+#[cfg(feature = "net")]
+use crate::ingress::PenumIngress;
+use crate::registry::TxStatus;
+use crate::transaction::TxHash;
+
+// Future returned by an ingress method
+pub type IngressFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, IngressError>> + Send + 'a>>;
+
+// What a surrounding service needs from an ingress, so it can hold an Arc<dyn Ingress> and run
+// against PenumIngress in production and a stub, or another backend, in tests
+//
+// Methods return boxed futures rather than being async fns so the trait stays usable as a trait
+// object, like RelayTransport.
+pub trait Ingress: Send + Sync {
+    // Accepts a raw signed transaction, resolving to its hash once it is pending
+    fn submit<'a>(&'a self, tx_bytes: Vec<u8>) -> IngressFuture<'a, TxHash>;
+
+    // Where a submitted transaction is in the pipeline, None if it was never submitted here
+    fn status(&self, tx_hash: &TxHash) -> Option<TxStatus>;
+
+    // Cuts every batch whose window has passed and releases held batches that are ready, for callers
+    // driving the ingress from their own loop instead of a spawned task
+    fn process_batches(&self) -> IngressFuture<'_, ()>;
+
+    // Forwards everything still pending, resolving once no batch is held back any more
+    fn drain(&self) -> IngressFuture<'_, ()>;
+
+    // Asks a running background task to drain and exit
+    fn shutdown(&self);
+}
+
+#[cfg(feature = "net")]
+impl Ingress for PenumIngress {
+    fn submit<'a>(&'a self, tx_bytes: Vec<u8>) -> IngressFuture<'a, TxHash> {
+        Box::pin(self.submit_transaction(tx_bytes))
+    }
+
+    fn status(&self, tx_hash: &TxHash) -> Option<TxStatus> {
+        self.status_of(tx_hash)
+    }
+
+    fn process_batches(&self) -> IngressFuture<'_, ()> {
+        Box::pin(PenumIngress::process_batches(self))
+    }
+
+    fn drain(&self) -> IngressFuture<'_, ()> {
+        Box::pin(PenumIngress::drain(self))
+    }
+
+    fn shutdown(&self) {
+        PenumIngress::shutdown(self)
+    }
+}
+
+#[cfg(all(test, feature = "net"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::relay::{InMemoryRelay, RelayForwarder};
+    use crate::test_utils::dynamic_fee_tx;
+    use crate::transaction::transaction_hash;
+
+    // Keeps whatever it is given pending, and forgets it on drain instead of forwarding it
+    #[derive(Default)]
+    struct StubIngress {
+        pending: Mutex<Vec<TxHash>>,
+    }
+
+    impl Ingress for StubIngress {
+        fn submit<'a>(&'a self, tx_bytes: Vec<u8>) -> IngressFuture<'a, TxHash> {
+            Box::pin(async move {
+                let tx_hash = transaction_hash(&tx_bytes);
+                self.pending.lock().unwrap().push(tx_hash);
+                Ok(tx_hash)
+            })
+        }
+
+        fn status(&self, tx_hash: &TxHash) -> Option<TxStatus> {
+            self.pending.lock().unwrap().contains(tx_hash).then_some(TxStatus::Pending)
+        }
+
+        fn process_batches(&self) -> IngressFuture<'_, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn drain(&self) -> IngressFuture<'_, ()> {
+            Box::pin(async {
+                self.pending.lock().unwrap().clear();
+                Ok(())
+            })
+        }
+
+        fn shutdown(&self) {}
+    }
+
+    // A caller that only knows the trait
+    async fn submit_and_drain(ingress: Arc<dyn Ingress>) -> Vec<Option<TxStatus>> {
+        let tx_hash = ingress.submit(dynamic_fee_tx(0)).await.unwrap();
+        let mut statuses = vec![ingress.status(&tx_hash)];
+        ingress.process_batches().await.unwrap();
+        ingress.drain().await.unwrap();
+        statuses.push(ingress.status(&tx_hash));
+        ingress.shutdown();
+        statuses
+    }
+
+    #[tokio::test]
+    async fn test_callers_swap_backends_behind_the_trait() {
+        let stub: Arc<dyn Ingress> = Arc::new(StubIngress::default());
+        assert_eq!(submit_and_drain(stub).await, vec![Some(TxStatus::Pending), None]);
+
+        let relay = InMemoryRelay::new("memory");
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), Vec::new())
+            .with_relay_forwarder(RelayForwarder::from_transports(vec![Box::new(relay.clone())]));
+        let ingress: Arc<dyn Ingress> = Arc::new(ingress);
+        assert_eq!(submit_and_drain(ingress).await, vec![Some(TxStatus::Pending), Some(TxStatus::Forwarded)]);
+        assert_eq!(relay.forwarded().unwrap().len(), 1);
+    }
+}
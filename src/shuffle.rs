@@ -0,0 +1,88 @@
+use sha2::{Digest, Sha256};
+
+// Fisher-Yates shuffle with a fully specified seed-to-permutation mapping
+//
+// For i from len - 1 down to 1, draw a u64 as the first 8 bytes (big-endian) of
+// SHA-256(seed || counter), with counter a big-endian u64 starting at 0 and advanced on every draw.
+// Draws at or above the largest multiple of i + 1 that fits in 2^64 are rejected, so j = draw % (i + 1)
+// is unbiased; then swap positions i and j. Anyone holding the seed can reproduce the permutation
+// in any language, independently of the rand crate's generator.
+fn fisher_yates(len: usize, seed: [u8; 32], mut swap: impl FnMut(usize, usize)) {
+    let mut counter = 0u64;
+    for i in (1..len).rev() {
+        let bound = i as u64 + 1;
+        let rejected = (u64::MAX % bound + 1) % bound; // 2^64 mod bound
+        let j = loop {
+            let draw = draw(&seed, counter);
+            counter += 1;
+            if draw <= u64::MAX - rejected {
+                break (draw % bound) as usize;
+            }
+        };
+        swap(i, j);
+    }
+}
+
+fn draw(seed: &[u8; 32], counter: u64) -> u64 {
+    let digest = Sha256::new().chain_update(seed).chain_update(counter.to_be_bytes()).finalize();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+// Permutation the seed maps items to: position k of the shuffled order holds items[permutation[k]]
+pub fn shuffle_with_seed<T>(items: &[T], seed: [u8; 32]) -> Vec<usize> {
+    let mut permutation: Vec<usize> = (0..items.len()).collect();
+    fisher_yates(items.len(), seed, |i, j| permutation.swap(i, j));
+    permutation
+}
+
+// Reorders items as the permutation says, e.g. to check a forwarded order against its seed
+pub fn apply_permutation<T: Clone>(items: &[T], permutation: &[usize]) -> Vec<T> {
+    permutation.iter().map(|&index| items[index].clone()).collect()
+}
+
+// Shuffles items in place, returning the permutation applied
+pub(crate) fn shuffle_in_place<T>(items: &mut [T], seed: [u8; 32]) -> Vec<usize> {
+    let mut permutation: Vec<usize> = (0..items.len()).collect();
+    fisher_yates(items.len(), seed, |i, j| {
+        items.swap(i, j);
+        permutation.swap(i, j);
+    });
+    permutation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permutation_is_bijection() {
+        for len in [0, 1, 2, 3, 17, 256, 1000] {
+            let items: Vec<usize> = (0..len).collect();
+            let mut permutation = shuffle_with_seed(&items, [len as u8; 32]);
+
+            assert_eq!(permutation.len(), len);
+            permutation.sort_unstable();
+            assert_eq!(permutation, items);
+        }
+    }
+
+    #[test]
+    fn test_permutation_reproduces_shuffle() {
+        let items: Vec<String> = (0..64).map(|i| format!("tx-{}", i)).collect();
+        let permutation = shuffle_with_seed(&items, [9; 32]);
+
+        let mut shuffled = items.clone();
+        assert_eq!(shuffle_in_place(&mut shuffled, [9; 32]), permutation);
+        assert_eq!(apply_permutation(&items, &permutation), shuffled);
+        assert_ne!(shuffled, items);
+        assert_ne!(shuffle_with_seed(&items, [10; 32]), permutation);
+
+        // The mapping is part of the audit trail, so it must never change between releases
+        assert_eq!(shuffle_with_seed(&[(); 8], [0; 32]), GOLDEN_PERMUTATION);
+    }
+
+    // Computed independently from the mapping documented on fisher_yates
+    const GOLDEN_PERMUTATION: [usize; 8] = [7, 3, 0, 6, 1, 2, 5, 4];
+}
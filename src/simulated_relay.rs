@@ -0,0 +1,181 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::batch::TransactionBatch;
+use crate::error::{lock, IngressError};
+use crate::relay::{RelayFuture, RelayResult, RelayTransport};
+
+// What a simulated relay does with one batch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimulatedOutcome {
+    Accept,      // Reported as HTTP 200
+    Reject(u16), // Received but refused with this HTTP status
+    Unreachable, // Never received, like a refused connection
+}
+
+// A simulated relay's answer to one batch, given after delay
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SimulatedResponse {
+    pub outcome: SimulatedOutcome,
+    pub delay: Duration,
+}
+
+impl SimulatedResponse {
+    pub fn accept() -> Self {
+        Self::from(SimulatedOutcome::Accept)
+    }
+
+    pub fn reject(status: u16) -> Self {
+        Self::from(SimulatedOutcome::Reject(status))
+    }
+
+    pub fn unreachable() -> Self {
+        Self::from(SimulatedOutcome::Unreachable)
+    }
+
+    // Answers only once delay has passed, measured on the tokio clock so paused-time tests stay instant
+    pub fn after(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+impl From<SimulatedOutcome> for SimulatedResponse {
+    fn from(outcome: SimulatedOutcome) -> Self {
+        Self {
+            outcome,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+// Relay that answers from a script instead of the network, for end-to-end tests of quorum, retry
+// and timing behaviour without HTTP mocks; clones share the script and received batches
+//
+// Each batch takes the next scripted response, falling back to the default response (accept,
+// unless with_default_response says otherwise) once the script runs out.
+#[derive(Clone)]
+pub struct SimulatedRelay {
+    name: String,
+    default_response: SimulatedResponse,
+    script: Arc<Mutex<VecDeque<SimulatedResponse>>>,
+    received: Arc<Mutex<Vec<TransactionBatch>>>,
+}
+
+impl SimulatedRelay {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            default_response: SimulatedResponse::accept(),
+            script: Arc::new(Mutex::new(VecDeque::new())),
+            received: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    // Response for every batch not covered by the script
+    pub fn with_default_response(mut self, response: SimulatedResponse) -> Self {
+        self.default_response = response;
+        self
+    }
+
+    // Responses for the next batches, in order
+    pub fn with_script(self, responses: impl IntoIterator<Item = SimulatedResponse>) -> Self {
+        self.script.lock().unwrap_or_else(PoisonError::into_inner).extend(responses);
+        self
+    }
+
+    // Batches that reached the relay, accepted or rejected, in arrival order
+    pub fn received_batches(&self) -> Result<Vec<TransactionBatch>, IngressError> {
+        Ok(lock(&self.received)?.clone())
+    }
+
+    // A poisoned lock still holds a valid script
+    fn next_response(&self) -> SimulatedResponse {
+        let mut script = self.script.lock().unwrap_or_else(PoisonError::into_inner);
+        script.pop_front().unwrap_or(self.default_response)
+    }
+
+    async fn respond(&self, batch: &TransactionBatch) -> RelayResult {
+        let response = self.next_response();
+        if !response.delay.is_zero() {
+            tokio::time::sleep(response.delay).await;
+        }
+
+        let mut result = RelayResult::new(self.name.as_str());
+        let status = match response.outcome {
+            SimulatedOutcome::Unreachable => {
+                let message = format!("{}: simulated connection failure", self.name);
+                result.error = Some(IngressError::RelayUnreachable(message));
+                return result;
+            }
+            SimulatedOutcome::Accept => 200,
+            SimulatedOutcome::Reject(status) => {
+                result.error = Some(IngressError::RelayRejected(format!("HTTP {}", status)));
+                status
+            }
+        };
+        result.status = Some(status);
+        if let Err(error) = lock(&self.received).map(|mut received| received.push(batch.clone())) {
+            result.error.get_or_insert(error);
+        }
+        result
+    }
+}
+
+impl RelayTransport for SimulatedRelay {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn submit<'a>(&'a self, batch: &'a TransactionBatch) -> RelayFuture<'a> {
+        Box::pin(self.respond(batch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::TransactionEnvelope;
+    use crate::relay::RelayForwarder;
+
+    fn batch(payload: u8) -> TransactionBatch {
+        TransactionBatch::new(vec![TransactionEnvelope::new(vec![0x02, payload], String::new())]).unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_forwarder_aggregates_simulated_relay_results() {
+        let accepting = SimulatedRelay::new("accepting");
+        let rejecting = SimulatedRelay::new("rejecting").with_default_response(SimulatedResponse::reject(503));
+        let flaky = SimulatedRelay::new("flaky")
+            .with_script([SimulatedResponse::unreachable(), SimulatedResponse::accept().after(Duration::from_secs(2))]);
+        let forwarder = RelayForwarder::from_transports(vec![
+            Box::new(accepting.clone()),
+            Box::new(rejecting.clone()),
+            Box::new(flaky.clone()),
+        ]);
+
+        let first = forwarder.forward_batch(&batch(1)).await;
+        let outcomes: Vec<(&str, bool, Option<u16>)> =
+            first.iter().map(|result| (result.relay_url.as_str(), result.is_success(), result.status)).collect();
+        assert_eq!(
+            outcomes,
+            vec![("accepting", true, Some(200)), ("rejecting", false, Some(503)), ("flaky", false, None)]
+        );
+        assert!(matches!(first[1].error, Some(IngressError::RelayRejected(_))));
+        assert!(matches!(first[2].error, Some(IngressError::RelayUnreachable(_))));
+
+        // The flaky relay's script moves on to a slow acceptance; simulated delays count as latency
+        let second = forwarder.forward_batch(&batch(2)).await;
+        assert_eq!(second.iter().filter(|result| result.is_success()).count(), 2);
+        assert!(second[2].is_success());
+        assert!(second[2].latency >= Duration::from_secs(2));
+
+        // An unreachable relay never received the batch; a rejecting one did
+        assert_eq!(accepting.received_batches().unwrap().len(), 2);
+        assert_eq!(rejecting.received_batches().unwrap().len(), 2);
+        let flaky_received = flaky.received_batches().unwrap();
+        assert_eq!(flaky_received.len(), 1);
+        assert_eq!(flaky_received[0].transactions[0].tx_bytes, vec![0x02, 2]);
+    }
+}
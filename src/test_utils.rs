@@ -0,0 +1,245 @@
+// Shared fixtures for unit tests
+
+use k256::ecdsa::SigningKey;
+
+use crate::decoy::DynamicFeeTx;
+use crate::entropy::Entropy;
+use crate::error::IngressError;
+use crate::rlp::{encode_bytes, encode_list, encode_u64};
+use crate::transaction::{keccak256, Address, BYTES_PER_BLOB};
+
+// Default test key, the private key used by the EIP-155 example (0x4646...46)
+pub(crate) const TEST_KEY: u8 = 0x46;
+
+// EIP-155 example transaction signed with the private key 0x4646...46
+pub(crate) const EIP155_EXAMPLE_TX: &str = "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+// Its Keccak-256 transaction hash, as block explorers show it
+pub(crate) const EIP155_EXAMPLE_TX_HASH: &str = "33469b22e9f636356c4160a87eb19df52b7412e8eac32a4a55ffe88ea8350788";
+
+fn signing_key(key: u8) -> SigningKey {
+    SigningKey::from_slice(&[key; 32]).unwrap()
+}
+
+// Helper function to strip leading zeros from a big-endian scalar
+fn trim(bytes: &[u8]) -> &[u8] {
+    let first = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    &bytes[first..]
+}
+
+// Address controlled by the private key [key; 32]
+pub(crate) fn test_address(key: u8) -> Address {
+    let public_key = signing_key(key).verifying_key().to_encoded_point(false);
+    let hash = keccak256(&public_key.as_bytes()[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+// Signs a prehash and returns the RLP-encoded (recovery id, r, s)
+fn sign(key: u8, hash: &[u8; 32]) -> (u8, Vec<u8>, Vec<u8>) {
+    let (signature, recovery_id) = signing_key(key).sign_prehash_recoverable(hash).unwrap();
+    (
+        recovery_id.to_byte(),
+        encode_bytes(trim(&signature.r().to_bytes())),
+        encode_bytes(trim(&signature.s().to_bytes())),
+    )
+}
+
+// Builds an EIP-155 legacy transaction on chain 1 signed with the test key
+pub(crate) fn legacy_tx(nonce: u64) -> Vec<u8> {
+    let mut fields = vec![
+        encode_u64(nonce),
+        encode_u64(20_000_000_000), // gas price
+        encode_u64(21_000),         // gas limit
+        encode_bytes(&[0x35; 20]),  // to
+        encode_u64(1_000_000_000_000_000_000),
+        encode_bytes(&[]),          // data
+    ];
+
+    let mut signing_fields = fields.clone();
+    signing_fields.extend([encode_u64(1), encode_bytes(&[]), encode_bytes(&[])]);
+    let (recovery_id, r, s) = sign(TEST_KEY, &keccak256(&encode_list(&signing_fields)));
+
+    fields.extend([encode_u64(35 + 2 + recovery_id as u64), r, s]);
+    encode_list(&fields)
+}
+
+// Builds a pre-EIP-155 legacy transaction, valid on any chain, signed with the test key
+pub(crate) fn unprotected_legacy_tx(nonce: u64) -> Vec<u8> {
+    let mut fields = vec![
+        encode_u64(nonce),
+        encode_u64(20_000_000_000), // gas price
+        encode_u64(21_000),         // gas limit
+        encode_bytes(&[0x35; 20]),  // to
+        encode_u64(1_000_000_000_000_000_000),
+        encode_bytes(&[]),          // data
+    ];
+
+    let (recovery_id, r, s) = sign(TEST_KEY, &keccak256(&encode_list(&fields)));
+
+    fields.extend([encode_u64(27 + recovery_id as u64), r, s]);
+    encode_list(&fields)
+}
+
+// Builds an EIP-1559 transaction on chain 1 signed with the test key
+pub(crate) fn dynamic_fee_tx(nonce: u64) -> Vec<u8> {
+    signed_dynamic_fee_tx(TEST_KEY, nonce)
+}
+
+// Builds an EIP-1559 transaction bidding the given fees, signed with the private key [key; 32]
+pub(crate) fn fee_bidding_tx(key: u8, max_priority_fee: u64, max_fee: u64) -> Vec<u8> {
+    DynamicFeeTx {
+        chain_id: 1,
+        nonce: 0,
+        max_priority_fee,
+        max_fee,
+        gas_limit: 21_000,
+        to: [0x35; 20],
+        value: 1_000_000_000_000_000,
+    }
+    .sign(&signing_key(key))
+    .unwrap()
+}
+
+// Builds an EIP-1559 transaction on the given chain signed with the test key
+#[cfg(feature = "net")]
+pub(crate) fn dynamic_fee_tx_on_chain(chain_id: u64) -> Vec<u8> {
+    DynamicFeeTx {
+        chain_id,
+        nonce: 0,
+        max_priority_fee: 1_000_000_000,
+        max_fee: 30_000_000_000,
+        gas_limit: 21_000,
+        to: [0x35; 20],
+        value: 1_000_000_000_000_000,
+    }
+    .sign(&signing_key(TEST_KEY))
+    .unwrap()
+}
+
+// Builds an EIP-1559 transfer to the given recipient, signed with the private key [key; 32]
+pub(crate) fn transfer_to(key: u8, to: Address) -> Vec<u8> {
+    DynamicFeeTx {
+        chain_id: 1,
+        nonce: 0,
+        max_priority_fee: 1_000_000_000,
+        max_fee: 30_000_000_000,
+        gas_limit: 21_000,
+        to,
+        value: 1_000_000_000_000_000,
+    }
+    .sign(&signing_key(key))
+    .unwrap()
+}
+
+// Builds an EIP-1559 transaction on chain 1 signed with the private key [key; 32]
+pub(crate) fn signed_dynamic_fee_tx(key: u8, nonce: u64) -> Vec<u8> {
+    DynamicFeeTx {
+        chain_id: 1,
+        nonce,
+        max_priority_fee: 1_000_000_000,
+        max_fee: 30_000_000_000,
+        gas_limit: 21_000,
+        to: [0x35; 20],
+        value: 1_000_000_000_000_000,
+    }
+    .sign(&signing_key(key))
+    .unwrap()
+}
+
+// Builds an EIP-4844 blob transaction on chain 1 carrying blob_count blobs, signed with the test key
+//
+// With with_sidecar the transaction is in network form, followed by its blobs, commitments and proofs;
+// without, it is the bare form that appears in blocks.
+pub(crate) fn blob_tx(nonce: u64, blob_count: usize, with_sidecar: bool) -> Vec<u8> {
+    use sha2::{Digest, Sha256};
+
+    let commitments: Vec<Vec<u8>> = (0..blob_count).map(|i| vec![i as u8 + 1; 48]).collect();
+    let hashes: Vec<Vec<u8>> = commitments
+        .iter()
+        .map(|commitment| {
+            let mut hash: [u8; 32] = Sha256::digest(commitment).into();
+            hash[0] = 0x01;
+            encode_bytes(&hash)
+        })
+        .collect();
+
+    let mut fields = vec![
+        encode_u64(1), // chain id
+        encode_u64(nonce),
+        encode_u64(1_000_000_000),  // max priority fee
+        encode_u64(30_000_000_000), // max fee
+        encode_u64(21_000),         // gas limit
+        encode_bytes(&[0x35; 20]),  // to
+        encode_u64(0),
+        encode_bytes(&[]),          // data
+        encode_list(&[]),           // access list
+        encode_u64(1_000_000_000),  // max fee per blob gas
+        encode_list(&hashes),
+    ];
+
+    let mut signing_payload = vec![0x03];
+    signing_payload.extend(encode_list(&fields));
+    let (recovery_id, r, s) = sign(TEST_KEY, &keccak256(&signing_payload));
+    fields.extend([encode_u64(recovery_id as u64), r, s]);
+
+    let body = encode_list(&fields);
+    let mut tx = vec![0x03];
+    if with_sidecar {
+        let blobs: Vec<Vec<u8>> = (0..blob_count).map(|_| encode_bytes(&vec![0u8; BYTES_PER_BLOB])).collect();
+        let commitments: Vec<Vec<u8>> = commitments.iter().map(|commitment| encode_bytes(commitment)).collect();
+        let proofs: Vec<Vec<u8>> = (0..blob_count).map(|_| encode_bytes(&[0xc0; 48])).collect();
+        tx.extend(encode_list(&[body, encode_list(&blobs), encode_list(&commitments), encode_list(&proofs)]));
+    } else {
+        tx.extend(body);
+    }
+    tx
+}
+
+// Node answering eth_blockNumber with the given head, for relays that target blocks relative to it
+#[cfg(feature = "net")]
+pub(crate) async fn node_at_block(head: u64) -> wiremock::MockServer {
+    let node = wiremock::MockServer::start().await;
+    set_head(&node, head).await;
+    node
+}
+
+// Moves a node from node_at_block to a new head, replacing everything it answered before
+#[cfg(feature = "net")]
+pub(crate) async fn set_head(node: &wiremock::MockServer, head: u64) {
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, ResponseTemplate};
+
+    node.reset().await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({"method": "eth_blockNumber"})))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": format!("0x{:x}", head)})),
+        )
+        .mount(node)
+        .await;
+}
+
+// Entropy that succeeds for the first few fills and fails every one after
+pub(crate) struct FailingEntropy {
+    remaining: std::sync::Mutex<usize>,
+}
+
+impl FailingEntropy {
+    pub(crate) fn after(fills: usize) -> Self {
+        Self { remaining: std::sync::Mutex::new(fills) }
+    }
+}
+
+impl Entropy for FailingEntropy {
+    fn fill(&self, dest: &mut [u8]) -> Result<(), IngressError> {
+        let mut remaining = self.remaining.lock().unwrap();
+        if *remaining == 0 {
+            return Err(IngressError::RngFailure);
+        }
+        *remaining -= 1;
+        dest.fill(0x42);
+        Ok(())
+    }
+}
@@ -0,0 +1,630 @@
+use std::borrow::Cow;
+
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+
+use crate::error::IngressError;
+use crate::rlp::{self, RlpItem};
+
+// Ethereum transaction types recognized by the ingress
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TxType {
+    Legacy,     // Untyped RLP list (pre-EIP-2718)
+    AccessList, // EIP-2930, type 0x01
+    DynamicFee, // EIP-1559, type 0x02
+    Blob,       // EIP-4844, type 0x03
+}
+
+impl TxType {
+    // EIP-2718 type byte, None for legacy transactions
+    pub fn type_byte(&self) -> Option<u8> {
+        match self {
+            TxType::Legacy => None,
+            TxType::AccessList => Some(0x01),
+            TxType::DynamicFee => Some(0x02),
+            TxType::Blob => Some(0x03),
+        }
+    }
+
+    // Positions of the fields that are RLP lists; all other fields are byte strings
+    fn list_fields(&self) -> &'static [usize] {
+        match self {
+            TxType::Legacy => &[],
+            TxType::AccessList => &[7],
+            TxType::DynamicFee => &[8],
+            TxType::Blob => &[8, 10],
+        }
+    }
+
+    // Position of the nonce field; typed transactions lead with the chain id
+    fn nonce_index(&self) -> usize {
+        match self {
+            TxType::Legacy => 0,
+            _ => 1,
+        }
+    }
+
+    // Positions of the (max fee, max priority fee) fields; pre-EIP-1559 types bid a single gas price for both
+    fn fee_indices(&self) -> (usize, usize) {
+        match self {
+            TxType::Legacy => (1, 1),
+            TxType::AccessList => (2, 2),
+            TxType::DynamicFee | TxType::Blob => (3, 2),
+        }
+    }
+
+    // Position of the recipient field, which follows the gas limit
+    fn recipient_index(&self) -> usize {
+        match self {
+            TxType::Legacy => 3,
+            TxType::AccessList => 4,
+            TxType::DynamicFee | TxType::Blob => 5,
+        }
+    }
+
+    fn field_count(&self) -> usize {
+        match self {
+            TxType::Legacy => 9,
+            TxType::AccessList => 11,
+            TxType::DynamicFee => 12,
+            TxType::Blob => 14,
+        }
+    }
+}
+
+// Ethereum account address
+pub type Address = [u8; 20];
+
+// Size of one EIP-4844 blob: 4096 field elements of 32 bytes
+pub const BYTES_PER_BLOB: usize = 4096 * 32;
+
+// Size of a KZG commitment or proof in a blob sidecar
+const KZG_BYTES: usize = 48;
+
+// Version byte of a blob versioned hash, which is SHA-256 of the KZG commitment with its first byte replaced
+const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+
+// Fee caps a transaction bids, in wei per gas; ordered by max fee, then priority fee
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FeeBid {
+    pub max_fee: u128,
+    pub max_priority_fee: u128,
+}
+
+// Validates the envelope and RLP structure of a raw signed transaction, returning its type
+pub fn validate_transaction(tx_bytes: &[u8]) -> Result<TxType, IngressError> {
+    decode_fields(tx_bytes).map(|(tx_type, _)| tx_type)
+}
+
+// Decodes the account nonce of a raw signed transaction
+pub fn transaction_nonce(tx_bytes: &[u8]) -> Result<u64, IngressError> {
+    let (tx_type, fields) = decode_fields(tx_bytes)?;
+    decode_u64(&fields[tx_type.nonce_index()])
+}
+
+// Decodes the fee caps of a raw signed transaction
+pub fn transaction_fees(tx_bytes: &[u8]) -> Result<FeeBid, IngressError> {
+    let (tx_type, fields) = decode_fields(tx_bytes)?;
+    let (max_fee_index, priority_fee_index) = tx_type.fee_indices();
+    Ok(FeeBid {
+        max_fee: decode_u128(&fields[max_fee_index])?,
+        max_priority_fee: decode_u128(&fields[priority_fee_index])?,
+    })
+}
+
+// Decodes the recipient of a raw signed transaction, or None for a contract creation
+pub fn transaction_to(tx_bytes: &[u8]) -> Result<Option<Address>, IngressError> {
+    let (tx_type, fields) = decode_fields(tx_bytes)?;
+    match fields[tx_type.recipient_index()].as_bytes() {
+        Some([]) => Ok(None),
+        Some(to) => Address::try_from(to)
+            .map(Some)
+            .map_err(|_| IngressError::InvalidTransaction("recipient is not a 20-byte address".to_string())),
+        None => Err(IngressError::InvalidTransaction("recipient is not a byte string".to_string())),
+    }
+}
+
+// Decodes the chain ID a raw signed transaction is bound to, or None for a pre-EIP-155 legacy transaction
+pub fn transaction_chain_id(tx_bytes: &[u8]) -> Result<Option<u64>, IngressError> {
+    let (tx_type, fields) = decode_fields(tx_bytes)?;
+    match tx_type {
+        // Legacy transactions fold the chain ID into v = chain_id * 2 + 35/36
+        TxType::Legacy => match decode_u64(&fields[fields.len() - 3])? {
+            27 | 28 => Ok(None),
+            v if v >= 35 => Ok(Some((v - 35) / 2)),
+            _ => Err(IngressError::InvalidSignature),
+        },
+        // Typed transactions carry it as their first field
+        _ => decode_u64(&fields[0]).map(Some),
+    }
+}
+
+// Keccak-256 transaction hash, as block explorers show it
+pub type TxHash = [u8; 32];
+
+// Standard Ethereum transaction hash: Keccak-256 over the raw signed bytes, including any type prefix
+//
+// A blob transaction's hash never covers its sidecar, so one in network form is hashed without it.
+pub fn transaction_hash(tx_bytes: &[u8]) -> TxHash {
+    keccak256(&canonical_transaction(tx_bytes))
+}
+
+// Decodes the blob versioned hashes of a raw signed transaction; empty for every type but blob transactions
+pub fn blob_versioned_hashes(tx_bytes: &[u8]) -> Result<Vec<[u8; 32]>, IngressError> {
+    let (tx_type, fields) = decode_fields(tx_bytes)?;
+    if tx_type != TxType::Blob {
+        return Ok(Vec::new());
+    }
+    Ok(versioned_hash_items(&fields)
+        .iter()
+        .filter_map(|hash| hash.as_bytes().and_then(|hash| <[u8; 32]>::try_from(hash).ok()))
+        .collect())
+}
+
+// Bytes of a raw transaction taken up by its blob sidecar (blobs, commitments and proofs)
+//
+// Only blob transactions in the EIP-4844 network form carry a sidecar; this is 0 for every other
+// transaction, including ones that fail to decode.
+pub fn blob_sidecar_len(tx_bytes: &[u8]) -> usize {
+    tx_bytes.len() - canonical_transaction(tx_bytes).len()
+}
+
+// The transaction as it appears in a block: a blob transaction in network form without its sidecar,
+// anything else unchanged
+fn canonical_transaction(tx_bytes: &[u8]) -> Cow<'_, [u8]> {
+    match tx_bytes.first() {
+        Some(0x03) => match decode_envelope(tx_bytes) {
+            Ok((_, fields, Some(_))) => {
+                let mut canonical = vec![0x03];
+                canonical.extend(RlpItem::List(fields).encode());
+                Cow::Owned(canonical)
+            }
+            _ => Cow::Borrowed(tx_bytes),
+        },
+        _ => Cow::Borrowed(tx_bytes),
+    }
+}
+
+// Recovers the sender address from the transaction signature
+pub fn recover_sender(tx_bytes: &[u8]) -> Result<Address, IngressError> {
+    let (tx_type, fields) = decode_fields(tx_bytes)?;
+
+    // The signature is always the last three fields: v / y_parity, r, s
+    let unsigned = &fields[..fields.len() - 3];
+    let v = decode_u64(&fields[fields.len() - 3])?;
+    let r = fields[fields.len() - 2].as_bytes().unwrap_or_default();
+    let s = fields[fields.len() - 1].as_bytes().unwrap_or_default();
+
+    let mut unsigned_encoded: Vec<Vec<u8>> = unsigned.iter().map(RlpItem::encode).collect();
+    let (signing_payload, recovery_id) = match tx_type.type_byte() {
+        // Legacy: pre-EIP-155 uses v = 27/28, EIP-155 uses v = chain_id * 2 + 35/36
+        None => {
+            let recovery_id = match v {
+                27 | 28 => v - 27,
+                v if v >= 35 => {
+                    unsigned_encoded.push(rlp::encode_u64((v - 35) / 2));
+                    unsigned_encoded.push(rlp::encode_bytes(&[]));
+                    unsigned_encoded.push(rlp::encode_bytes(&[]));
+                    (v - 35) % 2
+                }
+                _ => return Err(IngressError::InvalidSignature),
+            };
+            (rlp::encode_list(&unsigned_encoded), recovery_id)
+        }
+        // Typed: keccak256(type || rlp(fields without signature)), with y_parity as the recovery id
+        Some(type_byte) => {
+            let mut payload = vec![type_byte];
+            payload.extend(rlp::encode_list(&unsigned_encoded));
+            (payload, v)
+        }
+    };
+
+    let signing_hash = keccak256(&signing_payload);
+    recover_address(&signing_hash, r, s, recovery_id)
+}
+
+// Helper function to recover the signing address from a prehashed message and (r, s, recovery_id)
+fn recover_address(hash: &[u8; 32], r: &[u8], s: &[u8], recovery_id: u64) -> Result<Address, IngressError> {
+    if r.len() > 32 || s.len() > 32 {
+        return Err(IngressError::InvalidSignature);
+    }
+    let mut signature_bytes = [0u8; 64];
+    signature_bytes[32 - r.len()..32].copy_from_slice(r);
+    signature_bytes[64 - s.len()..].copy_from_slice(s);
+
+    let signature = Signature::from_slice(&signature_bytes).map_err(|_| IngressError::InvalidSignature)?;
+    // EIP-2: reject malleable high-s signatures
+    if signature.normalize_s().is_some() {
+        return Err(IngressError::InvalidSignature);
+    }
+    let recovery_id = u8::try_from(recovery_id)
+        .ok()
+        .and_then(RecoveryId::from_byte)
+        .ok_or(IngressError::InvalidSignature)?;
+
+    let key = VerifyingKey::recover_from_prehash(hash, &signature, recovery_id)
+        .map_err(|_| IngressError::InvalidSignature)?;
+
+    // Address is the last 20 bytes of keccak256(uncompressed public key without the 0x04 prefix)
+    let public_key = key.to_encoded_point(false);
+    let hash = keccak256(&public_key.as_bytes()[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    Ok(address)
+}
+
+// Helper function for Keccak-256 hashing
+pub(crate) fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+// Helper function to decode a scalar RLP field as an unsigned integer
+fn decode_u64(item: &RlpItem<'_>) -> Result<u64, IngressError> {
+    let bytes = item
+        .as_bytes()
+        .filter(|bytes| bytes.len() <= 8)
+        .ok_or_else(|| IngressError::InvalidTransaction("expected an integer field".to_string()))?;
+    Ok(bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
+}
+
+// Helper function to decode a scalar RLP field wide enough for wei amounts
+fn decode_u128(item: &RlpItem<'_>) -> Result<u128, IngressError> {
+    let bytes = item
+        .as_bytes()
+        .filter(|bytes| bytes.len() <= 16)
+        .ok_or_else(|| IngressError::InvalidTransaction("expected a fee field".to_string()))?;
+    Ok(bytes.iter().fold(0u128, |acc, &b| (acc << 8) | b as u128))
+}
+
+// Helper function to split a raw transaction into its type and top-level RLP fields
+fn decode_fields(tx_bytes: &[u8]) -> Result<(TxType, Vec<RlpItem<'_>>), IngressError> {
+    decode_envelope(tx_bytes).map(|(tx_type, fields, _)| (tx_type, fields))
+}
+
+// Items of a decoded blob transaction's blob_versioned_hashes field
+fn versioned_hash_items<'a, 'b>(fields: &'b [RlpItem<'a>]) -> &'b [RlpItem<'a>] {
+    match &fields[10] {
+        RlpItem::List(hashes) => hashes,
+        RlpItem::Bytes(_) => &[],
+    }
+}
+
+// A decoded transaction's type, top-level RLP fields and blob sidecar lists, if it carries any
+type DecodedEnvelope<'a> = (TxType, Vec<RlpItem<'a>>, Option<Vec<RlpItem<'a>>>);
+
+// Helper function to split a raw transaction into its type, its top-level RLP fields and, for a blob
+// transaction in network form, the sidecar lists (blobs, commitments, proofs) that follow them
+fn decode_envelope(tx_bytes: &[u8]) -> Result<DecodedEnvelope<'_>, IngressError> {
+    let first = *tx_bytes.first().ok_or(IngressError::EmptyTransaction)?;
+
+    // Legacy transactions are a bare RLP list, typed ones carry an EIP-2718 prefix byte;
+    // EIP-2718 keeps type ids below 0x80, so a list prefix is never mistaken for a type
+    let (tx_type, payload) = match first {
+        0xc0..=0xff => (TxType::Legacy, tx_bytes),
+        0x01 => (TxType::AccessList, &tx_bytes[1..]),
+        0x02 => (TxType::DynamicFee, &tx_bytes[1..]),
+        0x03 => (TxType::Blob, &tx_bytes[1..]),
+        0x80..=0xbf => {
+            return Err(IngressError::InvalidTransaction(
+                "untyped transaction is an RLP string, not a list".to_string(),
+            ))
+        }
+        other => {
+            return Err(IngressError::InvalidTransaction(format!(
+                "unsupported transaction type 0x{:02x}",
+                other
+            )))
+        }
+    };
+
+    let decoded = rlp::decode(payload)
+        .map_err(|e| IngressError::InvalidTransaction(format!("malformed RLP: {}", e)))?;
+    let RlpItem::List(mut fields) = decoded else {
+        return Err(IngressError::InvalidTransaction(
+            "transaction payload is not an RLP list".to_string(),
+        ));
+    };
+
+    // Blob transactions broadcast to the network wrap their fields with the sidecar:
+    // rlp([tx_payload_body, blobs, commitments, proofs])
+    let mut sidecar = None;
+    if tx_type == TxType::Blob && matches!(fields.first(), Some(RlpItem::List(_))) {
+        if fields.len() != 4 {
+            return Err(IngressError::InvalidTransaction(format!(
+                "blob transaction network form has {} items, expected 4",
+                fields.len()
+            )));
+        }
+        let mut items = fields.into_iter();
+        let Some(RlpItem::List(body)) = items.next() else {
+            unreachable!("first item was checked to be a list");
+        };
+        fields = body;
+        sidecar = Some(items.collect::<Vec<_>>());
+    }
+
+    if fields.len() != tx_type.field_count() {
+        return Err(IngressError::InvalidTransaction(format!(
+            "{:?} transaction has {} fields, expected {}",
+            tx_type,
+            fields.len(),
+            tx_type.field_count()
+        )));
+    }
+
+    for (index, field) in fields.iter().enumerate() {
+        let expects_list = tx_type.list_fields().contains(&index);
+        if expects_list != matches!(field, RlpItem::List(_)) {
+            return Err(IngressError::InvalidTransaction(format!(
+                "{:?} transaction field {} has the wrong RLP shape",
+                tx_type, index
+            )));
+        }
+    }
+
+    if tx_type == TxType::Blob {
+        validate_blobs(&fields, sidecar.as_deref())?;
+    }
+
+    Ok((tx_type, fields, sidecar))
+}
+
+// Helper function to check a blob transaction's recipient, versioned hashes and, if present, its sidecar
+//
+// The sidecar must hold one blob, commitment and proof per versioned hash, and each hash must match
+// its commitment. The KZG proofs themselves are left to the execution client.
+fn validate_blobs(fields: &[RlpItem<'_>], sidecar: Option<&[RlpItem<'_>]>) -> Result<(), IngressError> {
+    let invalid = |reason: &str| Err(IngressError::InvalidTransaction(format!("blob transaction {}", reason)));
+
+    if fields[TxType::Blob.recipient_index()].as_bytes().is_none_or(|to| to.len() != 20) {
+        return invalid("cannot create a contract");
+    }
+
+    let hashes = versioned_hash_items(fields);
+    if hashes.is_empty() {
+        return invalid("carries no blob versioned hashes");
+    }
+    for hash in hashes {
+        match hash.as_bytes() {
+            Some(hash) if hash.len() == 32 && hash[0] == VERSIONED_HASH_VERSION_KZG => {}
+            _ => return invalid("has a malformed blob versioned hash"),
+        }
+    }
+
+    let Some(sidecar) = sidecar else {
+        return Ok(());
+    };
+    let sidecar_list = |index: usize, len: usize| match sidecar.get(index) {
+        Some(RlpItem::List(items))
+            if items.len() == hashes.len() && items.iter().all(|item| item.as_bytes().is_some_and(|b| b.len() == len)) =>
+        {
+            Ok(items.iter().filter_map(RlpItem::as_bytes).collect::<Vec<_>>())
+        }
+        _ => Err(IngressError::InvalidTransaction(
+            "blob transaction sidecar does not match its versioned hashes".to_string(),
+        )),
+    };
+    sidecar_list(0, BYTES_PER_BLOB)?;
+    let commitments = sidecar_list(1, KZG_BYTES)?;
+    sidecar_list(2, KZG_BYTES)?;
+
+    for (hash, commitment) in hashes.iter().zip(commitments) {
+        let mut expected: [u8; 32] = Sha256::digest(commitment).into();
+        expected[0] = VERSIONED_HASH_VERSION_KZG;
+        if hash.as_bytes() != Some(&expected[..]) {
+            return invalid("versioned hash does not match its commitment");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        blob_tx, dynamic_fee_tx, legacy_tx, test_address, transfer_to, unprotected_legacy_tx, EIP155_EXAMPLE_TX,
+        EIP155_EXAMPLE_TX_HASH, TEST_KEY,
+    };
+
+    // EIP-1559 transaction (chain 1, nonce 0) signed with the same key
+    const EIP1559_EXAMPLE_TX: &str = "02f8720180843b9aca008506fc23ac0082520894353535353535353535353535353535353535353587038d7ea4c6800080c080a0a9047639d1bb3a029b9a12146c35360f43fd065dfa27c727b160c81e308e9805a07fd2d58b4c1e62354bc057008576531a843c4de868e4b244fc060ca294dcb15c";
+    const EXAMPLE_SENDER: &str = "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f";
+
+    #[test]
+    fn test_validate_legacy_transaction() {
+        assert_eq!(validate_transaction(&legacy_tx(0)), Ok(TxType::Legacy));
+    }
+
+    #[test]
+    fn test_real_transactions_classified_by_envelope() {
+        // 0xf8 opens the legacy RLP list; it is not read as a type id
+        let legacy = hex::decode(EIP155_EXAMPLE_TX).unwrap();
+        assert_eq!(legacy[0], 0xf8);
+        assert_eq!(validate_transaction(&legacy), Ok(TxType::Legacy));
+        assert_eq!(hex::encode(recover_sender(&legacy).unwrap()), EXAMPLE_SENDER);
+
+        let typed = hex::decode(EIP1559_EXAMPLE_TX).unwrap();
+        assert_eq!(validate_transaction(&typed), Ok(TxType::DynamicFee));
+        assert_eq!(hex::encode(recover_sender(&typed).unwrap()), EXAMPLE_SENDER);
+
+        // A legacy body behind a type prefix is malformed, not reclassified
+        let mut prefixed = vec![0x02];
+        prefixed.extend(&legacy);
+        assert!(matches!(validate_transaction(&prefixed), Err(IngressError::InvalidTransaction(_))));
+    }
+
+    #[test]
+    fn test_transaction_hash_known_vector() {
+        let tx = hex::decode(EIP155_EXAMPLE_TX).unwrap();
+        assert_eq!(hex::encode(transaction_hash(&tx)), EIP155_EXAMPLE_TX_HASH);
+    }
+
+    #[test]
+    fn test_validate_eip1559_transaction() {
+        let tx = dynamic_fee_tx(7);
+        assert_eq!(tx[0], 0x02);
+        assert_eq!(validate_transaction(&tx), Ok(TxType::DynamicFee));
+    }
+
+    #[test]
+    fn test_validate_rejects_garbage() {
+        assert_eq!(validate_transaction(&[]), Err(IngressError::EmptyTransaction));
+
+        // Unknown type prefix
+        assert!(matches!(
+            validate_transaction(&[0x05, 0xc0]),
+            Err(IngressError::InvalidTransaction(_))
+        ));
+
+        // RLP string where a legacy list or a type prefix should be
+        assert_eq!(
+            validate_transaction(&[0x82, 0x01, 0x02]),
+            Err(IngressError::InvalidTransaction("untyped transaction is an RLP string, not a list".to_string()))
+        );
+
+        // Typed prefix followed by something that isn't RLP
+        assert!(matches!(
+            validate_transaction(&[0x02, 0x01, 0x02, 0x03]),
+            Err(IngressError::InvalidTransaction(_))
+        ));
+
+        // Well-formed RLP list with the wrong number of fields
+        let mut short = vec![0x02];
+        short.extend(rlp::encode_list(&[rlp::encode_u64(1), rlp::encode_u64(2)]));
+        assert!(matches!(validate_transaction(&short), Err(IngressError::InvalidTransaction(_))));
+
+        // Truncated legacy transaction
+        let legacy = legacy_tx(0);
+        assert!(matches!(
+            validate_transaction(&legacy[..legacy.len() - 1]),
+            Err(IngressError::InvalidTransaction(_))
+        ));
+    }
+
+    #[test]
+    fn test_transaction_chain_id() {
+        assert_eq!(transaction_chain_id(&hex::decode(EIP155_EXAMPLE_TX).unwrap()), Ok(Some(1)));
+        assert_eq!(transaction_chain_id(&hex::decode(EIP1559_EXAMPLE_TX).unwrap()), Ok(Some(1)));
+        assert_eq!(transaction_chain_id(&legacy_tx(0)), Ok(Some(1)));
+        assert_eq!(transaction_chain_id(&unprotected_legacy_tx(0)), Ok(None));
+    }
+
+    #[test]
+    fn test_transaction_to() {
+        assert_eq!(transaction_to(&hex::decode(EIP155_EXAMPLE_TX).unwrap()), Ok(Some([0x35; 20])));
+        assert_eq!(transaction_to(&hex::decode(EIP1559_EXAMPLE_TX).unwrap()), Ok(Some([0x35; 20])));
+        assert_eq!(transaction_to(&transfer_to(1, [0x77; 20])), Ok(Some([0x77; 20])));
+    }
+
+    #[test]
+    fn test_transaction_nonce() {
+        assert_eq!(transaction_nonce(&hex::decode(EIP155_EXAMPLE_TX).unwrap()), Ok(9));
+        assert_eq!(transaction_nonce(&legacy_tx(300)), Ok(300));
+        assert_eq!(transaction_nonce(&dynamic_fee_tx(0)), Ok(0));
+        assert_eq!(transaction_nonce(&dynamic_fee_tx(7)), Ok(7));
+    }
+
+    #[test]
+    fn test_transaction_fees() {
+        let legacy = transaction_fees(&hex::decode(EIP155_EXAMPLE_TX).unwrap()).unwrap();
+        assert_eq!(legacy, FeeBid { max_fee: 20_000_000_000, max_priority_fee: 20_000_000_000 });
+
+        let dynamic_fee = transaction_fees(&dynamic_fee_tx(0)).unwrap();
+        assert_eq!(dynamic_fee, FeeBid { max_fee: 30_000_000_000, max_priority_fee: 1_000_000_000 });
+    }
+
+    #[test]
+    fn test_recover_sender_known_vectors() {
+        let legacy = hex::decode(EIP155_EXAMPLE_TX).unwrap();
+        let dynamic_fee = hex::decode(EIP1559_EXAMPLE_TX).unwrap();
+
+        assert_eq!(hex::encode(recover_sender(&legacy).unwrap()), EXAMPLE_SENDER);
+        assert_eq!(hex::encode(recover_sender(&dynamic_fee).unwrap()), EXAMPLE_SENDER);
+
+        // The test fixtures sign with the same key deterministically (RFC 6979)
+        assert_eq!(dynamic_fee_tx(0), dynamic_fee);
+        assert_eq!(recover_sender(&legacy_tx(3)).unwrap(), test_address(0x46));
+    }
+
+    #[test]
+    fn test_recover_sender_rejects_bad_signatures() {
+        // Flip a byte of s: recovery yields a different key or fails, but never the real sender
+        let mut tampered = hex::decode(EIP1559_EXAMPLE_TX).unwrap();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0x01;
+        let recovered = recover_sender(&tampered);
+        assert!(recovered.is_err() || hex::encode(recovered.unwrap()) != EXAMPLE_SENDER);
+
+        // Zero r and s can never be a valid signature
+        let dynamic_fee = hex::decode(EIP1559_EXAMPLE_TX).unwrap();
+        let decoded = rlp::decode(&dynamic_fee[1..]).unwrap();
+        let mut fields: Vec<Vec<u8>> = decoded.as_list().unwrap().iter().map(RlpItem::encode).collect();
+        fields[10] = rlp::encode_bytes(&[]);
+        fields[11] = rlp::encode_bytes(&[]);
+        let mut zero_signature = vec![0x02];
+        zero_signature.extend(rlp::encode_list(&fields));
+        assert_eq!(validate_transaction(&zero_signature), Ok(TxType::DynamicFee));
+        assert_eq!(recover_sender(&zero_signature), Err(IngressError::InvalidSignature));
+
+        // Legacy v values other than 27/28 or >= 35 are invalid
+        let mut bad_v = hex::decode(EIP155_EXAMPLE_TX).unwrap();
+        let v_index = bad_v.len() - 67;
+        assert_eq!(bad_v[v_index], 0x25);
+        bad_v[v_index] = 0x1d;
+        assert_eq!(recover_sender(&bad_v), Err(IngressError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_blob_transaction_classified_with_versioned_hashes() {
+        let bare = blob_tx(7, 2, false);
+        assert_eq!(validate_transaction(&bare), Ok(TxType::Blob));
+        assert_eq!(transaction_nonce(&bare), Ok(7));
+        assert_eq!(recover_sender(&bare), Ok(test_address(TEST_KEY)));
+        assert_eq!(blob_sidecar_len(&bare), 0);
+
+        let hashes = blob_versioned_hashes(&bare).unwrap();
+        assert_eq!(hashes.len(), 2);
+        assert!(hashes.iter().all(|hash| hash[0] == VERSIONED_HASH_VERSION_KZG));
+        assert_eq!(blob_versioned_hashes(&dynamic_fee_tx(0)), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_blob_sidecar_measured_and_excluded_from_hash() {
+        let bare = blob_tx(0, 1, false);
+        let wrapped = blob_tx(0, 1, true);
+
+        assert_eq!(validate_transaction(&wrapped), Ok(TxType::Blob));
+        assert_eq!(recover_sender(&wrapped), Ok(test_address(TEST_KEY)));
+        assert_eq!(blob_versioned_hashes(&wrapped), blob_versioned_hashes(&bare));
+        // The sidecar is everything but the bare transaction: one blob plus list headers, a commitment and a proof
+        assert_eq!(blob_sidecar_len(&wrapped), wrapped.len() - bare.len());
+        assert!(blob_sidecar_len(&wrapped) > BYTES_PER_BLOB + 2 * KZG_BYTES);
+        assert_eq!(transaction_hash(&wrapped), transaction_hash(&bare));
+        assert_eq!(blob_sidecar_len(&dynamic_fee_tx(0)), 0);
+        assert_eq!(blob_sidecar_len(&[0x03, 0x01]), 0);
+    }
+
+    #[test]
+    fn test_blob_transaction_rejects_mismatched_sidecar() {
+        // A sidecar without any blobs for the transaction's versioned hash
+        let bare = blob_tx(0, 1, false);
+        let mut empty_sidecar = vec![0x03];
+        empty_sidecar.extend(rlp::encode_list(&[
+            bare[1..].to_vec(),
+            rlp::encode_list(&[]),
+            rlp::encode_list(&[]),
+            rlp::encode_list(&[]),
+        ]));
+        assert!(matches!(validate_transaction(&empty_sidecar), Err(IngressError::InvalidTransaction(_))));
+
+        // A commitment that doesn't hash to the versioned hash
+        let mut wrapped = blob_tx(0, 1, true);
+        let commitment = wrapped.windows(KZG_BYTES).position(|window| window == [0x01; KZG_BYTES]).unwrap();
+        wrapped[commitment] = 0x02;
+        assert_eq!(
+            validate_transaction(&wrapped),
+            Err(IngressError::InvalidTransaction("blob transaction versioned hash does not match its commitment".to_string()))
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::IngressError;
+use crate::transaction::{keccak256, Address};
+use crate::wire::UserOperationWire;
+
+// ERC-4337 account abstraction operation in the EntryPoint v0.6 layout, as bundlers take it over
+// eth_sendUserOperation; serializes to that JSON shape
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "UserOperationWire", try_from = "UserOperationWire")]
+pub struct UserOperation {
+    pub sender: Address,    // Smart account the operation runs as
+    pub nonce: [u8; 32],    // Big-endian: 192-bit key, then the 64-bit sequence within that key
+    pub init_code: Vec<u8>, // Factory address followed by its calldata, empty once the account exists
+    pub call_data: Vec<u8>,
+    pub call_gas_limit: u128,
+    pub verification_gas_limit: u128,
+    pub pre_verification_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+    pub paymaster_and_data: Vec<u8>, // Paymaster address followed by its data, empty when the account pays
+    pub signature: Vec<u8>,
+}
+
+impl UserOperation {
+    // Parses the JSON a client submits, or a batch member's bytes
+    pub fn decode(bytes: &[u8]) -> Result<Self, IngressError> {
+        serde_json::from_slice(bytes).map_err(|error| IngressError::InvalidUserOperation(error.to_string()))
+    }
+
+    // JSON a batch member carries in place of raw transaction bytes; the same operation always
+    // encodes the same way, so its commitment leaf is stable
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    // Checks the fields every bundler requires, without simulating the operation
+    //
+    // Whether the signature is valid is for the account to decide on chain; only its presence is checked.
+    pub fn validate(&self) -> Result<(), IngressError> {
+        let invalid = |reason: &str| Err(IngressError::InvalidUserOperation(reason.to_string()));
+        if self.sender == [0; 20] {
+            return invalid("sender is the zero address");
+        }
+        if self.signature.is_empty() {
+            return invalid("signature is empty");
+        }
+        if self.call_gas_limit == 0 || self.verification_gas_limit == 0 || self.pre_verification_gas == 0 {
+            return invalid("callGasLimit, verificationGasLimit and preVerificationGas must be non-zero");
+        }
+        if self.max_fee_per_gas == 0 {
+            return invalid("maxFeePerGas must be non-zero");
+        }
+        if self.max_priority_fee_per_gas > self.max_fee_per_gas {
+            return invalid("maxPriorityFeePerGas exceeds maxFeePerGas");
+        }
+        if !self.init_code.is_empty() && self.init_code.len() < 20 {
+            return invalid("initCode is shorter than a factory address");
+        }
+        if !self.paymaster_and_data.is_empty() && self.paymaster_and_data.len() < 20 {
+            return invalid("paymasterAndData is shorter than a paymaster address");
+        }
+        Ok(())
+    }
+
+    // 192-bit key of the nonce; operations under different keys of one account are sequenced independently
+    pub fn nonce_key(&self) -> [u8; 24] {
+        let mut key = [0; 24];
+        key.copy_from_slice(&self.nonce[..24]);
+        key
+    }
+
+    // 64-bit sequence of the nonce within its key, which the EntryPoint requires in ascending order
+    pub fn nonce_sequence(&self) -> u64 {
+        let mut sequence = [0; 8];
+        sequence.copy_from_slice(&self.nonce[24..]);
+        u64::from_be_bytes(sequence)
+    }
+
+    // userOpHash as the EntryPoint computes it, which bundlers return from eth_sendUserOperation
+    pub fn hash(&self, entry_point: &Address, chain_id: u64) -> [u8; 32] {
+        let words = [
+            address_word(&self.sender),
+            self.nonce,
+            keccak256(&self.init_code),
+            keccak256(&self.call_data),
+            u128_word(self.call_gas_limit),
+            u128_word(self.verification_gas_limit),
+            u128_word(self.pre_verification_gas),
+            u128_word(self.max_fee_per_gas),
+            u128_word(self.max_priority_fee_per_gas),
+            keccak256(&self.paymaster_and_data),
+        ];
+        let packed = keccak256(&words.concat());
+        keccak256(&[packed, address_word(entry_point), u128_word(chain_id.into())].concat())
+    }
+}
+
+// Helper function to ABI-encode an address as one left-padded word
+fn address_word(address: &Address) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(address);
+    word
+}
+
+// Helper function to ABI-encode an unsigned integer as one big-endian word
+fn u128_word(value: u128) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[16..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Spoils one field of a valid operation
+    type Breakage = fn(&mut UserOperation);
+
+    fn user_operation() -> UserOperation {
+        UserOperation {
+            sender: [0x11; 20],
+            nonce: [0; 32],
+            init_code: Vec::new(),
+            call_data: vec![0xb6, 0x1d, 0x27, 0xf6],
+            call_gas_limit: 100_000,
+            verification_gas_limit: 150_000,
+            pre_verification_gas: 45_000,
+            max_fee_per_gas: 30_000_000_000,
+            max_priority_fee_per_gas: 1_000_000_000,
+            paymaster_and_data: Vec::new(),
+            signature: vec![0x5a; 65],
+        }
+    }
+
+    #[test]
+    fn test_required_fields_validated() {
+        assert!(user_operation().validate().is_ok());
+
+        let cases: Vec<(Breakage, &str)> = vec![
+            (|op| op.sender = [0; 20], "zero address"),
+            (|op| op.signature.clear(), "signature"),
+            (|op| op.call_gas_limit = 0, "callGasLimit"),
+            (|op| op.pre_verification_gas = 0, "preVerificationGas"),
+            (|op| op.max_fee_per_gas = 0, "maxFeePerGas"),
+            (|op| op.max_priority_fee_per_gas = op.max_fee_per_gas + 1, "maxPriorityFeePerGas"),
+            (|op| op.init_code = vec![0x22; 19], "initCode"),
+            (|op| op.paymaster_and_data = vec![0x33; 4], "paymasterAndData"),
+        ];
+        for (break_op, reason) in cases {
+            let mut op = user_operation();
+            break_op(&mut op);
+            match op.validate() {
+                Err(IngressError::InvalidUserOperation(message)) => assert!(message.contains(reason), "{}", message),
+                other => panic!("expected {} to be refused, got {:?}", reason, other),
+            }
+        }
+
+        // A fresh account's factory and a paymaster are accepted
+        let mut op = user_operation();
+        op.init_code = [vec![0x22; 20], vec![0x01, 0x02]].concat();
+        op.paymaster_and_data = vec![0x33; 20];
+        assert!(op.validate().is_ok());
+    }
+
+    #[test]
+    fn test_user_operation_json_matches_bundler_layout() {
+        let mut op = user_operation();
+        op.nonce[23] = 0x01; // Key 1, sequence 5
+        op.nonce[31] = 0x05;
+
+        let json: serde_json::Value = serde_json::from_slice(&op.encode()).unwrap();
+        assert_eq!(json["sender"], format!("0x{}", "11".repeat(20)));
+        assert_eq!(json["nonce"], "0x10000000000000005");
+        assert_eq!(json["initCode"], "0x");
+        assert_eq!(json["callGasLimit"], "0x186a0");
+        assert_eq!(json["maxFeePerGas"], "0x6fc23ac00");
+        assert_eq!(UserOperation::decode(&op.encode()).unwrap(), op);
+
+        // Every field is required
+        let mut missing = json.clone();
+        missing.as_object_mut().unwrap().remove("signature");
+        let error = UserOperation::decode(missing.to_string().as_bytes()).unwrap_err();
+        assert!(matches!(error, IngressError::InvalidUserOperation(message) if message.contains("signature")));
+        let mut malformed = json;
+        malformed["nonce"] = serde_json::json!("5");
+        assert!(UserOperation::decode(malformed.to_string().as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_nonce_splits_into_key_and_sequence() {
+        let mut op = user_operation();
+        op.nonce[0] = 0x01;
+        op.nonce[23] = 0x02;
+        op.nonce[31] = 0x03;
+        op.nonce[24] = 0x04;
+
+        let mut key = [0; 24];
+        (key[0], key[23]) = (0x01, 0x02);
+        assert_eq!(op.nonce_key(), key);
+        assert_eq!(op.nonce_sequence(), 0x0400_0000_0000_0003);
+    }
+
+    #[test]
+    fn test_hash_commits_to_entry_point_and_chain() {
+        let op = user_operation();
+        let entry_point = [0x5f; 20];
+        let hash = op.hash(&entry_point, 1);
+        assert_eq!(hash, op.hash(&entry_point, 1));
+        assert_ne!(hash, op.hash(&entry_point, 10));
+        assert_ne!(hash, op.hash(&[0x00; 20], 1));
+
+        // The signature is not hashed, since it signs the hash
+        let mut resigned = op.clone();
+        resigned.signature = vec![0x6b; 65];
+        assert_eq!(resigned.hash(&entry_point, 1), hash);
+        let mut bumped = op;
+        bumped.nonce[31] = 1;
+        assert_ne!(bumped.hash(&entry_point, 1), hash);
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::batch::TransactionBatch;
+use crate::crypto::sha256_hash;
+use crate::envelope::TransactionEnvelope;
+use crate::error::{lock, IngressError};
+use crate::transaction::{transaction_nonce, Address};
+
+// Record tags, one record per line
+const ACCEPTED: &str = "accept";
+const COMMITTED: &str = "commit";
+const SEQUENCE: &str = "sequence";
+
+// What a log holds for the next run: the transactions still pending and where batch numbering resumes
+#[derive(Debug, Default)]
+pub struct Recovered {
+    pub pending: Vec<TransactionEnvelope>, // Accepted but never batched, in submission order
+    pub next_sequence: u64,                // One past the highest batch sequence logged, 0 for a fresh log
+}
+
+// A batch as the log recorded it when it left the pending pool
+#[derive(Clone, Debug)]
+pub struct CommittedBatch {
+    pub batch_id: String,
+    pub sequence: Option<u64>, // None if the log ends before the batch's sequence record
+    pub timestamp: Option<SystemTime>, // The batch's timestamp, None for batches logged before it was recorded
+    pub transactions: Vec<TransactionEnvelope>, // In batch order; decoys are never logged as accepted and are left out
+}
+
+// Append-only log of accepted transactions and the batches that took them out of the pending pool
+//
+// Accepted records are only flushed to the OS, so they survive a process crash; batch records are
+// fsynced, so a batch that may already have been forwarded is never recovered and forwarded again.
+pub struct WriteAheadLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl WriteAheadLog {
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, IngressError> {
+        let path = path.into();
+        let file = open_append(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Logs a transaction entering the pending pool
+    pub fn record_accepted(&self, tx: &TransactionEnvelope) -> Result<(), IngressError> {
+        let record = accepted_record(tx);
+        let mut file = lock(&self.file)?;
+        file.write_all(record.as_bytes())?;
+        Ok(())
+    }
+
+    // Logs and fsyncs a batch leaving the pending pool, followed by its sequence number and timestamp
+    pub fn record_committed(&self, batch: &TransactionBatch) -> Result<(), IngressError> {
+        let mut record = format!("{} {}", COMMITTED, batch.id);
+        // Keyed by SHA-256 regardless of the batch's commitment hash, matching recovery below
+        for tx in &batch.transactions {
+            record.push(' ');
+            record.push_str(&hex::encode(sha256_hash(&tx.tx_bytes)));
+        }
+        record.push('\n');
+        let timestamp_ms = batch.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        record.push_str(&format!("{} {} {}\n", SEQUENCE, batch.sequence, timestamp_ms));
+
+        let mut file = lock(&self.file)?;
+        file.write_all(record.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    // Pending transactions that were accepted but never batched, and the next batch sequence number
+    //
+    // The log is compacted down to exactly these transactions and the last sequence number, so it
+    // does not grow across restarts.
+    pub fn recover(&self) -> Result<Recovered, IngressError> {
+        let mut file = lock(&self.file)?;
+
+        let mut accepted = Vec::new();
+        let mut committed = HashSet::new();
+        let mut last_sequence = None;
+
+        for record in parse_log(&fs::read_to_string(&self.path)?)? {
+            match record {
+                Record::Accepted(tx) => accepted.push(tx),
+                Record::Committed(_, hashes) => committed.extend(hashes),
+                Record::Sequence(sequence, _) => last_sequence = last_sequence.max(Some(sequence)),
+            }
+        }
+
+        // A later record for the same sender and nonce is a fee bump that replaced the earlier one in place
+        let mut pending: Vec<TransactionEnvelope> = Vec::new();
+        let mut slots: HashMap<(Address, u64), usize> = HashMap::new();
+        for tx in accepted.into_iter().filter(|tx| !committed.contains(&sha256_hash(&tx.tx_bytes))) {
+            let slot = tx.sender.zip(tx.nonce);
+            match slot.and_then(|slot| slots.get(&slot)) {
+                Some(&index) => pending[index] = tx,
+                None => {
+                    if let Some(slot) = slot {
+                        slots.insert(slot, pending.len());
+                    }
+                    pending.push(tx);
+                }
+            }
+        }
+
+        // Rewrite through a temporary file so a crash during compaction keeps the old log intact
+        let compacted_path = self.path.with_extension("compact");
+        let mut compacted = File::create(&compacted_path)?;
+        if let Some(sequence) = last_sequence {
+            writeln!(compacted, "{} {}", SEQUENCE, sequence)?;
+        }
+        for tx in &pending {
+            compacted.write_all(accepted_record(tx).as_bytes())?;
+        }
+        compacted.sync_all()?;
+        fs::rename(&compacted_path, &self.path)?;
+        *file = open_append(&self.path)?;
+
+        Ok(Recovered {
+            pending,
+            next_sequence: last_sequence.map_or(0, |sequence| sequence + 1),
+        })
+    }
+}
+
+// Batches committed since the log at path was last compacted, in commit order
+//
+// Recovery compacts committed batches away, so batches from before the last restart of an ingress
+// using the log are gone. The log is only read, so this is safe while an ingress appends to it.
+pub fn committed_batches(path: impl AsRef<Path>) -> Result<Vec<CommittedBatch>, IngressError> {
+    let mut accepted: HashMap<Vec<u8>, TransactionEnvelope> = HashMap::new();
+    let mut batches: Vec<CommittedBatch> = Vec::new();
+
+    for record in parse_log(&fs::read_to_string(path)?)? {
+        match record {
+            Record::Accepted(tx) => {
+                accepted.insert(sha256_hash(&tx.tx_bytes), tx);
+            }
+            Record::Committed(batch_id, hashes) => batches.push(CommittedBatch {
+                batch_id,
+                sequence: None,
+                timestamp: None,
+                transactions: hashes.iter().filter_map(|hash| accepted.get(hash).cloned()).collect(),
+            }),
+            // A batch's sequence record directly follows it; one at the start of a compacted log has no batch
+            Record::Sequence(sequence, timestamp) => {
+                if let Some(batch) = batches.last_mut().filter(|batch| batch.sequence.is_none()) {
+                    batch.sequence = Some(sequence);
+                    batch.timestamp = timestamp;
+                }
+            }
+        }
+    }
+    Ok(batches)
+}
+
+enum Record {
+    Accepted(TransactionEnvelope),
+    Committed(String, Vec<Vec<u8>>),
+    Sequence(u64, Option<SystemTime>),
+}
+
+// Helper function to parse a whole log, allowing only the final line to be torn
+fn parse_log(contents: &str) -> Result<Vec<Record>, IngressError> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut records = Vec::with_capacity(lines.len());
+    for (index, line) in lines.iter().enumerate() {
+        match parse_record(line) {
+            Some(record) => records.push(record),
+            // A torn final line is what a crash mid-write leaves behind
+            None if index + 1 == lines.len() && !contents.ends_with('\n') => {}
+            None => return Err(IngressError::Storage(format!("corrupt record at line {}", index + 1))),
+        }
+    }
+    Ok(records)
+}
+
+fn open_append(path: &Path) -> Result<File, IngressError> {
+    Ok(OpenOptions::new().create(true).append(true).open(path)?)
+}
+
+// Helper function to format an accepted record; the inclusion deadline and conditions are trailing
+// fields only written when set, so logs from before they existed still parse
+//
+// Conditions are compact JSON, which never contains a space. A transaction with conditions but no
+// deadline writes "-" for the deadline.
+fn accepted_record(tx: &TransactionEnvelope) -> String {
+    let sender = tx.sender.map(hex::encode).unwrap_or_else(|| "-".to_string());
+    let mut record = format!("{} {} {} {}", ACCEPTED, tx.batch_id, sender, hex::encode(&tx.tx_bytes));
+    let valid_until_block = tx.valid_until_block.map(|block| block.to_string());
+    match (valid_until_block, &tx.conditions) {
+        (valid_until_block, Some(conditions)) => {
+            let conditions = serde_json::to_string(conditions).unwrap_or_default();
+            record.push_str(&format!(" {} {}", valid_until_block.as_deref().unwrap_or("-"), conditions));
+        }
+        (Some(valid_until_block), None) => record.push_str(&format!(" {}", valid_until_block)),
+        (None, None) => {}
+    }
+    record.push('\n');
+    record
+}
+
+// Helper function to parse one log line, returning None if it is malformed
+fn parse_record(line: &str) -> Option<Record> {
+    let mut parts = line.split(' ');
+    match parts.next()? {
+        ACCEPTED => {
+            let batch_id = parts.next()?.to_string();
+            let sender = match parts.next()? {
+                "-" => None,
+                encoded => Some(Address::try_from(hex::decode(encoded).ok()?.as_slice()).ok()?),
+            };
+            let tx_bytes = hex::decode(parts.next()?).ok()?;
+            let valid_until_block = match parts.next() {
+                None | Some("-") => None,
+                Some(block) => Some(block.parse().ok()?),
+            };
+            let conditions = match parts.next() {
+                Some(conditions) => Some(serde_json::from_str(conditions).ok()?),
+                None => None,
+            };
+            if parts.next().is_some() {
+                return None;
+            }
+
+            // The nonce is not stored, it is decoded again from the transaction itself
+            let nonce = transaction_nonce(&tx_bytes).ok();
+            let mut tx = TransactionEnvelope::new(tx_bytes, batch_id);
+            tx.sender = sender;
+            tx.nonce = nonce;
+            tx.valid_until_block = valid_until_block;
+            tx.conditions = conditions;
+            Some(Record::Accepted(tx))
+        }
+        COMMITTED => {
+            let batch_id = parts.next()?.to_string();
+            let hashes = parts.map(|hash| hex::decode(hash).ok()).collect::<Option<Vec<_>>>()?;
+            Some(Record::Committed(batch_id, hashes))
+        }
+        // The timestamp is missing from compacted logs and from logs written before it was recorded
+        SEQUENCE => {
+            let sequence = parts.next()?.parse().ok()?;
+            let timestamp = match parts.next() {
+                Some(timestamp_ms) => Some(UNIX_EPOCH + Duration::from_millis(timestamp_ms.parse().ok()?)),
+                None => None,
+            };
+            if parts.next().is_some() {
+                return None;
+            }
+            Some(Record::Sequence(sequence, timestamp))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::TransactionConditions;
+
+    fn envelope(byte: u8) -> TransactionEnvelope {
+        let mut tx = TransactionEnvelope::new(vec![0x02, byte], format!("tx-{}", byte));
+        tx.sender = Some([byte; 20]);
+        tx
+    }
+
+    #[test]
+    fn test_recover_skips_committed_transactions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ingress.wal");
+
+        let wal = WriteAheadLog::open(&path).unwrap();
+        let conditions = TransactionConditions { block_number_min: Some(18_999_990), ..Default::default() };
+        for byte in 1..=4 {
+            let mut tx = envelope(byte);
+            tx.valid_until_block = (byte == 2).then_some(19_000_000);
+            tx.conditions = (byte == 4).then(|| conditions.clone());
+            wal.record_accepted(&tx).unwrap();
+        }
+        let batch = TransactionBatch::new(vec![envelope(1), envelope(3)]).unwrap();
+        wal.record_committed(&batch).unwrap();
+        drop(wal);
+
+        let recovered = WriteAheadLog::open(&path).unwrap().recover().unwrap().pending;
+        let recovered_bytes: Vec<Vec<u8>> = recovered.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        assert_eq!(recovered_bytes, vec![vec![0x02, 2], vec![0x02, 4]]);
+        assert_eq!(recovered[0].sender, Some([2; 20]));
+        assert_eq!(recovered[0].batch_id, "tx-2");
+        assert_eq!(recovered[0].valid_until_block, Some(19_000_000));
+        assert_eq!(recovered[1].valid_until_block, None);
+        assert_eq!((&recovered[0].conditions, &recovered[1].conditions), (&None, &Some(conditions)));
+    }
+
+    #[test]
+    fn test_recover_compacts_and_tolerates_torn_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ingress.wal");
+
+        let wal = WriteAheadLog::open(&path).unwrap();
+        wal.record_accepted(&envelope(1)).unwrap();
+        let batch = TransactionBatch::new(vec![envelope(1)]).unwrap();
+        wal.record_committed(&batch).unwrap();
+        wal.record_accepted(&envelope(2)).unwrap();
+        drop(wal);
+
+        // Simulate a crash halfway through writing a record
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"accept tx-3 -").unwrap();
+
+        let wal = WriteAheadLog::open(&path).unwrap();
+        assert_eq!(wal.recover().unwrap().pending.len(), 1);
+        // The pending transaction and the batch sequence reached so far
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        // Recovering again over the compacted log is stable
+        let recovered = wal.recover().unwrap();
+        assert_eq!(recovered.pending.len(), 1);
+        assert_eq!(recovered.next_sequence, 1);
+    }
+
+    #[test]
+    fn test_committed_batches_read_back_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ingress.wal");
+
+        let wal = WriteAheadLog::open(&path).unwrap();
+        wal.record_accepted(&envelope(1)).unwrap();
+        wal.record_accepted(&envelope(2)).unwrap();
+        // The middle entry stands in for a decoy, which only ever appears in the batch record
+        let mut batch = TransactionBatch::new(vec![envelope(2), envelope(3), envelope(1)]).unwrap();
+        batch.sequence = 4;
+        batch.timestamp = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        wal.record_committed(&batch).unwrap();
+        drop(wal);
+
+        // A batch from a log written before timestamps were recorded
+        let old = format!("commit old-batch {}\nsequence 5\n", hex::encode(sha256_hash(&[0x02, 1])));
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(old.as_bytes()).unwrap();
+
+        let batches = committed_batches(&path).unwrap();
+        let summary: Vec<(&str, Option<u64>, Option<SystemTime>, usize)> = batches
+            .iter()
+            .map(|logged| (logged.batch_id.as_str(), logged.sequence, logged.timestamp, logged.transactions.len()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(batch.id.as_str(), Some(4), Some(batch.timestamp), 2), ("old-batch", Some(5), None, 1)]
+        );
+        let tx_bytes: Vec<Vec<u8>> = batches[0].transactions.iter().map(|tx| tx.tx_bytes.clone()).collect();
+        assert_eq!(tx_bytes, vec![vec![0x02, 2], vec![0x02, 1]]);
+
+        // Recovery reads old and new sequence records alike
+        assert_eq!(WriteAheadLog::open(&path).unwrap().recover().unwrap().next_sequence, 6);
+    }
+
+    #[test]
+    fn test_recover_rejects_corrupt_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ingress.wal");
+        fs::write(&path, "accept tx-1 - zz\naccept tx-2 - 0201\n").unwrap();
+
+        let result = WriteAheadLog::open(&path).unwrap().recover();
+        assert_eq!(result.unwrap_err(), IngressError::Storage("corrupt record at line 1".to_string()));
+    }
+}
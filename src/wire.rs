@@ -0,0 +1,492 @@
+// Stable, versioned JSON layout for batches and envelopes, for audit logs and tooling outside this crate
+//
+// Each batch and each envelope carries a "version" tag naming its layout. Fields a reader does not
+// know are ignored, so a layout can gain fields without a new version; a layout a reader does not
+// know is refused rather than guessed at. Byte fields are 0x-prefixed hex. The local receive time is
+// never written, and timestamps are kept to the millisecond.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::batch::TransactionBatch;
+use crate::crypto::{Commitment, CommitmentScheme, HashAlgo, Nonce};
+use crate::envelope::{KnownAccount, PrivacyHints, TransactionConditions, TransactionEnvelope};
+use crate::operator::OPERATOR_SIGNATURE_LEN;
+use crate::user_operation::UserOperation;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "version")]
+pub(crate) enum BatchWire {
+    #[serde(rename = "1")]
+    V1(BatchV1),
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct BatchV1 {
+    id: String,
+    commitment: Commitment,
+    nonce: Nonce,
+    hash_algo: String,
+    commitment_scheme: String,
+    timestamp_ms: u64, // Milliseconds since the Unix epoch
+    #[serde(default)]
+    sequence: u64,
+    #[serde(default)]
+    operator_signature: Option<String>,
+    #[serde(default)]
+    permutation: Option<Vec<usize>>,
+    transactions: Vec<TransactionEnvelope>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "version")]
+pub(crate) enum EnvelopeWire {
+    #[serde(rename = "1")]
+    V1(EnvelopeV1),
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct EnvelopeV1 {
+    tx: String,
+    batch_id: String,
+    envelope_version: u32, // Bound into the commitment under the V2 and V3 schemes, so kept exactly
+    #[serde(default)]
+    sender: Option<String>,
+    #[serde(default)]
+    nonce: Option<u64>,
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    privacy_hints: PrivacyHints,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    valid_until_block: Option<u64>, // Left out when unset, so envelopes without one keep the first layout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    conditions: Option<TransactionConditions>,
+    #[serde(default)]
+    decoy: bool, // Decoys are committed to, so the commitment cannot be recomputed without them
+}
+
+// The options object of eth_sendRawTransactionConditional; unlike the rest of this layout, block
+// numbers are hex quantities and keys are camelCase, as relays expect them
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ConditionsWire {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    known_accounts: BTreeMap<String, KnownAccountWire>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    block_number_min: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    block_number_max: Option<String>,
+}
+
+// A UserOperation as eth_sendUserOperation takes it, in the EntryPoint v0.6 layout; like
+// ConditionsWire, keys are camelCase and numbers hex quantities, and every field is required
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UserOperationWire {
+    sender: String,
+    nonce: String,
+    init_code: String,
+    call_data: String,
+    call_gas_limit: String,
+    verification_gas_limit: String,
+    pre_verification_gas: String,
+    max_fee_per_gas: String,
+    max_priority_fee_per_gas: String,
+    paymaster_and_data: String,
+    signature: String,
+}
+
+// A storage root, or an object of slot values
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum KnownAccountWire {
+    StorageRoot(String),
+    Slots(BTreeMap<String, String>),
+}
+
+impl From<TransactionBatch> for BatchWire {
+    fn from(batch: TransactionBatch) -> Self {
+        let since_epoch = batch.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+        BatchWire::V1(BatchV1 {
+            id: batch.id,
+            commitment: batch.commitment,
+            nonce: batch.nonce,
+            hash_algo: hash_algo_name(batch.hash_algo).to_string(),
+            commitment_scheme: scheme_name(batch.commitment_scheme).to_string(),
+            timestamp_ms: u64::try_from(since_epoch.as_millis()).unwrap_or(u64::MAX),
+            sequence: batch.sequence,
+            operator_signature: batch.operator_signature.map(|signature| encode_hex(&signature)),
+            permutation: batch.permutation,
+            transactions: batch.transactions,
+        })
+    }
+}
+
+impl TryFrom<BatchWire> for TransactionBatch {
+    type Error = String;
+
+    fn try_from(wire: BatchWire) -> Result<Self, Self::Error> {
+        let BatchWire::V1(batch) = wire;
+        let operator_signature = match batch.operator_signature {
+            Some(signature) => Some(decode_fixed::<OPERATOR_SIGNATURE_LEN>("operator_signature", &signature)?),
+            None => None,
+        };
+        Ok(Self {
+            id: batch.id,
+            transactions: batch.transactions,
+            commitment: batch.commitment,
+            timestamp: UNIX_EPOCH + Duration::from_millis(batch.timestamp_ms),
+            nonce: batch.nonce,
+            hash_algo: parse_hash_algo(&batch.hash_algo)?,
+            commitment_scheme: parse_scheme(&batch.commitment_scheme)?,
+            operator_signature,
+            permutation: batch.permutation,
+            sequence: batch.sequence,
+            trigger: None,
+        })
+    }
+}
+
+impl From<TransactionEnvelope> for EnvelopeWire {
+    fn from(envelope: TransactionEnvelope) -> Self {
+        EnvelopeWire::V1(EnvelopeV1 {
+            tx: encode_hex(&envelope.tx_bytes),
+            batch_id: envelope.batch_id,
+            envelope_version: envelope.envelope_version,
+            sender: envelope.sender.map(|sender| encode_hex(&sender)),
+            nonce: envelope.nonce,
+            encrypted: envelope.encrypted,
+            privacy_hints: envelope.privacy_hints,
+            valid_until_block: envelope.valid_until_block,
+            conditions: envelope.conditions,
+            decoy: envelope.decoy,
+        })
+    }
+}
+
+impl TryFrom<EnvelopeWire> for TransactionEnvelope {
+    type Error = String;
+
+    fn try_from(wire: EnvelopeWire) -> Result<Self, Self::Error> {
+        let EnvelopeWire::V1(envelope) = wire;
+        let sender = match envelope.sender {
+            Some(sender) => Some(decode_fixed::<20>("sender", &sender)?),
+            None => None,
+        };
+        Ok(Self {
+            tx_bytes: decode_hex("tx", &envelope.tx)?,
+            batch_id: envelope.batch_id,
+            envelope_version: envelope.envelope_version,
+            sender,
+            nonce: envelope.nonce,
+            encrypted: envelope.encrypted,
+            privacy_hints: envelope.privacy_hints,
+            valid_until_block: envelope.valid_until_block,
+            conditions: envelope.conditions,
+            decoy: envelope.decoy,
+            received_at: None,
+        })
+    }
+}
+
+impl From<TransactionConditions> for ConditionsWire {
+    fn from(conditions: TransactionConditions) -> Self {
+        let known_accounts = conditions
+            .known_accounts
+            .into_iter()
+            .map(|(address, account)| {
+                let account = match account {
+                    KnownAccount::StorageRoot(root) => KnownAccountWire::StorageRoot(encode_hex(&root)),
+                    KnownAccount::Slots(slots) => KnownAccountWire::Slots(
+                        slots.iter().map(|(slot, value)| (encode_hex(slot), encode_hex(value))).collect(),
+                    ),
+                };
+                (encode_hex(&address), account)
+            })
+            .collect();
+        ConditionsWire {
+            known_accounts,
+            block_number_min: conditions.block_number_min.map(encode_quantity),
+            block_number_max: conditions.block_number_max.map(encode_quantity),
+        }
+    }
+}
+
+impl TryFrom<ConditionsWire> for TransactionConditions {
+    type Error = String;
+
+    fn try_from(wire: ConditionsWire) -> Result<Self, Self::Error> {
+        let mut known_accounts = BTreeMap::new();
+        for (address, account) in wire.known_accounts {
+            let account = match account {
+                KnownAccountWire::StorageRoot(root) => KnownAccount::StorageRoot(decode_fixed("knownAccounts", &root)?),
+                KnownAccountWire::Slots(slots) => KnownAccount::Slots(
+                    slots
+                        .iter()
+                        .map(|(slot, value)| {
+                            Ok((decode_fixed("knownAccounts", slot)?, decode_fixed("knownAccounts", value)?))
+                        })
+                        .collect::<Result<_, String>>()?,
+                ),
+            };
+            known_accounts.insert(decode_fixed("knownAccounts", &address)?, account);
+        }
+        Ok(Self {
+            known_accounts,
+            block_number_min: wire.block_number_min.map(|block| decode_quantity("blockNumberMin", &block)).transpose()?,
+            block_number_max: wire.block_number_max.map(|block| decode_quantity("blockNumberMax", &block)).transpose()?,
+        })
+    }
+}
+
+impl From<UserOperation> for UserOperationWire {
+    fn from(op: UserOperation) -> Self {
+        // The nonce is a 256-bit quantity, so it is written without leading zero bytes like the others
+        let nonce = match hex::encode(op.nonce).trim_start_matches('0') {
+            "" => "0x0".to_string(),
+            digits => format!("0x{}", digits),
+        };
+        UserOperationWire {
+            sender: encode_hex(&op.sender),
+            nonce,
+            init_code: encode_hex(&op.init_code),
+            call_data: encode_hex(&op.call_data),
+            call_gas_limit: format!("0x{:x}", op.call_gas_limit),
+            verification_gas_limit: format!("0x{:x}", op.verification_gas_limit),
+            pre_verification_gas: format!("0x{:x}", op.pre_verification_gas),
+            max_fee_per_gas: format!("0x{:x}", op.max_fee_per_gas),
+            max_priority_fee_per_gas: format!("0x{:x}", op.max_priority_fee_per_gas),
+            paymaster_and_data: encode_hex(&op.paymaster_and_data),
+            signature: encode_hex(&op.signature),
+        }
+    }
+}
+
+impl TryFrom<UserOperationWire> for UserOperation {
+    type Error = String;
+
+    fn try_from(wire: UserOperationWire) -> Result<Self, Self::Error> {
+        Ok(Self {
+            sender: decode_fixed("sender", &wire.sender)?,
+            nonce: decode_word("nonce", &wire.nonce)?,
+            init_code: decode_hex("initCode", &wire.init_code)?,
+            call_data: decode_hex("callData", &wire.call_data)?,
+            call_gas_limit: decode_u128("callGasLimit", &wire.call_gas_limit)?,
+            verification_gas_limit: decode_u128("verificationGasLimit", &wire.verification_gas_limit)?,
+            pre_verification_gas: decode_u128("preVerificationGas", &wire.pre_verification_gas)?,
+            max_fee_per_gas: decode_u128("maxFeePerGas", &wire.max_fee_per_gas)?,
+            max_priority_fee_per_gas: decode_u128("maxPriorityFeePerGas", &wire.max_priority_fee_per_gas)?,
+            paymaster_and_data: decode_hex("paymasterAndData", &wire.paymaster_and_data)?,
+            signature: decode_hex("signature", &wire.signature)?,
+        })
+    }
+}
+
+impl Serialize for Commitment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Commitment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Nonce {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Nonce {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+fn hash_algo_name(algo: HashAlgo) -> &'static str {
+    match algo {
+        HashAlgo::Sha256 => "sha256",
+        HashAlgo::Keccak256 => "keccak256",
+    }
+}
+
+fn parse_hash_algo(name: &str) -> Result<HashAlgo, String> {
+    match name {
+        "sha256" => Ok(HashAlgo::Sha256),
+        "keccak256" => Ok(HashAlgo::Keccak256),
+        other => Err(format!("unknown hash_algo {:?}", other)),
+    }
+}
+
+fn scheme_name(scheme: CommitmentScheme) -> &'static str {
+    match scheme {
+        CommitmentScheme::V1 => "v1",
+        CommitmentScheme::V2 => "v2",
+        CommitmentScheme::V3 => "v3",
+    }
+}
+
+fn parse_scheme(name: &str) -> Result<CommitmentScheme, String> {
+    match name {
+        "v1" => Ok(CommitmentScheme::V1),
+        "v2" => Ok(CommitmentScheme::V2),
+        "v3" => Ok(CommitmentScheme::V3),
+        other => Err(format!("unknown commitment_scheme {:?}", other)),
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+// Helper function to decode a 0x-prefixed hex field, naming the field on failure
+fn decode_hex(field: &str, value: &str) -> Result<Vec<u8>, String> {
+    let digits = value.strip_prefix("0x").ok_or_else(|| format!("{} must start with 0x", field))?;
+    hex::decode(digits).map_err(|error| format!("{}: {}", field, error))
+}
+
+fn encode_quantity(value: u64) -> String {
+    format!("0x{:x}", value)
+}
+
+fn decode_quantity(field: &str, value: &str) -> Result<u64, String> {
+    value
+        .strip_prefix("0x")
+        .and_then(|digits| u64::from_str_radix(digits, 16).ok())
+        .ok_or_else(|| format!("{} must be a 0x-prefixed hex quantity", field))
+}
+
+fn decode_u128(field: &str, value: &str) -> Result<u128, String> {
+    value
+        .strip_prefix("0x")
+        .and_then(|digits| u128::from_str_radix(digits, 16).ok())
+        .ok_or_else(|| format!("{} must be a 0x-prefixed hex quantity of at most 128 bits", field))
+}
+
+// Helper function to read a hex quantity of up to 256 bits into a big-endian word
+fn decode_word(field: &str, value: &str) -> Result<[u8; 32], String> {
+    let digits = value.strip_prefix("0x").unwrap_or("");
+    if digits.is_empty() || digits.len() > 64 {
+        return Err(format!("{} must be a 0x-prefixed hex quantity of at most 256 bits", field));
+    }
+    let padded = format!("{:0>64}", digits);
+    let bytes = hex::decode(padded).map_err(|error| format!("{}: {}", field, error))?;
+    let mut word = [0u8; 32];
+    word.copy_from_slice(&bytes);
+    Ok(word)
+}
+
+fn decode_fixed<const N: usize>(field: &str, value: &str) -> Result<[u8; N], String> {
+    let bytes = decode_hex(field, value)?;
+    bytes.as_slice().try_into().map_err(|_| format!("{}: expected {} bytes, got {}", field, N, bytes.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::test_utils::{dynamic_fee_tx, EIP155_EXAMPLE_TX};
+
+    // Written by the first release of this layout; a change that breaks reading it breaks stored batches
+    const BATCH_V1_FIXTURE: &str = r#"{
+      "version": "1",
+      "id": "batch-7",
+      "commitment": "0x8d82d4e0ee2af9fc92188e6b136704fea920d4f34c74a9e096c97fc53eb2c3f4",
+      "nonce": "0x1111111111111111111111111111111111111111111111111111111111111111",
+      "hash_algo": "keccak256",
+      "commitment_scheme": "v3",
+      "timestamp_ms": 1700000000123,
+      "sequence": 7,
+      "operator_signature": null,
+      "permutation": null,
+      "transactions": [
+        {
+          "version": "1",
+          "tx": "0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+          "batch_id": "",
+          "envelope_version": 1,
+          "sender": "0x9d9d9d9d9d9d9d9d9d9d9d9d9d9d9d9d9d9d9d9d",
+          "nonce": 9,
+          "encrypted": false,
+          "privacy_hints": {"calldata": false, "contract_address": false, "function_selector": false, "logs": true},
+          "decoy": false
+        }
+      ]
+    }"#;
+
+    #[test]
+    fn test_batch_round_trips_through_json() {
+        let mut decoy = TransactionEnvelope::new(dynamic_fee_tx(1), String::new());
+        decoy.decoy = true;
+        let mut real = TransactionEnvelope::new(dynamic_fee_tx(0), "batch".to_string());
+        real.envelope_version = 2;
+        real.sender = Some([0x42; 20]);
+        real.valid_until_block = Some(19_000_000);
+        real.conditions = Some(TransactionConditions {
+            known_accounts: BTreeMap::from([
+                ([0x11; 20], KnownAccount::StorageRoot([0x22; 32])),
+                ([0x33; 20], KnownAccount::Slots(BTreeMap::from([([0; 32], [0x44; 32])]))),
+            ]),
+            block_number_min: None,
+            block_number_max: Some(19_000_000),
+        });
+        real.received_at = Some(SystemTime::now());
+        let mut batch = TransactionBatch::new(vec![real, decoy]).unwrap().with_commitment_scheme(CommitmentScheme::V2);
+        batch.operator_signature = Some([0x5a; OPERATOR_SIGNATURE_LEN]);
+        batch.permutation = Some(vec![1, 0]);
+        batch.sequence = 3;
+
+        let json = serde_json::to_string(&batch).unwrap();
+        let decoded: TransactionBatch = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+        assert_eq!(decoded.recompute_commitment(), batch.commitment);
+        assert_eq!(decoded.operator_signature, batch.operator_signature);
+        assert_eq!(decoded.permutation, Some(vec![1, 0]));
+        assert!(decoded.transactions[1].is_decoy());
+        assert_eq!(decoded.valid_until_block(), Some(19_000_000));
+        assert_eq!(decoded.transactions[0].conditions, batch.transactions[0].conditions);
+
+        // Conditions keep the eth_sendRawTransactionConditional layout
+        let conditions = serde_json::to_value(&batch.transactions[0].conditions).unwrap();
+        assert_eq!(conditions["knownAccounts"][encode_hex(&[0x11; 20])], encode_hex(&[0x22; 32]));
+        assert_eq!(conditions["knownAccounts"][encode_hex(&[0x33; 20])][encode_hex(&[0; 32])], encode_hex(&[0x44; 32]));
+        assert_eq!(conditions["blockNumberMax"], "0x121eac0");
+        assert!(conditions.get("blockNumberMin").is_none());
+
+        // The local receive time stays local, and timestamps keep millisecond precision
+        assert_eq!(decoded.transactions[0].received_at, None);
+        let millis = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap().as_millis();
+        assert_eq!(millis(decoded.timestamp), millis(batch.timestamp));
+    }
+
+    #[test]
+    fn test_pinned_fixture_deserializes() {
+        let batch: TransactionBatch = serde_json::from_str(BATCH_V1_FIXTURE).unwrap();
+        assert_eq!(batch.id, "batch-7");
+        assert_eq!(batch.hash_algo, HashAlgo::Keccak256);
+        assert_eq!(batch.timestamp, UNIX_EPOCH + Duration::from_millis(1_700_000_000_123));
+        assert_eq!(batch.transactions[0].tx_bytes, hex::decode(EIP155_EXAMPLE_TX).unwrap());
+        assert_eq!(batch.transactions[0].sender, Some([0x9d; 20]));
+        assert!(batch.transactions[0].privacy_hints.logs);
+        assert_eq!(batch.recompute_commitment(), batch.commitment);
+
+        let fixture: serde_json::Value = serde_json::from_str(BATCH_V1_FIXTURE).unwrap();
+        assert_eq!(serde_json::to_value(&batch).unwrap(), fixture);
+
+        // Fields from a later writer are ignored, an unknown layout version is refused
+        let mut newer = fixture.clone();
+        newer["anchored_at_block"] = serde_json::json!(19_000_000);
+        newer["transactions"][0]["bundle_id"] = serde_json::json!("b-1");
+        assert!(serde_json::from_value::<TransactionBatch>(newer).is_ok());
+        let mut unknown = fixture;
+        unknown["version"] = serde_json::json!("2");
+        assert!(serde_json::from_value::<TransactionBatch>(unknown).is_err());
+    }
+}
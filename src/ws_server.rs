@@ -0,0 +1,139 @@
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::ingress::PenumIngress;
+
+// Replies a connection may have waiting to be sent before its client counts as too slow and is dropped
+pub const DEFAULT_REPLY_BUFFER: usize = 256;
+
+// Router upgrading GET /ws to a WebSocket that takes one raw transaction per frame
+pub fn ws_router(ingress: PenumIngress) -> Router {
+    Router::new().route("/ws", get(upgrade)).with_state(ingress)
+}
+
+// Serves the WebSocket endpoint on the given listener until the task is dropped
+pub async fn serve_ws(listener: TcpListener, ingress: PenumIngress) -> std::io::Result<()> {
+    axum::serve(listener, ws_router(ingress)).await
+}
+
+async fn upgrade(State(ingress): State<PenumIngress>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(socket, ingress))
+}
+
+// Submits each frame in arrival order and queues its reply for a separate writer task
+//
+// A client that stops reading its replies fills the bounded queue and is disconnected, rather than
+// leaving submissions parked behind a full socket. Submitting in order means a client sending too
+// fast is simply not read from, so TCP flow control pushes back on it.
+async fn handle_socket(socket: WebSocket, ingress: PenumIngress) {
+    let (mut sink, mut stream) = socket.split();
+    let (replies, mut outgoing) = mpsc::channel::<Message>(DEFAULT_REPLY_BUFFER);
+    let writer = tokio::spawn(async move {
+        while let Some(reply) = outgoing.recv().await {
+            if sink.send(reply).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(Ok(message)) = stream.next().await {
+        let tx_bytes = match message {
+            Message::Binary(bytes) => Ok(bytes.to_vec()),
+            Message::Text(text) => decode_hex(text.as_str()),
+            Message::Close(_) => break,
+            // Pings are answered by the socket itself
+            Message::Ping(_) | Message::Pong(_) => continue,
+        };
+        let reply = match tx_bytes {
+            Ok(tx_bytes) => submit(&ingress, tx_bytes).await,
+            Err(message) => json!({ "error": message }),
+        };
+
+        if replies.try_send(Message::Text(reply.to_string().into())).is_err() {
+            warn!(buffered = DEFAULT_REPLY_BUFFER, "dropping websocket client that is not reading its replies");
+            writer.abort();
+            return;
+        }
+    }
+
+    // Let the writer flush what is queued before the connection closes
+    drop(replies);
+    let _ = writer.await;
+}
+
+// Text frames carry the transaction as 0x-prefixed hex, as eth_sendRawTransaction does
+fn decode_hex(text: &str) -> Result<Vec<u8>, String> {
+    text.trim()
+        .strip_prefix("0x")
+        .and_then(|hex_payload| hex::decode(hex_payload).ok())
+        .ok_or_else(|| "hex string without 0x prefix or invalid hex".to_string())
+}
+
+async fn submit(ingress: &PenumIngress, tx_bytes: Vec<u8>) -> Value {
+    match ingress.submit_transaction(tx_bytes).await {
+        Ok(tx_hash) => json!({ "result": format!("0x{}", hex::encode(tx_hash)) }),
+        Err(error) => json!({ "error": error.to_string() }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::HashAlgo;
+    use crate::test_utils::dynamic_fee_tx;
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite;
+
+    async fn start_server() -> String {
+        let ingress = PenumIngress::new(10, Duration::from_secs(3600), Vec::new());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_ws(listener, ingress));
+        format!("ws://{}/ws", addr)
+    }
+
+    async fn next_reply<S>(socket: &mut S) -> Value
+    where
+        S: futures::Stream<Item = Result<tungstenite::Message, tungstenite::Error>> + Unpin,
+    {
+        let reply = socket.next().await.unwrap().unwrap();
+        serde_json::from_str(reply.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_raw_transaction_frame_returns_hash() {
+        let url = start_server().await;
+        let (mut socket, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+        let tx = dynamic_fee_tx(0);
+
+        socket.send(tungstenite::Message::Binary(tx.clone().into())).await.unwrap();
+
+        let reply = next_reply(&mut socket).await;
+        assert_eq!(reply["result"], format!("0x{}", hex::encode(HashAlgo::Keccak256.hash(&tx))));
+    }
+
+    #[tokio::test]
+    async fn test_hex_frames_and_errors_answered_in_order() {
+        let url = start_server().await;
+        let (mut socket, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+        let tx = dynamic_fee_tx(1);
+        let hex_tx = format!("0x{}", hex::encode(&tx));
+
+        socket.send(tungstenite::Message::Text(hex_tx.clone().into())).await.unwrap();
+        socket.send(tungstenite::Message::Text(hex_tx.into())).await.unwrap();
+        socket.send(tungstenite::Message::Text("02ff".into())).await.unwrap();
+
+        assert_eq!(next_reply(&mut socket).await["result"], format!("0x{}", hex::encode(HashAlgo::Keccak256.hash(&tx))));
+        // The resubmission is rejected without closing the connection
+        assert!(next_reply(&mut socket).await["error"].is_string());
+        assert_eq!(next_reply(&mut socket).await["error"], "hex string without 0x prefix or invalid hex");
+    }
+}